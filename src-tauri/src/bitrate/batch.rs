@@ -0,0 +1,450 @@
+//! Batch bitrate report across a folder
+//!
+//! Runs the overall analysis for every video/audio file in a directory (using
+//! cached results where the file is unchanged) and summarizes the folder:
+//! per-file statistics, outliers relative to the folder median, bits per
+//! pixel for video, and the encodes furthest above their codec's typical rate.
+
+use log::{info, warn};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::cache::{get_cached_analysis, save_to_cache, AnalysisParams};
+use super::overall::analyze_overall;
+use crate::config;
+use crate::media::{get_media_streams, CANCELLED_MESSAGE};
+use crate::types::{
+    is_video_audio_extension, BatchBitrateOptions, BatchBitrateReport, BatchFileError,
+    BatchFileReport, BitrateProgress, OverallBitrateAnalysis, StreamType, WastefulEncode,
+};
+
+/// Video/audio files in `dir`, sorted by path (hidden entries are skipped)
+///
+/// Symlinked directories aren't followed when recursing, so a link back to
+/// an ancestor can't loop forever.
+pub fn collect_media_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .map_err(|e| format!("Failed to read directory {}: {}", current.display(), e))?;
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if file_type.is_symlink() && path.is_dir() {
+                continue;
+            } else if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(is_video_audio_extension)
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Parse an ffprobe rate such as `30000/1001` or `25`
+pub fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let value = match rate.split_once('/') {
+        Some((num, den)) => {
+            let den = den.trim().parse::<f64>().ok()?;
+            if den == 0.0 {
+                return None;
+            }
+            num.trim().parse::<f64>().ok()? / den
+        }
+        None => rate.trim().parse::<f64>().ok()?,
+    };
+    (value > 0.0).then_some(value)
+}
+
+/// Bits per pixel at which a codec typically looks transparent
+///
+/// None for intermediate/lossless codecs, which are large on purpose.
+pub fn reference_bits_per_pixel(codec: &str) -> Option<f64> {
+    match codec {
+        "h264" => Some(0.10),
+        "hevc" | "h265" => Some(0.06),
+        "vp9" => Some(0.06),
+        "av1" => Some(0.045),
+        "mpeg4" | "msmpeg4v3" => Some(0.15),
+        "mpeg2video" | "mpeg1video" => Some(0.20),
+        "vp8" => Some(0.12),
+        _ => None,
+    }
+}
+
+fn median_u64(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2
+    }
+}
+
+fn median_f64(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    Some(if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    })
+}
+
+/// Per-file entry from an analysis; folder-relative fields are filled by `summarize`
+fn file_report(analysis: &OverallBitrateAnalysis, file_size: u64) -> BatchFileReport {
+    let video = get_media_streams(analysis.path.clone())
+        .ok()
+        .and_then(|streams| {
+            streams
+                .streams
+                .into_iter()
+                .find(|s| s.stream_type == StreamType::Video && !s.is_cover_art)
+        });
+
+    let video_bitrate = video.as_ref().and_then(|stream| {
+        let contribution = analysis
+            .stream_contributions
+            .iter()
+            .find(|c| c.stream_index == stream.index)?;
+        if contribution.data_points.is_empty() {
+            return None;
+        }
+        let sum: u64 = contribution.data_points.iter().map(|p| p.bitrate).sum();
+        Some(sum / contribution.data_points.len() as u64)
+    });
+    let frame_rate = video
+        .as_ref()
+        .and_then(|s| s.frame_rate.as_deref())
+        .and_then(parse_frame_rate);
+    let width = video.as_ref().and_then(|s| s.width);
+    let height = video.as_ref().and_then(|s| s.height);
+
+    let bits_per_pixel = match (video_bitrate, width, height, frame_rate) {
+        (Some(rate), Some(w), Some(h), Some(fps)) if w > 0 && h > 0 => {
+            Some(rate as f64 / (w as f64 * h as f64 * fps))
+        }
+        _ => None,
+    };
+
+    BatchFileReport {
+        path: analysis.path.clone(),
+        file_size,
+        duration: analysis.duration,
+        avg_bitrate: analysis.statistics.avg_bitrate,
+        peak_bitrate: analysis.statistics.max_bitrate,
+        std_deviation: analysis.statistics.std_deviation,
+        video_codec: video.and_then(|s| s.codec_name),
+        width,
+        height,
+        frame_rate,
+        video_bitrate,
+        bits_per_pixel,
+        ratio_to_median: 1.0,
+        outlier: false,
+        sampled: analysis.sampling.is_some(),
+        from_cache: analysis.from_cache,
+    }
+}
+
+/// Fill in folder medians, outliers and the wasteful-encode ranking
+pub fn summarize(
+    directory: &str,
+    mut files: Vec<BatchFileReport>,
+    failed: Vec<BatchFileError>,
+    options: &BatchBitrateOptions,
+) -> BatchBitrateReport {
+    let averages: Vec<u64> = files.iter().map(|f| f.avg_bitrate).collect();
+    let median_avg_bitrate = median_u64(&averages);
+    let bpps: Vec<f64> = files.iter().filter_map(|f| f.bits_per_pixel).collect();
+    let median_bits_per_pixel = median_f64(&bpps);
+
+    let factor = options.outlier_factor.max(1.0);
+    for file in &mut files {
+        if median_avg_bitrate > 0 {
+            file.ratio_to_median = file.avg_bitrate as f64 / median_avg_bitrate as f64;
+            file.outlier = file.ratio_to_median >= factor || file.ratio_to_median <= 1.0 / factor;
+        }
+    }
+
+    let mut wasteful: Vec<WastefulEncode> = files
+        .iter()
+        .filter_map(|file| {
+            let codec = file.video_codec.as_deref()?;
+            let reference = reference_bits_per_pixel(codec)?;
+            let bpp = file.bits_per_pixel?;
+            let excess_ratio = bpp / reference;
+            if excess_ratio <= 1.0 {
+                return None;
+            }
+            let video_bytes = file.video_bitrate? as f64 * file.duration / 8.0;
+            Some(WastefulEncode {
+                path: file.path.clone(),
+                codec: codec.to_string(),
+                bits_per_pixel: bpp,
+                reference_bits_per_pixel: reference,
+                excess_ratio,
+                potential_savings_bytes: (video_bytes * (1.0 - 1.0 / excess_ratio)) as u64,
+            })
+        })
+        .collect();
+    wasteful.sort_by(|a, b| b.excess_ratio.total_cmp(&a.excess_ratio));
+    wasteful.truncate(options.max_wasteful);
+
+    BatchBitrateReport {
+        directory: directory.to_string(),
+        files,
+        failed,
+        median_avg_bitrate,
+        median_bits_per_pixel,
+        wasteful,
+    }
+}
+
+/// Analyze every media file in a directory and build the folder report
+///
+/// Files that fail to analyze are listed in `failed` rather than aborting the
+/// batch. New analyses are saved to the bitrate cache.
+pub fn analyze_folder(
+    directory: &str,
+    options: &BatchBitrateOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<BatchBitrateReport, String> {
+    if options.interval_seconds <= 0.0 {
+        return Err("Interval must be greater than zero".to_string());
+    }
+    let validated_dir = config::validate_path(Path::new(directory))?;
+    if !validated_dir.is_dir() {
+        return Err("Path is not a directory".to_string());
+    }
+
+    on_progress(0.0, "Finding media files...");
+    let paths = collect_media_files(&validated_dir, options.recursive)?;
+    if paths.is_empty() {
+        return Err("No video or audio files found in the folder".to_string());
+    }
+    info!(
+        "Batch bitrate report for {}: {} files",
+        directory,
+        paths.len()
+    );
+
+    let total = paths.len() as f64;
+    let on_progress = RefCell::new(on_progress);
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+    let params = AnalysisParams {
+        interval_seconds: options.interval_seconds,
+        sampling: options.sampling.clone(),
+    };
+
+    for (idx, path) in paths.iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err(CANCELLED_MESSAGE.to_string());
+        }
+        let path_str = path.to_string_lossy().to_string();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path_str.clone());
+        let stage = format!("Analyzing {} ({}/{})", name, idx + 1, paths.len());
+        (on_progress.borrow_mut())(idx as f64 / total * 100.0, &stage);
+
+        let analysis = match get_cached_analysis(&path_str, &params) {
+            Some(cached) => Ok(cached),
+            None => {
+                let result = analyze_overall(
+                    &path_str,
+                    options.interval_seconds,
+                    &options.sampling,
+                    cancelled,
+                    &|progress: BitrateProgress| {
+                        let pct = (idx as f64 + progress.percentage / 100.0) / total * 100.0;
+                        (on_progress.borrow_mut())(pct, &stage);
+                    },
+                );
+                if let Ok(analysis) = &result {
+                    if let Err(e) = save_to_cache(&path_str, &params, analysis) {
+                        warn!("Failed to cache bitrate analysis for {}: {}", path_str, e);
+                    }
+                }
+                result
+            }
+        };
+
+        match analysis {
+            Ok(analysis) => {
+                let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                files.push(file_report(&analysis, file_size));
+            }
+            Err(_) if cancelled.load(Ordering::SeqCst) => {
+                return Err(CANCELLED_MESSAGE.to_string());
+            }
+            Err(error) => {
+                warn!("Batch bitrate analysis failed for {}: {}", path_str, error);
+                failed.push(BatchFileError {
+                    path: path_str,
+                    error,
+                });
+            }
+        }
+    }
+
+    let report = summarize(directory, files, failed, options);
+    info!(
+        "Batch bitrate report complete for {}: {} analyzed, {} failed, {} wasteful",
+        directory,
+        report.files.len(),
+        report.failed.len(),
+        report.wasteful.len()
+    );
+    (on_progress.borrow_mut())(100.0, "Complete");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(
+        path: &str,
+        avg_bitrate: u64,
+        codec: Option<&str>,
+        bpp: Option<f64>,
+    ) -> BatchFileReport {
+        BatchFileReport {
+            path: path.to_string(),
+            file_size: 0,
+            duration: 100.0,
+            avg_bitrate,
+            peak_bitrate: avg_bitrate * 2,
+            std_deviation: 0.0,
+            video_codec: codec.map(|c| c.to_string()),
+            width: Some(1920),
+            height: Some(1080),
+            frame_rate: Some(24.0),
+            video_bitrate: Some(avg_bitrate),
+            bits_per_pixel: bpp,
+            ratio_to_median: 1.0,
+            outlier: false,
+            sampled: false,
+            from_cache: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_frame_rate() {
+        assert!((parse_frame_rate("30000/1001").unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(parse_frame_rate("25"), Some(25.0));
+        assert_eq!(parse_frame_rate("0/0"), None);
+        assert_eq!(parse_frame_rate("n/a"), None);
+    }
+
+    #[test]
+    fn test_summarize_outliers() {
+        let files = vec![
+            report("/a.mkv", 4_000_000, None, None),
+            report("/b.mkv", 5_000_000, None, None),
+            report("/c.mkv", 6_000_000, None, None),
+            report("/d.mkv", 20_000_000, None, None),
+            report("/e.mkv", 1_000_000, None, None),
+        ];
+        let summary = summarize("/media", files, Vec::new(), &BatchBitrateOptions::default());
+        assert_eq!(summary.median_avg_bitrate, 5_000_000);
+        let outliers: Vec<&str> = summary
+            .files
+            .iter()
+            .filter(|f| f.outlier)
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(outliers, vec!["/d.mkv", "/e.mkv"]);
+        assert_eq!(summary.files[3].ratio_to_median, 4.0);
+    }
+
+    #[test]
+    fn test_summarize_ranks_wasteful_encodes() {
+        let files = vec![
+            report("/lean.mkv", 3_000_000, Some("h264"), Some(0.06)),
+            report("/fat.mkv", 20_000_000, Some("h264"), Some(0.40)),
+            report("/fat_hevc.mkv", 10_000_000, Some("hevc"), Some(0.12)),
+            report("/prores.mov", 150_000_000, Some("prores"), Some(3.0)),
+        ];
+        let summary = summarize("/media", files, Vec::new(), &BatchBitrateOptions::default());
+        let ranked: Vec<&str> = summary.wasteful.iter().map(|w| w.path.as_str()).collect();
+        // Intermediate codecs and encodes under the reference are not listed
+        assert_eq!(ranked, vec!["/fat.mkv", "/fat_hevc.mkv"]);
+        assert!((summary.wasteful[0].excess_ratio - 4.0).abs() < 1e-9);
+        // 20 Mbps over 100s is 250 MB, three quarters of which is excess
+        assert!(
+            summary.wasteful[0]
+                .potential_savings_bytes
+                .abs_diff(187_500_000)
+                <= 1
+        );
+        assert!((summary.median_bits_per_pixel.unwrap() - 0.26).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_collect_media_files() {
+        let dir = std::env::temp_dir().join(format!("seer_batch_{}", std::process::id()));
+        let nested = dir.join("season 1");
+        fs::create_dir_all(&nested).unwrap();
+        for name in ["b.mkv", "a.mp3", "cover.jpg", ".hidden.mp4"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        fs::write(nested.join("e01.mp4"), b"").unwrap();
+
+        let flat = collect_media_files(&dir, false).unwrap();
+        let names: Vec<String> = flat
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["a.mp3", "b.mkv"]);
+        assert_eq!(collect_media_files(&dir, true).unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_media_files_skips_symlinked_dirs() {
+        let dir = std::env::temp_dir().join(format!("seer_batch_loop_{}", std::process::id()));
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("e01.mp4"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, nested.join("loop")).unwrap();
+
+        let files = collect_media_files(&dir, true).unwrap();
+        assert_eq!(files, vec![nested.join("e01.mp4")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
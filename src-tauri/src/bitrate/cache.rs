@@ -1,42 +1,414 @@
 //! Bitrate analysis cache module
 //!
-//! This module previously handled file-based caching for bitrate analysis results.
-//! Caching is now handled by the frontend via SQLite database for better consistency
-//! and to centralize all data storage in one place.
-//!
-//! The functions here are kept as stubs to maintain API compatibility with the
-//! commands module, but they now delegate caching responsibility to the frontend.
+//! Overall bitrate analyses are cached in the app's SQLite database
+//! (`bitrate_analysis`, `bitrate_data_points`, `bitrate_statistics`,
+//! `peak_intervals`, `stream_contributions`, `stream_data_points`), keyed by
+//! file path, `compute_file_hash` and the parameters the analysis was run with
+//! (see `AnalysisParams`). The frontend reads and writes the same
+//! tables, so analyses cached by either side are visible to the other.
+
+use log::{debug, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+
+use crate::database;
+use crate::types::{
+    BitrateDataPoint, BitrateStatistics, OverallBitrateAnalysis, PeakInterval, SamplingConfig,
+    StreamContribution, StreamType,
+};
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+/// Parameters an analysis was run with; a cached analysis is only reused for
+/// a request with the same parameters
+#[derive(Debug, Clone)]
+pub struct AnalysisParams {
+    pub interval_seconds: f64,
+    pub sampling: SamplingConfig,
+}
+
+impl AnalysisParams {
+    fn sampling_json(&self) -> Result<String, String> {
+        serde_json::to_string(&self.sampling)
+            .map_err(|e| format!("Failed to serialize sampling config: {}", e))
+    }
+}
 
-use crate::types::OverallBitrateAnalysis;
+fn stream_type_name(stream_type: &StreamType) -> String {
+    serde_json::to_value(stream_type)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn parse_stream_type(name: &str) -> StreamType {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .unwrap_or(StreamType::Unknown)
+}
+
+/// Interval the data points were aggregated at (spacing of the first two points)
+pub(super) fn interval_seconds(data_points: &[BitrateDataPoint]) -> f64 {
+    match data_points {
+        [first, second, ..] => (second.timestamp - first.timestamp).max(0.0),
+        _ => 0.0,
+    }
+}
+
+fn load_data_points(
+    conn: &Connection,
+    sql: &str,
+    owner_id: i64,
+) -> Result<Vec<BitrateDataPoint>, String> {
+    let mut stmt = conn.prepare(sql).map_err(sql_err)?;
+    let rows = stmt
+        .query_map(params![owner_id], |row| {
+            Ok(BitrateDataPoint {
+                timestamp: row.get(0)?,
+                bitrate: row.get::<_, i64>(1)? as u64,
+                frame_type: row.get(2)?,
+            })
+        })
+        .map_err(sql_err)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(sql_err)
+}
 
-/// Get cached analysis - always returns None as caching is now handled by frontend
+/// id, duration, interval_seconds, sampling, sampling_config
+type AnalysisRow = (i64, f64, f64, Option<String>, Option<String>);
+
+/// Load a cached analysis for `path` if it was stored for `file_hash`
 ///
-/// The frontend checks the SQLite database for cached analysis before calling
-/// the backend analysis functions. This stub is kept for API compatibility.
-pub fn get_cached_analysis(_path: &str) -> Option<OverallBitrateAnalysis> {
-    // Caching is now handled by the frontend via SQLite database
-    // See: src/lib/database.ts - getBitrateAnalysis()
-    None
+/// With `params`, only an analysis run with the same parameters is returned;
+/// without, the most recent analysis of the file is returned whatever its
+/// parameters.
+pub fn load_analysis(
+    conn: &Connection,
+    path: &str,
+    file_hash: &str,
+    params: Option<&AnalysisParams>,
+) -> Result<Option<OverallBitrateAnalysis>, String> {
+    let analysis: Option<AnalysisRow> = conn
+        .query_row(
+            "SELECT id, duration, interval_seconds, sampling, sampling_config FROM bitrate_analysis
+             WHERE file_path = ?1 AND file_hash = ?2
+             ORDER BY created_at DESC LIMIT 1",
+            params![path, file_hash],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(sql_err)?;
+    let Some((analysis_id, duration, cached_interval, sampling, sampling_config)) = analysis else {
+        return Ok(None);
+    };
+    if let Some(params) = params {
+        if cached_interval != params.interval_seconds {
+            debug!(
+                "Cached bitrate analysis for {} used a {}s interval, {}s requested",
+                path, cached_interval, params.interval_seconds
+            );
+            return Ok(None);
+        }
+        if sampling_config.as_deref() != Some(params.sampling_json()?.as_str()) {
+            debug!(
+                "Cached bitrate analysis for {} used different sampling settings",
+                path
+            );
+            return Ok(None);
+        }
+    }
+
+    let stats = conn
+        .query_row(
+            "SELECT id, min_bitrate, max_bitrate, avg_bitrate, median_bitrate, std_deviation, total_frames
+             FROM bitrate_statistics WHERE analysis_id = ?1",
+            params![analysis_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    BitrateStatistics {
+                        min_bitrate: row.get::<_, i64>(1)? as u64,
+                        max_bitrate: row.get::<_, i64>(2)? as u64,
+                        avg_bitrate: row.get::<_, i64>(3)? as u64,
+                        median_bitrate: row.get::<_, i64>(4)? as u64,
+                        std_deviation: row.get(5)?,
+                        peak_intervals: Vec::new(),
+                        total_frames: row.get::<_, i64>(6)? as usize,
+                    },
+                ))
+            },
+        )
+        .optional()
+        .map_err(sql_err)?;
+    let Some((statistics_id, mut statistics)) = stats else {
+        warn!("Cached bitrate analysis {} has no statistics", analysis_id);
+        return Ok(None);
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT start_time, end_time, peak_bitrate, duration FROM peak_intervals
+             WHERE statistics_id = ?1 ORDER BY start_time",
+        )
+        .map_err(sql_err)?;
+    statistics.peak_intervals = stmt
+        .query_map(params![statistics_id], |row| {
+            Ok(PeakInterval {
+                start_time: row.get(0)?,
+                end_time: row.get(1)?,
+                peak_bitrate: row.get::<_, i64>(2)? as u64,
+                duration: row.get(3)?,
+            })
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?;
+
+    let data_points = load_data_points(
+        conn,
+        "SELECT timestamp, bitrate, frame_type FROM bitrate_data_points
+         WHERE analysis_id = ?1 ORDER BY timestamp",
+        analysis_id,
+    )?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, stream_index, stream_type, codec_name, percentage FROM stream_contributions
+             WHERE analysis_id = ?1 ORDER BY id",
+        )
+        .map_err(sql_err)?;
+    let contributions = stmt
+        .query_map(params![analysis_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?;
+
+    let mut stream_contributions = Vec::with_capacity(contributions.len());
+    for (contribution_id, stream_index, stream_type, codec_name, percentage) in contributions {
+        stream_contributions.push(StreamContribution {
+            stream_index,
+            stream_type: parse_stream_type(&stream_type),
+            codec_name,
+            percentage,
+            data_points: load_data_points(
+                conn,
+                "SELECT timestamp, bitrate, frame_type FROM stream_data_points
+                 WHERE contribution_id = ?1 ORDER BY timestamp",
+                contribution_id,
+            )?,
+        });
+    }
+
+    Ok(Some(OverallBitrateAnalysis {
+        path: path.to_string(),
+        duration,
+        data_points,
+        statistics,
+        stream_contributions,
+        from_cache: true,
+        sampling: sampling.and_then(|json| serde_json::from_str(&json).ok()),
+    }))
+}
+
+/// Store an analysis, replacing anything previously cached for `path`
+pub fn store_analysis(
+    conn: &mut Connection,
+    path: &str,
+    file_hash: &str,
+    file_size: u64,
+    params: &AnalysisParams,
+    result: &OverallBitrateAnalysis,
+) -> Result<(), String> {
+    let tx = conn.transaction().map_err(sql_err)?;
+
+    tx.execute(
+        "DELETE FROM bitrate_analysis WHERE file_path = ?1",
+        params![path],
+    )
+    .map_err(sql_err)?;
+    tx.execute(
+        "INSERT INTO bitrate_analysis
+         (file_path, file_hash, file_size, duration, interval_seconds, sampling, sampling_config)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            path,
+            file_hash,
+            file_size as i64,
+            result.duration,
+            params.interval_seconds,
+            result
+                .sampling
+                .as_ref()
+                .and_then(|s| serde_json::to_string(s).ok()),
+            params.sampling_json()?
+        ],
+    )
+    .map_err(sql_err)?;
+    let analysis_id = tx.last_insert_rowid();
+
+    {
+        let mut insert_point = tx
+            .prepare(
+                "INSERT INTO bitrate_data_points (analysis_id, timestamp, bitrate, frame_type)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(sql_err)?;
+        for point in &result.data_points {
+            insert_point
+                .execute(params![
+                    analysis_id,
+                    point.timestamp,
+                    point.bitrate as i64,
+                    point.frame_type
+                ])
+                .map_err(sql_err)?;
+        }
+    }
+
+    let stats = &result.statistics;
+    tx.execute(
+        "INSERT INTO bitrate_statistics
+         (analysis_id, min_bitrate, max_bitrate, avg_bitrate, median_bitrate, std_deviation, total_frames)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            analysis_id,
+            stats.min_bitrate as i64,
+            stats.max_bitrate as i64,
+            stats.avg_bitrate as i64,
+            stats.median_bitrate as i64,
+            stats.std_deviation,
+            stats.total_frames as i64
+        ],
+    )
+    .map_err(sql_err)?;
+    let statistics_id = tx.last_insert_rowid();
+
+    for peak in &stats.peak_intervals {
+        tx.execute(
+            "INSERT INTO peak_intervals (statistics_id, start_time, end_time, peak_bitrate, duration)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                statistics_id,
+                peak.start_time,
+                peak.end_time,
+                peak.peak_bitrate as i64,
+                peak.duration
+            ],
+        )
+        .map_err(sql_err)?;
+    }
+
+    for contribution in &result.stream_contributions {
+        tx.execute(
+            "INSERT INTO stream_contributions (analysis_id, stream_index, stream_type, codec_name, percentage)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                analysis_id,
+                contribution.stream_index,
+                stream_type_name(&contribution.stream_type),
+                contribution.codec_name,
+                contribution.percentage
+            ],
+        )
+        .map_err(sql_err)?;
+        let contribution_id = tx.last_insert_rowid();
+
+        let mut insert_point = tx
+            .prepare(
+                "INSERT INTO stream_data_points (contribution_id, timestamp, bitrate, frame_type)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(sql_err)?;
+        for point in &contribution.data_points {
+            insert_point
+                .execute(params![
+                    contribution_id,
+                    point.timestamp,
+                    point.bitrate as i64,
+                    point.frame_type
+                ])
+                .map_err(sql_err)?;
+        }
+    }
+
+    tx.commit().map_err(sql_err)
 }
 
-/// Save analysis to cache - no-op as caching is now handled by frontend
+/// Get a cached analysis for a file, if one exists for its current contents
+/// and was run with `params`
 ///
-/// The frontend saves analysis results to SQLite after receiving them from
-/// the backend. This stub is kept for API compatibility.
-pub fn save_to_cache(_path: &str, _result: &OverallBitrateAnalysis) -> Result<(), String> {
-    // Caching is now handled by the frontend via SQLite database
-    // See: src/lib/database.ts - saveBitrateAnalysis()
+/// Returns None when the database isn't available, nothing is cached, the
+/// file changed since it was analyzed (hash mismatch), or the cached analysis
+/// was run with different parameters.
+pub fn get_cached_analysis(path: &str, params: &AnalysisParams) -> Option<OverallBitrateAnalysis> {
+    lookup(path, Some(params))
+}
+
+/// Get the most recent cached analysis for a file's current contents,
+/// whatever parameters it was run with
+pub fn get_latest_analysis(path: &str) -> Option<OverallBitrateAnalysis> {
+    lookup(path, None)
+}
+
+fn lookup(path: &str, params: Option<&AnalysisParams>) -> Option<OverallBitrateAnalysis> {
+    let file_hash = compute_file_hash(path).ok()?;
+    let conn = database::open_connection()
+        .map_err(|e| debug!("Bitrate cache unavailable: {}", e))
+        .ok()?;
+    match load_analysis(&conn, path, &file_hash, params) {
+        Ok(cached) => {
+            if cached.is_some() {
+                debug!("Bitrate cache hit for {}", path);
+            }
+            cached
+        }
+        Err(e) => {
+            warn!("Failed to read bitrate cache for {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Save an analysis to the cache, keyed by path, the file's current hash and
+/// the parameters it was run with
+pub fn save_to_cache(
+    path: &str,
+    params: &AnalysisParams,
+    result: &OverallBitrateAnalysis,
+) -> Result<(), String> {
+    let file_hash = compute_file_hash(path)?;
+    let file_size = fs::metadata(path)
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+    let mut conn = database::open_connection()?;
+    store_analysis(&mut conn, path, &file_hash, file_size, params, result)?;
+    debug!("Saved bitrate analysis for {} to cache", path);
     Ok(())
 }
 
-/// Clear cache - returns 0 as file-based cache is no longer used
+/// Remove all cached bitrate analyses, returning how many were removed
 ///
-/// Cache clearing is now handled via the frontend's database functions.
-/// See: Settings dialog -> Clear Bitrate Data
+/// Data points, statistics and stream contributions are removed by cascade.
 pub fn clear_cache() -> Result<usize, String> {
-    // File-based cache has been removed
-    // Database cache is cleared via frontend: clearAllBitrateAnalysis()
-    Ok(0)
+    let conn = database::open_connection()?;
+    conn.execute("DELETE FROM bitrate_analysis", [])
+        .map_err(sql_err)
 }
 
 /// Compute a fast hash for a file based on size, mtime, and sample bytes
@@ -86,3 +458,203 @@ pub fn compute_file_hash(path: &str) -> Result<String, String> {
     let hash = hasher.finalize();
     Ok(format!("{:x}", hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        database::configure_connection(&conn).unwrap();
+        database::apply_migrations(&conn);
+        conn
+    }
+
+    fn params() -> AnalysisParams {
+        AnalysisParams {
+            interval_seconds: 1.0,
+            sampling: SamplingConfig::default(),
+        }
+    }
+
+    fn point(timestamp: f64, bitrate: u64) -> BitrateDataPoint {
+        BitrateDataPoint {
+            timestamp,
+            bitrate,
+            frame_type: Some("I".to_string()),
+        }
+    }
+
+    fn sample_analysis() -> OverallBitrateAnalysis {
+        OverallBitrateAnalysis {
+            path: "/media/a.mkv".to_string(),
+            duration: 2.0,
+            data_points: vec![point(0.0, 1000), point(1.0, 3000)],
+            statistics: BitrateStatistics {
+                min_bitrate: 1000,
+                max_bitrate: 3000,
+                avg_bitrate: 2000,
+                median_bitrate: 2000,
+                std_deviation: 1000.0,
+                peak_intervals: vec![PeakInterval {
+                    start_time: 1.0,
+                    end_time: 2.0,
+                    peak_bitrate: 3000,
+                    duration: 1.0,
+                }],
+                total_frames: 2,
+            },
+            stream_contributions: vec![StreamContribution {
+                stream_index: 1,
+                stream_type: StreamType::Audio,
+                codec_name: "aac".to_string(),
+                percentage: 10.0,
+                data_points: vec![point(0.0, 100)],
+            }],
+            from_cache: false,
+            sampling: None,
+        }
+    }
+
+    #[test]
+    fn test_store_and_load_roundtrip() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash1",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+
+        let cached = load_analysis(&conn, &analysis.path, "hash1", Some(&params()))
+            .unwrap()
+            .unwrap();
+        assert!(cached.from_cache);
+        assert_eq!(cached.duration, 2.0);
+        assert_eq!(cached.data_points.len(), 2);
+        assert_eq!(cached.data_points[1].bitrate, 3000);
+        assert_eq!(cached.statistics.median_bitrate, 2000);
+        assert_eq!(cached.statistics.peak_intervals.len(), 1);
+        assert_eq!(cached.stream_contributions.len(), 1);
+        assert_eq!(
+            cached.stream_contributions[0].stream_type,
+            StreamType::Audio
+        );
+        assert_eq!(cached.stream_contributions[0].data_points[0].bitrate, 100);
+
+        let interval: f64 = conn
+            .query_row("SELECT interval_seconds FROM bitrate_analysis", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(interval, 1.0);
+    }
+
+    #[test]
+    fn test_load_misses_on_hash_change() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash1",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+        assert!(
+            load_analysis(&conn, &analysis.path, "hash2", Some(&params()))
+                .unwrap()
+                .is_none()
+        );
+        assert!(load_analysis(&conn, "/other.mkv", "hash1", Some(&params()))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_load_misses_on_interval_change() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash1",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+
+        let params = AnalysisParams {
+            interval_seconds: 5.0,
+            ..params()
+        };
+        assert!(load_analysis(&conn, &analysis.path, "hash1", Some(&params))
+            .unwrap()
+            .is_none());
+        assert!(load_analysis(&conn, &analysis.path, "hash1", None)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_load_misses_on_sampling_change() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash1",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+
+        let mut params = params();
+        params.sampling.sample_count *= 2;
+        assert!(load_analysis(&conn, &analysis.path, "hash1", Some(&params))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_store_replaces_previous_analysis() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash1",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+        store_analysis(
+            &mut conn,
+            &analysis.path,
+            "hash2",
+            4096,
+            &params(),
+            &analysis,
+        )
+        .unwrap();
+
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("bitrate_analysis"), 1);
+        // Old rows are removed by cascade
+        assert_eq!(count("bitrate_data_points"), 2);
+        assert_eq!(count("peak_intervals"), 1);
+        assert_eq!(count("stream_data_points"), 1);
+    }
+}
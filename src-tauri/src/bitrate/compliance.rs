@@ -0,0 +1,302 @@
+//! Streaming-compliance checks against VBV / HRD bitrate ceilings
+//!
+//! Simulates the decoder buffer over the per-interval bitrate series from
+//! `aggregate_bitrate_intervals`: the buffer fills at `maxrate` and drains at
+//! the measured bitrate. Going below empty is an underflow (the stream is too
+//! bursty for the target); going above full is an overflow, which only counts
+//! for CBR targets that require a constant delivery rate.
+//!
+//! The simulation is as precise as the series it runs on; shorter analysis
+//! intervals catch short bursts that a 1s series averages away.
+
+use log::debug;
+
+use super::cache::interval_seconds;
+use crate::types::{
+    BitrateDataPoint, BufferLevelPoint, ComplianceOptions, CompliancePreset, CompliancePresetInfo,
+    ComplianceReport, ComplianceViolation, ComplianceViolationKind, VbvSettings,
+};
+
+/// All presets in display order
+pub const COMPLIANCE_PRESETS: [CompliancePreset; 7] = [
+    CompliancePreset::BluRay,
+    CompliancePreset::Dvd,
+    CompliancePreset::Hls1080p,
+    CompliancePreset::Hls720p,
+    CompliancePreset::Hls540p,
+    CompliancePreset::Hls360p,
+    CompliancePreset::BroadcastAtsc,
+];
+
+/// Human-readable name and buffer model for a preset
+///
+/// HLS rungs follow the Apple authoring ladder with a two-second buffer.
+pub fn preset_settings(preset: CompliancePreset) -> (&'static str, VbvSettings) {
+    let (name, maxrate_kbps, bufsize_kbits, constant_bitrate) = match preset {
+        CompliancePreset::BluRay => ("Blu-ray (H.264 L4.1)", 40_000, 30_000, false),
+        CompliancePreset::Dvd => ("DVD-Video", 9_800, 1_835, false),
+        CompliancePreset::Hls1080p => ("HLS 1080p rung", 7_800, 15_600, false),
+        CompliancePreset::Hls720p => ("HLS 720p rung", 4_500, 9_000, false),
+        CompliancePreset::Hls540p => ("HLS 540p rung", 2_000, 4_000, false),
+        CompliancePreset::Hls360p => ("HLS 360p rung", 730, 1_460, false),
+        CompliancePreset::BroadcastAtsc => ("ATSC broadcast (CBR)", 19_390, 7_995, true),
+    };
+    (
+        name,
+        VbvSettings {
+            maxrate_kbps,
+            bufsize_kbits,
+            constant_bitrate,
+        },
+    )
+}
+
+/// Presets with their settings, for the frontend picker
+pub fn list_compliance_presets() -> Vec<CompliancePresetInfo> {
+    COMPLIANCE_PRESETS
+        .iter()
+        .map(|&preset| {
+            let (name, settings) = preset_settings(preset);
+            CompliancePresetInfo {
+                preset,
+                name: name.to_string(),
+                settings,
+            }
+        })
+        .collect()
+}
+
+/// Combine the preset (if any) with explicit overrides
+pub fn resolve_vbv_settings(options: &ComplianceOptions) -> Result<VbvSettings, String> {
+    let base = options.preset.map(|p| preset_settings(p).1);
+
+    let maxrate_kbps = options
+        .maxrate_kbps
+        .or(base.as_ref().map(|b| b.maxrate_kbps))
+        .ok_or("Choose a preset or set maxrate")?;
+    let bufsize_kbits = options
+        .bufsize_kbits
+        .or(base.as_ref().map(|b| b.bufsize_kbits))
+        .ok_or("Choose a preset or set bufsize")?;
+    let constant_bitrate = options
+        .constant_bitrate
+        .or(base.as_ref().map(|b| b.constant_bitrate))
+        .unwrap_or(false);
+
+    if maxrate_kbps == 0 || bufsize_kbits == 0 {
+        return Err("maxrate and bufsize must be greater than zero".to_string());
+    }
+    if !(0.0..=1.0).contains(&options.initial_fullness) {
+        return Err("Initial buffer fullness must be between 0 and 1".to_string());
+    }
+
+    Ok(VbvSettings {
+        maxrate_kbps,
+        bufsize_kbits,
+        constant_bitrate,
+    })
+}
+
+/// Open violation being extended while consecutive intervals keep violating
+struct OpenViolation {
+    kind: ComplianceViolationKind,
+    start_time: f64,
+    peak_bitrate: u64,
+    max_excess: f64,
+}
+
+impl OpenViolation {
+    fn close(self, end_time: f64) -> ComplianceViolation {
+        ComplianceViolation {
+            kind: self.kind,
+            start_time: self.start_time,
+            end_time,
+            peak_bitrate: self.peak_bitrate,
+            duration: end_time - self.start_time,
+            max_excess_kbits: self.max_excess / 1000.0,
+        }
+    }
+}
+
+/// Run the buffer model over a bitrate series
+pub fn check_compliance(
+    path: &str,
+    data_points: &[BitrateDataPoint],
+    settings: &VbvSettings,
+    options: &ComplianceOptions,
+) -> ComplianceReport {
+    let interval = interval_seconds(data_points);
+    let maxrate = settings.maxrate_kbps as f64 * 1000.0;
+    let bufsize = settings.bufsize_kbits as f64 * 1000.0;
+
+    let mut level = bufsize * options.initial_fullness;
+    let mut violations = Vec::new();
+    let mut open: Option<OpenViolation> = None;
+    let mut buffer_levels = Vec::with_capacity(data_points.len());
+    let mut time_over_maxrate = 0.0;
+
+    for point in data_points {
+        let end_time = point.timestamp + interval;
+        if point.bitrate as f64 > maxrate {
+            time_over_maxrate += interval;
+        }
+
+        // Fill at maxrate and drain at the measured rate over the interval
+        level += (maxrate - point.bitrate as f64) * interval;
+        let violation = if level < 0.0 {
+            let excess = -level;
+            level = 0.0;
+            Some((ComplianceViolationKind::Underflow, excess))
+        } else if level > bufsize {
+            let excess = level - bufsize;
+            level = bufsize;
+            settings
+                .constant_bitrate
+                .then_some((ComplianceViolationKind::Overflow, excess))
+        } else {
+            None
+        };
+
+        match (violation, open.take()) {
+            (Some((kind, excess)), Some(mut current)) if current.kind == kind => {
+                current.peak_bitrate = current.peak_bitrate.max(point.bitrate);
+                current.max_excess = current.max_excess.max(excess);
+                open = Some(current);
+            }
+            (violation, current) => {
+                if let Some(current) = current {
+                    violations.push(current.close(point.timestamp));
+                }
+                open = violation.map(|(kind, excess)| OpenViolation {
+                    kind,
+                    start_time: point.timestamp,
+                    peak_bitrate: point.bitrate,
+                    max_excess: excess,
+                });
+            }
+        }
+
+        buffer_levels.push(BufferLevelPoint {
+            timestamp: end_time,
+            fullness: level / bufsize,
+        });
+    }
+    if let (Some(current), Some(last)) = (open, data_points.last()) {
+        violations.push(current.close(last.timestamp + interval));
+    }
+
+    debug!(
+        "check_compliance: {} points, maxrate={}k, bufsize={}k, {} violations",
+        data_points.len(),
+        settings.maxrate_kbps,
+        settings.bufsize_kbits,
+        violations.len()
+    );
+
+    ComplianceReport {
+        path: path.to_string(),
+        preset: options.preset,
+        settings: settings.clone(),
+        stream_index: options.stream_index,
+        compliant: violations.is_empty(),
+        violations,
+        buffer_levels,
+        time_over_maxrate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(bitrates: &[u64]) -> Vec<BitrateDataPoint> {
+        bitrates
+            .iter()
+            .enumerate()
+            .map(|(i, &bitrate)| BitrateDataPoint {
+                timestamp: i as f64,
+                bitrate,
+                frame_type: None,
+            })
+            .collect()
+    }
+
+    fn settings(maxrate_kbps: u64, bufsize_kbits: u64, cbr: bool) -> VbvSettings {
+        VbvSettings {
+            maxrate_kbps,
+            bufsize_kbits,
+            constant_bitrate: cbr,
+        }
+    }
+
+    #[test]
+    fn test_resolve_preset_with_override() {
+        let options = ComplianceOptions {
+            preset: Some(CompliancePreset::Hls720p),
+            bufsize_kbits: Some(4_500),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_vbv_settings(&options).unwrap(),
+            settings(4_500, 4_500, false)
+        );
+        assert!(resolve_vbv_settings(&ComplianceOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_within_maxrate_is_compliant() {
+        let options = ComplianceOptions::default();
+        let report = check_compliance(
+            "/a.ts",
+            &series(&[800_000, 1_000_000, 900_000]),
+            &settings(1_000, 2_000, false),
+            &options,
+        );
+        assert!(report.compliant);
+        assert_eq!(report.buffer_levels.len(), 3);
+        assert_eq!(report.time_over_maxrate, 0.0);
+    }
+
+    #[test]
+    fn test_burst_underflows() {
+        // Buffer starts at 1800 kbit; 3s at 2 Mbps against 1 Mbps fill drains 3000 kbit
+        let options = ComplianceOptions::default();
+        let report = check_compliance(
+            "/a.ts",
+            &series(&[1_000_000, 2_000_000, 2_000_000, 2_500_000, 500_000]),
+            &settings(1_000, 2_000, false),
+            &options,
+        );
+        assert!(!report.compliant);
+        assert_eq!(report.violations.len(), 1);
+        let v = &report.violations[0];
+        assert_eq!(v.kind, ComplianceViolationKind::Underflow);
+        assert_eq!((v.start_time, v.end_time), (2.0, 4.0));
+        assert_eq!(v.peak_bitrate, 2_500_000);
+        assert_eq!(v.max_excess_kbits, 1_500.0);
+        assert_eq!(report.time_over_maxrate, 3.0);
+    }
+
+    #[test]
+    fn test_overflow_only_for_cbr() {
+        let points = series(&[500_000, 500_000, 500_000]);
+        let options = ComplianceOptions::default();
+        let vbr = check_compliance("/a.ts", &points, &settings(1_000, 1_000, false), &options);
+        assert!(vbr.compliant);
+        assert_eq!(vbr.buffer_levels.last().unwrap().fullness, 1.0);
+
+        let cbr = check_compliance("/a.ts", &points, &settings(1_000, 1_000, true), &options);
+        assert_eq!(cbr.violations.len(), 1);
+        assert_eq!(cbr.violations[0].kind, ComplianceViolationKind::Overflow);
+        assert_eq!(cbr.violations[0].end_time, 3.0);
+    }
+
+    #[test]
+    fn test_presets_listed() {
+        let presets = list_compliance_presets();
+        assert_eq!(presets.len(), COMPLIANCE_PRESETS.len());
+        assert!(presets
+            .iter()
+            .any(|p| p.preset == CompliancePreset::BroadcastAtsc && p.settings.constant_bitrate));
+    }
+}
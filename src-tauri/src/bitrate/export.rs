@@ -0,0 +1,489 @@
+//! Bitrate analysis export (CSV, JSON and standalone SVG chart)
+//!
+//! Renders an `OverallBitrateAnalysis` the same way regardless of caller, so
+//! reports written by batch jobs match the ones exported from the panel.
+//! The SVG has no external references and opens in any browser or editor.
+
+use log::info;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+use crate::types::{
+    BitrateExportFormat, BitrateExportResult, OverallBitrateAnalysis, StreamContribution,
+    StreamType,
+};
+
+const SVG_WIDTH: f64 = 1200.0;
+const SVG_HEIGHT: f64 = 480.0;
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 50.0;
+const MARGIN_BOTTOM: f64 = 70.0;
+
+/// Fill colours per stream type; repeated types get progressively lighter
+const VIDEO_COLORS: [&str; 3] = ["#3b82f6", "#60a5fa", "#93c5fd"];
+const AUDIO_COLORS: [&str; 3] = ["#22c55e", "#4ade80", "#86efac"];
+const OTHER_COLORS: [&str; 3] = ["#f59e0b", "#fbbf24", "#fcd34d"];
+
+/// Column header for a stream, e.g. `stream_1_audio`
+fn stream_column(contribution: &StreamContribution) -> String {
+    let kind = serde_json::to_value(&contribution.stream_type)
+        .ok()
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("stream_{}_{}", contribution.stream_index, kind)
+}
+
+/// CSV with a row per interval: timestamp, total bitrate, then each stream
+pub fn analysis_to_csv(analysis: &OverallBitrateAnalysis) -> String {
+    let mut out = String::from("timestamp,bitrate");
+    for contribution in &analysis.stream_contributions {
+        out.push(',');
+        out.push_str(&stream_column(contribution));
+    }
+    out.push('\n');
+
+    for (idx, point) in analysis.data_points.iter().enumerate() {
+        let _ = write!(out, "{},{}", point.timestamp, point.bitrate);
+        for contribution in &analysis.stream_contributions {
+            match contribution.data_points.get(idx) {
+                Some(p) => {
+                    let _ = write!(out, ",{}", p.bitrate);
+                }
+                None => out.push(','),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Pretty-printed JSON of the full analysis
+pub fn analysis_to_json(analysis: &OverallBitrateAnalysis) -> Result<String, String> {
+    serde_json::to_string_pretty(analysis).map_err(|e| format!("Failed to serialize: {}", e))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Round a tick step up to 1, 2 or 5 times a power of ten
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let normalized = raw / magnitude;
+    let nice = if normalized <= 1.0 {
+        1.0
+    } else if normalized <= 2.0 {
+        2.0
+    } else if normalized <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+fn format_axis_time(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    if total >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            total / 3600,
+            (total % 3600) / 60,
+            total % 60
+        )
+    } else {
+        format!("{}:{:02}", total / 60, total % 60)
+    }
+}
+
+fn format_mbps(bitrate: f64) -> String {
+    let mbps = bitrate / 1_000_000.0;
+    if mbps >= 10.0 {
+        format!("{:.0} Mbps", mbps)
+    } else {
+        format!("{:.1} Mbps", mbps)
+    }
+}
+
+fn stream_color(stream_type: &StreamType, nth_of_type: usize) -> &'static str {
+    let palette = match stream_type {
+        StreamType::Video => &VIDEO_COLORS,
+        StreamType::Audio => &AUDIO_COLORS,
+        _ => &OTHER_COLORS,
+    };
+    palette[nth_of_type.min(palette.len() - 1)]
+}
+
+/// Standalone SVG chart: stacked stream areas, total bitrate line, shaded peaks
+pub fn analysis_to_svg(analysis: &OverallBitrateAnalysis) -> String {
+    let plot_w = SVG_WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_h = SVG_HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let duration = if analysis.duration > 0.0 {
+        analysis.duration
+    } else {
+        analysis
+            .data_points
+            .last()
+            .map(|p| p.timestamp)
+            .unwrap_or(0.0)
+            .max(1.0)
+    };
+
+    // Stacked tops per stream, in contribution order
+    let count = analysis.data_points.len();
+    let mut baseline = vec![0.0; count];
+    let mut layers: Vec<(Vec<f64>, Vec<f64>)> = Vec::new();
+    for contribution in &analysis.stream_contributions {
+        let top: Vec<f64> = (0..count)
+            .map(|i| {
+                baseline[i]
+                    + contribution
+                        .data_points
+                        .get(i)
+                        .map(|p| p.bitrate as f64)
+                        .unwrap_or(0.0)
+            })
+            .collect();
+        layers.push((baseline.clone(), top.clone()));
+        baseline = top;
+    }
+
+    let max_value = analysis
+        .data_points
+        .iter()
+        .map(|p| p.bitrate as f64)
+        .chain(baseline.iter().copied())
+        .fold(0.0, f64::max);
+    let y_step = nice_step(max_value.max(1.0) / 5.0);
+    let y_max = (max_value / y_step).ceil().max(1.0) * y_step;
+
+    let x = |t: f64| MARGIN_LEFT + (t / duration).clamp(0.0, 1.0) * plot_w;
+    let y = |v: f64| MARGIN_TOP + plot_h - (v / y_max).clamp(0.0, 1.0) * plot_h;
+
+    let file_name = Path::new(&analysis.path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| analysis.path.clone());
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+        w = SVG_WIDTH,
+        h = SVG_HEIGHT
+    );
+    svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n");
+    let stats = &analysis.statistics;
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="22" font-size="15" font-weight="bold" fill="#111827">{}</text>"##,
+        MARGIN_LEFT,
+        escape_xml(&file_name)
+    );
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="40" fill="#4b5563">avg {} / median {} / max {}{}</text>"##,
+        MARGIN_LEFT,
+        format_mbps(stats.avg_bitrate as f64),
+        format_mbps(stats.median_bitrate as f64),
+        format_mbps(stats.max_bitrate as f64),
+        if analysis.sampling.is_some() {
+            " (sampled)"
+        } else {
+            ""
+        }
+    );
+
+    // Peak intervals behind everything else
+    for peak in &stats.peak_intervals {
+        let x0 = x(peak.start_time);
+        let x1 = x(peak.end_time).max(x0 + 1.0);
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#ef4444" fill-opacity="0.15"/>"##,
+            x0,
+            MARGIN_TOP,
+            x1 - x0,
+            plot_h
+        );
+    }
+
+    // Grid and y-axis labels
+    let mut tick = 0.0;
+    while tick <= y_max + y_step / 2.0 {
+        let ty = y(tick);
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="{ty:.1}" x2="{:.1}" y2="{ty:.1}" stroke="#e5e7eb"/>"##,
+            MARGIN_LEFT,
+            MARGIN_LEFT + plot_w
+        );
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" text-anchor="end" fill="#6b7280">{}</text>"##,
+            MARGIN_LEFT - 6.0,
+            ty + 4.0,
+            format_mbps(tick)
+        );
+        tick += y_step;
+    }
+
+    // X-axis labels
+    let x_step = nice_step(duration / 8.0);
+    let mut t = 0.0;
+    while t <= duration + x_step / 2.0 {
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" text-anchor="middle" fill="#6b7280">{}</text>"##,
+            x(t),
+            MARGIN_TOP + plot_h + 18.0,
+            format_axis_time(t.min(duration))
+        );
+        t += x_step;
+    }
+
+    // Stream contribution areas
+    let mut seen: Vec<&StreamType> = Vec::new();
+    let mut legend: Vec<(String, &'static str)> = Vec::new();
+    for (contribution, (base, top)) in analysis.stream_contributions.iter().zip(&layers) {
+        let nth = seen
+            .iter()
+            .filter(|t| **t == &contribution.stream_type)
+            .count();
+        seen.push(&contribution.stream_type);
+        let color = stream_color(&contribution.stream_type, nth);
+
+        let mut d = String::new();
+        for (i, point) in analysis.data_points.iter().enumerate() {
+            let cmd = if i == 0 { 'M' } else { 'L' };
+            let _ = write!(d, "{}{:.1},{:.1} ", cmd, x(point.timestamp), y(top[i]));
+        }
+        for (i, point) in analysis.data_points.iter().enumerate().rev() {
+            let _ = write!(d, "L{:.1},{:.1} ", x(point.timestamp), y(base[i]));
+        }
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}Z" fill="{}" fill-opacity="0.6" stroke="none"/>"#,
+            d, color
+        );
+        legend.push((
+            format!(
+                "#{} {} ({:.1}%)",
+                contribution.stream_index, contribution.codec_name, contribution.percentage
+            ),
+            color,
+        ));
+    }
+
+    // Total bitrate line
+    if !analysis.data_points.is_empty() {
+        let points: Vec<String> = analysis
+            .data_points
+            .iter()
+            .map(|p| format!("{:.1},{:.1}", x(p.timestamp), y(p.bitrate as f64)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r##"<polyline points="{}" fill="none" stroke="#1f2937" stroke-width="1.5"/>"##,
+            points.join(" ")
+        );
+    }
+
+    // Axes
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{l:.1}" y1="{b:.1}" x2="{r:.1}" y2="{b:.1}" stroke="#9ca3af"/>"##,
+        l = MARGIN_LEFT,
+        r = MARGIN_LEFT + plot_w,
+        b = MARGIN_TOP + plot_h
+    );
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{l:.1}" y1="{t:.1}" x2="{l:.1}" y2="{b:.1}" stroke="#9ca3af"/>"##,
+        l = MARGIN_LEFT,
+        t = MARGIN_TOP,
+        b = MARGIN_TOP + plot_h
+    );
+
+    // Legend
+    let legend_y = SVG_HEIGHT - 22.0;
+    let mut lx = MARGIN_LEFT;
+    legend.push(("Total".to_string(), "#1f2937"));
+    if !stats.peak_intervals.is_empty() {
+        legend.push(("Peak intervals".to_string(), "#ef4444"));
+    }
+    for (label, color) in &legend {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="12" height="12" fill="{}" fill-opacity="0.6"/>"#,
+            lx,
+            legend_y - 10.0,
+            color
+        );
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" fill="#374151">{}</text>"##,
+            lx + 16.0,
+            legend_y,
+            escape_xml(label)
+        );
+        lx += 16.0 + label.chars().count() as f64 * 7.0 + 20.0;
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Write an analysis to `output_path` in the given format
+///
+/// The output directory must be within the allowed directories; an existing
+/// file at the path is replaced.
+pub fn export_analysis(
+    analysis: &OverallBitrateAnalysis,
+    format: BitrateExportFormat,
+    output_path: &str,
+) -> Result<BitrateExportResult, String> {
+    let output = Path::new(output_path);
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or("Invalid output path")?;
+    let validated_parent = config::validate_path(parent)?;
+    let file_name = output.file_name().ok_or("Invalid output path")?;
+    let output = validated_parent.join(file_name);
+    if output.is_dir() {
+        return Err(format!("Output is a directory: {}", output.display()));
+    }
+
+    let content = match format {
+        BitrateExportFormat::Csv => analysis_to_csv(analysis),
+        BitrateExportFormat::Json => analysis_to_json(analysis)?,
+        BitrateExportFormat::Svg => analysis_to_svg(analysis),
+    };
+    fs::write(&output, &content).map_err(|e| format!("Failed to write export: {}", e))?;
+
+    info!(
+        "Exported bitrate analysis of {} as {:?} to {}",
+        analysis.path,
+        format,
+        output.display()
+    );
+
+    Ok(BitrateExportResult {
+        output_path: output.to_string_lossy().to_string(),
+        format,
+        bytes_written: content.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BitrateDataPoint, BitrateStatistics, PeakInterval};
+
+    fn points(bitrates: &[u64]) -> Vec<BitrateDataPoint> {
+        bitrates
+            .iter()
+            .enumerate()
+            .map(|(i, &bitrate)| BitrateDataPoint {
+                timestamp: i as f64,
+                bitrate,
+                frame_type: None,
+            })
+            .collect()
+    }
+
+    fn analysis() -> OverallBitrateAnalysis {
+        OverallBitrateAnalysis {
+            path: "/media/a & b.mkv".to_string(),
+            duration: 3.0,
+            data_points: points(&[3_000_000, 5_000_000, 3_500_000]),
+            statistics: BitrateStatistics {
+                min_bitrate: 3_000_000,
+                max_bitrate: 5_000_000,
+                avg_bitrate: 3_833_333,
+                median_bitrate: 3_500_000,
+                std_deviation: 850_000.0,
+                peak_intervals: vec![PeakInterval {
+                    start_time: 1.0,
+                    end_time: 2.0,
+                    peak_bitrate: 5_000_000,
+                    duration: 1.0,
+                }],
+                total_frames: 90,
+            },
+            stream_contributions: vec![
+                StreamContribution {
+                    stream_index: 0,
+                    stream_type: StreamType::Video,
+                    codec_name: "h264".to_string(),
+                    percentage: 95.0,
+                    data_points: points(&[2_800_000, 4_800_000, 3_300_000]),
+                },
+                StreamContribution {
+                    stream_index: 1,
+                    stream_type: StreamType::Audio,
+                    codec_name: "aac".to_string(),
+                    percentage: 5.0,
+                    data_points: points(&[200_000, 200_000]),
+                },
+            ],
+            from_cache: false,
+            sampling: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_has_stream_columns() {
+        let csv = analysis_to_csv(&analysis());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,bitrate,stream_0_video,stream_1_audio");
+        assert_eq!(lines[1], "0,3000000,2800000,200000");
+        // Missing stream samples leave an empty cell
+        assert_eq!(lines[3], "2,3500000,3300000,");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_json_round_trips() {
+        let json = analysis_to_json(&analysis()).unwrap();
+        let parsed: OverallBitrateAnalysis = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.data_points.len(), 3);
+        assert_eq!(parsed.stream_contributions.len(), 2);
+    }
+
+    #[test]
+    fn test_svg_contents() {
+        let svg = analysis_to_svg(&analysis());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("a &amp; b.mkv"));
+        // One area per stream, one peak band and the total line
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert_eq!(
+            svg.matches(r##"fill="#ef4444" fill-opacity="0.15""##)
+                .count(),
+            1
+        );
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert!(svg.contains("#1 aac (5.0%)"));
+    }
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(nice_step(830_000.0), 1_000_000.0);
+        assert_eq!(nice_step(1_300_000.0), 2_000_000.0);
+        assert_eq!(nice_step(37.0), 50.0);
+        assert_eq!(format_axis_time(3725.0), "1:02:05");
+        assert_eq!(format_axis_time(65.0), "1:05");
+    }
+}
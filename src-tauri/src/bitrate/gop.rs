@@ -0,0 +1,498 @@
+//! GOP structure and keyframe interval analysis
+//!
+//! This module handles:
+//! - Reading per-frame picture types and key flags with ffprobe
+//! - Splitting the stream into GOPs at keyframes
+//! - GOP length distribution and open/closed GOP detection
+//! - I/P/B frame size ratios per GOP
+//! - Flagging keyframe intervals that hurt seeking or streaming
+
+use log::{debug, error, info};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crate::config;
+use crate::media::{find_command, get_media_streams, CANCELLED_MESSAGE};
+use crate::types::{GopAnalysis, GopInfo, GopLengthBucket, KeyframeIntervalIssue, StreamType};
+
+/// Keyframe intervals longer than this make seeking sluggish
+pub const MAX_SEEKABLE_KEYFRAME_INTERVAL_SECS: f64 = 10.0;
+
+/// Intervals deviating from the median by more than this factor are irregular
+const IRREGULAR_INTERVAL_FACTOR: f64 = 1.5;
+
+/// A decoded frame as seen by the GOP analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct GopFrame {
+    pub timestamp: f64,
+    pub size: u64,
+    pub pict_type: char,
+    pub key_frame: bool,
+}
+
+/// Parse one `ffprobe -show_frames -of compact=p=0` line
+///
+/// Lines look like `key_frame=1|best_effort_timestamp_time=0.000000|pkt_size=1234|pict_type=I`.
+/// Field order doesn't matter; `pts_time` is used when the best-effort timestamp is missing.
+pub fn parse_gop_frame_line(line: &str) -> Option<GopFrame> {
+    let mut timestamp = None;
+    let mut size = None;
+    let mut pict_type = None;
+    let mut key_frame = false;
+
+    for field in line.trim().split('|') {
+        match field.split_once('=') {
+            Some(("key_frame", v)) => key_frame = v == "1",
+            Some(("best_effort_timestamp_time", v)) | Some(("pts_time", v))
+                if timestamp.is_none() =>
+            {
+                timestamp = v.parse::<f64>().ok();
+            }
+            Some(("pkt_size", v)) => size = v.parse::<u64>().ok(),
+            Some(("pict_type", v)) => pict_type = v.chars().next(),
+            _ => {}
+        }
+    }
+
+    Some(GopFrame {
+        timestamp: timestamp?,
+        size: size?,
+        pict_type: pict_type.unwrap_or('?'),
+        key_frame,
+    })
+}
+
+/// Parse ffprobe frame output, returning frames in presentation order
+pub fn parse_gop_frames(output: &str) -> Vec<GopFrame> {
+    let mut frames: Vec<GopFrame> = output.lines().filter_map(parse_gop_frame_line).collect();
+    frames.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    frames
+}
+
+/// Read frame types and sizes for a video stream (decodes the stream)
+///
+/// Output is parsed as it streams in so the job can report progress against
+/// `duration` and kill ffprobe when cancelled.
+pub fn probe_gop_frames(
+    path: &str,
+    stream_index: i32,
+    duration: f64,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64),
+) -> Result<Vec<GopFrame>, String> {
+    let ffprobe_cmd = find_command("ffprobe").unwrap_or_else(|| "ffprobe".to_string());
+    debug!(
+        "probe_gop_frames: file={}, stream_index={}",
+        path, stream_index
+    );
+
+    let mut child = Command::new(&ffprobe_cmd)
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg(stream_index.to_string())
+        .arg("-show_frames")
+        .arg("-show_entries")
+        .arg("frame=key_frame,best_effort_timestamp_time,pts_time,pkt_size,pict_type")
+        .arg("-of")
+        .arg("compact=p=0")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn ffprobe: {}", e))?;
+
+    let stderr_handle = child.stderr.take();
+    let stderr_thread = thread::spawn(move || {
+        let mut err = String::new();
+        if let Some(mut e) = stderr_handle {
+            let _ = e.read_to_string(&mut err);
+        }
+        err
+    });
+
+    let mut frames = Vec::new();
+    let mut last_reported = 0.0;
+    if let Some(stdout) = child.stdout.take() {
+        let reader = BufReader::with_capacity(64 * 1024, stdout);
+        for line in reader.lines().map_while(Result::ok) {
+            if cancelled.load(Ordering::SeqCst) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(CANCELLED_MESSAGE.to_string());
+            }
+            if let Some(frame) = parse_gop_frame_line(&line) {
+                // One progress update per percent is plenty for a per-frame stream
+                let pct = if duration > 0.0 {
+                    (frame.timestamp / duration * 100.0).clamp(0.0, 100.0)
+                } else {
+                    0.0
+                };
+                if pct - last_reported >= 1.0 {
+                    last_reported = pct;
+                    on_progress(pct);
+                }
+                frames.push(frame);
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for ffprobe: {}", e))?;
+    let err_msg = stderr_thread.join().unwrap_or_default();
+    if !status.success() {
+        error!(
+            "ffprobe (GOP) failed for stream {}: {}",
+            stream_index, err_msg
+        );
+        return Err(format!("ffprobe failed: {}", err_msg));
+    }
+
+    if frames.is_empty() {
+        return Err(format!("No frames found for stream {}", stream_index));
+    }
+    frames.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    Ok(frames)
+}
+
+fn average_size(frames: &[&GopFrame], pict_type: char) -> Option<f64> {
+    let sizes: Vec<u64> = frames
+        .iter()
+        .filter(|f| f.pict_type == pict_type)
+        .map(|f| f.size)
+        .collect();
+    if sizes.is_empty() {
+        None
+    } else {
+        Some(sizes.iter().sum::<u64>() as f64 / sizes.len() as f64)
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    }
+}
+
+/// Flag keyframe intervals that are too long or irregular
+///
+/// The final GOP is allowed to be short (the stream simply ends).
+pub fn find_irregular_intervals(gops: &[GopInfo]) -> Vec<KeyframeIntervalIssue> {
+    let durations: Vec<f64> = gops.iter().map(|g| g.duration).collect();
+    let typical = median(&durations);
+    let last = gops.len().saturating_sub(1);
+
+    gops.iter()
+        .enumerate()
+        .filter_map(|(i, gop)| {
+            let reason = if gop.duration > MAX_SEEKABLE_KEYFRAME_INTERVAL_SECS {
+                format!(
+                    "Keyframe interval of {:.2}s exceeds {:.0}s and slows seeking",
+                    gop.duration, MAX_SEEKABLE_KEYFRAME_INTERVAL_SECS
+                )
+            } else if typical > 0.0 && gop.duration > typical * IRREGULAR_INTERVAL_FACTOR {
+                format!(
+                    "Keyframe interval of {:.2}s is longer than the typical {:.2}s",
+                    gop.duration, typical
+                )
+            } else if i != last
+                && typical > 0.0
+                && gop.duration < typical / IRREGULAR_INTERVAL_FACTOR
+            {
+                format!(
+                    "Keyframe interval of {:.2}s is shorter than the typical {:.2}s (unaligned segments)",
+                    gop.duration, typical
+                )
+            } else {
+                return None;
+            };
+
+            Some(KeyframeIntervalIssue {
+                start_time: gop.start_time,
+                end_time: gop.start_time + gop.duration,
+                interval: gop.duration,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Build the GOP analysis from frames in presentation order
+///
+/// A GOP starts at each keyframe. It is considered open when the frame displayed
+/// right before its keyframe is a B-frame, i.e. leading B-frames reference across
+/// the GOP boundary.
+pub fn analyze_gop_structure(
+    path: &str,
+    stream_index: i32,
+    frames: &[GopFrame],
+    duration: f64,
+) -> GopAnalysis {
+    let keyframe_positions: Vec<usize> = frames
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.key_frame)
+        .map(|(i, _)| i)
+        .collect();
+
+    let stream_end = frames
+        .last()
+        .map(|f| f.timestamp)
+        .unwrap_or(0.0)
+        .max(duration);
+
+    let mut gops = Vec::with_capacity(keyframe_positions.len());
+    for (gop_index, &start) in keyframe_positions.iter().enumerate() {
+        let end = keyframe_positions
+            .get(gop_index + 1)
+            .copied()
+            .unwrap_or(frames.len());
+        let gop_frames: Vec<&GopFrame> = frames[start..end].iter().collect();
+        let start_time = frames[start].timestamp;
+        let end_time = frames.get(end).map(|f| f.timestamp).unwrap_or(stream_end);
+
+        let count = |t: char| gop_frames.iter().filter(|f| f.pict_type == t).count();
+        let avg_i = average_size(&gop_frames, 'I');
+        let ratio = |t: char| match (average_size(&gop_frames, t), avg_i) {
+            (Some(avg), Some(i)) if i > 0.0 => Some(avg / i),
+            _ => None,
+        };
+
+        gops.push(GopInfo {
+            index: gop_index,
+            start_time,
+            duration: (end_time - start_time).max(0.0),
+            frame_count: gop_frames.len(),
+            open: start > 0 && frames[start - 1].pict_type == 'B',
+            i_frames: count('I'),
+            p_frames: count('P'),
+            b_frames: count('B'),
+            total_size: gop_frames.iter().map(|f| f.size).sum(),
+            p_to_i_ratio: ratio('P'),
+            b_to_i_ratio: ratio('B'),
+        });
+    }
+
+    let mut distribution: BTreeMap<usize, usize> = BTreeMap::new();
+    for gop in &gops {
+        *distribution.entry(gop.frame_count).or_insert(0) += 1;
+    }
+
+    let durations: Vec<f64> = gops.iter().map(|g| g.duration).collect();
+    let open_gop_count = gops.iter().filter(|g| g.open).count();
+
+    GopAnalysis {
+        path: path.to_string(),
+        stream_index,
+        keyframe_times: keyframe_positions
+            .iter()
+            .map(|&i| frames[i].timestamp)
+            .collect(),
+        length_distribution: distribution
+            .into_iter()
+            .map(|(frame_count, occurrences)| GopLengthBucket {
+                frame_count,
+                occurrences,
+            })
+            .collect(),
+        average_gop_seconds: if durations.is_empty() {
+            0.0
+        } else {
+            durations.iter().sum::<f64>() / durations.len() as f64
+        },
+        median_gop_seconds: median(&durations),
+        max_gop_seconds: durations.iter().cloned().fold(0.0, f64::max),
+        open_gop_count,
+        closed_gop_count: gops.len() - open_gop_count,
+        irregular_intervals: find_irregular_intervals(&gops),
+        gops,
+    }
+}
+
+/// Run a full GOP analysis for a video stream
+pub fn analyze_gop(
+    path: &str,
+    stream_index: i32,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<GopAnalysis, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    on_progress(0.0, "Getting stream info...");
+    let streams = get_media_streams(path.to_string())?;
+    let is_video = streams
+        .streams
+        .iter()
+        .any(|s| s.index == stream_index && s.stream_type == StreamType::Video && !s.is_cover_art);
+    if !is_video {
+        return Err(format!("Stream {} is not a video stream", stream_index));
+    }
+
+    let frames = probe_gop_frames(
+        &validated_path.to_string_lossy(),
+        stream_index,
+        streams.duration,
+        cancelled,
+        &mut |pct| on_progress(pct * 0.95, "Reading frames..."),
+    )?;
+
+    on_progress(95.0, "Analyzing GOP structure...");
+    let analysis = analyze_gop_structure(path, stream_index, &frames, streams.duration);
+    info!(
+        "GOP analysis for stream {} of {}: {} GOPs ({} open), avg {:.2}s, {} irregular",
+        stream_index,
+        path,
+        analysis.gops.len(),
+        analysis.open_gop_count,
+        analysis.average_gop_seconds,
+        analysis.irregular_intervals.len()
+    );
+    on_progress(100.0, "Complete");
+    Ok(analysis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp: f64, pict_type: char, size: u64) -> GopFrame {
+        GopFrame {
+            timestamp,
+            size,
+            pict_type,
+            key_frame: pict_type == 'I',
+        }
+    }
+
+    /// Build frames at 1 fps from a pattern like "IPBBIPBB"
+    fn frames_from_pattern(pattern: &str) -> Vec<GopFrame> {
+        pattern
+            .chars()
+            .enumerate()
+            .map(|(i, t)| {
+                let size = match t {
+                    'I' => 1000,
+                    'P' => 500,
+                    _ => 250,
+                };
+                frame(i as f64, t, size)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_gop_frames() {
+        let output = "key_frame=1|best_effort_timestamp_time=0.000000|pkt_size=5000|pict_type=I\n\
+                      key_frame=0|best_effort_timestamp_time=0.080000|pkt_size=300|pict_type=B\n\
+                      key_frame=0|best_effort_timestamp_time=0.040000|pkt_size=800|pict_type=P\n\
+                      garbage line\n";
+        let frames = parse_gop_frames(output);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], frame(0.0, 'I', 5000));
+        // Sorted into presentation order
+        assert_eq!(frames[1].pict_type, 'P');
+        assert_eq!(frames[2].pict_type, 'B');
+    }
+
+    #[test]
+    fn test_parse_gop_frames_pts_fallback() {
+        let output =
+            "key_frame=1|best_effort_timestamp_time=N/A|pts_time=1.5|pkt_size=10|pict_type=I";
+        let frames = parse_gop_frames(output);
+        assert_eq!(frames[0].timestamp, 1.5);
+    }
+
+    #[test]
+    fn test_gop_split_and_ratios() {
+        let frames = frames_from_pattern("IPBBIPBB");
+        let analysis = analyze_gop_structure("/a.mkv", 0, &frames, 8.0);
+        assert_eq!(analysis.gops.len(), 2);
+        assert_eq!(analysis.keyframe_times, vec![0.0, 4.0]);
+
+        let gop = &analysis.gops[0];
+        assert_eq!(gop.frame_count, 4);
+        assert_eq!((gop.i_frames, gop.p_frames, gop.b_frames), (1, 1, 2));
+        assert_eq!(gop.total_size, 2000);
+        assert_eq!(gop.p_to_i_ratio, Some(0.5));
+        assert_eq!(gop.b_to_i_ratio, Some(0.25));
+        assert_eq!(gop.duration, 4.0);
+        // Last GOP runs to the end of the stream
+        assert_eq!(analysis.gops[1].duration, 4.0);
+    }
+
+    #[test]
+    fn test_open_gop_detection() {
+        // B-frames displayed right before the second keyframe -> open GOP
+        let analysis = analyze_gop_structure("/a.mkv", 0, &frames_from_pattern("IPBBIPBP"), 8.0);
+        assert!(!analysis.gops[0].open);
+        assert!(analysis.gops[1].open);
+        assert_eq!(analysis.open_gop_count, 1);
+        assert_eq!(analysis.closed_gop_count, 1);
+
+        let closed = analyze_gop_structure("/a.mkv", 0, &frames_from_pattern("IBBPIBBP"), 8.0);
+        assert_eq!(closed.open_gop_count, 0);
+    }
+
+    #[test]
+    fn test_length_distribution() {
+        let analysis =
+            analyze_gop_structure("/a.mkv", 0, &frames_from_pattern("IPPIPPIPPPP"), 11.0);
+        let buckets: Vec<(usize, usize)> = analysis
+            .length_distribution
+            .iter()
+            .map(|b| (b.frame_count, b.occurrences))
+            .collect();
+        assert_eq!(buckets, vec![(3, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn test_irregular_intervals() {
+        // Regular 2s GOPs, one 5s GOP, one 0.5s GOP mid-stream, short final GOP is fine
+        let mut frames = Vec::new();
+        for t in [0.0, 2.0, 4.0, 9.0, 9.5, 11.5, 13.5] {
+            frames.push(frame(t, 'I', 1000));
+        }
+        let analysis = analyze_gop_structure("/a.mkv", 0, &frames, 14.0);
+        let flagged: Vec<f64> = analysis
+            .irregular_intervals
+            .iter()
+            .map(|i| i.start_time)
+            .collect();
+        assert_eq!(flagged, vec![4.0, 9.0]);
+    }
+
+    #[test]
+    fn test_long_interval_flagged_for_seeking() {
+        let frames = vec![frame(0.0, 'I', 1000), frame(12.0, 'I', 1000)];
+        let analysis = analyze_gop_structure("/a.mkv", 0, &frames, 24.0);
+        assert_eq!(analysis.irregular_intervals.len(), 2);
+        assert!(analysis.irregular_intervals[0]
+            .reason
+            .contains("slows seeking"));
+        assert_eq!(analysis.max_gop_seconds, 12.0);
+    }
+
+    #[test]
+    fn test_no_keyframes() {
+        let frames = vec![frame(0.0, 'P', 10)];
+        let analysis = analyze_gop_structure("/a.mkv", 0, &frames, 1.0);
+        assert!(analysis.gops.is_empty());
+        assert_eq!(analysis.average_gop_seconds, 0.0);
+    }
+}
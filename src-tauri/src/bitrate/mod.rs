@@ -2,18 +2,51 @@
 //!
 //! This module handles bitrate analysis for media files, including:
 //! - Frame-by-frame bitrate parsing using ffprobe
-//! - File hash computation for cache validation
+//! - Per-frame QP and picture type capture
+//! - GOP structure and keyframe interval analysis
+//! - VBV/HRD compliance checks against delivery presets
+//! - CSV, JSON and SVG chart export of overall analyses
+//! - Batch folder reports with outliers and wasteful-encode ranking
+//! - File hash computation and SQLite caching of overall analyses
 //!
-//! Note: Caching is now handled by the frontend via SQLite database.
-//! The backend focuses on analysis and provides hash computation for
-//! cache validation. Job queue management is handled by the centralized jobs module.
+//! Note: The cache lives in the shared app database, so results are reused
+//! whether the analysis was started by the frontend or by the backend.
+//! Job queue management is handled by the centralized jobs module.
 
+mod batch;
 mod cache;
+mod compliance;
+mod export;
+mod gop;
+mod overall;
 mod parser;
+mod quality;
 
-pub use cache::{clear_cache, compute_file_hash, get_cached_analysis, save_to_cache};
+pub use batch::{
+    analyze_folder, collect_media_files, parse_frame_rate, reference_bits_per_pixel, summarize,
+};
+pub use cache::{
+    clear_cache, compute_file_hash, get_cached_analysis, get_latest_analysis, save_to_cache,
+    AnalysisParams,
+};
+pub use compliance::{
+    check_compliance, list_compliance_presets, preset_settings, resolve_vbv_settings,
+    COMPLIANCE_PRESETS,
+};
+pub use export::{analysis_to_csv, analysis_to_json, analysis_to_svg, export_analysis};
+pub use gop::{
+    analyze_gop, analyze_gop_structure, find_irregular_intervals, parse_gop_frame_line,
+    parse_gop_frames, probe_gop_frames, GopFrame, MAX_SEEKABLE_KEYFRAME_INTERVAL_SECS,
+};
+pub use overall::analyze_overall;
 pub use parser::{
-    aggregate_bitrate_intervals, calculate_statistics, extrapolate_sampled_data,
-    parse_ffprobe_auto, parse_ffprobe_frames, parse_ffprobe_packets, parse_ffprobe_sampled,
-    sort_streams_audio_first, SAMPLE_COUNT, SAMPLE_DURATION_SECS, SAMPLING_THRESHOLD_BYTES,
+    aggregate_bitrate_intervals, calculate_statistics, estimate_sampling_confidence,
+    extrapolate_sampled_data, parse_ffprobe_auto, parse_ffprobe_frames, parse_ffprobe_packets,
+    parse_ffprobe_packets_limited, parse_ffprobe_sampled, plan_sample_windows,
+    sort_streams_audio_first, SampleWindow, PACKET_SCAN_TIMEOUT, SAMPLE_COUNT,
+    SAMPLE_DURATION_SECS, SAMPLING_THRESHOLD_BYTES,
+};
+pub use quality::{
+    aggregate_quality_intervals, build_quality_analysis, find_starved_intervals,
+    parse_showinfo_log, probe_frame_quality, FrameQuality,
 };
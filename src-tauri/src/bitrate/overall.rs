@@ -0,0 +1,413 @@
+//! Overall (all streams combined) bitrate analysis
+//!
+//! Analyzes every video and audio stream in parallel, sampling large files,
+//! and combines them into one timeline with per-stream contributions. Used by
+//! the `analyze_overall_bitrate` command and by batch folder reports.
+
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::parser::{
+    calculate_statistics, estimate_sampling_confidence, extrapolate_sampled_data,
+    parse_ffprobe_auto, parse_ffprobe_sampled, plan_sample_windows, SampleWindow,
+};
+use crate::files::get_file_metadata;
+use crate::media::get_media_streams;
+use crate::types::{
+    BitrateDataPoint, BitrateProgress, OverallBitrateAnalysis, SamplingConfig, StreamContribution,
+    StreamType,
+};
+
+/// Analyze all video/audio streams of a file into one bitrate timeline
+///
+/// Files above the sampling threshold are sampled and interpolated. Checks
+/// `cancelled` between stages and before each stream.
+pub fn analyze_overall(
+    path: &str,
+    interval_seconds: f64,
+    sampling_config: &SamplingConfig,
+    cancelled: &AtomicBool,
+    on_progress: &dyn Fn(BitrateProgress),
+) -> Result<OverallBitrateAnalysis, String> {
+    // Track start time for ETA calculation
+    let analysis_start = std::time::Instant::now();
+
+    // Helper to report progress with ETA and sampling info
+    let emit_progress_enhanced = |current: usize,
+                                  total: usize,
+                                  percentage: f64,
+                                  stage: String,
+                                  using_sampling: Option<bool>,
+                                  stream_count: Option<usize>,
+                                  current_stream: Option<usize>| {
+        let elapsed = analysis_start.elapsed().as_secs_f64();
+
+        // Calculate ETA based on elapsed time and percentage
+        let eta_seconds = if percentage > 5.0 && percentage < 100.0 {
+            let remaining_percentage = 100.0 - percentage;
+            Some((elapsed / percentage) * remaining_percentage)
+        } else {
+            None
+        };
+
+        on_progress(BitrateProgress {
+            current,
+            total,
+            percentage,
+            stage,
+            eta_seconds,
+            elapsed_seconds: Some(elapsed),
+            using_sampling,
+            stream_count,
+            current_stream,
+        });
+    };
+
+    // Simple progress helper for initial stages
+    let emit_progress = |current: usize, total: usize, percentage: f64, stage: String| {
+        emit_progress_enhanced(current, total, percentage, stage, None, None, None);
+    };
+
+    // Stage 1: Get all streams
+    emit_progress(0, 100, 0.0, "Getting stream information...".to_string());
+
+    debug!("Stage 1: Getting streams");
+    let streams = get_media_streams(path.to_string())?;
+    let file_size = streams.total_size;
+    info!(
+        "Found {} total streams, file size: {:.2} GB",
+        streams.streams.len(),
+        file_size as f64 / 1024.0 / 1024.0 / 1024.0
+    );
+
+    // Stage 2: Get duration
+    emit_progress(5, 100, 5.0, "Reading file metadata...".to_string());
+
+    let metadata_json = get_file_metadata(path.to_string())?;
+    let metadata: serde_json::Value = serde_json::from_str(
+        &metadata_json
+            .ffprobe_data
+            .ok_or("No ffprobe data available")?,
+    )
+    .map_err(|e| format!("Failed to parse metadata: {}", e))?;
+
+    let duration = metadata["format"]["duration"]
+        .as_str()
+        .and_then(|s| s.parse::<f64>().ok())
+        .ok_or("Could not determine duration")?;
+
+    // Check if we should use sampling mode for large files
+    let sample_windows = plan_sample_windows(duration, file_size, sampling_config);
+    let use_sampling = sample_windows.is_some();
+    if use_sampling {
+        info!(
+            "Large file detected ({:.2} GB >= {:.2} GB threshold), will use sampling mode",
+            file_size as f64 / 1024.0 / 1024.0 / 1024.0,
+            sampling_config.threshold_bytes as f64 / 1024.0 / 1024.0 / 1024.0
+        );
+    }
+
+    // Check for cancellation
+    if cancelled.load(Ordering::SeqCst) {
+        return Err("Analysis cancelled".to_string());
+    }
+
+    // Count video/audio streams for progress tracking
+    let analysis_streams: Vec<_> = streams
+        .streams
+        .iter()
+        .filter(|s| s.stream_type == StreamType::Video || s.stream_type == StreamType::Audio)
+        .cloned()
+        .collect();
+
+    let total_streams = analysis_streams.len();
+    info!(
+        "Will analyze {} video/audio streams in parallel (sampling: {})",
+        total_streams, use_sampling
+    );
+    let num_intervals = (duration / interval_seconds).ceil() as usize;
+
+    // Stage 3: Analyze streams in PARALLEL using rayon
+    debug!("Stage 3: Starting parallel stream analysis");
+    let stage_msg = if let Some(windows) = &sample_windows {
+        format!(
+            "Sampling {} streams ({} x {}s intervals)...",
+            total_streams,
+            windows.len(),
+            sampling_config.sample_duration_secs
+        )
+    } else {
+        format!("Analyzing {} streams in parallel...", total_streams)
+    };
+    emit_progress_enhanced(
+        10,
+        100,
+        10.0,
+        stage_msg,
+        Some(use_sampling),
+        Some(total_streams),
+        None,
+    );
+
+    // Shared progress counter for parallel execution
+    let analyzed_count = AtomicUsize::new(0);
+
+    // Process streams in parallel
+    let stream_results: Vec<_> = analysis_streams
+        .par_iter()
+        .filter_map(|stream| {
+            // Check for cancellation
+            if cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            let stream_type_name = match stream.stream_type {
+                StreamType::Audio => "audio",
+                StreamType::Video => "video",
+                _ => "stream",
+            };
+
+            debug!(
+                "Parallel: Analyzing {} stream {} ({})",
+                stream_type_name,
+                stream.index,
+                stream.codec_name.as_deref().unwrap_or("unknown")
+            );
+
+            // Use sampling for large files, otherwise fast packet mode
+            let (frames, read_windows) = if let Some(windows) = &sample_windows {
+                match parse_ffprobe_sampled(path, stream.index, duration, windows) {
+                    Ok((f, read)) => {
+                        analyzed_count.fetch_add(1, Ordering::SeqCst);
+                        (f, Some(read))
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to parse stream {} ({}): {}",
+                            stream.index,
+                            stream.codec_name.as_deref().unwrap_or("unknown"),
+                            e
+                        );
+                        return None;
+                    }
+                }
+            } else {
+                match parse_ffprobe_auto(path, stream.index, false) {
+                    Ok(f) => {
+                        analyzed_count.fetch_add(1, Ordering::SeqCst);
+                        (f, None)
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to parse stream {} ({}): {}",
+                            stream.index,
+                            stream.codec_name.as_deref().unwrap_or("unknown"),
+                            e
+                        );
+                        return None;
+                    }
+                }
+            };
+
+            // Track per-stream interval bitrates
+            let mut stream_intervals: Vec<u64> = vec![0; num_intervals];
+
+            if let Some(read) = &read_windows {
+                debug!("Stream {} was analyzed using sampling mode", stream.index);
+                // Fill the gaps between samples by interpolation
+                let points = extrapolate_sampled_data(&frames, read, duration, interval_seconds);
+                for (slot, point) in stream_intervals.iter_mut().zip(points) {
+                    *slot = point.bitrate;
+                }
+            } else {
+                // Aggregate this stream's contribution
+                for (timestamp, size, _) in &frames {
+                    let interval_idx = (*timestamp / interval_seconds).floor() as usize;
+                    if interval_idx < num_intervals {
+                        stream_intervals[interval_idx] += size;
+                    }
+                }
+                for slot in stream_intervals.iter_mut() {
+                    *slot = ((*slot * 8) as f64 / interval_seconds) as u64;
+                }
+            }
+
+            Some((
+                stream.index,
+                stream.stream_type.clone(),
+                stream.codec_name.clone(),
+                stream_intervals,
+                read_windows.map(|read| (frames, read)),
+            ))
+        })
+        .collect();
+
+    // Check if cancelled during parallel processing
+    if cancelled.load(Ordering::SeqCst) {
+        info!("Analysis of {} cancelled during stream analysis", path);
+        return Err("Analysis cancelled".to_string());
+    }
+
+    let analyzed_count = analyzed_count.load(Ordering::SeqCst);
+
+    // Combine results from parallel processing
+    let mut combined_intervals: Vec<u64> = vec![0; num_intervals];
+    let mut stream_data: Vec<(i32, StreamType, Option<String>, Vec<u64>)> = Vec::new();
+    let mut sampled_streams = Vec::new();
+
+    for (stream_index, stream_type, codec_name, intervals, sampled) in stream_results {
+        // Add to combined intervals
+        for (i, bitrate) in intervals.iter().enumerate() {
+            combined_intervals[i] += bitrate;
+        }
+        stream_data.push((stream_index, stream_type, codec_name, intervals));
+        sampled_streams.extend(sampled);
+    }
+
+    // Confidence of the sampled estimate, over windows every stream managed to read
+    let sampling_info = sample_windows.as_ref().map(|windows| {
+        let common: Vec<SampleWindow> = windows
+            .iter()
+            .filter(|&&(start, length)| {
+                sampled_streams.iter().all(|(_, read)| {
+                    read.iter()
+                        .any(|&(s, d)| s <= start && s + d >= start + length)
+                })
+            })
+            .copied()
+            .collect();
+        let packets: Vec<(f64, u64, Option<String>)> = sampled_streams
+            .iter()
+            .flat_map(|(frames, _)| frames.iter().cloned())
+            .collect();
+        estimate_sampling_confidence(&packets, &common, duration, sampling_config.placement)
+    });
+    if let Some(info) = &sampling_info {
+        info!(
+            "Sampled average {} bps +/- {:.1}% ({:.0}% confidence, {:.1}% coverage)",
+            info.estimated_avg_bitrate,
+            info.relative_margin * 100.0,
+            info.confidence_level * 100.0,
+            info.coverage * 100.0
+        );
+    }
+
+    info!(
+        "Parallel analysis complete: {}/{} streams analyzed in {:.2}s",
+        analyzed_count,
+        total_streams,
+        analysis_start.elapsed().as_secs_f64()
+    );
+
+    // Emit progress update after parallel analysis completes
+    emit_progress_enhanced(
+        85,
+        100,
+        85.0,
+        format!(
+            "Stream analysis complete ({}/{} streams)",
+            analyzed_count, total_streams
+        ),
+        Some(use_sampling),
+        Some(total_streams),
+        Some(total_streams),
+    );
+
+    // Check if we analyzed any streams
+    if analyzed_count == 0 {
+        error!("Failed to analyze any streams in the file");
+        return Err("Failed to analyze any streams in the file".to_string());
+    }
+
+    info!(
+        "Successfully analyzed {}/{} streams",
+        analyzed_count, total_streams
+    );
+
+    // Stage 4: Aggregate data
+    emit_progress(90, 100, 90.0, "Aggregating bitrate data...".to_string());
+
+    // Calculate combined total before consuming combined_intervals
+    let combined_total: u64 = combined_intervals.iter().sum();
+
+    let data_points: Vec<BitrateDataPoint> = combined_intervals
+        .into_iter()
+        .enumerate()
+        .map(|(idx, bitrate)| BitrateDataPoint {
+            timestamp: idx as f64 * interval_seconds,
+            bitrate,
+            frame_type: None,
+        })
+        .collect();
+
+    // Calculate stream contributions with per-stream data points
+    let mut stream_contributions: Vec<StreamContribution> = Vec::new();
+    for (stream_index, stream_type, codec_name, intervals) in stream_data {
+        // Calculate this stream's total bitrate from actual data
+        let stream_total: u64 = intervals.iter().sum();
+        let percentage = if combined_total > 0 {
+            (stream_total as f64 / combined_total as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        // Convert intervals to data points
+        let stream_data_points: Vec<BitrateDataPoint> = intervals
+            .into_iter()
+            .enumerate()
+            .map(|(idx, bitrate)| BitrateDataPoint {
+                timestamp: idx as f64 * interval_seconds,
+                bitrate,
+                frame_type: None,
+            })
+            .collect();
+
+        stream_contributions.push(StreamContribution {
+            stream_index,
+            stream_type,
+            codec_name: codec_name.unwrap_or_default(),
+            percentage,
+            data_points: stream_data_points,
+        });
+    }
+
+    // Stage 5: Calculate statistics
+    emit_progress_enhanced(
+        95,
+        100,
+        95.0,
+        "Calculating statistics...".to_string(),
+        Some(use_sampling),
+        Some(total_streams),
+        Some(total_streams),
+    );
+
+    let statistics = calculate_statistics(&data_points);
+
+    // Complete
+    let total_elapsed = analysis_start.elapsed().as_secs_f64();
+    emit_progress_enhanced(
+        100,
+        100,
+        100.0,
+        format!("Complete in {:.1}s", total_elapsed),
+        Some(use_sampling),
+        Some(total_streams),
+        Some(total_streams),
+    );
+    info!(
+        "Overall bitrate analysis complete in {:.2}s (sampling: {})",
+        total_elapsed, use_sampling
+    );
+
+    Ok(OverallBitrateAnalysis {
+        path: path.to_string(),
+        duration,
+        data_points,
+        statistics,
+        stream_contributions,
+        from_cache: false,
+        sampling: sampling_info,
+    })
+}
@@ -10,23 +10,30 @@
 use log::{debug, error, info, warn};
 use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
 
-use crate::media::find_command;
-use crate::types::{BitrateDataPoint, BitrateStatistics, PeakInterval, StreamInfo, StreamType};
+use crate::media::{find_command, CANCELLED_MESSAGE};
+use crate::types::{
+    BitrateDataPoint, BitrateStatistics, PeakInterval, SamplingConfig, SamplingInfo,
+    SamplingPlacement, StreamInfo, StreamType,
+};
 
-/// File size threshold for sampling mode (5 GB)
+/// Default file size threshold for sampling mode (5 GB)
 /// Files larger than this will use sampling instead of full analysis
 pub const SAMPLING_THRESHOLD_BYTES: u64 = 5 * 1024 * 1024 * 1024;
 
-/// Number of sample intervals to analyze for large files
+/// Default number of sample intervals to analyze for large files
 /// We sample at the start, middle, and end for representative data
 pub const SAMPLE_COUNT: usize = 10;
 
-/// Duration of each sample interval in seconds
+/// Default duration of each sample interval in seconds
 pub const SAMPLE_DURATION_SECS: f64 = 30.0;
 
+/// How long a packet scan may take before ffprobe is killed
+pub const PACKET_SCAN_TIMEOUT: Duration = Duration::from_secs(180);
+
 /// Parse ffprobe packet data for a specific stream (FAST MODE)
 ///
 /// Uses -show_packets which is significantly faster than -show_frames
@@ -38,7 +45,20 @@ pub fn parse_ffprobe_packets(
     path: &str,
     stream_index: i32,
 ) -> Result<Vec<(f64, u64, Option<String>)>, String> {
-    parse_ffprobe_packets_internal(path, stream_index, None)
+    parse_ffprobe_packets_internal(path, stream_index, None, Some(PACKET_SCAN_TIMEOUT), None)
+}
+
+/// Parse ffprobe packet data with a custom timeout and a cancel flag
+///
+/// `timeout: None` waits for ffprobe however long it takes, for callers that
+/// can be cancelled instead (e.g. jobs scanning a large file on slow storage).
+pub fn parse_ffprobe_packets_limited(
+    path: &str,
+    stream_index: i32,
+    timeout: Option<Duration>,
+    cancelled: Option<&AtomicBool>,
+) -> Result<Vec<(f64, u64, Option<String>)>, String> {
+    parse_ffprobe_packets_internal(path, stream_index, None, timeout, cancelled)
 }
 
 /// Parse ffprobe packet data with optional read interval for sampling
@@ -49,6 +69,8 @@ fn parse_ffprobe_packets_internal(
     path: &str,
     stream_index: i32,
     read_interval: Option<&str>,
+    timeout: Option<Duration>,
+    cancelled: Option<&AtomicBool>,
 ) -> Result<Vec<(f64, u64, Option<String>)>, String> {
     let mode_desc = read_interval
         .map(|i| format!("sampled [{}]", i))
@@ -138,12 +160,16 @@ fn parse_ffprobe_packets_internal(
         .unwrap_or_else(|_| Vec::new())
     });
 
-    // Wait with timeout (3 minutes for packet mode - faster than frame mode)
-    let timeout = Duration::from_secs(180);
     let start = std::time::Instant::now();
 
     let status = loop {
-        if start.elapsed() > timeout {
+        if cancelled.is_some_and(|c| c.load(Ordering::SeqCst)) {
+            debug!("ffprobe (packet mode) killed after cancellation");
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CANCELLED_MESSAGE.to_string());
+        }
+        if let Some(timeout) = timeout.filter(|t| start.elapsed() > *t) {
             error!(
                 "ffprobe (packet mode) timed out after {} seconds for stream {}",
                 timeout.as_secs(),
@@ -703,78 +729,126 @@ pub fn parse_ffprobe_auto(
     }
 }
 
-/// Parse ffprobe data with automatic sampling for large files
-///
-/// For files larger than SAMPLING_THRESHOLD_BYTES, uses sampling mode which
-/// reads only portions of the file and extrapolates the results.
-/// This can provide 10-100x speedup for very large files.
+/// A sampled window as `(start, duration)` in seconds
+pub type SampleWindow = (f64, f64);
+
+/// Two-sided 95% t critical values for 1-30 degrees of freedom
+const T_CRITICAL_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Small xorshift generator for random sample placement (no need for crypto quality)
+fn next_random(state: &mut u64) -> f64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    (*state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Decide where to sample a file, or None if it should be analyzed fully
 ///
-/// Returns: (data, was_sampled)
-pub fn parse_ffprobe_sampled(
-    path: &str,
-    stream_index: i32,
+/// Files below the size threshold, and files too short for the samples not to
+/// overlap, are analyzed fully. The duration is split into `sample_count` equal
+/// slots; uniform placement samples the start of each slot, random placement a
+/// random position inside it.
+pub fn plan_sample_windows(
     duration: f64,
     file_size: u64,
-) -> Result<(Vec<(f64, u64, Option<String>)>, bool), String> {
-    // Check if file is large enough to warrant sampling
-    if file_size < SAMPLING_THRESHOLD_BYTES {
+    config: &SamplingConfig,
+) -> Option<Vec<SampleWindow>> {
+    if file_size < config.threshold_bytes {
         debug!(
             "File size {} bytes < threshold {} bytes, using full analysis",
-            file_size, SAMPLING_THRESHOLD_BYTES
+            file_size, config.threshold_bytes
         );
-        let data = parse_ffprobe_packets(path, stream_index)?;
-        return Ok((data, false));
+        return None;
     }
-
-    info!(
-        "Large file detected ({:.2} GB), using sampling mode for stream {}",
-        file_size as f64 / 1024.0 / 1024.0 / 1024.0,
-        stream_index
-    );
-
-    // Calculate sample positions distributed across the file
-    // We want samples at: start, evenly distributed middle sections, and end
-    let mut sample_positions: Vec<f64> = Vec::with_capacity(SAMPLE_COUNT);
-
-    if duration <= SAMPLE_DURATION_SECS * SAMPLE_COUNT as f64 {
-        // File is short enough to analyze fully despite large size (high bitrate)
+    if config.sample_count == 0
+        || config.sample_duration_secs <= 0.0
+        || duration <= config.sample_duration_secs * config.sample_count as f64
+    {
         debug!("Duration {:.1}s is short, analyzing fully", duration);
-        let data = parse_ffprobe_packets(path, stream_index)?;
-        return Ok((data, false));
+        return None;
     }
 
-    // Distribute samples evenly across the duration
-    let interval = duration / SAMPLE_COUNT as f64;
-    for i in 0..SAMPLE_COUNT {
-        let pos = i as f64 * interval;
-        sample_positions.push(pos);
-    }
+    let slot = duration / config.sample_count as f64;
+    let mut state = config
+        .seed
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(1)
+        })
+        .max(1);
+
+    let windows: Vec<SampleWindow> = (0..config.sample_count)
+        .map(|i| {
+            let slot_start = i as f64 * slot;
+            let start = match config.placement {
+                SamplingPlacement::Uniform => slot_start,
+                SamplingPlacement::Random => {
+                    slot_start + next_random(&mut state) * (slot - config.sample_duration_secs)
+                }
+            };
+            (start, config.sample_duration_secs)
+        })
+        .collect();
 
     debug!(
-        "Sampling {} positions across {:.1}s duration: {:?}",
-        SAMPLE_COUNT, duration, sample_positions
+        "Sampling {} windows ({:?}) across {:.1}s duration: {:?}",
+        windows.len(),
+        config.placement,
+        duration,
+        windows
+    );
+    Some(windows)
+}
+
+/// Read packets for the planned sample windows of one stream
+///
+/// Returns the packets and the windows that were read successfully. If every
+/// sample fails, falls back to a full analysis reported as one window covering
+/// the whole file.
+pub fn parse_ffprobe_sampled(
+    path: &str,
+    stream_index: i32,
+    duration: f64,
+    windows: &[SampleWindow],
+) -> Result<(Vec<(f64, u64, Option<String>)>, Vec<SampleWindow>), String> {
+    info!(
+        "Using sampling mode for stream {} ({} windows)",
+        stream_index,
+        windows.len()
     );
 
-    // Collect samples from each position
     let mut all_packets: Vec<(f64, u64, Option<String>)> = Vec::new();
-    let mut total_sample_duration = 0.0;
+    let mut read_windows: Vec<SampleWindow> = Vec::with_capacity(windows.len());
 
-    for (idx, start_pos) in sample_positions.iter().enumerate() {
-        // Format: "start%+duration" - read SAMPLE_DURATION_SECS starting at start_pos
-        let read_interval = format!("{}%+{}", start_pos, SAMPLE_DURATION_SECS);
+    for (idx, &(start_pos, sample_duration)) in windows.iter().enumerate() {
+        // Format: "start%+duration" - read sample_duration seconds starting at start_pos
+        let read_interval = format!("{}%+{}", start_pos, sample_duration);
 
         debug!(
             "Reading sample {}/{} at position {:.1}s",
             idx + 1,
-            SAMPLE_COUNT,
+            windows.len(),
             start_pos
         );
 
-        match parse_ffprobe_packets_internal(path, stream_index, Some(&read_interval)) {
+        match parse_ffprobe_packets_internal(
+            path,
+            stream_index,
+            Some(&read_interval),
+            Some(PACKET_SCAN_TIMEOUT),
+            None,
+        ) {
             Ok(packets) => {
                 debug!("Sample {} returned {} packets", idx + 1, packets.len());
                 all_packets.extend(packets);
-                total_sample_duration += SAMPLE_DURATION_SECS;
+                read_windows.push((start_pos, sample_duration));
             }
             Err(e) => {
                 warn!("Sample {} failed: {}", idx + 1, e);
@@ -789,9 +863,10 @@ pub fn parse_ffprobe_sampled(
             stream_index
         );
         let data = parse_ffprobe_packets(path, stream_index)?;
-        return Ok((data, false));
+        return Ok((data, vec![(0.0, duration)]));
     }
 
+    let total_sample_duration: f64 = read_windows.iter().map(|(_, d)| d).sum();
     info!(
         "Sampling complete: {} packets from {:.1}s of {:.1}s total ({:.1}% coverage)",
         all_packets.len(),
@@ -800,77 +875,174 @@ pub fn parse_ffprobe_sampled(
         (total_sample_duration / duration) * 100.0
     );
 
-    Ok((all_packets, true))
+    Ok((all_packets, read_windows))
+}
+
+/// Seconds of interval `[start, end)` covered by the sample windows
+fn sampled_overlap(windows: &[SampleWindow], start: f64, end: f64) -> f64 {
+    windows
+        .iter()
+        .map(|&(ws, wd)| ((ws + wd).min(end) - ws.max(start)).max(0.0))
+        .sum()
 }
 
 /// Extrapolate sampled data to create full duration estimate
 ///
-/// Takes sampled packet data and creates interpolated data points
-/// for the full duration. This is used for visualization when sampling.
+/// Intervals covered by a sample window (at least half of the interval) use
+/// the measured bitrate, scaled to the covered part. Intervals between samples
+/// are linearly interpolated between the nearest measured intervals; intervals
+/// before the first / after the last sample repeat the nearest measurement.
 pub fn extrapolate_sampled_data(
     sampled_data: &[(f64, u64, Option<String>)],
-    sampled_duration: f64,
+    windows: &[SampleWindow],
     full_duration: f64,
     interval_seconds: f64,
 ) -> Vec<BitrateDataPoint> {
-    if sampled_data.is_empty() {
+    if sampled_data.is_empty() || interval_seconds <= 0.0 {
         return Vec::new();
     }
 
-    // Calculate average bitrate from samples
-    let total_bytes: u64 = sampled_data.iter().map(|(_, size, _)| size).sum();
-    let avg_bitrate = if sampled_duration > 0.0 {
-        ((total_bytes * 8) as f64 / sampled_duration) as u64
-    } else {
-        0
-    };
-
-    debug!(
-        "Extrapolating: {} bytes over {:.1}s = {} bps average",
-        total_bytes, sampled_duration, avg_bitrate
-    );
-
     // First, aggregate the actual sampled data into intervals
     let num_intervals = (full_duration / interval_seconds).ceil() as usize;
-    let mut intervals: Vec<(u64, usize)> = vec![(0, 0); num_intervals]; // (total_bytes, count)
-
+    let mut totals: Vec<u64> = vec![0; num_intervals];
     for (timestamp, size, _) in sampled_data {
         let interval_idx = (*timestamp / interval_seconds).floor() as usize;
         if interval_idx < num_intervals {
-            intervals[interval_idx].0 += size;
-            intervals[interval_idx].1 += 1;
+            totals[interval_idx] += size;
         }
     }
 
-    // Create data points, using actual data where available and avg for gaps
-    let data_points: Vec<BitrateDataPoint> = intervals
-        .into_iter()
+    // Measured bitrate for intervals covered by samples
+    let measured: Vec<Option<f64>> = totals
+        .iter()
         .enumerate()
-        .map(|(idx, (total_size, count))| {
-            let bitrate = if count > 0 {
-                // We have actual data for this interval
-                ((total_size * 8) as f64 / interval_seconds) as u64
-            } else {
-                // No data - use average (this interval was not sampled)
-                avg_bitrate
+        .map(|(idx, &total_size)| {
+            let start = idx as f64 * interval_seconds;
+            let covered = sampled_overlap(windows, start, start + interval_seconds);
+            (covered >= interval_seconds / 2.0).then(|| (total_size * 8) as f64 / covered)
+        })
+        .collect();
+
+    let known: Vec<usize> = (0..num_intervals)
+        .filter(|&i| measured[i].is_some())
+        .collect();
+    if known.is_empty() {
+        debug!("No interval is covered by a sample window");
+        return Vec::new();
+    }
+
+    let mut next_known = 0;
+    let data_points: Vec<BitrateDataPoint> = (0..num_intervals)
+        .map(|idx| {
+            while next_known < known.len() && known[next_known] < idx {
+                next_known += 1;
+            }
+            let bitrate = match measured[idx] {
+                Some(value) => value,
+                None => {
+                    let before = next_known.checked_sub(1).map(|k| known[k]);
+                    let after = known.get(next_known).copied();
+                    match (before, after) {
+                        (Some(b), Some(a)) => {
+                            let (vb, va) = (measured[b].unwrap_or(0.0), measured[a].unwrap_or(0.0));
+                            vb + (va - vb) * (idx - b) as f64 / (a - b) as f64
+                        }
+                        (Some(b), None) => measured[b].unwrap_or(0.0),
+                        (None, Some(a)) => measured[a].unwrap_or(0.0),
+                        (None, None) => 0.0,
+                    }
+                }
             };
             BitrateDataPoint {
                 timestamp: idx as f64 * interval_seconds,
-                bitrate,
+                bitrate: bitrate.round() as u64,
                 frame_type: None,
             }
         })
         .collect();
 
     info!(
-        "Extrapolated {} data points (avg bitrate: {} bps)",
+        "Extrapolated {} data points from {} measured intervals",
         data_points.len(),
-        avg_bitrate
+        known.len()
     );
 
     data_points
 }
 
+/// Estimate how well the sampled windows represent the whole file
+///
+/// Treats each window's average bitrate as one observation and builds a 95%
+/// confidence interval for the mean (Student's t, with a finite population
+/// correction since the windows cover part of a fixed-length file).
+pub fn estimate_sampling_confidence(
+    sampled_data: &[(f64, u64, Option<String>)],
+    windows: &[SampleWindow],
+    full_duration: f64,
+    placement: SamplingPlacement,
+) -> SamplingInfo {
+    let window_bitrates: Vec<f64> = windows
+        .iter()
+        .map(|&(start, duration)| {
+            let bytes: u64 = sampled_data
+                .iter()
+                .filter(|(t, _, _)| *t >= start && *t < start + duration)
+                .map(|(_, size, _)| size)
+                .sum();
+            if duration > 0.0 {
+                (bytes * 8) as f64 / duration
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let n = window_bitrates.len();
+    let sampled_seconds: f64 = windows.iter().map(|(_, d)| d).sum();
+    let mean = if n > 0 {
+        window_bitrates.iter().sum::<f64>() / n as f64
+    } else {
+        0.0
+    };
+
+    let margin = if n > 1 {
+        let variance = window_bitrates
+            .iter()
+            .map(|b| (b - mean) * (b - mean))
+            .sum::<f64>()
+            / (n - 1) as f64;
+        // Number of window-sized blocks the file could be split into
+        let population = (full_duration / (sampled_seconds / n as f64)).max(n as f64);
+        let fpc = if population > 1.0 {
+            ((population - n as f64) / (population - 1.0))
+                .max(0.0)
+                .sqrt()
+        } else {
+            0.0
+        };
+        let t = T_CRITICAL_95.get(n - 2).copied().unwrap_or(1.96);
+        t * (variance / n as f64).sqrt() * fpc
+    } else {
+        // A single window says nothing about the spread
+        mean
+    };
+
+    SamplingInfo {
+        placement,
+        sample_count: n,
+        sampled_seconds,
+        coverage: if full_duration > 0.0 {
+            (sampled_seconds / full_duration).min(1.0)
+        } else {
+            0.0
+        },
+        estimated_avg_bitrate: mean.round() as u64,
+        margin_bitrate: margin.round() as u64,
+        relative_margin: if mean > 0.0 { margin / mean } else { 0.0 },
+        confidence_level: 0.95,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1235,6 +1407,7 @@ mod tests {
             stream_type: StreamType::Video,
             codec_name: Some("h264".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1261,6 +1434,7 @@ mod tests {
             stream_type: StreamType::Audio,
             codec_name: Some("aac".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1287,6 +1461,7 @@ mod tests {
             stream_type: StreamType::Video,
             codec_name: Some("h264".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1325,6 +1500,7 @@ mod tests {
             stream_type: StreamType::Audio,
             codec_name: Some("aac".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1351,6 +1527,7 @@ mod tests {
             stream_type: StreamType::Audio,
             codec_name: Some("aac".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1388,6 +1565,7 @@ mod tests {
             stream_type: StreamType::Video,
             codec_name: Some("h264".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1414,6 +1592,7 @@ mod tests {
             stream_type: StreamType::Audio,
             codec_name: Some("aac".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1440,6 +1619,7 @@ mod tests {
             stream_type: StreamType::Subtitle,
             codec_name: Some("subrip".to_string()),
             codec_long_name: None,
+            profile: None,
             language: None,
             title: None,
             is_default: false,
@@ -1476,29 +1656,29 @@ mod tests {
     #[test]
     fn test_extrapolate_empty_samples() {
         let sampled: Vec<(f64, u64, Option<String>)> = vec![];
-        let result = extrapolate_sampled_data(&sampled, 10.0, 100.0, 1.0);
+        let result = extrapolate_sampled_data(&sampled, &[(0.0, 10.0)], 100.0, 1.0);
         assert!(result.is_empty());
     }
 
     #[test]
     fn test_extrapolate_single_sample() {
         let sampled = vec![(5.0, 10000, None)]; // 10KB at 5s
-        let result = extrapolate_sampled_data(&sampled, 10.0, 100.0, 1.0);
+        let result = extrapolate_sampled_data(&sampled, &[(5.0, 1.0)], 100.0, 1.0);
         assert_eq!(result.len(), 100); // 100 seconds / 1 second intervals
 
         // Check that the interval containing the sample has actual data
         assert_eq!(result[5].bitrate, 80000); // 10000 bytes * 8 / 1 second
 
-        // Check that other intervals use average bitrate
-        let avg_bitrate = (10000 * 8) as f64 / 10.0; // Total bytes * 8 / sampled duration
-        assert_eq!(result[0].bitrate, avg_bitrate as u64);
-        assert_eq!(result[10].bitrate, avg_bitrate as u64);
+        // A single measurement is held before and after it
+        assert_eq!(result[0].bitrate, 80000);
+        assert_eq!(result[99].bitrate, 80000);
     }
 
     #[test]
     fn test_extrapolate_multiple_samples() {
         let sampled = vec![(1.0, 5000, None), (2.0, 6000, None), (50.0, 7000, None)];
-        let result = extrapolate_sampled_data(&sampled, 10.0, 100.0, 1.0);
+        let windows = vec![(1.0, 2.0), (50.0, 1.0)];
+        let result = extrapolate_sampled_data(&sampled, &windows, 100.0, 1.0);
         assert_eq!(result.len(), 100);
 
         // Intervals with actual data should have calculated bitrates
@@ -1506,28 +1686,32 @@ mod tests {
         assert_eq!(result[2].bitrate, 48000); // 6000 * 8 / 1
         assert_eq!(result[50].bitrate, 56000); // 7000 * 8 / 1
 
-        // Average bitrate: (5000 + 6000 + 7000) * 8 / 10.0 = 14400
-        assert_eq!(result[25].bitrate, 14400); // Gap interval uses average
+        // Gaps are interpolated between the neighbouring samples
+        assert_eq!(result[26].bitrate, 52000); // halfway between 2s and 50s
+        assert_eq!(result[0].bitrate, 40000);
+        assert_eq!(result[75].bitrate, 56000);
     }
 
     #[test]
-    fn test_extrapolate_zero_sampled_duration() {
-        let sampled = vec![(1.0, 5000, None)];
-        let result = extrapolate_sampled_data(&sampled, 0.0, 100.0, 1.0);
-        assert_eq!(result.len(), 100);
-        // When sampled_duration is 0, avg_bitrate = 0
-        // But intervals with actual data still compute bitrate from their data
-        // Interval 1 has the sample, so it has non-zero bitrate
-        assert_eq!(result[1].bitrate, 40000); // 5000 * 8 / 1.0
-                                              // Other intervals use avg_bitrate which is 0
-        assert_eq!(result[0].bitrate, 0);
-        assert_eq!(result[10].bitrate, 0);
+    fn test_extrapolate_sampled_silence_is_kept() {
+        // A sampled interval with no packets is a real zero, not a gap
+        let sampled = vec![(0.0, 1000, None), (2.0, 1000, None)];
+        let result = extrapolate_sampled_data(&sampled, &[(0.0, 3.0)], 3.0, 1.0);
+        assert_eq!(result[1].bitrate, 0);
+    }
+
+    #[test]
+    fn test_extrapolate_partial_interval_coverage() {
+        // Window covers half of interval 1; its bitrate is scaled to the covered part
+        let sampled = vec![(1.5, 1000, None)];
+        let result = extrapolate_sampled_data(&sampled, &[(1.5, 1.0)], 4.0, 1.0);
+        assert_eq!(result[1].bitrate, 16000); // 1000 * 8 / 0.5
     }
 
     #[test]
     fn test_extrapolate_different_interval_size() {
         let sampled = vec![(2.5, 10000, None)]; // 10KB at 2.5s
-        let result = extrapolate_sampled_data(&sampled, 5.0, 10.0, 0.5);
+        let result = extrapolate_sampled_data(&sampled, &[(2.5, 0.5)], 10.0, 0.5);
         assert_eq!(result.len(), 20); // 10 seconds / 0.5 second intervals
 
         // Interval index = 2.5 / 0.5 = 5
@@ -1537,7 +1721,7 @@ mod tests {
     #[test]
     fn test_extrapolate_timestamps() {
         let sampled = vec![(1.0, 5000, None)];
-        let result = extrapolate_sampled_data(&sampled, 10.0, 10.0, 1.0);
+        let result = extrapolate_sampled_data(&sampled, &[(0.0, 10.0)], 10.0, 1.0);
 
         // Check timestamps are correct
         assert_eq!(result[0].timestamp, 0.0);
@@ -1548,9 +1732,83 @@ mod tests {
     #[test]
     fn test_extrapolate_no_frame_types() {
         let sampled = vec![(1.0, 5000, Some("I".to_string()))];
-        let result = extrapolate_sampled_data(&sampled, 10.0, 100.0, 1.0);
+        let result = extrapolate_sampled_data(&sampled, &[(1.0, 1.0)], 100.0, 1.0);
 
         // Extrapolated data should not have frame types (set to None)
         assert!(result.iter().all(|p| p.frame_type.is_none()));
     }
+
+    // ========== plan_sample_windows tests ==========
+
+    fn sampling_config(placement: SamplingPlacement) -> SamplingConfig {
+        SamplingConfig {
+            threshold_bytes: 1000,
+            sample_count: 4,
+            sample_duration_secs: 10.0,
+            placement,
+            seed: Some(42),
+        }
+    }
+
+    #[test]
+    fn test_plan_sample_windows_full_analysis() {
+        let config = sampling_config(SamplingPlacement::Uniform);
+        // Below the size threshold
+        assert!(plan_sample_windows(1000.0, 999, &config).is_none());
+        // Too short for non-overlapping samples
+        assert!(plan_sample_windows(40.0, 5000, &config).is_none());
+    }
+
+    #[test]
+    fn test_plan_sample_windows_uniform() {
+        let config = sampling_config(SamplingPlacement::Uniform);
+        let windows = plan_sample_windows(400.0, 5000, &config).unwrap();
+        assert_eq!(
+            windows,
+            vec![(0.0, 10.0), (100.0, 10.0), (200.0, 10.0), (300.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn test_plan_sample_windows_random_stays_in_slots() {
+        let config = sampling_config(SamplingPlacement::Random);
+        let windows = plan_sample_windows(400.0, 5000, &config).unwrap();
+        assert_eq!(windows.len(), 4);
+        for (i, (start, duration)) in windows.iter().enumerate() {
+            let slot_start = i as f64 * 100.0;
+            assert!(*start >= slot_start && start + duration <= slot_start + 100.0);
+        }
+        // Same seed, same placement
+        assert_eq!(plan_sample_windows(400.0, 5000, &config).unwrap(), windows);
+    }
+
+    // ========== estimate_sampling_confidence tests ==========
+
+    #[test]
+    fn test_confidence_constant_bitrate() {
+        // Every window has 1000 bytes per second -> no spread
+        let sampled: Vec<(f64, u64, Option<String>)> = (0..4)
+            .flat_map(|w| (0..10).map(move |s| ((w * 100 + s) as f64, 1000, None)))
+            .collect();
+        let windows = vec![(0.0, 10.0), (100.0, 10.0), (200.0, 10.0), (300.0, 10.0)];
+        let info =
+            estimate_sampling_confidence(&sampled, &windows, 400.0, SamplingPlacement::Uniform);
+        assert_eq!(info.sample_count, 4);
+        assert_eq!(info.sampled_seconds, 40.0);
+        assert_eq!(info.coverage, 0.1);
+        assert_eq!(info.estimated_avg_bitrate, 8000);
+        assert_eq!(info.margin_bitrate, 0);
+    }
+
+    #[test]
+    fn test_confidence_widens_with_spread() {
+        let windows = vec![(0.0, 1.0), (100.0, 1.0), (200.0, 1.0)];
+        let steady = vec![(0.0, 1000, None), (100.0, 1000, None), (200.0, 1000, None)];
+        let bursty = vec![(0.0, 500, None), (100.0, 1000, None), (200.0, 1500, None)];
+        let a = estimate_sampling_confidence(&steady, &windows, 300.0, SamplingPlacement::Uniform);
+        let b = estimate_sampling_confidence(&bursty, &windows, 300.0, SamplingPlacement::Uniform);
+        assert_eq!(a.estimated_avg_bitrate, b.estimated_avg_bitrate);
+        assert!(b.relative_margin > a.relative_margin);
+        assert!(b.margin_bitrate > 0);
+    }
 }
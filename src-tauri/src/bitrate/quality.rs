@@ -0,0 +1,339 @@
+//! Per-frame quantizer (QP) and picture type capture
+//!
+//! ffprobe reports picture types but not the quantizer: the decoder only exports
+//! it as `venc_params` side data, which `-show_frames` lists without contents.
+//! So this decodes the stream once with `-export_side_data venc_params` and the
+//! `showinfo` filter, which logs both the picture type and the frame QP.
+//!
+//! QP is available for codecs whose decoder exports encoding parameters
+//! (H.264, MPEG-2/4, VP9, AV1 via libdav1d); other codecs still get picture types.
+
+use log::{debug, info};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use crate::config;
+use crate::media::run_ffmpeg;
+use crate::types::{BitrateDataPoint, QualityAnalysis, QualityDataPoint, StarvedInterval};
+
+/// Intervals must exceed the average QP by at least this much to count as starved
+const MIN_STARVED_QP_DELTA: f64 = 3.0;
+
+/// A decoded frame's picture type and quantizer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameQuality {
+    pub timestamp: f64,
+    pub pict_type: Option<String>,
+    pub qp: Option<f64>,
+}
+
+/// Value following `key` in a showinfo line, up to the next whitespace or `;`
+fn showinfo_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.trim_start()
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .next()
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse `showinfo` log output into frames
+///
+/// Frame lines carry `pts_time:` and `type:`; the QP is logged on a following
+/// `side data - video encoding parameters: type 0; qp=23; ...` line.
+pub fn parse_showinfo_log(stderr: &str) -> Vec<FrameQuality> {
+    let mut frames: Vec<FrameQuality> = Vec::new();
+
+    for line in stderr.lines().filter(|l| l.contains("showinfo")) {
+        if line.contains(" pts_time:") {
+            let Some(timestamp) =
+                showinfo_value(line, " pts_time:").and_then(|v| v.parse::<f64>().ok())
+            else {
+                continue;
+            };
+            frames.push(FrameQuality {
+                timestamp,
+                pict_type: showinfo_value(line, " type:")
+                    .filter(|t| *t != "?")
+                    .map(|t| t.to_string()),
+                qp: None,
+            });
+        } else if line.contains("video encoding parameters") {
+            if let (Some(frame), Some(qp)) = (
+                frames.last_mut(),
+                showinfo_value(line, "qp=").and_then(|v| v.parse::<f64>().ok()),
+            ) {
+                frame.qp = Some(qp);
+            }
+        }
+    }
+
+    frames
+}
+
+/// Decode a video stream and capture per-frame picture type and QP
+pub fn probe_frame_quality(
+    path: &str,
+    stream_index: i32,
+    duration: f64,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64),
+) -> Result<Vec<FrameQuality>, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    let args: Vec<String> = vec![
+        "-export_side_data".to_string(),
+        "venc_params".to_string(),
+        "-i".to_string(),
+        validated_path.to_string_lossy().to_string(),
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+        "-vf".to_string(),
+        "showinfo".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+
+    debug!(
+        "probe_frame_quality: path={}, stream_index={}",
+        path, stream_index
+    );
+    let stderr = run_ffmpeg(&args, Some(duration), Some(cancelled), on_progress)?;
+    let frames = parse_showinfo_log(&stderr);
+    if frames.is_empty() {
+        return Err(format!("No frames decoded for stream {}", stream_index));
+    }
+
+    info!(
+        "Captured quality for {} frames of stream {} ({} with QP)",
+        frames.len(),
+        stream_index,
+        frames.iter().filter(|f| f.qp.is_some()).count()
+    );
+    Ok(frames)
+}
+
+/// Aggregate frame quality into the same intervals as the bitrate series
+pub fn aggregate_quality_intervals(
+    frames: &[FrameQuality],
+    interval_seconds: f64,
+    duration: f64,
+) -> Vec<QualityDataPoint> {
+    if frames.is_empty() || interval_seconds <= 0.0 {
+        return Vec::new();
+    }
+
+    let num_intervals = (duration / interval_seconds).ceil() as usize;
+    let mut points: Vec<QualityDataPoint> = (0..num_intervals)
+        .map(|idx| QualityDataPoint {
+            timestamp: idx as f64 * interval_seconds,
+            avg_qp: None,
+            min_qp: None,
+            max_qp: None,
+            i_frames: 0,
+            p_frames: 0,
+            b_frames: 0,
+        })
+        .collect();
+    let mut qp_sums: Vec<(f64, usize)> = vec![(0.0, 0); num_intervals];
+
+    for frame in frames {
+        let idx = (frame.timestamp / interval_seconds).floor() as usize;
+        let Some(point) = points.get_mut(idx) else {
+            continue;
+        };
+        match frame.pict_type.as_deref() {
+            Some("I") => point.i_frames += 1,
+            Some("P") => point.p_frames += 1,
+            Some("B") => point.b_frames += 1,
+            _ => {}
+        }
+        if let Some(qp) = frame.qp {
+            qp_sums[idx].0 += qp;
+            qp_sums[idx].1 += 1;
+            point.min_qp = Some(point.min_qp.map_or(qp, |m| m.min(qp)));
+            point.max_qp = Some(point.max_qp.map_or(qp, |m| m.max(qp)));
+        }
+    }
+
+    for (point, (sum, count)) in points.iter_mut().zip(qp_sums) {
+        if count > 0 {
+            point.avg_qp = Some(sum / count as f64);
+        }
+    }
+    points
+}
+
+/// Find spans where the interval QP is well above the stream's usual QP
+///
+/// Threshold is one standard deviation above the mean, and at least
+/// `MIN_STARVED_QP_DELTA` above it so flat encodes don't report noise.
+pub fn find_starved_intervals(
+    quality: &[QualityDataPoint],
+    bitrate: &[BitrateDataPoint],
+    interval_seconds: f64,
+) -> Vec<StarvedInterval> {
+    let qps: Vec<f64> = quality.iter().filter_map(|p| p.avg_qp).collect();
+    if qps.len() < 2 {
+        return Vec::new();
+    }
+    let mean = qps.iter().sum::<f64>() / qps.len() as f64;
+    let std_dev =
+        (qps.iter().map(|q| (q - mean) * (q - mean)).sum::<f64>() / qps.len() as f64).sqrt();
+    let threshold = mean + std_dev.max(MIN_STARVED_QP_DELTA);
+
+    let mut intervals = Vec::new();
+    // (start index, qp sum, bitrate sum, count)
+    let mut open: Option<(usize, f64, u64, usize)> = None;
+    let mut close = |open: (usize, f64, u64, usize), end_idx: usize| {
+        let (start_idx, qp_sum, bitrate_sum, count) = open;
+        let start_time = quality[start_idx].timestamp;
+        let end_time = quality[end_idx - 1].timestamp + interval_seconds;
+        intervals.push(StarvedInterval {
+            start_time,
+            end_time,
+            duration: end_time - start_time,
+            avg_qp: qp_sum / count as f64,
+            avg_bitrate: bitrate_sum / count as u64,
+        });
+    };
+
+    for (idx, point) in quality.iter().enumerate() {
+        match point.avg_qp.filter(|qp| *qp > threshold) {
+            Some(qp) => {
+                let rate = bitrate.get(idx).map(|b| b.bitrate).unwrap_or(0);
+                let entry = open.get_or_insert((idx, 0.0, 0, 0));
+                entry.1 += qp;
+                entry.2 += rate;
+                entry.3 += 1;
+            }
+            None => {
+                if let Some(current) = open.take() {
+                    close(current, idx);
+                }
+            }
+        }
+    }
+    if let Some(current) = open.take() {
+        close(current, quality.len());
+    }
+
+    intervals
+}
+
+/// Build the quality part of a stream analysis
+pub fn build_quality_analysis(
+    frames: &[FrameQuality],
+    bitrate: &[BitrateDataPoint],
+    interval_seconds: f64,
+    duration: f64,
+) -> QualityAnalysis {
+    let data_points = aggregate_quality_intervals(frames, interval_seconds, duration);
+    let qps: Vec<f64> = frames.iter().filter_map(|f| f.qp).collect();
+
+    QualityAnalysis {
+        qp_available: !qps.is_empty(),
+        avg_qp: (!qps.is_empty()).then(|| qps.iter().sum::<f64>() / qps.len() as f64),
+        max_qp: qps.iter().cloned().reduce(f64::max),
+        starved_intervals: find_starved_intervals(&data_points, bitrate, interval_seconds),
+        data_points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[Parsed_showinfo_0 @ 0x1] config in time_base: 1/1000, frame_rate: 25/1
+[Parsed_showinfo_0 @ 0x1] n:   0 pts:      0 pts_time:0       duration:40 pos: 1234 fmt:yuv420p sar:1/1 s:1920x1080 i:P iskey:1 type:I checksum:AB12
+[Parsed_showinfo_0 @ 0x1]   side data - video encoding parameters: type 0; qp=20; 8160 blocks;
+[Parsed_showinfo_0 @ 0x1] n:   1 pts:     40 pts_time:0.04    duration:40 pos: 5678 fmt:yuv420p sar:1/1 s:1920x1080 i:P iskey:0 type:B checksum:CD34
+[Parsed_showinfo_0 @ 0x1]   side data - video encoding parameters: type 0; qp=26; delta_qp[1][0]=-2; 8160 blocks;
+[Parsed_showinfo_0 @ 0x1] n:   2 pts:     80 pts_time:0.08    duration:40 pos: 9999 fmt:yuv420p sar:1/1 s:1920x1080 i:P iskey:0 type:P checksum:EF56
+frame=    3 fps=0.0 q=-0.0 Lsize=N/A time=00:00:00.12
+";
+
+    fn frame(timestamp: f64, pict_type: &str, qp: Option<f64>) -> FrameQuality {
+        FrameQuality {
+            timestamp,
+            pict_type: Some(pict_type.to_string()),
+            qp,
+        }
+    }
+
+    #[test]
+    fn test_parse_showinfo_log() {
+        let frames = parse_showinfo_log(LOG);
+        assert_eq!(
+            frames,
+            vec![
+                frame(0.0, "I", Some(20.0)),
+                frame(0.04, "B", Some(26.0)),
+                // No side data: picture type only
+                frame(0.08, "P", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregate_quality_intervals() {
+        let frames = vec![
+            frame(0.0, "I", Some(20.0)),
+            frame(0.5, "B", Some(30.0)),
+            frame(1.2, "P", None),
+        ];
+        let points = aggregate_quality_intervals(&frames, 1.0, 2.0);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].avg_qp, Some(25.0));
+        assert_eq!(
+            (points[0].min_qp, points[0].max_qp),
+            (Some(20.0), Some(30.0))
+        );
+        assert_eq!((points[0].i_frames, points[0].b_frames), (1, 1));
+        assert_eq!(points[1].avg_qp, None);
+        assert_eq!(points[1].p_frames, 1);
+    }
+
+    #[test]
+    fn test_find_starved_intervals() {
+        let qps = [22.0, 22.0, 22.0, 35.0, 36.0, 22.0, 22.0, 22.0];
+        let quality: Vec<QualityDataPoint> = qps
+            .iter()
+            .enumerate()
+            .map(|(i, &qp)| QualityDataPoint {
+                timestamp: i as f64,
+                avg_qp: Some(qp),
+                min_qp: Some(qp),
+                max_qp: Some(qp),
+                i_frames: 0,
+                p_frames: 1,
+                b_frames: 0,
+            })
+            .collect();
+        let bitrate: Vec<BitrateDataPoint> = (0..qps.len())
+            .map(|i| BitrateDataPoint {
+                timestamp: i as f64,
+                bitrate: 1000,
+                frame_type: None,
+            })
+            .collect();
+
+        let starved = find_starved_intervals(&quality, &bitrate, 1.0);
+        assert_eq!(starved.len(), 1);
+        assert_eq!((starved[0].start_time, starved[0].end_time), (3.0, 5.0));
+        assert_eq!(starved[0].avg_qp, 35.5);
+        assert_eq!(starved[0].avg_bitrate, 1000);
+    }
+
+    #[test]
+    fn test_flat_encode_has_no_starved_intervals() {
+        let frames: Vec<FrameQuality> = (0..10)
+            .map(|i| frame(i as f64, "P", Some(23.0 + (i % 2) as f64)))
+            .collect();
+        let analysis = build_quality_analysis(&frames, &[], 1.0, 10.0);
+        assert!(analysis.qp_available);
+        assert_eq!(analysis.avg_qp, Some(23.5));
+        assert_eq!(analysis.max_qp, Some(24.0));
+        assert!(analysis.starved_intervals.is_empty());
+    }
+}
@@ -3,9 +3,9 @@
 //! This module contains all Tauri commands for bitrate analysis operations,
 //! including progress reporting via Tauri window events.
 //!
-//! Note: Caching is handled by the frontend via SQLite database.
-//! The backend focuses purely on analysis - the frontend checks the DB cache
-//! before calling these analysis functions and saves results after completion.
+//! Note: Overall analyses are cached in the shared SQLite database. The backend
+//! returns cached results (`from_cache: true`) when the file is unchanged and
+//! saves new results after completion.
 //!
 //! Performance optimizations:
 //! - Uses packet-mode ffprobe (fast) by default, falls back to frame-mode if needed
@@ -13,21 +13,22 @@
 //! - Streaming CSV parsing for reduced memory overhead
 
 use log::{debug, error, info, warn};
-use rayon::prelude::*;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use tauri::Emitter;
 
+use super::job_runner::run_job;
 use crate::bitrate::{
-    aggregate_bitrate_intervals, calculate_statistics, compute_file_hash, parse_ffprobe_auto,
-    parse_ffprobe_sampled, SAMPLE_COUNT, SAMPLE_DURATION_SECS, SAMPLING_THRESHOLD_BYTES,
+    self, aggregate_bitrate_intervals, analyze_overall, calculate_statistics, compute_file_hash,
+    get_cached_analysis, get_latest_analysis, parse_ffprobe_auto, save_to_cache, AnalysisParams,
 };
 use crate::files::get_file_metadata;
 use crate::jobs::{self, JobProgress, JobStartResult, JobType};
-use crate::media::get_media_streams;
+use crate::media::{get_media_streams, CANCELLED_MESSAGE};
 use crate::types::{
-    BitrateAnalysis, BitrateDataPoint, BitrateProgress, JobStatus, OverallBitrateAnalysis,
-    QueueStatus, StreamContribution, StreamType,
+    BatchBitrateOptions, BatchBitrateReport, BitrateAnalysis, BitrateExportFormat,
+    BitrateExportResult, BitrateProgress, ComplianceOptions, CompliancePresetInfo,
+    ComplianceReport, GopAnalysis, JobStatus, OverallBitrateAnalysis, QueueStatus, SamplingConfig,
+    StreamType,
 };
 
 /// Compute a file hash for cache validation
@@ -47,16 +48,21 @@ pub fn compute_file_hash_cmd(path: String) -> Result<String, String> {
 }
 
 /// Analyze bitrate for a specific stream in a media file
+///
+/// With `include_quality`, video streams are also decoded once to capture
+/// per-frame QP and picture types (considerably slower than bitrate alone).
 #[tauri::command]
 pub async fn analyze_stream_bitrate(
     path: String,
     stream_index: i32,
     interval_seconds: f64,
+    include_quality: Option<bool>,
     window: tauri::Window,
 ) -> Result<BitrateAnalysis, String> {
+    let include_quality = include_quality.unwrap_or(false);
     info!(
-        "analyze_stream_bitrate command: path={}, stream_index={}, interval={}s",
-        path, stream_index, interval_seconds
+        "analyze_stream_bitrate command: path={}, stream_index={}, interval={}s, quality={}",
+        path, stream_index, interval_seconds, include_quality
     );
 
     let path_clone = path.clone();
@@ -195,6 +201,40 @@ pub async fn analyze_stream_bitrate(
 
         let data_points = aggregate_bitrate_intervals(frames, interval_seconds, duration);
 
+        // Optional: decode for QP / picture types (video only)
+        let quality = if include_quality && stream.stream_type == StreamType::Video {
+            let quality_frames = bitrate::probe_frame_quality(
+                &path_clone,
+                stream_index,
+                duration,
+                &cancelled,
+                &mut |pct| {
+                    emit_progress(
+                        80,
+                        100,
+                        80.0 + pct * 0.1,
+                        "Capturing frame quality...".to_string(),
+                    )
+                },
+            );
+            // Quality is supplementary: keep the bitrate result unless the user cancelled
+            match quality_frames {
+                Ok(frames) => Some(bitrate::build_quality_analysis(
+                    &frames,
+                    &data_points,
+                    interval_seconds,
+                    duration,
+                )),
+                Err(e) if e == CANCELLED_MESSAGE => return Err(e),
+                Err(e) => {
+                    warn!("Frame quality capture failed for {}: {}", path_clone, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         // Stage 5: Calculate statistics
         emit_progress(90, 100, 90.0, "Calculating statistics...".to_string());
 
@@ -220,6 +260,7 @@ pub async fn analyze_stream_bitrate(
             duration,
             data_points,
             statistics,
+            quality,
         })
     })
     .await
@@ -245,20 +286,37 @@ pub async fn analyze_stream_bitrate(
 
 /// Analyze overall bitrate for a media file (all streams combined)
 ///
-/// Note: Caching is handled by the frontend. The frontend should:
-/// 1. Check the database cache before calling this function
-/// 2. Save the result to the database after this function returns
+/// Returns the cached analysis if the file hasn't changed since it was last
+/// analyzed; otherwise analyzes the file and saves the result to the cache.
 #[tauri::command]
 pub async fn analyze_overall_bitrate(
     path: String,
     interval_seconds: f64,
+    sampling: Option<SamplingConfig>,
     window: tauri::Window,
 ) -> Result<OverallBitrateAnalysis, String> {
+    let sampling_config = sampling.unwrap_or_default();
     info!(
         "analyze_overall_bitrate command: path={}, interval={}s",
         path, interval_seconds
     );
 
+    let cache_params = AnalysisParams {
+        interval_seconds,
+        sampling: sampling_config.clone(),
+    };
+    let cache_path = path.clone();
+    let lookup_params = cache_params.clone();
+    let cached = tauri::async_runtime::spawn_blocking(move || {
+        get_cached_analysis(&cache_path, &lookup_params)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?;
+    if let Some(analysis) = cached {
+        info!("Using cached overall bitrate analysis for {}", path);
+        return Ok(analysis);
+    }
+
     let path_clone = path.clone();
     let window_clone = window.clone();
 
@@ -313,363 +371,26 @@ pub async fn analyze_overall_bitrate(
             path_clone, job_id, interval_seconds
         );
 
-        // Track start time for ETA calculation
-        let analysis_start = std::time::Instant::now();
-
-        // Helper to emit progress and update job state with enhanced info
-        let emit_progress_enhanced =
-            |current: usize,
-             total: usize,
-             percentage: f64,
-             stage: String,
-             using_sampling: Option<bool>,
-             stream_count: Option<usize>,
-             current_stream: Option<usize>| {
-                let elapsed = analysis_start.elapsed().as_secs_f64();
-
-                // Calculate ETA based on elapsed time and percentage
-                let eta_seconds = if percentage > 5.0 && percentage < 100.0 {
-                    let remaining_percentage = 100.0 - percentage;
-                    Some((elapsed / percentage) * remaining_percentage)
-                } else {
-                    None
-                };
-
-                let progress = BitrateProgress {
-                    current,
-                    total,
-                    percentage,
-                    stage: stage.clone(),
-                    eta_seconds,
-                    elapsed_seconds: Some(elapsed),
-                    using_sampling,
-                    stream_count,
-                    current_stream,
-                };
+        analyze_overall(
+            &path_clone,
+            interval_seconds,
+            &sampling_config,
+            &cancelled,
+            &|progress: BitrateProgress| {
                 window_clone.emit("bitrate-progress", &progress).ok();
                 jobs::update_job_progress(
                     &path_clone,
                     JobProgress {
-                        current,
-                        total,
-                        percentage,
-                        stage,
+                        current: progress.current,
+                        total: progress.total,
+                        percentage: progress.percentage,
+                        stage: progress.stage,
                     },
                 );
                 // Small yield to allow event delivery
                 std::thread::sleep(std::time::Duration::from_millis(10));
-            };
-
-        // Simple progress helper for initial stages
-        let emit_progress = |current: usize, total: usize, percentage: f64, stage: String| {
-            emit_progress_enhanced(current, total, percentage, stage, None, None, None);
-        };
-
-        // Stage 1: Get all streams
-        emit_progress(0, 100, 0.0, "Getting stream information...".to_string());
-
-        debug!("Stage 1: Getting streams");
-        let streams = get_media_streams(path_clone.clone())?;
-        let file_size = streams.total_size;
-        info!(
-            "Found {} total streams, file size: {:.2} GB",
-            streams.streams.len(),
-            file_size as f64 / 1024.0 / 1024.0 / 1024.0
-        );
-
-        // Check if we should use sampling mode for large files
-        let use_sampling = file_size >= SAMPLING_THRESHOLD_BYTES;
-        if use_sampling {
-            info!(
-                "Large file detected ({:.2} GB >= {:.2} GB threshold), will use sampling mode",
-                file_size as f64 / 1024.0 / 1024.0 / 1024.0,
-                SAMPLING_THRESHOLD_BYTES as f64 / 1024.0 / 1024.0 / 1024.0
-            );
-        }
-
-        // Stage 2: Get duration
-        emit_progress(5, 100, 5.0, "Reading file metadata...".to_string());
-
-        let metadata_json = get_file_metadata(path_clone.clone())?;
-        let metadata: serde_json::Value = serde_json::from_str(
-            &metadata_json
-                .ffprobe_data
-                .ok_or("No ffprobe data available")?,
+            },
         )
-        .map_err(|e| format!("Failed to parse metadata: {}", e))?;
-
-        let duration = metadata["format"]["duration"]
-            .as_str()
-            .and_then(|s| s.parse::<f64>().ok())
-            .ok_or("Could not determine duration")?;
-
-        // Check for cancellation
-        if cancelled.load(Ordering::SeqCst) {
-            return Err("Analysis cancelled".to_string());
-        }
-
-        // Count video/audio streams for progress tracking
-        let analysis_streams: Vec<_> = streams
-            .streams
-            .iter()
-            .filter(|s| s.stream_type == StreamType::Video || s.stream_type == StreamType::Audio)
-            .cloned()
-            .collect();
-
-        let total_streams = analysis_streams.len();
-        info!(
-            "Will analyze {} video/audio streams in parallel (sampling: {})",
-            total_streams, use_sampling
-        );
-        let num_intervals = (duration / interval_seconds).ceil() as usize;
-
-        // Stage 3: Analyze streams in PARALLEL using rayon
-        debug!("Stage 3: Starting parallel stream analysis");
-        let stage_msg = if use_sampling {
-            format!(
-                "Sampling {} streams ({} x {}s intervals)...",
-                total_streams, SAMPLE_COUNT, SAMPLE_DURATION_SECS
-            )
-        } else {
-            format!("Analyzing {} streams in parallel...", total_streams)
-        };
-        emit_progress_enhanced(
-            10,
-            100,
-            10.0,
-            stage_msg,
-            Some(use_sampling),
-            Some(total_streams),
-            None,
-        );
-
-        // Shared progress counter for parallel execution
-        let analyzed_count = Arc::new(AtomicUsize::new(0));
-        let cancelled_clone = cancelled.clone();
-
-        // Process streams in parallel
-        let stream_results: Vec<_> = analysis_streams
-            .par_iter()
-            .filter_map(|stream| {
-                // Check for cancellation
-                if cancelled_clone.load(Ordering::SeqCst) {
-                    return None;
-                }
-
-                let stream_type_name = match stream.stream_type {
-                    StreamType::Audio => "audio",
-                    StreamType::Video => "video",
-                    _ => "stream",
-                };
-
-                debug!(
-                    "Parallel: Analyzing {} stream {} ({})",
-                    stream_type_name,
-                    stream.index,
-                    stream.codec_name.as_deref().unwrap_or("unknown")
-                );
-
-                // Use sampling for large files, otherwise fast packet mode
-                let (frames, was_sampled) = if use_sampling {
-                    match parse_ffprobe_sampled(&path_clone, stream.index, duration, file_size) {
-                        Ok((f, sampled)) => {
-                            analyzed_count.fetch_add(1, Ordering::SeqCst);
-                            (f, sampled)
-                        }
-                        Err(e) => {
-                            warn!(
-                                "Failed to parse stream {} ({}): {}",
-                                stream.index,
-                                stream.codec_name.as_deref().unwrap_or("unknown"),
-                                e
-                            );
-                            return None;
-                        }
-                    }
-                } else {
-                    match parse_ffprobe_auto(&path_clone, stream.index, false) {
-                        Ok(f) => {
-                            analyzed_count.fetch_add(1, Ordering::SeqCst);
-                            (f, false)
-                        }
-                        Err(e) => {
-                            warn!(
-                                "Failed to parse stream {} ({}): {}",
-                                stream.index,
-                                stream.codec_name.as_deref().unwrap_or("unknown"),
-                                e
-                            );
-                            return None;
-                        }
-                    }
-                };
-
-                if was_sampled {
-                    debug!("Stream {} was analyzed using sampling mode", stream.index);
-                }
-
-                // Track per-stream intervals
-                let mut stream_intervals: Vec<u64> = vec![0; num_intervals];
-
-                // Aggregate this stream's contribution
-                for (timestamp, size, _) in &frames {
-                    let interval_idx = (*timestamp / interval_seconds).floor() as usize;
-                    if interval_idx < num_intervals {
-                        stream_intervals[interval_idx] += size;
-                    }
-                }
-
-                Some((
-                    stream.index,
-                    stream.stream_type.clone(),
-                    stream.codec_name.clone(),
-                    stream_intervals,
-                ))
-            })
-            .collect();
-
-        // Check if cancelled during parallel processing
-        if cancelled.load(Ordering::SeqCst) {
-            info!("Job {} cancelled during stream analysis", job_id);
-            return Err("Analysis cancelled".to_string());
-        }
-
-        let analyzed_count = analyzed_count.load(Ordering::SeqCst);
-
-        // Combine results from parallel processing
-        let mut combined_intervals: Vec<u64> = vec![0; num_intervals];
-        let mut stream_data: Vec<(i32, StreamType, Option<String>, Vec<u64>)> = Vec::new();
-
-        for (stream_index, stream_type, codec_name, intervals) in stream_results {
-            // Add to combined intervals
-            for (i, size) in intervals.iter().enumerate() {
-                combined_intervals[i] += size;
-            }
-            stream_data.push((stream_index, stream_type, codec_name, intervals));
-        }
-
-        info!(
-            "Parallel analysis complete: {}/{} streams analyzed in {:.2}s",
-            analyzed_count,
-            total_streams,
-            analysis_start.elapsed().as_secs_f64()
-        );
-
-        // Emit progress update after parallel analysis completes
-        emit_progress_enhanced(
-            85,
-            100,
-            85.0,
-            format!(
-                "Stream analysis complete ({}/{} streams)",
-                analyzed_count, total_streams
-            ),
-            Some(use_sampling),
-            Some(total_streams),
-            Some(total_streams),
-        );
-
-        // Check if we analyzed any streams
-        if analyzed_count == 0 {
-            error!("Failed to analyze any streams in the file");
-            return Err("Failed to analyze any streams in the file".to_string());
-        }
-
-        info!(
-            "Successfully analyzed {}/{} streams",
-            analyzed_count, total_streams
-        );
-
-        // Stage 4: Aggregate data
-        emit_progress(90, 100, 90.0, "Aggregating bitrate data...".to_string());
-
-        // Calculate combined total before consuming combined_intervals
-        let combined_total: u64 = combined_intervals.iter().sum();
-
-        let data_points: Vec<BitrateDataPoint> = combined_intervals
-            .into_iter()
-            .enumerate()
-            .map(|(idx, total_size)| {
-                let bitrate = ((total_size * 8) as f64 / interval_seconds) as u64;
-                BitrateDataPoint {
-                    timestamp: idx as f64 * interval_seconds,
-                    bitrate,
-                    frame_type: None,
-                }
-            })
-            .collect();
-
-        // Calculate stream contributions with per-stream data points
-        let mut stream_contributions: Vec<StreamContribution> = Vec::new();
-        for (stream_index, stream_type, codec_name, intervals) in stream_data {
-            // Calculate this stream's total bitrate from actual data
-            let stream_total: u64 = intervals.iter().sum();
-            let percentage = if combined_total > 0 {
-                (stream_total as f64 / combined_total as f64) * 100.0
-            } else {
-                0.0
-            };
-
-            // Convert intervals to data points
-            let stream_data_points: Vec<BitrateDataPoint> = intervals
-                .into_iter()
-                .enumerate()
-                .map(|(idx, total_size)| {
-                    let bitrate = ((total_size * 8) as f64 / interval_seconds) as u64;
-                    BitrateDataPoint {
-                        timestamp: idx as f64 * interval_seconds,
-                        bitrate,
-                        frame_type: None,
-                    }
-                })
-                .collect();
-
-            stream_contributions.push(StreamContribution {
-                stream_index,
-                stream_type,
-                codec_name: codec_name.unwrap_or_default(),
-                percentage,
-                data_points: stream_data_points,
-            });
-        }
-
-        // Stage 5: Calculate statistics
-        emit_progress_enhanced(
-            95,
-            100,
-            95.0,
-            "Calculating statistics...".to_string(),
-            Some(use_sampling),
-            Some(total_streams),
-            Some(total_streams),
-        );
-
-        let statistics = calculate_statistics(&data_points);
-
-        // Complete
-        let total_elapsed = analysis_start.elapsed().as_secs_f64();
-        emit_progress_enhanced(
-            100,
-            100,
-            100.0,
-            format!("Complete in {:.1}s", total_elapsed),
-            Some(use_sampling),
-            Some(total_streams),
-            Some(total_streams),
-        );
-        info!(
-            "Overall bitrate analysis complete in {:.2}s (sampling: {})",
-            total_elapsed, use_sampling
-        );
-
-        Ok(OverallBitrateAnalysis {
-            path: path_clone,
-            duration,
-            data_points,
-            statistics,
-            stream_contributions,
-            from_cache: false,
-        })
     })
     .await
     .map_err(|e| format!("Task join error: {}", e));
@@ -683,8 +404,17 @@ pub async fn analyze_overall_bitrate(
         .emit("job-queue-update", jobs::get_queue_status())
         .ok();
 
-    // Note: Caching is now handled by the frontend
-    // The frontend will save this result to the SQLite database
+    if let Ok(Ok(analysis)) = &result {
+        let cache_path = path.clone();
+        let analysis = analysis.clone();
+        let saved = tauri::async_runtime::spawn_blocking(move || {
+            save_to_cache(&cache_path, &cache_params, &analysis)
+        })
+        .await;
+        if let Ok(Err(e)) = saved {
+            warn!("Failed to cache bitrate analysis for {}: {}", path, e);
+        }
+    }
 
     // Now propagate error if there was one
     match &result {
@@ -702,6 +432,133 @@ pub async fn analyze_overall_bitrate(
     result?
 }
 
+/// Analyze GOP structure and keyframe intervals for a video stream
+#[tauri::command]
+pub async fn analyze_gop(
+    path: String,
+    stream_index: i32,
+    window: tauri::Window,
+) -> Result<GopAnalysis, String> {
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::GopAnalysis, move |job| {
+        info!(
+            "Starting GOP analysis: path={}, stream_index={}",
+            path_for_work, stream_index
+        );
+        bitrate::analyze_gop(
+            &path_for_work,
+            stream_index,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+/// List the VBV/HRD presets available for compliance checks
+#[tauri::command]
+pub fn get_compliance_presets() -> Vec<CompliancePresetInfo> {
+    bitrate::list_compliance_presets()
+}
+
+/// Check a file's cached bitrate series against a VBV/HRD buffer model
+///
+/// Uses the cached overall analysis, so the overall bitrate analysis must have
+/// been run for the current file contents first.
+#[tauri::command]
+pub async fn check_bitrate_compliance(
+    path: String,
+    options: Option<ComplianceOptions>,
+) -> Result<ComplianceReport, String> {
+    let options = options.unwrap_or_default();
+    info!(
+        "check_bitrate_compliance command: path={}, preset={:?}",
+        path, options.preset
+    );
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = bitrate::resolve_vbv_settings(&options)?;
+        let analysis = get_latest_analysis(&path).ok_or(
+            "No bitrate analysis found for this file. Run the overall bitrate analysis first.",
+        )?;
+
+        let data_points = match options.stream_index {
+            Some(index) => {
+                &analysis
+                    .stream_contributions
+                    .iter()
+                    .find(|c| c.stream_index == index)
+                    .ok_or_else(|| format!("No bitrate data for stream {}", index))?
+                    .data_points
+            }
+            None => &analysis.data_points,
+        };
+
+        let report = bitrate::check_compliance(&path, data_points, &settings, &options);
+        info!(
+            "Compliance check for {}: {} violations",
+            path,
+            report.violations.len()
+        );
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Analyze every media file in a folder and build an aggregate report
+///
+/// Runs as one queued job keyed by the directory. Cached analyses are reused
+/// and new ones are cached, so re-running a report only analyzes changed files.
+#[tauri::command]
+pub async fn analyze_folder_bitrate(
+    directory: String,
+    options: Option<BatchBitrateOptions>,
+    window: tauri::Window,
+) -> Result<BatchBitrateReport, String> {
+    let options = options.unwrap_or_default();
+    info!(
+        "analyze_folder_bitrate command: directory={}, recursive={}",
+        directory, options.recursive
+    );
+
+    let dir_clone = directory.clone();
+    run_job(
+        window,
+        &directory,
+        JobType::BatchBitrateReport,
+        move |job| {
+            bitrate::analyze_folder(
+                &dir_clone,
+                &options,
+                job.cancel_flag(),
+                &mut |pct, stage| job.report(pct, stage),
+            )
+        },
+    )
+    .await
+}
+
+/// Write an overall bitrate analysis to disk as CSV, JSON or an SVG chart
+#[tauri::command]
+pub async fn export_bitrate_analysis(
+    analysis: OverallBitrateAnalysis,
+    format: BitrateExportFormat,
+    output_path: String,
+) -> Result<BitrateExportResult, String> {
+    info!(
+        "export_bitrate_analysis command: path={}, format={:?}, output={}",
+        analysis.path, format, output_path
+    );
+
+    tauri::async_runtime::spawn_blocking(move || {
+        bitrate::export_analysis(&analysis, format, &output_path)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
 /// Cancel an ongoing bitrate analysis for a file
 #[tauri::command]
 pub async fn cancel_bitrate_analysis(path: String, window: tauri::Window) -> Result<bool, String> {
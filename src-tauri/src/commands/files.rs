@@ -4,9 +4,10 @@
 
 use crate::files;
 use crate::files::filters::{FilterCriteria, FilterResult};
+use crate::journal;
 use crate::types::{
     BulkRenameResult, DependenciesResult, FileEntry, FileMetadata, FileOperationResult,
-    RenamePattern, RenamePreview,
+    JournalChange, JournalOperationKind, RenamePattern, RenamePreview,
 };
 
 #[tauri::command]
@@ -103,6 +104,7 @@ pub async fn execute_bulk_rename(
         let mut success = 0;
         let mut failed = 0;
         let mut errors = Vec::new();
+        let mut changes = Vec::new();
 
         for preview in previews {
             // Skip if name unchanged
@@ -112,7 +114,13 @@ pub async fn execute_bulk_rename(
             }
 
             match std::fs::rename(&preview.original_path, &preview.new_path) {
-                Ok(_) => success += 1,
+                Ok(_) => {
+                    success += 1;
+                    changes.push(JournalChange::Moved {
+                        from: preview.original_path,
+                        to: preview.new_path,
+                    });
+                }
                 Err(e) => {
                     failed += 1;
                     errors.push(format!("{}: {}", preview.original_name, e));
@@ -120,6 +128,13 @@ pub async fn execute_bulk_rename(
             }
         }
 
+        // One journal entry so the whole batch is undone together
+        journal::record(
+            JournalOperationKind::BulkRename,
+            format!("Rename {} file(s)", changes.len()),
+            changes,
+        );
+
         Ok(BulkRenameResult {
             success,
             failed,
@@ -0,0 +1,144 @@
+//! Shared job runner for queued long-running commands
+//!
+//! Wraps the enqueue -> wait for slot -> run -> complete lifecycle used by
+//! job-backed commands, and emits `job-progress` events on their behalf.
+
+use log::{debug, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::Emitter;
+
+use crate::bitrate::compute_file_hash;
+use crate::jobs::{self, JobProgress, JobStartResult, JobType};
+use crate::types::JobProgressEvent;
+
+/// Handle passed to job work closures for progress and cancellation
+pub struct JobHandle {
+    path: String,
+    job_type: String,
+    cancelled: Arc<AtomicBool>,
+    window: tauri::Window,
+}
+
+impl JobHandle {
+    /// Cancellation flag for this job
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+
+    /// Update job progress in the queue and emit a `job-progress` event
+    pub fn report(&self, percentage: f64, stage: &str) {
+        let percentage = percentage.clamp(0.0, 100.0);
+        jobs::update_job_progress(
+            &self.path,
+            JobProgress {
+                current: percentage.round() as usize,
+                total: 100,
+                percentage,
+                stage: stage.to_string(),
+            },
+        );
+        self.window
+            .emit(
+                "job-progress",
+                JobProgressEvent {
+                    path: self.path.clone(),
+                    job_type: self.job_type.clone(),
+                    percentage,
+                    stage: stage.to_string(),
+                },
+            )
+            .ok();
+    }
+}
+
+/// Job ID for a path: the file hash, or a hash of the path itself when the
+/// path is not a readable file yet (e.g. the output of a job)
+fn job_id_for_path(path: &str) -> String {
+    compute_file_hash(path).unwrap_or_else(|_| {
+        use sha2::{Digest, Sha256};
+        format!("{:x}", Sha256::digest(path.as_bytes()))
+    })
+}
+
+/// Run `work` as a queued job for `path`
+///
+/// The job is keyed by `path` and waits for a free slot before running.
+/// Returns an error if a job already exists for the path or the job is cancelled
+/// before it starts.
+pub async fn run_job<T, F>(
+    window: tauri::Window,
+    path: &str,
+    job_type: JobType,
+    work: F,
+) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&JobHandle) -> Result<T, String> + Send + 'static,
+{
+    let file_hash = job_id_for_path(path);
+    let job_type_name = job_type.name().to_string();
+
+    let job_id = match jobs::enqueue_job(path, &file_hash, job_type) {
+        JobStartResult::Started(id) | JobStartResult::Queued(id) => id,
+        JobStartResult::AlreadyExists(job_id) => {
+            return Err(format!(
+                "A job is already queued or in progress for this file (job {})",
+                job_id
+            ));
+        }
+    };
+    let cancelled = jobs::get_job_cancel_flag(path).ok_or("Failed to get job cancellation flag")?;
+
+    window
+        .emit("job-queue-update", jobs::get_queue_status())
+        .ok();
+
+    // Wait until this job is actually running (not just queued)
+    loop {
+        if cancelled.load(Ordering::SeqCst) {
+            debug!("Job cancelled before starting: {}", path);
+            jobs::complete_job(path);
+            window
+                .emit("job-queue-update", jobs::get_queue_status())
+                .ok();
+            return Err("Operation cancelled".to_string());
+        }
+
+        if jobs::get_job_details(path).is_some() {
+            break;
+        }
+
+        // Cancelling a queued job removes it from the queue entirely
+        if jobs::get_job_cancel_flag(path).is_none() {
+            debug!("Queued job removed before starting: {}", path);
+            window
+                .emit("job-queue-update", jobs::get_queue_status())
+                .ok();
+            return Err("Operation cancelled".to_string());
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    info!("Starting {} job {} for: {}", job_type_name, job_id, path);
+
+    let handle = JobHandle {
+        path: path.to_string(),
+        job_type: job_type_name,
+        cancelled,
+        window: window.clone(),
+    };
+
+    // Run in blocking thread to avoid blocking async runtime
+    let result = tauri::async_runtime::spawn_blocking(move || work(&handle))
+        .await
+        .map_err(|e| format!("Task join error: {}", e));
+
+    jobs::complete_job(path);
+    window
+        .emit("job-queue-update", jobs::get_queue_status())
+        .ok();
+
+    result?
+}
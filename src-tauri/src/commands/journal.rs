@@ -0,0 +1,29 @@
+//! Undo journal Tauri commands
+
+use crate::journal;
+use crate::types::{JournalOperation, UndoResult};
+
+/// Default number of entries returned by `get_undo_history`
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+#[tauri::command]
+pub async fn undo_last_operation() -> Result<UndoResult, String> {
+    tauri::async_runtime::spawn_blocking(journal::undo_last_operation)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn undo_operation(id: i64) -> Result<UndoResult, String> {
+    tauri::async_runtime::spawn_blocking(move || journal::undo_operation(id))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_undo_history(limit: Option<usize>) -> Result<Vec<JournalOperation>, String> {
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    tauri::async_runtime::spawn_blocking(move || journal::get_history(limit))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
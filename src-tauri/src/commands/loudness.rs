@@ -0,0 +1,69 @@
+//! Loudness analysis Tauri commands
+//!
+//! Both commands run as queued jobs and report progress via `job-progress` events.
+
+use log::info;
+
+use super::job_runner::run_job;
+use crate::jobs::JobType;
+use crate::loudness;
+use crate::types::{LoudnessAnalysis, NormalizationOptions, NormalizationResult};
+
+/// Default interval for the momentary loudness time series
+const DEFAULT_LOUDNESS_INTERVAL_SECS: f64 = 1.0;
+
+/// Measure EBU R128 loudness for an audio stream
+#[tauri::command]
+pub async fn analyze_loudness(
+    path: String,
+    stream_index: i32,
+    interval_seconds: Option<f64>,
+    window: tauri::Window,
+) -> Result<LoudnessAnalysis, String> {
+    let interval = interval_seconds
+        .filter(|i| *i > 0.0)
+        .unwrap_or(DEFAULT_LOUDNESS_INTERVAL_SECS);
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::LoudnessAnalysis, move |job| {
+        info!(
+            "Starting loudness analysis: path={}, stream={}, interval={}s",
+            path_for_work, stream_index, interval
+        );
+        loudness::analyze_loudness(
+            &path_for_work,
+            stream_index,
+            interval,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+/// Normalize an audio stream to a target loudness (two-pass loudnorm)
+#[tauri::command]
+pub async fn normalize_loudness(
+    path: String,
+    stream_index: i32,
+    options: Option<NormalizationOptions>,
+    window: tauri::Window,
+) -> Result<NormalizationResult, String> {
+    let options = options.unwrap_or_default();
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::LoudnessNormalization, move |job| {
+        info!(
+            "Starting loudness normalization: path={}, stream={}, target={} LUFS",
+            path_for_work, stream_index, options.target_lufs
+        );
+        loudness::normalize_loudness(
+            &path_for_work,
+            stream_index,
+            &options,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
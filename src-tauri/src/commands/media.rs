@@ -5,12 +5,17 @@
 use log::{debug, info};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
+use super::job_runner::run_job;
 use crate::bitrate::compute_file_hash;
 use crate::jobs::{self, JobStartResult, JobType};
 use crate::media;
-use crate::types::{BulkStreamRemovalResult, MediaStreams, StreamRemovalOp, StreamRemovalResult};
+use crate::types::{
+    BulkStreamRemovalResult, ConcatCheck, ConcatResult, ContactSheetOptions, ContactSheetResult,
+    DiskSpaceCheck, MediaStreams, OptimizationOptions, OptimizationReport, SplitMode, SplitPreview,
+    SplitResult, StreamRemovalOp, StreamRemovalResult, ThumbnailResult,
+};
 
 #[tauri::command]
 pub fn get_media_streams(path: String) -> Result<MediaStreams, String> {
@@ -72,7 +77,7 @@ pub async fn remove_streams(
         }
 
         // Perform the actual stream removal
-        media::remove_streams(path_clone, stream_indices, overwrite)
+        media::remove_streams(path_clone, stream_indices, overwrite, Some(&cancelled))
     })
     .await
     .map_err(|e| format!("Task join error: {}", e))?;
@@ -88,6 +93,19 @@ pub async fn remove_streams(
     result
 }
 
+/// Check free space before removing streams (the job itself refuses if it won't fit)
+#[tauri::command]
+pub async fn check_stream_removal_space(
+    path: String,
+    stream_indices: Vec<i32>,
+) -> Result<DiskSpaceCheck, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        media::preflight_stream_removal(&path, &stream_indices)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
 #[tauri::command]
 pub async fn bulk_remove_streams(
     operations: Vec<StreamRemovalOp>,
@@ -197,7 +215,12 @@ pub async fn bulk_remove_streams(
                     path_for_work, stream_indices
                 );
 
-                media::remove_streams(path_for_work, stream_indices, overwrite)
+                media::remove_streams(
+                    path_for_work,
+                    stream_indices,
+                    overwrite,
+                    Some(&cancelled_clone),
+                )
             })
             .await;
 
@@ -230,3 +253,125 @@ pub async fn bulk_remove_streams(
         errors,
     })
 }
+
+/// Quantified suggestions for making a file smaller
+#[tauri::command]
+pub async fn get_optimization_suggestions(
+    path: String,
+    options: Option<OptimizationOptions>,
+) -> Result<OptimizationReport, String> {
+    let options = options.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || media::analyze_optimizations(&path, &options))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn preview_media_split(path: String, mode: SplitMode) -> Result<SplitPreview, String> {
+    tauri::async_runtime::spawn_blocking(move || media::preview_split(&path, &mode, None))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn split_media(
+    path: String,
+    mode: SplitMode,
+    window: tauri::Window,
+) -> Result<SplitResult, String> {
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::MediaSplit, move |job| {
+        info!(
+            "Starting media split: path={}, mode={:?}",
+            path_for_work, mode
+        );
+        media::split_media(
+            &path_for_work,
+            &mode,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn check_concat_compatibility(paths: Vec<String>) -> Result<ConcatCheck, String> {
+    tauri::async_runtime::spawn_blocking(move || media::check_concat(&paths))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn concat_files(
+    paths: Vec<String>,
+    output_path: String,
+    add_chapters: bool,
+    window: tauri::Window,
+) -> Result<ConcatResult, String> {
+    // Keyed by the output so concurrent joins into the same file are rejected
+    let output_for_work = output_path.clone();
+
+    run_job(window, &output_path, JobType::MediaConcat, move |job| {
+        info!(
+            "Starting concat: {} inputs -> {}",
+            paths.len(),
+            output_for_work
+        );
+        media::concat_files(
+            &paths,
+            &output_for_work,
+            add_chapters,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_thumbnail(
+    app: tauri::AppHandle,
+    path: String,
+    width: Option<u32>,
+) -> Result<ThumbnailResult, String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to get app cache dir: {}", e))?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        media::get_thumbnail(
+            &path,
+            &cache_dir,
+            width.unwrap_or(media::DEFAULT_THUMBNAIL_WIDTH),
+        )
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn create_contact_sheet(
+    path: String,
+    options: Option<ContactSheetOptions>,
+    window: tauri::Window,
+) -> Result<ContactSheetResult, String> {
+    let options = options.unwrap_or_default();
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::ContactSheet, move |job| {
+        info!(
+            "Starting contact sheet: path={}, grid={}x{}",
+            path_for_work, options.columns, options.rows
+        );
+        media::create_contact_sheet(
+            &path_for_work,
+            &options,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
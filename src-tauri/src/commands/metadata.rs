@@ -1,6 +1,10 @@
+use super::job_runner::run_job;
+use crate::jobs::{self, FileClaim, JobType};
 use crate::metadata;
 use crate::types::{
-    MetadataOperation, MetadataSnapshot, MetadataToolAvailability, MetadataUpdateResult,
+    BulkMetadataResult, BulkMetadataTarget, CoverArt, ExifEdit, ExifMetadata, ExifShift,
+    MetadataOperation, MetadataSnapshot, MetadataTemplate, MetadataToolAvailability,
+    MetadataUpdateResult,
 };
 
 #[tauri::command]
@@ -13,11 +17,200 @@ pub async fn update_metadata(
     path: String,
     operations: Vec<MetadataOperation>,
 ) -> Result<MetadataUpdateResult, String> {
+    jobs::ensure_not_claimed(&path)?;
     tauri::async_runtime::spawn_blocking(move || metadata::update_metadata(path, operations))
         .await
         .map_err(|e| format!("Task join error: {}", e))?
 }
 
+/// Apply metadata operations to many files as one queued job
+///
+/// Targets without their own operations use `operations`. With `dry_run`,
+/// nothing is written and the result previews each file's before/after values.
+#[tauri::command]
+pub async fn bulk_update_metadata(
+    targets: Vec<BulkMetadataTarget>,
+    operations: Option<Vec<MetadataOperation>>,
+    dry_run: Option<bool>,
+    window: tauri::Window,
+) -> Result<BulkMetadataResult, String> {
+    let operations = operations.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+    let paths: Vec<String> = targets.iter().map(|t| t.path.clone()).collect();
+    let (key, _claim) = start_batch(&JobType::BulkMetadataUpdate, &paths, dry_run)?;
+
+    run_job(window, &key, JobType::BulkMetadataUpdate, move |job| {
+        metadata::bulk_update_metadata(
+            &targets,
+            &operations,
+            dry_run,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+/// Unique job key for a multi-file metadata job, plus a claim on its files
+///
+/// Dry runs don't write, so their files are left unclaimed.
+fn start_batch(
+    job_type: &JobType,
+    paths: &[String],
+    dry_run: bool,
+) -> Result<(String, Option<FileClaim>), String> {
+    if paths.is_empty() {
+        return Err("No files provided".to_string());
+    }
+    let key = jobs::batch_job_key(job_type);
+    let claim = if dry_run {
+        None
+    } else {
+        Some(jobs::claim_files(&key, paths)?)
+    };
+    Ok((key, claim))
+}
+
+#[tauri::command]
+pub async fn list_metadata_templates() -> Result<Vec<MetadataTemplate>, String> {
+    tauri::async_runtime::spawn_blocking(metadata::get_templates)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Create a template (no id) or update an existing one
+#[tauri::command]
+pub async fn save_metadata_template(
+    template: MetadataTemplate,
+) -> Result<MetadataTemplate, String> {
+    tauri::async_runtime::spawn_blocking(move || metadata::store_template(&template))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_metadata_template(id: i64) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || metadata::remove_template(id))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Apply a saved template to one or more files as a queued job
+///
+/// With `dry_run`, nothing is written and the result previews each file's
+/// before/after values.
+#[tauri::command]
+pub async fn apply_metadata_template(
+    template_id: i64,
+    paths: Vec<String>,
+    dry_run: Option<bool>,
+    window: tauri::Window,
+) -> Result<BulkMetadataResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let (key, _claim) = start_batch(&JobType::BulkMetadataUpdate, &paths, dry_run)?;
+    let template =
+        tauri::async_runtime::spawn_blocking(move || metadata::get_template(template_id))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))??;
+
+    run_job(window, &key, JobType::BulkMetadataUpdate, move |job| {
+        metadata::apply_metadata_template(
+            &template,
+            &paths,
+            dry_run,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_cover_art(path: String) -> Result<Option<CoverArt>, String> {
+    tauri::async_runtime::spawn_blocking(move || metadata::get_cover_art(&path))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn set_cover_art(path: String, image_path: String) -> Result<(), String> {
+    jobs::ensure_not_claimed(&path)?;
+    tauri::async_runtime::spawn_blocking(move || metadata::set_cover_art(&path, &image_path))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn remove_cover_art(path: String) -> Result<bool, String> {
+    jobs::ensure_not_claimed(&path)?;
+    tauri::async_runtime::spawn_blocking(move || metadata::remove_cover_art(&path))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_exif_metadata(path: String) -> Result<ExifMetadata, String> {
+    tauri::async_runtime::spawn_blocking(move || metadata::read_exif(&path))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn edit_exif_metadata(
+    path: String,
+    edits: Vec<ExifEdit>,
+) -> Result<ExifMetadata, String> {
+    jobs::ensure_not_claimed(&path)?;
+    tauri::async_runtime::spawn_blocking(move || metadata::edit_exif(&path, &edits))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn shift_exif_metadata(
+    paths: Vec<String>,
+    shift: ExifShift,
+    dry_run: Option<bool>,
+    window: tauri::Window,
+) -> Result<BulkMetadataResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let (key, _claim) = start_batch(&JobType::BulkMetadataUpdate, &paths, dry_run)?;
+
+    run_job(window, &key, JobType::BulkMetadataUpdate, move |job| {
+        metadata::shift_exif(
+            &paths,
+            &shift,
+            dry_run,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
+/// Strip identifying metadata; `keep` overrides the saved keep list
+#[tauri::command]
+pub async fn scrub_metadata(
+    paths: Vec<String>,
+    keep: Option<Vec<String>>,
+    dry_run: Option<bool>,
+    window: tauri::Window,
+) -> Result<BulkMetadataResult, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let (key, _claim) = start_batch(&JobType::MetadataScrub, &paths, dry_run)?;
+
+    run_job(window, &key, JobType::MetadataScrub, move |job| {
+        metadata::scrub_metadata(
+            &paths,
+            keep,
+            dry_run,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )
+    })
+    .await
+}
+
 #[tauri::command]
 pub fn metadata_tools() -> MetadataToolAvailability {
     metadata::tool_status()
@@ -3,8 +3,12 @@
 //! This module re-exports all Tauri commands for the application.
 //! Commands are organized by domain:
 //! - File operations (list, metadata, rename, delete, move, copy)
-//! - Media operations (streams, removal)
+//! - Media operations (streams, removal, trim/split)
+//! - Metadata editing (single, bulk, templates)
 //! - Bitrate analysis (analyze, cancel, cache)
+//! - Loudness analysis and normalization
+//! - Detection timeline (scene cuts, black frames, silence)
+//! - Undo journal (undo last/specific operation, history)
 //! - Settings operations (get/set settings, folder picker, path validation)
 //! - Installer operations (install dependencies, get strategies)
 //! - System utilities (dependencies, home dir)
@@ -12,14 +16,21 @@
 mod bitrate;
 mod files;
 mod installer;
+mod job_runner;
+mod journal;
+mod loudness;
 mod media;
 mod metadata;
 mod settings;
+mod timeline;
 
 // Use wildcard re-exports to include macro-generated items from #[tauri::command]
 pub use bitrate::*;
 pub use files::*;
 pub use installer::*;
+pub use journal::*;
+pub use loudness::*;
 pub use media::*;
 pub use metadata::*;
 pub use settings::*;
+pub use timeline::*;
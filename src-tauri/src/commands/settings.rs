@@ -182,3 +182,64 @@ pub fn get_default_downloads_dir() -> String {
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_else(|| "~/Downloads".to_string())
 }
+
+/// Whether rewritten media files are fully decoded before replacing originals
+#[tauri::command]
+pub fn get_full_decode_verification() -> bool {
+    crate::config::full_decode_verification()
+}
+
+/// Enable or disable the full decode check (slower, catches corrupt output)
+#[tauri::command]
+pub fn set_full_decode_verification(enabled: bool) -> Result<(), String> {
+    crate::config::set_full_decode_verification(enabled)
+}
+
+/// Retention policy for backups kept by the undo journal
+#[tauri::command]
+pub fn get_undo_retention() -> crate::config::UndoRetention {
+    crate::config::undo_retention()
+}
+
+/// Change the undo backup retention and apply it to existing backups
+#[tauri::command]
+pub async fn set_undo_retention(retention: crate::config::UndoRetention) -> Result<usize, String> {
+    crate::config::set_undo_retention(retention)?;
+    tauri::async_runtime::spawn_blocking(crate::journal::apply_retention)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Whether rewritten files keep their original timestamps and permissions
+#[tauri::command]
+pub fn get_preserve_file_attributes() -> bool {
+    crate::config::preserve_file_attributes()
+}
+
+/// Keep (default) or reset timestamps and permissions when files are rewritten
+#[tauri::command]
+pub fn set_preserve_file_attributes(enabled: bool) -> Result<(), String> {
+    crate::config::set_preserve_file_attributes(enabled)
+}
+
+/// Largest width/height embedded cover art is scaled to (0 = no limit)
+#[tauri::command]
+pub fn get_cover_art_max_dimension() -> u32 {
+    crate::config::cover_art_max_dimension()
+}
+
+#[tauri::command]
+pub fn set_cover_art_max_dimension(max_dimension: u32) -> Result<(), String> {
+    crate::config::set_cover_art_max_dimension(max_dimension)
+}
+
+/// Tags a privacy scrub keeps (e.g. copyright)
+#[tauri::command]
+pub fn get_scrub_keep_tags() -> Vec<String> {
+    crate::config::scrub_keep_tags()
+}
+
+#[tauri::command]
+pub fn set_scrub_keep_tags(tags: Vec<String>) -> Result<(), String> {
+    crate::config::set_scrub_keep_tags(tags)
+}
@@ -0,0 +1,50 @@
+//! Detection timeline Tauri commands
+
+use log::{info, warn};
+
+use super::job_runner::run_job;
+use crate::jobs::JobType;
+use crate::timeline;
+use crate::types::{DetectionOptions, TimelineAnalysis};
+
+/// Detect scene cuts, black and silent segments and suggest chapter/trim points
+///
+/// Returns the cached timeline if the file hasn't changed since it was last
+/// analyzed with the same options; otherwise analyzes the file and caches it.
+#[tauri::command]
+pub async fn analyze_timeline(
+    path: String,
+    options: Option<DetectionOptions>,
+    window: tauri::Window,
+) -> Result<TimelineAnalysis, String> {
+    let options = options.unwrap_or_default();
+
+    let cache_path = path.clone();
+    let cache_options = options.clone();
+    let cached = tauri::async_runtime::spawn_blocking(move || {
+        timeline::get_cached_timeline(&cache_path, &cache_options)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?;
+    if let Some(analysis) = cached {
+        info!("Using cached timeline analysis for {}", path);
+        return Ok(analysis);
+    }
+
+    let path_for_work = path.clone();
+
+    run_job(window, &path, JobType::TimelineDetection, move |job| {
+        info!("Starting timeline detection: path={}", path_for_work);
+        let analysis = timeline::analyze_timeline(
+            &path_for_work,
+            &options,
+            job.cancel_flag(),
+            &mut |pct, stage| job.report(pct, stage),
+        )?;
+        if let Err(e) = timeline::save_timeline_to_cache(&path_for_work, &analysis) {
+            warn!("Failed to cache timeline analysis: {}", e);
+        }
+        Ok(analysis)
+    })
+    .await
+}
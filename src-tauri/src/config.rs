@@ -3,6 +3,9 @@
 //! Manages user settings including:
 //! - Allowed directories for file operations (whitelist)
 //! - Security settings
+//! - Verification of rewritten media files
+//! - Retention of undo backups
+//! - Preserving timestamps and permissions of rewritten files
 
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
@@ -15,6 +18,62 @@ pub struct AppConfig {
     /// Whitelist of directories allowed for file operations
     /// Empty list = unrestricted (first-time setup)
     pub allowed_directories: Vec<PathBuf>,
+    /// Fully decode rewritten media files before replacing the original
+    #[serde(default)]
+    pub full_decode_verification: bool,
+    /// How long backups of rewritten files are kept for undo
+    #[serde(default)]
+    pub undo_retention: UndoRetention,
+    /// Keep the original mtime/atime and mode bits when a file is rewritten in place
+    #[serde(default = "default_preserve_file_attributes")]
+    pub preserve_file_attributes: bool,
+    /// Embedded cover art larger than this (width or height, px) is scaled down; 0 = no limit
+    #[serde(default = "default_cover_art_max_dimension")]
+    pub cover_art_max_dimension: u32,
+    /// Tags a privacy scrub never removes (matched by name, case-insensitive)
+    #[serde(default = "default_scrub_keep_tags")]
+    pub scrub_keep_tags: Vec<String>,
+}
+
+fn default_preserve_file_attributes() -> bool {
+    true
+}
+
+fn default_cover_art_max_dimension() -> u32 {
+    1200
+}
+
+fn default_scrub_keep_tags() -> Vec<String> {
+    ["Copyright", "CopyrightNotice", "Rights"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Retention policy for backups kept by the undo journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UndoRetention {
+    /// Days a backup is kept; 0 disables backups (rewrites can't be undone)
+    pub backup_days: u32,
+    /// Total size of kept backups; the oldest are removed beyond this
+    pub max_backup_bytes: u64,
+}
+
+impl Default for UndoRetention {
+    fn default() -> Self {
+        Self {
+            backup_days: 7,
+            max_backup_bytes: 20 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl UndoRetention {
+    /// Whether rewritten files keep a backup at all
+    pub fn keeps_backups(&self) -> bool {
+        self.backup_days > 0 && self.max_backup_bytes > 0
+    }
 }
 
 impl Default for AppConfig {
@@ -26,6 +85,11 @@ impl Default for AppConfig {
         }
         Self {
             allowed_directories: allowed,
+            full_decode_verification: false,
+            undo_retention: UndoRetention::default(),
+            preserve_file_attributes: default_preserve_file_attributes(),
+            cover_art_max_dimension: default_cover_art_max_dimension(),
+            scrub_keep_tags: default_scrub_keep_tags(),
         }
     }
 }
@@ -136,3 +200,92 @@ pub fn set_allowed_directories(directories: Vec<PathBuf>) -> Result<(), String>
     config.allowed_directories = validated;
     Ok(())
 }
+
+/// Whether writing operations fully decode their output before replacing files
+pub fn full_decode_verification() -> bool {
+    CONFIG
+        .read()
+        .map(|config| config.full_decode_verification)
+        .unwrap_or(false)
+}
+
+/// Enable or disable the full decode check after writing operations
+pub fn set_full_decode_verification(enabled: bool) -> Result<(), String> {
+    let mut config = CONFIG
+        .write()
+        .map_err(|e| format!("Config lock error: {}", e))?;
+    config.full_decode_verification = enabled;
+    Ok(())
+}
+
+/// Current retention policy for undo backups
+pub fn undo_retention() -> UndoRetention {
+    CONFIG
+        .read()
+        .map(|config| config.undo_retention.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the retention policy for undo backups
+pub fn set_undo_retention(retention: UndoRetention) -> Result<(), String> {
+    let mut config = CONFIG
+        .write()
+        .map_err(|e| format!("Config lock error: {}", e))?;
+    config.undo_retention = retention;
+    Ok(())
+}
+
+/// Whether in-place rewrites keep the original timestamps and permissions
+pub fn preserve_file_attributes() -> bool {
+    CONFIG
+        .read()
+        .map(|config| config.preserve_file_attributes)
+        .unwrap_or(true)
+}
+
+/// Enable or disable keeping timestamps and permissions on rewritten files
+pub fn set_preserve_file_attributes(enabled: bool) -> Result<(), String> {
+    let mut config = CONFIG
+        .write()
+        .map_err(|e| format!("Config lock error: {}", e))?;
+    config.preserve_file_attributes = enabled;
+    Ok(())
+}
+
+/// Largest width/height of embedded cover art (0 = keep the image size)
+pub fn cover_art_max_dimension() -> u32 {
+    CONFIG
+        .read()
+        .map(|config| config.cover_art_max_dimension)
+        .unwrap_or_else(|_| default_cover_art_max_dimension())
+}
+
+/// Set the largest width/height of embedded cover art (0 = no limit)
+pub fn set_cover_art_max_dimension(max_dimension: u32) -> Result<(), String> {
+    let mut config = CONFIG
+        .write()
+        .map_err(|e| format!("Config lock error: {}", e))?;
+    config.cover_art_max_dimension = max_dimension;
+    Ok(())
+}
+
+/// Tags kept by privacy scrubs
+pub fn scrub_keep_tags() -> Vec<String> {
+    CONFIG
+        .read()
+        .map(|config| config.scrub_keep_tags.clone())
+        .unwrap_or_else(|_| default_scrub_keep_tags())
+}
+
+/// Replace the list of tags kept by privacy scrubs
+pub fn set_scrub_keep_tags(tags: Vec<String>) -> Result<(), String> {
+    let mut config = CONFIG
+        .write()
+        .map_err(|e| format!("Config lock error: {}", e))?;
+    config.scrub_keep_tags = tags
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    Ok(())
+}
@@ -3,10 +3,25 @@
 //! This module defines the database schema and migrations for the Seer application.
 //! The database is used for:
 //! - Job tracking (background tasks like bitrate analysis, re-encoding, etc.)
-//! - Caching (bitrate analysis results, media metadata, etc.)
+//! - Caching (bitrate analysis results, detection timelines, media metadata, etc.)
+//! - The undo journal for destructive file and media operations
+//!
+//! Migrations are applied by `tauri-plugin-sql` when the frontend loads the
+//! database. The backend opens the same file directly with `rusqlite` so that
+//! work started outside the frontend (background jobs, watch rules) can use it too.
 
+use once_cell::sync::OnceCell;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri_plugin_sql::{Migration, MigrationKind};
 
+/// Full path of the SQLite file, set once at startup
+static DATABASE_PATH: OnceCell<PathBuf> = OnceCell::new();
+
+/// How long backend connections wait for a lock held by the frontend
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Get all database migrations
 pub fn get_migrations() -> Vec<Migration> {
     vec![
@@ -215,6 +230,123 @@ pub fn get_migrations() -> Vec<Migration> {
             "#,
             kind: MigrationKind::Up,
         },
+        // Migration 11: Create detection timeline tables (scene cuts, black and silent segments)
+        Migration {
+            version: 11,
+            description: "create_timeline_tables",
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS timeline_analysis (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    file_path TEXT NOT NULL,
+                    file_hash TEXT NOT NULL,
+                    duration REAL NOT NULL,
+                    options TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    UNIQUE(file_path, file_hash)
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_timeline_analysis_file_path ON timeline_analysis(file_path);
+                CREATE INDEX IF NOT EXISTS idx_timeline_analysis_file_hash ON timeline_analysis(file_hash);
+
+                CREATE TABLE IF NOT EXISTS timeline_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    analysis_id INTEGER NOT NULL,
+                    event_type TEXT NOT NULL,
+                    start_time REAL NOT NULL,
+                    end_time REAL NOT NULL,
+                    score REAL,
+                    FOREIGN KEY (analysis_id) REFERENCES timeline_analysis(id) ON DELETE CASCADE
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_timeline_events_analysis_id ON timeline_events(analysis_id);
+
+                CREATE TABLE IF NOT EXISTS timeline_suggestions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    analysis_id INTEGER NOT NULL,
+                    kind TEXT NOT NULL,
+                    timestamp REAL NOT NULL,
+                    reason TEXT NOT NULL,
+                    FOREIGN KEY (analysis_id) REFERENCES timeline_analysis(id) ON DELETE CASCADE
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_timeline_suggestions_analysis_id ON timeline_suggestions(analysis_id);
+            "#,
+            kind: MigrationKind::Up,
+        },
+        // Migration 12: Record how sampled bitrate analyses were taken (JSON, NULL for full analyses)
+        Migration {
+            version: 12,
+            description: "add_bitrate_analysis_sampling",
+            sql: r#"
+                ALTER TABLE bitrate_analysis ADD COLUMN sampling TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
+        // Migration 13: Create undo journal tables for destructive file and media operations
+        Migration {
+            version: 13,
+            description: "create_operation_journal_tables",
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS operation_journal (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    kind TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'applied',
+                    backup_bytes INTEGER NOT NULL DEFAULT 0,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    undone_at TEXT
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_operation_journal_status ON operation_journal(status);
+                CREATE INDEX IF NOT EXISTS idx_operation_journal_created_at ON operation_journal(created_at);
+
+                CREATE TABLE IF NOT EXISTS operation_journal_changes (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    operation_id INTEGER NOT NULL,
+                    change_type TEXT NOT NULL,
+                    old_path TEXT,
+                    new_path TEXT NOT NULL,
+                    backup_path TEXT,
+                    FOREIGN KEY (operation_id) REFERENCES operation_journal(id) ON DELETE CASCADE
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_operation_journal_changes_operation_id ON operation_journal_changes(operation_id);
+            "#,
+            kind: MigrationKind::Up,
+        },
+        // Migration 14: Create metadata templates table (fields stored as JSON)
+        Migration {
+            version: 14,
+            description: "create_metadata_templates_table",
+            sql: r#"
+                CREATE TABLE IF NOT EXISTS metadata_templates (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    fields TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+                );
+            "#,
+            kind: MigrationKind::Up,
+        },
+        // Migration 15: Extra data for journal changes (JSON, e.g. tag edits to revert)
+        Migration {
+            version: 15,
+            description: "add_operation_journal_change_details",
+            sql: r#"
+                ALTER TABLE operation_journal_changes ADD COLUMN details TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
+        // Migration 16: Sampling settings a bitrate analysis was requested with (JSON, part of the cache key)
+        Migration {
+            version: 16,
+            description: "add_bitrate_analysis_sampling_config",
+            sql: r#"
+                ALTER TABLE bitrate_analysis ADD COLUMN sampling_config TEXT;
+            "#,
+            kind: MigrationKind::Up,
+        },
     ]
 }
 
@@ -225,3 +357,44 @@ pub const DATABASE_NAME: &str = "seer.db";
 pub fn get_database_url() -> String {
     format!("sqlite:{}", DATABASE_NAME)
 }
+
+/// Record where the database lives
+///
+/// `tauri-plugin-sql` resolves `sqlite:` URLs relative to the app config directory,
+/// so `dir` must be that directory for both sides to share one file.
+pub fn init_database_path(dir: &Path) {
+    let _ = DATABASE_PATH.set(dir.join(DATABASE_NAME));
+}
+
+/// Open a backend connection to the app database
+///
+/// Fails if the path was never initialized (e.g. outside the Tauri app) or the
+/// database hasn't been created by the frontend yet.
+pub fn open_connection() -> Result<Connection, String> {
+    let path = DATABASE_PATH
+        .get()
+        .ok_or("Database path has not been initialized")?;
+    if !path.exists() {
+        return Err(format!("Database does not exist yet: {}", path.display()));
+    }
+    let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+    configure_connection(&conn)?;
+    Ok(conn)
+}
+
+/// Per-connection settings (cascading deletes, waiting on the frontend's locks)
+pub fn configure_connection(conn: &Connection) -> Result<(), String> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to set busy timeout: {}", e))?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")
+        .map_err(|e| format!("Failed to enable foreign keys: {}", e))
+}
+
+/// Apply all migrations to a connection (used by tests; the app uses the plugin)
+#[cfg(test)]
+pub fn apply_migrations(conn: &Connection) {
+    for migration in get_migrations() {
+        conn.execute_batch(migration.sql)
+            .unwrap_or_else(|e| panic!("Migration {} failed: {}", migration.version, e));
+    }
+}
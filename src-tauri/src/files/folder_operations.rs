@@ -1,19 +1,50 @@
 //! Folder creation operations
 //!
 //! This module handles creating folders from file selections with multiple modes.
+//! Created folders and moved files are recorded in the undo journal as one operation.
 
+use crate::journal;
 use crate::media::find_command;
-use crate::types::{FolderCreationMode, FolderCreationResult, GroupCriteria};
+use crate::types::{
+    FolderCreationMode, FolderCreationResult, GroupCriteria, JournalChange, JournalOperationKind,
+};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 use std::time::UNIX_EPOCH;
 
+/// Create `folder` (and missing parents), journaling it if it didn't exist
+fn create_dir_journaled(folder: &Path, changes: &mut Vec<JournalChange>) -> std::io::Result<()> {
+    let existed = folder.exists();
+    fs::create_dir_all(folder)?;
+    if !existed {
+        changes.push(JournalChange::CreatedDir {
+            path: folder.to_string_lossy().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Move `source` to `dest`, journaling the move
+fn move_journaled(
+    source: &Path,
+    dest: &Path,
+    changes: &mut Vec<JournalChange>,
+) -> std::io::Result<()> {
+    fs::rename(source, dest)?;
+    changes.push(JournalChange::Moved {
+        from: source.to_string_lossy().to_string(),
+        to: dest.to_string_lossy().to_string(),
+    });
+    Ok(())
+}
+
 /// Create folders per file (movie.mp4 → movie/movie.mp4)
 fn create_folder_per_file(
     paths: Vec<String>,
     parent: &str,
+    changes: &mut Vec<JournalChange>,
 ) -> Result<FolderCreationResult, String> {
     let parent_path = Path::new(parent);
     let mut success = 0;
@@ -37,13 +68,13 @@ fn create_folder_per_file(
         // Create folder path
         let folder_path = parent_path.join(base_name);
 
-        match fs::create_dir_all(&folder_path) {
+        match create_dir_journaled(&folder_path, changes) {
             Ok(_) => {
                 folders_created.push(folder_path.to_string_lossy().to_string());
 
                 // Move file into folder
                 let dest = folder_path.join(file_name);
-                match fs::rename(&source, &dest) {
+                match move_journaled(source, &dest, changes) {
                     Ok(_) => success += 1,
                     Err(e) => {
                         failed += 1;
@@ -71,12 +102,13 @@ fn create_single_folder_and_move(
     paths: Vec<String>,
     folder_name: String,
     parent: &str,
+    changes: &mut Vec<JournalChange>,
 ) -> Result<FolderCreationResult, String> {
     let parent_path = Path::new(parent);
     let folder_path = parent_path.join(&folder_name);
 
     // Create the folder
-    fs::create_dir_all(&folder_path)
+    create_dir_journaled(&folder_path, changes)
         .map_err(|e| format!("Failed to create folder '{}': {}", folder_name, e))?;
 
     let mut success = 0;
@@ -92,7 +124,7 @@ fn create_single_folder_and_move(
             .ok_or_else(|| format!("Invalid file name: {}", path))?;
 
         let dest = folder_path.join(file_name);
-        match fs::rename(&source, &dest) {
+        match move_journaled(source, &dest, changes) {
             Ok(_) => success += 1,
             Err(e) => {
                 failed += 1;
@@ -114,6 +146,7 @@ fn create_grouped_folders(
     paths: Vec<String>,
     criteria: GroupCriteria,
     parent: &str,
+    changes: &mut Vec<JournalChange>,
 ) -> Result<FolderCreationResult, String> {
     let parent_path = Path::new(parent);
 
@@ -135,7 +168,7 @@ fn create_grouped_folders(
     for (folder_name, file_paths) in groups {
         let folder_path = parent_path.join(&folder_name);
 
-        match fs::create_dir_all(&folder_path) {
+        match create_dir_journaled(&folder_path, changes) {
             Ok(_) => {
                 folders_created.push(folder_path.to_string_lossy().to_string());
 
@@ -144,7 +177,7 @@ fn create_grouped_folders(
                     let file_name = source.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
                     let dest = folder_path.join(file_name);
-                    match fs::rename(&source, &dest) {
+                    match move_journaled(source, &dest, changes) {
                         Ok(_) => success += 1,
                         Err(e) => {
                             failed += 1;
@@ -382,15 +415,28 @@ pub fn create_folders_from_selection(
     mode: FolderCreationMode,
     parent_dir: String,
 ) -> Result<FolderCreationResult, String> {
-    match mode {
-        FolderCreationMode::PerFile => create_folder_per_file(paths, &parent_dir),
+    let mut changes = Vec::new();
+    let result = match mode {
+        FolderCreationMode::PerFile => create_folder_per_file(paths, &parent_dir, &mut changes),
         FolderCreationMode::Grouped { criteria } => {
-            create_grouped_folders(paths, criteria, &parent_dir)
+            create_grouped_folders(paths, criteria, &parent_dir, &mut changes)
         }
         FolderCreationMode::Single { name } => {
-            create_single_folder_and_move(paths, name, &parent_dir)
+            create_single_folder_and_move(paths, name, &parent_dir, &mut changes)
         }
-    }
+    };
+
+    // Journal whatever was done, even if a later file failed
+    let description = match &result {
+        Ok(summary) => format!(
+            "Move {} file(s) into {} folder(s)",
+            summary.success,
+            summary.folders_created.len()
+        ),
+        Err(_) => "Create folders (partially completed)".to_string(),
+    };
+    journal::record(JournalOperationKind::CreateFolders, description, changes);
+    result
 }
 
 #[cfg(test)]
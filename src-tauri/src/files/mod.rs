@@ -9,11 +9,13 @@
 //! - Bulk rename operations with various patterns
 //! - Folder creation from selections (per-file, grouped, single)
 //! - Smart filtering by size, date, extension, and media properties
+//! - Crash-safe in-place replacement of rewritten files
 
 pub mod filters;
 pub mod folder_operations;
 mod operations;
 pub mod rename_patterns;
+mod replace;
 
 pub use operations::{
     check_command, check_dependencies, copy_dir_recursive, copy_file, create_folder, delete_file,
@@ -24,3 +26,7 @@ pub use operations::{
 pub use filters::{apply_filters, get_available_extensions, FilterCriteria, FilterResult};
 pub use folder_operations::create_folders_from_selection;
 pub use rename_patterns::preview_renames;
+pub use replace::{
+    atomic_replace, atomic_restore, copy_file_attributes, file_times, init_recovery_dir,
+    recover_interrupted_replaces, recover_pending_in, restore_file_times,
+};
@@ -12,10 +12,11 @@ use std::path::Path;
 use std::process::Command;
 
 use crate::config;
+use crate::journal;
 use crate::media::{find_command, get_probe_string};
 use crate::types::{
     is_video_audio_extension, DependenciesResult, DependencyStatus, FileEntry, FileMetadata,
-    FileOperationResult, MEDIA_EXTENSIONS,
+    FileOperationResult, JournalChange, JournalOperationKind, MEDIA_EXTENSIONS,
 };
 
 /// Check if a file is a media file based on extension
@@ -252,6 +253,19 @@ pub fn rename_file(path: String, new_name: String) -> Result<FileOperationResult
         validated_path, new_path
     );
 
+    let old_name = validated_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    journal::record(
+        JournalOperationKind::Rename,
+        format!("Rename '{}' to '{}'", old_name, new_name),
+        vec![JournalChange::Moved {
+            from: validated_path.to_string_lossy().to_string(),
+            to: new_path.to_string_lossy().to_string(),
+        }],
+    );
+
     Ok(FileOperationResult {
         success: true,
         message: format!("Renamed to '{}'", new_name),
@@ -356,6 +370,19 @@ pub fn move_file(path: String, destination: String) -> Result<FileOperationResul
 
     info!("Successfully moved {:?} to {:?}", validated_src, new_path);
 
+    journal::record(
+        JournalOperationKind::Move,
+        format!(
+            "Move '{}' to '{}'",
+            file_name.to_string_lossy(),
+            destination
+        ),
+        vec![JournalChange::Moved {
+            from: validated_src.to_string_lossy().to_string(),
+            to: new_path.to_string_lossy().to_string(),
+        }],
+    );
+
     Ok(FileOperationResult {
         success: true,
         message: format!("Moved to '{}'", destination),
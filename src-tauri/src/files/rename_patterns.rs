@@ -5,6 +5,7 @@
 use crate::media::find_command;
 use crate::types::{CaseMode, RenamePattern, RenamePreview};
 use log::{debug, warn};
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::process::Command;
@@ -164,55 +165,223 @@ fn get_media_metadata(path: &str) -> Option<(String, Option<String>, Option<Stri
     Some((file_type, video_codec, audio_codec))
 }
 
-/// Apply template pattern with variables
+/// Split `s` on `sep`, ignoring separators inside single or double quotes
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == sep => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            None => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parse comma-separated filter arguments, stripping surrounding quotes
+fn parse_filter_args(args: &str) -> Vec<String> {
+    split_unquoted(args, ',')
+        .into_iter()
+        .map(|arg| {
+            let arg = arg.trim();
+            let quoted = arg.len() >= 2
+                && ((arg.starts_with('\'') && arg.ends_with('\''))
+                    || (arg.starts_with('"') && arg.ends_with('"')));
+            if quoted {
+                arg[1..arg.len() - 1].to_string()
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Apply one `|filter` to a template value
+/// Filters: replace:'from','to', lower, upper, title, trim
+fn apply_filter(value: String, filter: &str) -> Result<String, String> {
+    let (name, args) = match filter.split_once(':') {
+        Some((name, args)) => (name.trim(), Some(args)),
+        None => (filter.trim(), None),
+    };
+    match (name, args) {
+        ("replace", Some(args)) => match parse_filter_args(args).as_slice() {
+            [from, to] if !from.is_empty() => Ok(value.replace(from.as_str(), to)),
+            _ => Err(format!(
+                "Filter 'replace' expects two arguments, e.g. replace:'.',' ' (got '{}')",
+                args
+            )),
+        },
+        ("lower", None) => Ok(apply_case_transform(&value, &CaseMode::Lowercase)),
+        ("upper", None) => Ok(apply_case_transform(&value, &CaseMode::Uppercase)),
+        ("title", None) => Ok(apply_case_transform(&value, &CaseMode::TitleCase)),
+        ("trim", None) => Ok(value.trim().to_string()),
+        _ => Err(format!("Unknown template filter '{}'", filter)),
+    }
+}
+
+/// Values for template variables of one file; media variables are probed on first use
+struct TemplateVars<'a> {
+    path: &'a str,
+    name: &'a str,
+    ext: &'a str,
+    parent: &'a str,
+    date: String,
+    index: usize,
+    counter: usize,
+    media: Option<(String, Option<String>, Option<String>)>,
+}
+
+impl<'a> TemplateVars<'a> {
+    fn new(path: &'a str, index: usize, counter: usize) -> Self {
+        let path_obj = Path::new(path);
+        Self {
+            path,
+            name: path_obj.file_stem().and_then(|n| n.to_str()).unwrap_or(""),
+            ext: path_obj.extension().and_then(|e| e.to_str()).unwrap_or(""),
+            parent: path_obj
+                .parent()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or(""),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            index,
+            counter,
+            media: None,
+        }
+    }
+
+    /// Value of a variable, or None if it isn't one
+    fn get(&mut self, var: &str) -> Option<String> {
+        let value = match var {
+            "name" => self.name.to_string(),
+            "ext" => self.ext.to_string(),
+            "date" => self.date.clone(),
+            "index" => self.index.to_string(),
+            "counter" => self.counter.to_string(),
+            "parent" => self.parent.to_string(),
+            "type" | "video_codec" | "audio_codec" => {
+                let path = self.path;
+                // Not a media file or ffprobe failed
+                let (file_type, video_codec, audio_codec) = self.media.get_or_insert_with(|| {
+                    get_media_metadata(path).unwrap_or_else(|| {
+                        (
+                            "file".to_string(),
+                            Some("none".to_string()),
+                            Some("none".to_string()),
+                        )
+                    })
+                });
+                match var {
+                    "type" => file_type.clone(),
+                    "video_codec" => video_codec.clone().unwrap_or_else(|| "unknown".to_string()),
+                    _ => audio_codec.clone().unwrap_or_else(|| "unknown".to_string()),
+                }
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Expand the contents of one `{...}` token, or None to keep it literally
+    fn expand_token(&mut self, token: &str) -> Result<Option<String>, String> {
+        // {regex:var:pattern} -> first capture group (or whole match), empty if no match
+        if let Some(rest) = token.strip_prefix("regex:") {
+            let (var, pattern) = rest
+                .split_once(':')
+                .ok_or_else(|| format!("Expected {{regex:variable:pattern}}, got {{{}}}", token))?;
+            let value = self
+                .get(var.trim())
+                .ok_or_else(|| format!("Unknown template variable '{}'", var.trim()))?;
+            let re =
+                Regex::new(pattern).map_err(|e| format!("Invalid regex '{}': {}", pattern, e))?;
+            let matched = re.captures(&value).and_then(|caps| {
+                caps.iter()
+                    .skip(1)
+                    .flatten()
+                    .next()
+                    .or_else(|| caps.get(0))
+                    .map(|m| m.as_str().to_string())
+            });
+            return Ok(Some(matched.unwrap_or_default()));
+        }
+
+        let mut parts = split_unquoted(token, '|').into_iter();
+        let var = parts.next().unwrap_or("").trim();
+        let Some(mut value) = self.get(var) else {
+            return Ok(None);
+        };
+        for filter in parts {
+            value = apply_filter(value, filter)?;
+        }
+        Ok(Some(value))
+    }
+}
+
+/// Expand template variables without touching the extension
+///
+/// Tokens: `{var}`, `{var|filter|...}` and `{regex:var:pattern}`.
 /// Vars: {name}, {ext}, {date}, {index}, {counter}, {parent}, {type}, {video_codec}, {audio_codec}
-pub fn apply_template(
+/// Unknown `{...}` tokens are kept as written.
+pub fn expand_template(
     path: &str,
     template: &str,
     index: usize,
     counter: usize,
 ) -> Result<String, String> {
-    let path_obj = Path::new(path);
+    let mut vars = TemplateVars::new(path, index, counter);
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        // Find the matching brace so regex quantifiers like \d{4} stay inside the token
+        let mut depth = 0;
+        let close = rest[open..].char_indices().find_map(|(i, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(open + i)
+        });
+        let Some(close) = close else {
+            result.push_str(&rest[open..]);
+            return Ok(result);
+        };
 
-    let original_name = path_obj.file_stem().and_then(|n| n.to_str()).unwrap_or("");
+        let token = &rest[open + 1..close];
+        match vars.expand_token(token)? {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    result.push_str(rest);
 
-    let ext = path_obj.extension().and_then(|e| e.to_str()).unwrap_or("");
+    Ok(result)
+}
 
-    let parent = path_obj
-        .parent()
-        .and_then(|p| p.file_name())
-        .and_then(|n| n.to_str())
+/// Apply template pattern with variables (see `expand_template`)
+pub fn apply_template(
+    path: &str,
+    template: &str,
+    index: usize,
+    counter: usize,
+) -> Result<String, String> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
         .unwrap_or("");
 
-    // Get current date
-    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
-
-    let mut result = template.to_string();
-
-    // Replace basic variables
-    result = result.replace("{name}", original_name);
-    result = result.replace("{ext}", ext);
-    result = result.replace("{date}", &date);
-    result = result.replace("{index}", &index.to_string());
-    result = result.replace("{counter}", &counter.to_string());
-    result = result.replace("{parent}", parent);
-
-    // Replace media variables if needed
-    if result.contains("{type}")
-        || result.contains("{video_codec}")
-        || result.contains("{audio_codec}")
-    {
-        if let Some((file_type, video_codec, audio_codec)) = get_media_metadata(path) {
-            result = result.replace("{type}", &file_type);
-            result = result.replace("{video_codec}", video_codec.as_deref().unwrap_or("unknown"));
-            result = result.replace("{audio_codec}", audio_codec.as_deref().unwrap_or("unknown"));
-        } else {
-            // Not a media file or ffprobe failed
-            result = result.replace("{type}", "file");
-            result = result.replace("{video_codec}", "none");
-            result = result.replace("{audio_codec}", "none");
-        }
-    }
+    let mut result = expand_template(path, template, index, counter)?;
 
     // Ensure we have an extension if original had one
     if !ext.is_empty() && !result.contains('.') {
@@ -719,6 +888,47 @@ mod tests {
         assert_eq!(result, "3_10_file.mp4");
     }
 
+    #[test]
+    fn test_template_filters() {
+        let result = expand_template(
+            "/path/The.Movie.2019.mkv",
+            "{name|replace:'.',' '|lower}",
+            0,
+            1,
+        )
+        .unwrap();
+        assert_eq!(result, "the movie 2019");
+        let result =
+            expand_template("/path/a_b.mkv", "{name|replace:\"_\",\"|\"|upper}", 0, 1).unwrap();
+        assert_eq!(result, "A|B");
+    }
+
+    #[test]
+    fn test_template_regex() {
+        let path = "/path/The.Movie.2019.1080p.mkv";
+        assert_eq!(
+            expand_template(path, r"{regex:name:(\d{4})}", 0, 1).unwrap(),
+            "2019"
+        );
+        assert_eq!(
+            expand_template(path, r"{regex:name:\d+p}", 0, 1).unwrap(),
+            "1080p"
+        );
+        assert_eq!(
+            expand_template(path, r"{regex:name:(x264)}", 0, 1).unwrap(),
+            ""
+        );
+        assert!(expand_template(path, "{regex:name:(}", 0, 1).is_err());
+        assert!(expand_template(path, "{regex:nope:(.*)}", 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_template_unknown_tokens_kept() {
+        let result = expand_template("/path/file.mp4", "{unknown}_{name}_{", 0, 1).unwrap();
+        assert_eq!(result, "{unknown}_file_{");
+        assert!(expand_template("/path/file.mp4", "{name|reverse}", 0, 1).is_err());
+    }
+
     // ========== preview_renames tests ==========
 
     #[test]
@@ -0,0 +1,581 @@
+//! Crash-safe replacement of files rewritten in place
+//!
+//! Every writer that rewrites a file (stream removal, metadata edits, loudness
+//! normalization) produces a complete replacement next to the original and
+//! swaps it in with `atomic_replace`:
+//!
+//! 1. Ownership and extended attributes of the original (plus timestamps and
+//!    mode bits, unless `preserve_file_attributes` is off) are copied onto the
+//!    replacement, which is then fsynced.
+//! 2. A marker describing the swap is written to the recovery directory.
+//! 3. The original is renamed to its backup path, the replacement renamed into
+//!    place, and the directory fsynced.
+//! 4. The backup is deleted (unless it is kept for undo) and the marker removed.
+//!
+//! If the app dies between 2 and 4, `recover_interrupted_replaces` finds the
+//! marker on the next start: when the original is missing it is restored from
+//! the backup, otherwise the swap completed and only cleanup remains.
+
+use log::{debug, info, warn};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, FileTimes};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+/// Directory holding markers for replacements in progress, set once at startup
+static RECOVERY_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Distinguishes markers created in the same millisecond
+static MARKER_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const MARKER_EXTENSION: &str = "seer-replace";
+
+/// Record of a replacement in progress
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct ReplaceMarker {
+    original: PathBuf,
+    replacement: PathBuf,
+    backup: PathBuf,
+    /// Whether the backup outlives the swap (kept for undo)
+    keep_backup: bool,
+}
+
+/// Record where replacement markers are kept
+///
+/// Without it (e.g. in tests), markers are written next to the original and
+/// are only found by `recover_pending_in` on that directory.
+pub fn init_recovery_dir(dir: &Path) {
+    let _ = RECOVERY_DIR.set(dir.to_path_buf());
+}
+
+fn timestamp_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+/// Copy ownership and extended attributes of `from` onto `to`, plus access and
+/// modification times and permissions when `times_and_mode` is set
+///
+/// Best effort: a failure is logged and the remaining attributes are still
+/// copied (another user's file can't be chowned, not every filesystem supports
+/// xattrs or setting times), since losing an attribute shouldn't fail the
+/// rewrite it accompanies.
+pub fn copy_file_attributes(from: &Path, to: &Path, times_and_mode: bool) {
+    let metadata = match fs::metadata(from) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            warn!("Could not read attributes of {}: {}", from.display(), e);
+            return;
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Err(e) = std::os::unix::fs::chown(to, Some(metadata.uid()), Some(metadata.gid())) {
+            debug!("Could not copy ownership to {}: {}", to.display(), e);
+        }
+        copy_xattrs(from, to);
+    }
+
+    if !times_and_mode {
+        return;
+    }
+
+    restore_file_times(to, times_of(&metadata));
+
+    // Last: after chown (which may clear setuid/setgid) and while still writable
+    if let Err(e) = fs::set_permissions(to, metadata.permissions()) {
+        warn!("Could not copy permissions to {}: {}", to.display(), e);
+    }
+}
+
+fn times_of(metadata: &fs::Metadata) -> FileTimes {
+    let mut times = FileTimes::new();
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Ok(modified) = metadata.modified() {
+        times = times.set_modified(modified);
+    }
+    times
+}
+
+/// Access and modification times of `path`, for `restore_file_times` to put
+/// back after the file is edited in place rather than replaced
+pub fn file_times(path: &Path) -> Option<FileTimes> {
+    match fs::metadata(path) {
+        Ok(metadata) => Some(times_of(&metadata)),
+        Err(e) => {
+            warn!("Could not read timestamps of {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Set the access and modification times of `path`, logging a failure
+pub fn restore_file_times(path: &Path, times: FileTimes) {
+    if let Err(e) = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_times(times))
+    {
+        warn!("Could not set timestamps of {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(unix)]
+fn copy_xattrs(from: &Path, to: &Path) {
+    let names = match xattr::list(from) {
+        Ok(names) => names,
+        Err(e) => {
+            debug!(
+                "Could not list extended attributes of {}: {}",
+                from.display(),
+                e
+            );
+            return;
+        }
+    };
+    for name in names {
+        match xattr::get(from, &name) {
+            Ok(Some(value)) => {
+                if let Err(e) = xattr::set(to, &name, &value) {
+                    debug!(
+                        "Could not copy extended attribute {:?} to {}: {}",
+                        name,
+                        to.display(),
+                        e
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(e) => debug!("Could not read extended attribute {:?}: {}", name, e),
+        }
+    }
+}
+
+/// Flush a file's contents to disk
+fn sync_file(path: &Path) -> Result<(), String> {
+    File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(|e| format!("Failed to flush {}: {}", path.display(), e))
+}
+
+/// Flush a directory entry update (renames) to disk; not supported on Windows
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = File::open(dir).and_then(|d| d.sync_all()) {
+        debug!("Failed to flush directory {}: {}", dir.display(), e);
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Hidden sibling holding the original while it is being replaced
+fn aside_path(original: &Path) -> PathBuf {
+    let name = original
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+    original.with_file_name(format!(".{}.seer_old_{}", name, timestamp_millis()))
+}
+
+fn write_marker(marker_dir: &Path, marker: &ReplaceMarker) -> Result<PathBuf, String> {
+    fs::create_dir_all(marker_dir)
+        .map_err(|e| format!("Failed to create recovery folder: {}", e))?;
+    // Hidden, since without a recovery dir it sits next to the media file
+    let path = marker_dir.join(format!(
+        ".{}_{}_{}.{}",
+        timestamp_millis(),
+        std::process::id(),
+        MARKER_COUNTER.fetch_add(1, Ordering::SeqCst),
+        MARKER_EXTENSION
+    ));
+    let json = serde_json::to_vec(marker)
+        .map_err(|e| format!("Failed to serialize replace marker: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write replace marker: {}", e))?;
+    sync_file(&path)?;
+    sync_dir(marker_dir);
+    Ok(path)
+}
+
+/// Replace `original` with `replacement` so a crash never loses both
+///
+/// `replacement` must be a complete file on the same filesystem. The original
+/// contents are moved to `backup` when given (and kept there), otherwise to a
+/// hidden sibling that is deleted after the swap.
+pub fn atomic_replace(
+    original: &Path,
+    replacement: &Path,
+    backup: Option<&Path>,
+) -> Result<(), String> {
+    let preserve = config::preserve_file_attributes();
+    replace_with_marker_in(
+        &marker_dir(original),
+        original,
+        replacement,
+        backup,
+        Some(preserve),
+    )
+}
+
+/// Swap a backup made by `atomic_replace` back in place of `original`
+///
+/// The backup is the original file moved aside, so it keeps its own
+/// timestamps, mode and ownership instead of taking the current file's.
+pub fn atomic_restore(original: &Path, backup: &Path) -> Result<(), String> {
+    replace_with_marker_in(&marker_dir(original), original, backup, None, None)
+}
+
+/// Recovery directory, or the original's folder when none was set
+fn marker_dir(original: &Path) -> PathBuf {
+    match RECOVERY_DIR.get() {
+        Some(dir) => dir.clone(),
+        None => original
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf(),
+    }
+}
+
+/// `copy_attributes` is the `times_and_mode` flag of `copy_file_attributes`,
+/// or None to keep the replacement's own attributes
+fn replace_with_marker_in(
+    marker_dir: &Path,
+    original: &Path,
+    replacement: &Path,
+    backup: Option<&Path>,
+    copy_attributes: Option<bool>,
+) -> Result<(), String> {
+    if !replacement.is_file() {
+        return Err(format!(
+            "Replacement file is missing: {}",
+            replacement.display()
+        ));
+    }
+
+    if let Some(times_and_mode) = copy_attributes {
+        copy_file_attributes(original, replacement, times_and_mode);
+    }
+    sync_file(replacement)?;
+
+    let marker = ReplaceMarker {
+        original: original.to_path_buf(),
+        replacement: replacement.to_path_buf(),
+        backup: backup
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| aside_path(original)),
+        keep_backup: backup.is_some(),
+    };
+    let marker_path = write_marker(marker_dir, &marker)?;
+
+    if let Err(e) = fs::rename(original, &marker.backup) {
+        let _ = fs::remove_file(&marker_path);
+        return Err(format!("Failed to create backup: {}", e));
+    }
+
+    if let Err(e) = fs::rename(replacement, original) {
+        // Put the original back; the marker stays if even that fails
+        if fs::rename(&marker.backup, original).is_ok() {
+            let _ = fs::remove_file(&marker_path);
+        }
+        return Err(format!("Failed to replace original file: {}", e));
+    }
+
+    if let Some(dir) = original.parent() {
+        sync_dir(dir);
+    }
+    if !marker.keep_backup {
+        let _ = fs::remove_file(&marker.backup);
+    }
+    let _ = fs::remove_file(&marker_path);
+    Ok(())
+}
+
+/// Finish or roll back the replacement described by one marker
+fn recover_marker(marker: &ReplaceMarker) -> Result<&'static str, String> {
+    if !marker.original.exists() {
+        // Crashed between the two renames: restore the original
+        if !marker.backup.exists() {
+            return Err(format!(
+                "Neither {} nor its backup exists",
+                marker.original.display()
+            ));
+        }
+        fs::rename(&marker.backup, &marker.original)
+            .map_err(|e| format!("Failed to restore {}: {}", marker.original.display(), e))?;
+        let _ = fs::remove_file(&marker.replacement);
+        return Ok("restored original");
+    }
+
+    if marker.replacement.exists() {
+        // Crashed before the original was moved: drop the unused replacement
+        let _ = fs::remove_file(&marker.replacement);
+        return Ok("discarded replacement");
+    }
+
+    // The swap completed; only cleanup was left
+    if !marker.keep_backup {
+        let _ = fs::remove_file(&marker.backup);
+    }
+    Ok("completed")
+}
+
+/// Repair replacements interrupted by a crash, using markers in `marker_dir`
+///
+/// Returns how many markers were handled. Markers that can't be repaired are
+/// left in place (and logged) so nothing is deleted blindly.
+pub fn recover_pending_in(marker_dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(marker_dir) else {
+        return 0;
+    };
+
+    let mut recovered = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(MARKER_EXTENSION) {
+            continue;
+        }
+        let marker: ReplaceMarker = match fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))
+        {
+            Ok(marker) => marker,
+            Err(e) => {
+                warn!(
+                    "Ignoring unreadable replace marker {}: {}",
+                    path.display(),
+                    e
+                );
+                continue;
+            }
+        };
+
+        match recover_marker(&marker) {
+            Ok(outcome) => {
+                info!(
+                    "Recovered interrupted replace of {}: {}",
+                    marker.original.display(),
+                    outcome
+                );
+                let _ = fs::remove_file(&path);
+                recovered += 1;
+            }
+            Err(e) => warn!(
+                "Could not recover replace of {}: {}",
+                marker.original.display(),
+                e
+            ),
+        }
+    }
+    recovered
+}
+
+/// Repair replacements interrupted by a crash (run at startup)
+pub fn recover_interrupted_replaces() -> usize {
+    match RECOVERY_DIR.get() {
+        Some(dir) => recover_pending_in(dir),
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("seer_replace_{}_{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn marker_count(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.path().extension().and_then(|x| x.to_str()) == Some(MARKER_EXTENSION))
+            .count()
+    }
+
+    #[test]
+    fn test_atomic_replace_preserves_attributes() {
+        let dir = temp_dir("attrs");
+        let markers = dir.join("markers");
+        let original = dir.join("movie.mkv");
+        let replacement = dir.join("movie_temp.mkv");
+        fs::write(&original, "old").unwrap();
+        fs::write(&replacement, "new").unwrap();
+
+        let old_mtime = SystemTime::now() - Duration::from_secs(86_400 * 30);
+        File::options()
+            .write(true)
+            .open(&original)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(old_mtime))
+            .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&original, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        replace_with_marker_in(&markers, &original, &replacement, None, Some(true)).unwrap();
+
+        assert_eq!(fs::read_to_string(&original).unwrap(), "new");
+        assert!(!replacement.exists());
+        let metadata = fs::metadata(&original).unwrap();
+        assert_eq!(metadata.modified().unwrap(), old_mtime);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+        // Only the replaced file is left, no backup or marker
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(marker_count(&markers), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_atomic_replace_without_preserving_times() {
+        let dir = temp_dir("no_preserve");
+        let (original, replacement) = (dir.join("a.mkv"), dir.join("a_temp.mkv"));
+        fs::write(&original, "old").unwrap();
+        fs::write(&replacement, "new").unwrap();
+        let old_mtime = SystemTime::now() - Duration::from_secs(86_400 * 30);
+        File::options()
+            .write(true)
+            .open(&original)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(old_mtime))
+            .unwrap();
+
+        replace_with_marker_in(&dir, &original, &replacement, None, Some(false)).unwrap();
+        let modified = fs::metadata(&original).unwrap().modified().unwrap();
+        assert!(modified > old_mtime);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_atomic_replace_keeps_requested_backup() {
+        let dir = temp_dir("backup");
+        let (original, replacement, backup) =
+            (dir.join("a.mkv"), dir.join("a_temp.mkv"), dir.join("a.bak"));
+        fs::write(&original, "old").unwrap();
+        fs::write(&replacement, "new").unwrap();
+
+        replace_with_marker_in(&dir, &original, &replacement, Some(&backup), Some(true)).unwrap();
+        assert_eq!(fs::read_to_string(&original).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old");
+        assert_eq!(marker_count(&dir), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_restore_keeps_backup_attributes() {
+        let dir = temp_dir("restore");
+        let (original, replacement, backup) =
+            (dir.join("a.mkv"), dir.join("a_temp.mkv"), dir.join("a.bak"));
+        fs::write(&original, "old").unwrap();
+        fs::write(&replacement, "new").unwrap();
+        let old_mtime = SystemTime::now() - Duration::from_secs(86_400 * 30);
+        File::options()
+            .write(true)
+            .open(&original)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(old_mtime))
+            .unwrap();
+
+        replace_with_marker_in(&dir, &original, &replacement, Some(&backup), Some(false)).unwrap();
+        assert!(fs::metadata(&original).unwrap().modified().unwrap() > old_mtime);
+
+        replace_with_marker_in(&dir, &original, &backup, None, None).unwrap();
+        assert_eq!(fs::read_to_string(&original).unwrap(), "old");
+        assert_eq!(
+            fs::metadata(&original).unwrap().modified().unwrap(),
+            old_mtime
+        );
+        assert!(!backup.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_restores_original_after_crash_between_renames() {
+        let dir = temp_dir("crash");
+        let markers = dir.join("markers");
+        let marker = ReplaceMarker {
+            original: dir.join("a.mkv"),
+            replacement: dir.join("a_temp.mkv"),
+            backup: dir.join(".a.mkv.seer_old_1"),
+            keep_backup: false,
+        };
+        // Original already moved aside, replacement not yet in place
+        fs::write(&marker.backup, "old").unwrap();
+        fs::write(&marker.replacement, "new").unwrap();
+        write_marker(&markers, &marker).unwrap();
+
+        assert_eq!(recover_pending_in(&markers), 1);
+        assert_eq!(fs::read_to_string(&marker.original).unwrap(), "old");
+        assert!(!marker.replacement.exists());
+        assert!(!marker.backup.exists());
+        assert_eq!(marker_count(&markers), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recover_finishes_cleanup_after_swap() {
+        let dir = temp_dir("cleanup");
+        let marker = ReplaceMarker {
+            original: dir.join("a.mkv"),
+            replacement: dir.join("a_temp.mkv"),
+            backup: dir.join(".a.mkv.seer_old_1"),
+            keep_backup: false,
+        };
+        fs::write(&marker.original, "new").unwrap();
+        fs::write(&marker.backup, "old").unwrap();
+        write_marker(&dir, &marker).unwrap();
+
+        assert_eq!(recover_pending_in(&dir), 1);
+        assert_eq!(fs::read_to_string(&marker.original).unwrap(), "new");
+        assert!(!marker.backup.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_restore_file_times_after_in_place_write() {
+        let dir = temp_dir("times");
+        let path = dir.join("movie.mkv");
+        fs::write(&path, "old").unwrap();
+        let old_mtime = SystemTime::now() - Duration::from_secs(86_400 * 30);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(old_mtime))
+            .unwrap();
+
+        let times = file_times(&path).unwrap();
+        fs::write(&path, "edited").unwrap();
+        assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), old_mtime);
+        restore_file_times(&path, times);
+
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), old_mtime);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -6,8 +6,8 @@
 use dashmap::DashMap;
 use log::{debug, info, warn};
 use once_cell::sync::Lazy;
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use crate::types::{JobInfo, QueueStatus};
@@ -44,14 +44,37 @@ pub enum JobType {
         tool: String,
         method: String,
     },
+    MediaSplit,
+    MediaConcat,
+    ContactSheet,
+    LoudnessAnalysis,
+    LoudnessNormalization,
+    TimelineDetection,
+    GopAnalysis,
+    BatchBitrateReport,
+    BulkMetadataUpdate,
+    MetadataScrub,
 }
 
 impl JobType {
+    /// Name of `StreamRemoval` jobs, for callers that suggest one without its payload
+    pub const STREAM_REMOVAL_NAME: &'static str = "stream_removal";
+
     pub fn name(&self) -> &str {
         match self {
             JobType::BitrateAnalysis => "bitrate_analysis",
-            JobType::StreamRemoval { .. } => "stream_removal",
+            JobType::StreamRemoval { .. } => Self::STREAM_REMOVAL_NAME,
             JobType::DependencyInstallation { .. } => "dependency_installation",
+            JobType::MediaSplit => "media_split",
+            JobType::MediaConcat => "media_concat",
+            JobType::ContactSheet => "contact_sheet",
+            JobType::LoudnessAnalysis => "loudness_analysis",
+            JobType::LoudnessNormalization => "loudness_normalization",
+            JobType::TimelineDetection => "timeline_detection",
+            JobType::GopAnalysis => "gop_analysis",
+            JobType::BatchBitrateReport => "batch_bitrate_report",
+            JobType::BulkMetadataUpdate => "bulk_metadata_update",
+            JobType::MetadataScrub => "metadata_scrub",
         }
     }
 }
@@ -140,6 +163,81 @@ impl JobQueue {
 /// Default to 4 parallel jobs for better multi-core utilization
 static JOB_QUEUE: Lazy<JobQueue> = Lazy::new(|| JobQueue::new(4));
 
+/// Files claimed by multi-file jobs (file path -> job key)
+///
+/// Locked before the job queue whenever both are needed.
+static CLAIMED_FILES: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Distinguishes keys of multi-file jobs
+static BATCH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Unique key for a job over many files, which has no single path to key it by
+pub fn batch_job_key(job_type: &JobType) -> String {
+    format!(
+        "{}:{}",
+        job_type.name(),
+        BATCH_COUNTER.fetch_add(1, Ordering::SeqCst) + 1
+    )
+}
+
+/// Files claimed by a multi-file job, released when dropped
+///
+/// While claimed, other jobs can't be enqueued for the files and
+/// `ensure_not_claimed` rejects direct edits.
+#[derive(Debug)]
+pub struct FileClaim {
+    paths: Vec<String>,
+}
+
+impl Drop for FileClaim {
+    fn drop(&mut self) {
+        let mut claims = CLAIMED_FILES.lock().unwrap();
+        for path in &self.paths {
+            claims.remove(path);
+        }
+    }
+}
+
+/// Claim `paths` for the job `job_key`
+///
+/// Fails without claiming anything if a file is already claimed or has a
+/// queued or running job of its own.
+pub fn claim_files(job_key: &str, paths: &[String]) -> Result<FileClaim, String> {
+    let mut claims = CLAIMED_FILES.lock().unwrap();
+    for path in paths {
+        if let Some(owner) = claims.get(path) {
+            return Err(format!(
+                "{} is already being processed (job {})",
+                path, owner
+            ));
+        }
+        if JOB_QUEUE.is_queued_or_running(path) {
+            return Err(format!("{} already has a job queued or in progress", path));
+        }
+    }
+
+    let mut claimed = Vec::with_capacity(paths.len());
+    for path in paths {
+        if claims.insert(path.clone(), job_key.to_string()).is_none() {
+            claimed.push(path.clone());
+        }
+    }
+    debug!("Job {} claimed {} files", job_key, claimed.len());
+    Ok(FileClaim { paths: claimed })
+}
+
+/// Error if `path` is claimed by a multi-file job
+pub fn ensure_not_claimed(path: &str) -> Result<(), String> {
+    match CLAIMED_FILES.lock().unwrap().get(path) {
+        Some(owner) => Err(format!(
+            "This file is being processed by another job (job {})",
+            owner
+        )),
+        None => Ok(()),
+    }
+}
+
 /// Result of trying to enqueue a new job
 #[derive(Debug)]
 pub enum JobStartResult {
@@ -161,7 +259,14 @@ pub fn enqueue_job(path: &str, file_hash: &str, job_type: JobType) -> JobStartRe
         job_type.name()
     );
 
-    // Check if already queued or running (do this BEFORE taking any locks)
+    // Held until the job is enqueued so a batch can't claim the file meanwhile
+    let claims = CLAIMED_FILES.lock().unwrap();
+    if let Some(owner) = claims.get(path) {
+        warn!("File {} is claimed by job {}", path, owner);
+        return JobStartResult::AlreadyExists(owner.clone());
+    }
+
+    // Check if already queued or running (before the running-map entry lock)
     if JOB_QUEUE.is_queued_or_running(path) {
         debug!("Job already queued or running for path: {}", path);
         // Find the existing job ID
@@ -0,0 +1,782 @@
+//! Undo journal for destructive file and media operations
+//!
+//! Renames, moves, folder creation and in-place rewrites (stream removal,
+//! metadata edits) are recorded in the `operation_journal` and
+//! `operation_journal_changes` tables. Rewritten files keep their previous
+//! contents in a hidden `.seer-undo` folder next to the original, so undoing
+//! is a rename on the same filesystem rather than a copy. Tags edited in
+//! place (Matroska via mkvpropedit) keep no backup; the journal stores the
+//! operations that restore the previous values instead.
+//!
+//! Backups are removed according to `config::UndoRetention` (age and total
+//! size); operations whose backups were removed are marked expired. Journaling
+//! is best effort: an operation never fails because it couldn't be recorded.
+
+use log::{debug, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, UndoRetention};
+use crate::database;
+use crate::files::{atomic_replace, atomic_restore};
+use crate::media::invalidate_probe_cache;
+use crate::metadata::revert_matroska_tags;
+use crate::types::{
+    JournalChange, JournalOperation, JournalOperationKind, JournalStatus, UndoResult,
+};
+
+/// Hidden folder (next to the original) holding backups of rewritten files
+pub const BACKUP_DIR_NAME: &str = ".seer-undo";
+
+/// Journal entries kept regardless of age; older ones are dropped
+const MAX_JOURNAL_ENTRIES: i64 = 500;
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+fn enum_name<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn parse_enum<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|e| format!("Invalid journal value '{}': {}", name, e))
+}
+
+fn timestamp_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+// ============================================================================
+// Backups
+// ============================================================================
+
+/// Backup location for `original` inside its folder's `.seer-undo`
+fn backup_path_for(original: &Path) -> Result<PathBuf, String> {
+    let parent = original.parent().ok_or("Cannot get parent directory")?;
+    let name = original.file_name().ok_or("Cannot get file name")?;
+    let dir = parent.join(BACKUP_DIR_NAME);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup folder: {}", e))?;
+    Ok(dir.join(format!("{}_{}", timestamp_millis(), name.to_string_lossy())))
+}
+
+/// Replace `original` with `new_file`, keeping the previous contents for undo
+///
+/// Uses `files::atomic_replace`. Returns the backup path when the retention
+/// policy keeps backups; otherwise the previous contents are deleted once replaced.
+pub fn replace_with_backup(original: &Path, new_file: &Path) -> Result<Option<PathBuf>, String> {
+    let kept_backup = if config::undo_retention().keeps_backups() {
+        backup_path_for(original)
+            .map_err(|e| warn!("Undo backup unavailable for {}: {}", original.display(), e))
+            .ok()
+    } else {
+        None
+    };
+    atomic_replace(original, new_file, kept_backup.as_deref())?;
+    Ok(kept_backup)
+}
+
+/// Delete the backups held by `changes`, e.g. when they can't be journaled
+fn remove_backups(changes: &[JournalChange]) {
+    for change in changes {
+        if let JournalChange::Rewritten { backup, .. } = change {
+            let backup = Path::new(backup);
+            if let Err(e) = fs::remove_file(backup) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove backup {}: {}", backup.display(), e);
+                }
+            }
+            // Drop the backup folder once it's empty
+            if let Some(dir) = backup.parent() {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+/// Column values (`change_type`, `old_path`, `new_path`, `backup_path`, `details`) for a change
+type ChangeColumns<'a> = (
+    &'a str,
+    Option<&'a str>,
+    &'a str,
+    Option<&'a str>,
+    Option<String>,
+);
+
+fn change_columns(change: &JournalChange) -> Result<ChangeColumns<'_>, String> {
+    Ok(match change {
+        JournalChange::Moved { from, to } => ("moved", Some(from), to, None, None),
+        JournalChange::CreatedDir { path } => ("created_dir", None, path, None, None),
+        JournalChange::Rewritten { path, backup } => ("rewritten", None, path, Some(backup), None),
+        JournalChange::TagsEdited { path, revert } => {
+            let details = serde_json::to_string(revert)
+                .map_err(|e| format!("Failed to serialize journal change: {}", e))?;
+            ("tags_edited", None, path, None, Some(details))
+        }
+    })
+}
+
+fn change_from_columns(
+    change_type: &str,
+    old_path: Option<String>,
+    new_path: String,
+    backup_path: Option<String>,
+    details: Option<String>,
+) -> Result<JournalChange, String> {
+    match (change_type, old_path, backup_path) {
+        ("moved", Some(from), _) => Ok(JournalChange::Moved { from, to: new_path }),
+        ("created_dir", _, _) => Ok(JournalChange::CreatedDir { path: new_path }),
+        ("rewritten", _, Some(backup)) => Ok(JournalChange::Rewritten {
+            path: new_path,
+            backup,
+        }),
+        ("tags_edited", _, _) => {
+            let revert = serde_json::from_str(details.as_deref().unwrap_or_default())
+                .map_err(|e| format!("Invalid tag edit in journal: {}", e))?;
+            Ok(JournalChange::TagsEdited {
+                path: new_path,
+                revert,
+            })
+        }
+        (other, _, _) => Err(format!("Invalid journal change '{}'", other)),
+    }
+}
+
+/// Insert an operation and its changes, returning the operation id
+pub fn store_operation(
+    conn: &mut Connection,
+    kind: JournalOperationKind,
+    description: &str,
+    changes: &[JournalChange],
+) -> Result<i64, String> {
+    let backup_bytes: u64 = changes
+        .iter()
+        .filter_map(|change| match change {
+            JournalChange::Rewritten { backup, .. } => fs::metadata(backup).ok().map(|m| m.len()),
+            _ => None,
+        })
+        .sum();
+
+    let tx = conn.transaction().map_err(sql_err)?;
+    tx.execute(
+        "INSERT INTO operation_journal (kind, description, backup_bytes) VALUES (?1, ?2, ?3)",
+        params![enum_name(&kind), description, backup_bytes as i64],
+    )
+    .map_err(sql_err)?;
+    let operation_id = tx.last_insert_rowid();
+
+    {
+        let mut insert_change = tx
+            .prepare(
+                "INSERT INTO operation_journal_changes
+                 (operation_id, change_type, old_path, new_path, backup_path, details)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .map_err(sql_err)?;
+        for change in changes {
+            let (change_type, old_path, new_path, backup_path, details) = change_columns(change)?;
+            insert_change
+                .execute(params![
+                    operation_id,
+                    change_type,
+                    old_path,
+                    new_path,
+                    backup_path,
+                    details
+                ])
+                .map_err(sql_err)?;
+        }
+    }
+
+    tx.commit().map_err(sql_err)?;
+    Ok(operation_id)
+}
+
+fn load_changes(conn: &Connection, operation_id: i64) -> Result<Vec<JournalChange>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT change_type, old_path, new_path, backup_path, details FROM operation_journal_changes
+             WHERE operation_id = ?1 ORDER BY id",
+        )
+        .map_err(sql_err)?;
+    let rows = stmt
+        .query_map(params![operation_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?;
+
+    rows.into_iter()
+        .map(|(change_type, old_path, new_path, backup_path, details)| {
+            change_from_columns(&change_type, old_path, new_path, backup_path, details)
+        })
+        .collect()
+}
+
+type OperationRow = (i64, String, String, String, String, Option<String>, i64);
+
+fn load_operations(
+    conn: &Connection,
+    sql: &str,
+    param: i64,
+) -> Result<Vec<JournalOperation>, String> {
+    let mut stmt = conn.prepare(sql).map_err(sql_err)?;
+    let rows: Vec<OperationRow> = stmt
+        .query_map(params![param], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?;
+
+    rows.into_iter()
+        .map(
+            |(id, kind, description, status, created_at, undone_at, backup_bytes)| {
+                Ok(JournalOperation {
+                    id,
+                    kind: parse_enum(&kind)?,
+                    description,
+                    status: parse_enum(&status)?,
+                    created_at,
+                    undone_at,
+                    backup_bytes: backup_bytes.max(0) as u64,
+                    changes: load_changes(conn, id)?,
+                })
+            },
+        )
+        .collect()
+}
+
+const OPERATION_COLUMNS: &str =
+    "SELECT id, kind, description, status, created_at, undone_at, backup_bytes FROM operation_journal";
+
+/// Load one journaled operation
+pub fn load_operation(conn: &Connection, id: i64) -> Result<Option<JournalOperation>, String> {
+    let sql = format!("{} WHERE id = ?1", OPERATION_COLUMNS);
+    Ok(load_operations(conn, &sql, id)?.into_iter().next())
+}
+
+/// Most recent operations, newest first
+pub fn list_operations(conn: &Connection, limit: usize) -> Result<Vec<JournalOperation>, String> {
+    let sql = format!("{} ORDER BY id DESC LIMIT ?1", OPERATION_COLUMNS);
+    load_operations(conn, &sql, limit as i64)
+}
+
+/// Record a completed operation; returns its id, or None if it wasn't journaled
+///
+/// Backups referenced by `changes` are deleted when the journal can't be
+/// written, since nothing could restore them.
+pub fn record(
+    kind: JournalOperationKind,
+    description: String,
+    changes: Vec<JournalChange>,
+) -> Option<i64> {
+    if changes.is_empty() {
+        return None;
+    }
+
+    let result = database::open_connection().and_then(|mut conn| {
+        let id = store_operation(&mut conn, kind, &description, &changes)?;
+        if let Err(e) = prune(&conn, &config::undo_retention()) {
+            warn!("Failed to apply undo retention: {}", e);
+        }
+        Ok(id)
+    });
+
+    match result {
+        Ok(id) => {
+            debug!("Journaled operation {}: {}", id, description);
+            Some(id)
+        }
+        Err(e) => {
+            warn!("Failed to journal '{}': {}", description, e);
+            remove_backups(&changes);
+            None
+        }
+    }
+}
+
+// ============================================================================
+// Retention
+// ============================================================================
+
+/// Apply the retention policy, returning how many operations expired
+///
+/// Backups older than `backup_days` or beyond `max_backup_bytes` (counting
+/// from the newest) are deleted. Entries beyond `MAX_JOURNAL_ENTRIES` are
+/// dropped from the journal altogether.
+pub fn prune(conn: &Connection, retention: &UndoRetention) -> Result<usize, String> {
+    let oldest_kept: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM operation_journal ORDER BY id DESC LIMIT 1 OFFSET ?1",
+            params![MAX_JOURNAL_ENTRIES - 1],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(sql_err)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, backup_bytes, julianday('now') - julianday(created_at)
+             FROM operation_journal
+             WHERE status = 'applied' AND backup_bytes > 0
+             ORDER BY id DESC",
+        )
+        .map_err(sql_err)?;
+    let with_backups = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?;
+
+    let mut kept_bytes: u64 = 0;
+    let mut expired = Vec::new();
+    for (id, bytes, age_days) in with_backups {
+        let bytes = bytes.max(0) as u64;
+        if !retention.keeps_backups()
+            || age_days > retention.backup_days as f64
+            || kept_bytes + bytes > retention.max_backup_bytes
+            || oldest_kept.is_some_and(|oldest| id < oldest)
+        {
+            expired.push(id);
+        } else {
+            kept_bytes += bytes;
+        }
+    }
+
+    for id in &expired {
+        remove_backups(&load_changes(conn, *id)?);
+        conn.execute(
+            "UPDATE operation_journal SET status = 'expired', backup_bytes = 0 WHERE id = ?1",
+            params![id],
+        )
+        .map_err(sql_err)?;
+    }
+    if !expired.is_empty() {
+        info!("Expired undo backups for {} operation(s)", expired.len());
+    }
+
+    if let Some(oldest) = oldest_kept {
+        conn.execute(
+            "DELETE FROM operation_journal WHERE id < ?1",
+            params![oldest],
+        )
+        .map_err(sql_err)?;
+    }
+
+    Ok(expired.len())
+}
+
+/// Apply the configured retention policy to the app database
+pub fn apply_retention() -> Result<usize, String> {
+    let conn = database::open_connection()?;
+    prune(&conn, &config::undo_retention())
+}
+
+// ============================================================================
+// Undo
+// ============================================================================
+
+/// Check a change can be reverted; `Ok(true)` if it already has been
+fn check_change(change: &JournalChange) -> Result<bool, String> {
+    match change {
+        JournalChange::Moved { from, to } => {
+            let (from_path, to_path) = (Path::new(from), Path::new(to));
+            match (from_path.exists(), to_path.exists()) {
+                (true, false) => Ok(true),
+                (false, true) => Ok(false),
+                (_, false) => Err(format!("'{}' no longer exists", to)),
+                (true, true) => Err(format!("'{}' already exists", from)),
+            }
+        }
+        JournalChange::CreatedDir { path } => Ok(!Path::new(path).exists()),
+        JournalChange::Rewritten { path, backup } => {
+            if Path::new(backup).exists() {
+                Ok(false)
+            } else {
+                Err(format!("The backup of '{}' is no longer available", path))
+            }
+        }
+        // Reapplying old values is harmless, so it's never treated as done
+        JournalChange::TagsEdited { path, .. } => {
+            if Path::new(path).exists() {
+                Ok(false)
+            } else {
+                Err(format!("'{}' no longer exists", path))
+            }
+        }
+    }
+}
+
+fn revert_change(change: &JournalChange) -> Result<(), String> {
+    match change {
+        JournalChange::Moved { from, to } => {
+            if let Some(parent) = Path::new(from).parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to recreate '{}': {}", parent.display(), e))?;
+            }
+            fs::rename(to, from).map_err(|e| format!("Failed to move '{}' back: {}", to, e))
+        }
+        JournalChange::CreatedDir { path } => {
+            // Files added since are not ours to remove
+            if fs::remove_dir(path).is_err() {
+                debug!("Keeping folder {} (not empty)", path);
+            }
+            Ok(())
+        }
+        JournalChange::Rewritten { path, backup } => {
+            let target = Path::new(path);
+            let restored = if target.exists() {
+                atomic_restore(target, Path::new(backup))
+            } else {
+                fs::rename(backup, target).map_err(|e| e.to_string())
+            };
+            restored.map_err(|e| format!("Failed to restore '{}': {}", path, e))?;
+            if let Some(dir) = Path::new(backup).parent() {
+                let _ = fs::remove_dir(dir);
+            }
+            invalidate_probe_cache(path);
+            Ok(())
+        }
+        JournalChange::TagsEdited { path, revert } => revert_matroska_tags(path, revert)
+            .map_err(|e| format!("Failed to restore tags of '{}': {}", path, e)),
+    }
+}
+
+/// Revert `changes` in reverse order, returning (restored, errors)
+///
+/// Every change is checked first; if any can't be reverted nothing is touched.
+/// Changes found already reverted count as restored, so a partially failed
+/// undo can be retried.
+pub fn undo_changes(changes: &[JournalChange]) -> Result<(usize, Vec<String>), String> {
+    let already_reverted = changes
+        .iter()
+        .map(check_change)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Cannot undo: {}", e))?;
+
+    let mut restored = 0;
+    let mut errors = Vec::new();
+    for (change, done) in changes.iter().zip(already_reverted).rev() {
+        if done {
+            restored += 1;
+            continue;
+        }
+        match revert_change(change) {
+            Ok(()) => restored += 1,
+            Err(e) => errors.push(e),
+        }
+    }
+    Ok((restored, errors))
+}
+
+/// Paths an undo writes to must still be within the allowed directories
+fn validate_change_paths(changes: &[JournalChange]) -> Result<(), String> {
+    for change in changes {
+        let existing = match change {
+            JournalChange::Moved { to, .. } => to,
+            JournalChange::CreatedDir { path } => path,
+            JournalChange::Rewritten { backup, .. } => backup,
+            JournalChange::TagsEdited { path, .. } => path,
+        };
+        let existing = Path::new(existing);
+        if existing.exists() {
+            config::validate_path(existing)?;
+        }
+    }
+    Ok(())
+}
+
+/// Undo a journaled operation on `conn`
+pub fn undo_operation_with(conn: &Connection, id: i64) -> Result<UndoResult, String> {
+    let operation = load_operation(conn, id)?.ok_or(format!("Operation {} not found", id))?;
+    match operation.status {
+        JournalStatus::Applied => {}
+        JournalStatus::Undone => return Err("This operation has already been undone".to_string()),
+        JournalStatus::Expired => {
+            return Err(
+                "This operation can no longer be undone: its backups were removed".to_string(),
+            )
+        }
+    }
+
+    validate_change_paths(&operation.changes)?;
+    let (restored, errors) = undo_changes(&operation.changes)?;
+
+    if errors.is_empty() {
+        conn.execute(
+            "UPDATE operation_journal SET status = 'undone', undone_at = datetime('now'),
+             backup_bytes = 0 WHERE id = ?1",
+            params![id],
+        )
+        .map_err(sql_err)?;
+        info!("Undid operation {}: {}", id, operation.description);
+    } else {
+        warn!("Undo of operation {} left {} error(s)", id, errors.len());
+    }
+
+    Ok(UndoResult {
+        operation_id: id,
+        description: operation.description,
+        restored,
+        errors,
+    })
+}
+
+/// Undo a journaled operation by id
+pub fn undo_operation(id: i64) -> Result<UndoResult, String> {
+    let conn = database::open_connection()?;
+    undo_operation_with(&conn, id)
+}
+
+/// Undo the most recent operation that hasn't been undone
+pub fn undo_last_operation() -> Result<UndoResult, String> {
+    let conn = database::open_connection()?;
+    let id: i64 = conn
+        .query_row(
+            "SELECT id FROM operation_journal WHERE status = 'applied' ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(sql_err)?
+        .ok_or("Nothing to undo")?;
+    undo_operation_with(&conn, id)
+}
+
+/// Recent journal entries for the history view, newest first
+pub fn get_history(limit: usize) -> Result<Vec<JournalOperation>, String> {
+    let conn = database::open_connection()?;
+    list_operations(&conn, limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MetadataAction, MetadataOperation, MetadataScope};
+
+    fn test_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        database::configure_connection(&conn).unwrap();
+        database::apply_migrations(&conn);
+        conn
+    }
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("seer_journal_{}_{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path_str(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_store_and_load_operation() {
+        let mut conn = test_connection();
+        let changes = vec![
+            JournalChange::CreatedDir {
+                path: "/media/Season 1".to_string(),
+            },
+            JournalChange::Moved {
+                from: "/media/a.mkv".to_string(),
+                to: "/media/Season 1/a.mkv".to_string(),
+            },
+        ];
+        let id = store_operation(
+            &mut conn,
+            JournalOperationKind::CreateFolders,
+            "Create 1 folder",
+            &changes,
+        )
+        .unwrap();
+
+        let operation = load_operation(&conn, id).unwrap().unwrap();
+        assert_eq!(operation.kind, JournalOperationKind::CreateFolders);
+        assert_eq!(operation.status, JournalStatus::Applied);
+        assert_eq!(operation.changes, changes);
+        assert_eq!(list_operations(&conn, 10).unwrap().len(), 1);
+        assert!(load_operation(&conn, id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_store_and_load_tag_edit() {
+        let mut conn = test_connection();
+        let changes = vec![JournalChange::TagsEdited {
+            path: "/media/a.mkv".to_string(),
+            revert: vec![MetadataOperation {
+                action: MetadataAction::Set,
+                key: "title".to_string(),
+                value: Some("Old".to_string()),
+                scope: MetadataScope::Format,
+                stream_index: None,
+            }],
+        }];
+        let id = store_operation(
+            &mut conn,
+            JournalOperationKind::MetadataUpdate,
+            "Edit 1 metadata field(s) on 'a.mkv'",
+            &changes,
+        )
+        .unwrap();
+
+        let operation = load_operation(&conn, id).unwrap().unwrap();
+        assert_eq!(operation.changes, changes);
+        assert_eq!(operation.backup_bytes, 0);
+    }
+
+    #[test]
+    fn test_undo_changes_restores_files() {
+        let dir = temp_dir("undo");
+        let folder = dir.join("Movies");
+        let original = dir.join("a.mkv");
+        let moved = folder.join("a.mkv");
+        let rewritten = dir.join("b.mkv");
+        let backup = dir.join(BACKUP_DIR_NAME).join("1_b.mkv");
+        fs::create_dir_all(&folder).unwrap();
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        fs::write(&moved, "a").unwrap();
+        fs::write(&rewritten, "new").unwrap();
+        fs::write(&backup, "old").unwrap();
+
+        let changes = vec![
+            JournalChange::CreatedDir {
+                path: path_str(&folder),
+            },
+            JournalChange::Moved {
+                from: path_str(&original),
+                to: path_str(&moved),
+            },
+            JournalChange::Rewritten {
+                path: path_str(&rewritten),
+                backup: path_str(&backup),
+            },
+        ];
+        let (restored, errors) = undo_changes(&changes).unwrap();
+        assert_eq!((restored, errors.len()), (3, 0));
+        assert_eq!(fs::read_to_string(&original).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&rewritten).unwrap(), "old");
+        assert!(!folder.exists());
+        assert!(!backup.exists());
+
+        // Running it again finds the moves already reverted but the backup gone
+        let err = undo_changes(&changes).unwrap_err();
+        assert!(err.contains("no longer available"));
+        assert_eq!(undo_changes(&changes[..2]).unwrap(), (2, Vec::new()));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_undo_changes_conflict_touches_nothing() {
+        let dir = temp_dir("conflict");
+        let (a, b, c) = (dir.join("a.mkv"), dir.join("b.mkv"), dir.join("c.mkv"));
+        fs::write(&b, "renamed").unwrap();
+        // Something new now occupies the original name of the second rename
+        fs::write(&c, "renamed too").unwrap();
+        fs::write(dir.join("d.mkv"), "new file").unwrap();
+
+        let changes = vec![
+            JournalChange::Moved {
+                from: path_str(&a),
+                to: path_str(&b),
+            },
+            JournalChange::Moved {
+                from: path_str(&dir.join("d.mkv")),
+                to: path_str(&c),
+            },
+        ];
+        let err = undo_changes(&changes).unwrap_err();
+        assert!(err.starts_with("Cannot undo:"));
+        assert!(!a.exists() && b.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prune_expires_old_and_oversized_backups() {
+        let mut conn = test_connection();
+        let dir = temp_dir("prune");
+        let mut ids = Vec::new();
+        for (name, size) in [("old.mkv", 10), ("big.mkv", 100), ("new.mkv", 10)] {
+            let backup = dir.join(name);
+            fs::write(&backup, vec![0u8; size]).unwrap();
+            let change = JournalChange::Rewritten {
+                path: path_str(&dir.join(format!("orig_{}", name))),
+                backup: path_str(&backup),
+            };
+            ids.push(
+                store_operation(
+                    &mut conn,
+                    JournalOperationKind::MetadataUpdate,
+                    name,
+                    &[change],
+                )
+                .unwrap(),
+            );
+        }
+        conn.execute(
+            "UPDATE operation_journal SET created_at = datetime('now', '-10 days') WHERE id = ?1",
+            params![ids[0]],
+        )
+        .unwrap();
+
+        let retention = UndoRetention {
+            backup_days: 7,
+            max_backup_bytes: 50,
+        };
+        assert_eq!(prune(&conn, &retention).unwrap(), 2);
+
+        let status = |id| load_operation(&conn, id).unwrap().unwrap().status;
+        assert_eq!(status(ids[0]), JournalStatus::Expired);
+        assert_eq!(status(ids[1]), JournalStatus::Expired);
+        assert_eq!(status(ids[2]), JournalStatus::Applied);
+        assert!(!dir.join("old.mkv").exists());
+        assert!(!dir.join("big.mkv").exists());
+        assert!(dir.join("new.mkv").exists());
+
+        let err = undo_operation_with(&conn, ids[0]).unwrap_err();
+        assert!(err.contains("no longer be undone"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
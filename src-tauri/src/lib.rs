@@ -11,8 +11,11 @@ pub mod database;
 pub mod files;
 pub mod installer;
 pub mod jobs;
+pub mod journal;
+pub mod loudness;
 pub mod media;
 pub mod metadata;
+pub mod timeline;
 pub mod types;
 pub mod window;
 
@@ -20,6 +23,7 @@ pub mod window;
 pub use types::*;
 
 use database::{get_database_url, get_migrations};
+use tauri::Manager;
 
 /// Run the Tauri application
 pub fn run() {
@@ -46,6 +50,17 @@ pub fn run() {
                 .build(),
         )
         .setup(|app| {
+            let config_dir = app.path().app_config_dir()?;
+            database::init_database_path(&config_dir);
+            // Repair rewrites interrupted by a crash before anything touches those files
+            files::init_recovery_dir(&config_dir.join("pending-replaces"));
+            files::recover_interrupted_replaces();
+            // Expire old undo backups even if no new operation is journaled
+            std::thread::spawn(|| {
+                if let Err(e) = journal::apply_retention() {
+                    log::debug!("Undo retention not applied: {}", e);
+                }
+            });
             window::create_main_window(app)?;
             Ok(())
         })
@@ -69,26 +84,70 @@ pub fn run() {
             // Metadata operations
             commands::list_metadata,
             commands::update_metadata,
+            commands::bulk_update_metadata,
+            commands::list_metadata_templates,
+            commands::save_metadata_template,
+            commands::delete_metadata_template,
+            commands::apply_metadata_template,
+            commands::get_cover_art,
+            commands::set_cover_art,
+            commands::remove_cover_art,
+            commands::get_exif_metadata,
+            commands::edit_exif_metadata,
+            commands::shift_exif_metadata,
+            commands::scrub_metadata,
             commands::metadata_tools,
             // Media operations
             commands::get_media_streams,
             commands::remove_streams,
+            commands::check_stream_removal_space,
             commands::bulk_remove_streams,
+            commands::get_optimization_suggestions,
+            commands::preview_media_split,
+            commands::split_media,
+            commands::check_concat_compatibility,
+            commands::concat_files,
+            commands::get_thumbnail,
+            commands::create_contact_sheet,
             // Bitrate analysis
             commands::analyze_stream_bitrate,
             commands::analyze_overall_bitrate,
+            commands::analyze_gop,
+            commands::get_compliance_presets,
+            commands::check_bitrate_compliance,
+            commands::export_bitrate_analysis,
+            commands::analyze_folder_bitrate,
             commands::cancel_bitrate_analysis,
             commands::cancel_all_bitrate_jobs,
             commands::get_bitrate_job_status,
             commands::get_queue_status,
             commands::set_max_parallel_jobs,
             commands::compute_file_hash_cmd,
+            // Loudness analysis
+            commands::analyze_loudness,
+            commands::normalize_loudness,
+            // Detection timeline
+            commands::analyze_timeline,
+            // Undo journal
+            commands::undo_last_operation,
+            commands::undo_operation,
+            commands::get_undo_history,
             // Settings operations
             commands::get_initial_directory,
             commands::validate_path,
             commands::pick_folder,
             commands::save_last_directory,
             commands::get_default_downloads_dir,
+            commands::get_full_decode_verification,
+            commands::set_full_decode_verification,
+            commands::get_undo_retention,
+            commands::set_undo_retention,
+            commands::get_preserve_file_attributes,
+            commands::set_preserve_file_attributes,
+            commands::get_cover_art_max_dimension,
+            commands::set_cover_art_max_dimension,
+            commands::get_scrub_keep_tags,
+            commands::set_scrub_keep_tags,
             // Installer operations
             commands::get_install_strategies,
             commands::install_dependency,
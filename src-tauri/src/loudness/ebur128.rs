@@ -0,0 +1,287 @@
+//! EBU R128 loudness measurement
+//!
+//! Runs the `ebur128` filter over a single audio stream and parses its
+//! per-frame log (momentary/short-term loudness every 100ms) and summary
+//! (integrated loudness, loudness range, true peak).
+
+use log::{debug, info};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use crate::config;
+use crate::media::{get_media_streams, run_ffmpeg};
+use crate::types::{LoudnessAnalysis, LoudnessDataPoint, LoudnessSummary, StreamType};
+
+/// Floor used for silent frames (ebur128 reports -120.7 LUFS or -inf)
+pub const SILENCE_FLOOR_LUFS: f64 = -120.0;
+
+/// Parse a loudness value, mapping `-inf`/`nan` and values below the floor to the floor
+fn parse_lufs(value: &str) -> Option<f64> {
+    let v = value.trim().parse::<f64>().ok()?;
+    if v.is_nan() {
+        return None;
+    }
+    Some(v.max(SILENCE_FLOOR_LUFS))
+}
+
+/// Value following `key` in a whitespace separated ebur128 log line
+///
+/// Handles both `M:-23.1` and `M: -23.1`.
+fn field_after<'a>(tokens: &[&'a str], key: &str) -> Option<&'a str> {
+    let pos = tokens.iter().position(|t| t.starts_with(key))?;
+    let inline = &tokens[pos][key.len()..];
+    if inline.is_empty() {
+        tokens.get(pos + 1).copied()
+    } else {
+        Some(inline)
+    }
+}
+
+/// Parse a single per-frame ebur128 log line into `(timestamp, momentary, short_term)`
+pub fn parse_frame_line(line: &str) -> Option<(f64, f64, f64)> {
+    if !line.contains("ebur128") || !line.contains(" M:") {
+        return None;
+    }
+    let body = line.split_once(']').map(|(_, rest)| rest).unwrap_or(line);
+    let tokens: Vec<&str> = body.split_whitespace().collect();
+
+    let timestamp = field_after(&tokens, "t:")?.parse::<f64>().ok()?;
+    let momentary = parse_lufs(field_after(&tokens, "M:")?)?;
+    let short_term = parse_lufs(field_after(&tokens, "S:")?)?;
+    Some((timestamp, momentary, short_term))
+}
+
+/// Parse the ebur128 summary block printed when the filter is uninitialised
+pub fn parse_summary(stderr: &str) -> LoudnessSummary {
+    let mut summary = LoudnessSummary {
+        integrated: None,
+        loudness_range: None,
+        true_peak: None,
+        threshold: None,
+    };
+
+    let Some((_, block)) = stderr.rsplit_once("Summary:") else {
+        return summary;
+    };
+
+    // Section headers tell which "Threshold:" line belongs to integrated loudness
+    let mut section = "";
+    for line in block.lines() {
+        let line = line.trim();
+        if line.ends_with(':') {
+            section = line;
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let number = value.split_whitespace().next().and_then(parse_lufs);
+        match (section, key.trim()) {
+            ("Integrated loudness:", "I") => summary.integrated = number,
+            ("Integrated loudness:", "Threshold") => summary.threshold = number,
+            ("Loudness range:", "LRA") => summary.loudness_range = number,
+            ("True peak:", "Peak") => summary.true_peak = number,
+            _ => {}
+        }
+    }
+
+    summary
+}
+
+/// Combine two loudness values in the energy domain
+fn mean_lufs(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return SILENCE_FLOOR_LUFS;
+    }
+    let energy: f64 =
+        values.iter().map(|v| 10f64.powf(v / 10.0)).sum::<f64>() / values.len() as f64;
+    if energy <= 0.0 {
+        SILENCE_FLOOR_LUFS
+    } else {
+        (10.0 * energy.log10()).max(SILENCE_FLOOR_LUFS)
+    }
+}
+
+/// Aggregate 100ms ebur128 frames into fixed intervals (energy mean per interval)
+pub fn aggregate_loudness_intervals(
+    frames: &[(f64, f64, f64)],
+    interval_seconds: f64,
+    duration: f64,
+) -> Vec<LoudnessDataPoint> {
+    if frames.is_empty() || interval_seconds <= 0.0 {
+        return Vec::new();
+    }
+
+    let last_timestamp = frames.iter().map(|f| f.0).fold(0.0, f64::max);
+    let span = duration.max(last_timestamp);
+    let num_intervals = ((span / interval_seconds).ceil() as usize).max(1);
+    let mut intervals: Vec<(Vec<f64>, Vec<f64>)> = vec![(Vec::new(), Vec::new()); num_intervals];
+
+    for (timestamp, momentary, short_term) in frames {
+        let idx = ((timestamp / interval_seconds).floor() as usize).min(num_intervals - 1);
+        intervals[idx].0.push(*momentary);
+        intervals[idx].1.push(*short_term);
+    }
+
+    intervals
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (momentary, _))| !momentary.is_empty())
+        .map(|(idx, (momentary, short_term))| LoudnessDataPoint {
+            timestamp: idx as f64 * interval_seconds,
+            momentary: mean_lufs(&momentary),
+            short_term: mean_lufs(&short_term),
+        })
+        .collect()
+}
+
+/// Measure loudness of one audio stream with the `ebur128` filter
+pub fn analyze_loudness(
+    path: &str,
+    stream_index: i32,
+    interval_seconds: f64,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<LoudnessAnalysis, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    on_progress(0.0, "Getting stream info...");
+    let streams = get_media_streams(path.to_string())?;
+    let stream = streams
+        .streams
+        .iter()
+        .find(|s| s.index == stream_index)
+        .ok_or("Stream not found")?;
+    if stream.stream_type != StreamType::Audio {
+        return Err(format!("Stream {} is not an audio stream", stream_index));
+    }
+
+    let args: Vec<String> = vec![
+        "-i".to_string(),
+        validated_path.to_string_lossy().to_string(),
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+        "-af".to_string(),
+        "ebur128=peak=true:framelog=info".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+
+    debug!(
+        "analyze_loudness: path={}, stream={}, duration={:.2}s",
+        path, stream_index, streams.duration
+    );
+    let stderr = run_ffmpeg(&args, Some(streams.duration), Some(cancelled), &mut |pct| {
+        on_progress(pct * 0.95, "Measuring loudness...")
+    })?;
+
+    on_progress(95.0, "Aggregating loudness data...");
+    let frames: Vec<(f64, f64, f64)> = stderr.lines().filter_map(parse_frame_line).collect();
+    let summary = parse_summary(&stderr);
+    let data_points = aggregate_loudness_intervals(&frames, interval_seconds, streams.duration);
+
+    info!(
+        "Loudness analysis complete for stream {}: I={:?} LUFS, LRA={:?} LU, TP={:?} dBTP, {} points",
+        stream_index,
+        summary.integrated,
+        summary.loudness_range,
+        summary.true_peak,
+        data_points.len()
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(LoudnessAnalysis {
+        path: path.to_string(),
+        stream_index,
+        duration: streams.duration,
+        summary,
+        data_points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_LINE: &str = "[Parsed_ebur128_0 @ 0x600000c0c000] t: 1.2       TARGET:-23 LUFS    M: -18.4 S: -20.1     I: -19.0 LUFS       LRA:   2.3 LU  FTPK: -3.1 dBFS  TPK: -2.9 dBFS";
+
+    const SUMMARY: &str = "[Parsed_ebur128_0 @ 0x600000c0c000] Summary:
+
+  Integrated loudness:
+    I:         -16.2 LUFS
+    Threshold: -26.4 LUFS
+
+  Loudness range:
+    LRA:         5.3 LU
+    Threshold:  -36.4 LUFS
+    LRA low:    -21.0 LUFS
+    LRA high:   -15.7 LUFS
+
+  True peak:
+    Peak:        -0.8 dBFS
+";
+
+    #[test]
+    fn test_parse_frame_line() {
+        assert_eq!(parse_frame_line(FRAME_LINE), Some((1.2, -18.4, -20.1)));
+    }
+
+    #[test]
+    fn test_parse_frame_line_compact_and_silent() {
+        let line = "[Parsed_ebur128_0 @ 0x1] t: 0.1 TARGET:-23 LUFS M:-120.7 S:-inf I: -70.0 LUFS LRA: 0.0 LU";
+        assert_eq!(
+            parse_frame_line(line),
+            Some((0.1, SILENCE_FLOOR_LUFS, SILENCE_FLOOR_LUFS))
+        );
+    }
+
+    #[test]
+    fn test_parse_frame_line_ignores_other_lines() {
+        assert_eq!(parse_frame_line("Stream #0:1: Audio: aac"), None);
+        assert_eq!(parse_frame_line(SUMMARY), None);
+    }
+
+    #[test]
+    fn test_parse_summary() {
+        let summary = parse_summary(SUMMARY);
+        assert_eq!(summary.integrated, Some(-16.2));
+        assert_eq!(summary.threshold, Some(-26.4));
+        assert_eq!(summary.loudness_range, Some(5.3));
+        assert_eq!(summary.true_peak, Some(-0.8));
+    }
+
+    #[test]
+    fn test_parse_summary_missing() {
+        let summary = parse_summary("no summary here");
+        assert!(summary.integrated.is_none());
+        assert!(summary.true_peak.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_energy_mean() {
+        // Two equal values stay the same; the interval with only silence stays at the floor
+        let frames = vec![
+            (0.1, -20.0, -21.0),
+            (0.5, -20.0, -21.0),
+            (1.2, SILENCE_FLOOR_LUFS, SILENCE_FLOOR_LUFS),
+        ];
+        let points = aggregate_loudness_intervals(&frames, 1.0, 2.0);
+        assert_eq!(points.len(), 2);
+        assert!((points[0].momentary - -20.0).abs() < 1e-9);
+        assert!((points[0].short_term - -21.0).abs() < 1e-9);
+        assert_eq!(points[1].timestamp, 1.0);
+        assert!((points[1].momentary - SILENCE_FLOOR_LUFS).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_aggregate_energy_mean_is_louder_than_arithmetic() {
+        // -10 and -30 LUFS average to about -12.96 LUFS in the energy domain
+        let frames = vec![(0.0, -10.0, -10.0), (0.1, -30.0, -30.0)];
+        let points = aggregate_loudness_intervals(&frames, 1.0, 1.0);
+        assert!((points[0].momentary - -12.96).abs() < 0.01);
+    }
+}
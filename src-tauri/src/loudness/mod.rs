@@ -0,0 +1,18 @@
+//! Audio loudness module
+//!
+//! This module handles audio level analysis and normalization, including:
+//! - EBU R128 measurement (integrated loudness, LRA, true peak) via `ebur128`
+//! - Momentary/short-term loudness time series for charting
+//! - Two-pass `loudnorm` normalization to a target LUFS
+
+mod ebur128;
+mod normalize;
+
+pub use ebur128::{
+    aggregate_loudness_intervals, analyze_loudness, parse_frame_line, parse_summary,
+    SILENCE_FLOOR_LUFS,
+};
+pub use normalize::{
+    encoder_for_codec, normalize_loudness, parse_loudnorm_json, second_pass_filter,
+    LoudnormMeasurement,
+};
@@ -0,0 +1,349 @@
+//! Two-pass loudness normalization with the `loudnorm` filter
+//!
+//! The first pass measures the stream (`print_format=json`), the second pass
+//! feeds those measurements back for linear normalization to the target.
+//! Only the selected audio stream is re-encoded; everything else is copied.
+
+use log::{debug, info};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use crate::config;
+use crate::journal;
+use crate::media::{
+    get_media_streams, invalidate_probe_cache, run_ffmpeg, verify_output, ExpectedOutput,
+};
+use crate::types::{
+    JournalChange, JournalOperationKind, LoudnessSummary, NormalizationOptions,
+    NormalizationResult, StreamType,
+};
+
+/// Measurements from the first loudnorm pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoudnormMeasurement {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+/// Parse the JSON block printed by `loudnorm=print_format=json`
+pub fn parse_loudnorm_json(stderr: &str) -> Result<LoudnormMeasurement, String> {
+    let start = stderr
+        .rfind('{')
+        .ok_or("loudnorm did not report measurements")?;
+    let end = stderr[start..]
+        .find('}')
+        .map(|i| start + i + 1)
+        .ok_or("loudnorm measurements are incomplete")?;
+
+    let json: serde_json::Value = serde_json::from_str(&stderr[start..end])
+        .map_err(|e| format!("Failed to parse loudnorm output: {}", e))?;
+
+    let field = |key: &str| -> Result<f64, String> {
+        json.get(key)
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| {
+                format!(
+                    "loudnorm reported no usable '{}' (is the stream silent?)",
+                    key
+                )
+            })
+    };
+
+    Ok(LoudnormMeasurement {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+    })
+}
+
+/// Filter string for the second (linear) pass
+pub fn second_pass_filter(options: &NormalizationOptions, m: &LoudnormMeasurement) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:\
+         measured_thresh={}:offset={}:linear=true:print_format=summary",
+        options.target_lufs,
+        options.true_peak,
+        options.loudness_range,
+        m.input_i,
+        m.input_tp,
+        m.input_lra,
+        m.input_thresh,
+        m.target_offset
+    )
+}
+
+/// Encoder used to re-encode a normalized stream of the given codec
+///
+/// `None` for codecs ffmpeg can decode but not encode (DTS, TrueHD, ...);
+/// those aren't normalized rather than silently changing codec.
+pub fn encoder_for_codec(codec: &str) -> Option<&'static str> {
+    let encoder = match codec {
+        "aac" => "aac",
+        "mp3" => "libmp3lame",
+        "opus" => "libopus",
+        "vorbis" => "libvorbis",
+        "flac" => "flac",
+        "alac" => "alac",
+        "ac3" => "ac3",
+        "eac3" => "eac3",
+        "pcm_u8" => "pcm_u8",
+        "pcm_s8" => "pcm_s8",
+        "pcm_s16le" => "pcm_s16le",
+        "pcm_s16be" => "pcm_s16be",
+        "pcm_s24le" => "pcm_s24le",
+        "pcm_s24be" => "pcm_s24be",
+        "pcm_s32le" => "pcm_s32le",
+        "pcm_s32be" => "pcm_s32be",
+        "pcm_f32le" => "pcm_f32le",
+        "pcm_f32be" => "pcm_f32be",
+        "pcm_f64le" => "pcm_f64le",
+        "pcm_f64be" => "pcm_f64be",
+        _ => return None,
+    };
+    Some(encoder)
+}
+
+/// Normalize the loudness of one audio stream (two-pass loudnorm)
+pub fn normalize_loudness(
+    path: &str,
+    stream_index: i32,
+    options: &NormalizationOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<NormalizationResult, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+    if !(-70.0..=-5.0).contains(&options.target_lufs) {
+        return Err(format!(
+            "Target loudness {} LUFS is outside the supported range (-70 to -5)",
+            options.target_lufs
+        ));
+    }
+
+    on_progress(0.0, "Getting stream info...");
+    let streams = get_media_streams(path.to_string())?;
+    let audio_streams: Vec<_> = streams
+        .streams
+        .iter()
+        .filter(|s| s.stream_type == StreamType::Audio)
+        .collect();
+    let audio_position = audio_streams
+        .iter()
+        .position(|s| s.index == stream_index)
+        .ok_or_else(|| format!("Stream {} is not an audio stream", stream_index))?;
+    let stream = audio_streams[audio_position];
+    let codec = stream.codec_name.as_deref().unwrap_or("unknown");
+    let encoder = encoder_for_codec(codec).ok_or_else(|| {
+        format!(
+            "Can't normalize {} audio: ffmpeg has no encoder for it, and re-encoding to another codec isn't done automatically",
+            codec
+        )
+    })?;
+    let input = validated_path.to_string_lossy().to_string();
+
+    // Pass 1: measure
+    let filter = format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        options.target_lufs, options.true_peak, options.loudness_range
+    );
+    let args: Vec<String> = vec![
+        "-i".to_string(),
+        input.clone(),
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+        "-af".to_string(),
+        filter,
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let stderr = run_ffmpeg(&args, Some(streams.duration), Some(cancelled), &mut |pct| {
+        on_progress(pct * 0.5, "Measuring loudness (pass 1/2)...")
+    })?;
+    let measurement = parse_loudnorm_json(&stderr)?;
+    debug!("loudnorm pass 1 for {}: {:?}", path, measurement);
+
+    // Pass 2: normalize into a new file
+    let stem = validated_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    let ext = validated_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("mkv");
+    let parent = validated_path.parent().unwrap_or(Path::new("."));
+    let output_path = if options.overwrite {
+        parent.join(format!("{}_temp_{}.{}", stem, std::process::id(), ext))
+    } else {
+        parent.join(format!("{}_normalized.{}", stem, ext))
+    };
+
+    let mut args: Vec<String> = vec![
+        "-y".to_string(),
+        "-i".to_string(),
+        input,
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        format!("-filter:a:{}", audio_position),
+        second_pass_filter(options, &measurement),
+        format!("-c:a:{}", audio_position),
+        encoder.to_string(),
+    ];
+    // loudnorm resamples to 192kHz internally; restore the source rate
+    if let Some(sample_rate) = &stream.sample_rate {
+        args.extend([format!("-ar:a:{}", audio_position), sample_rate.clone()]);
+    }
+    if let Some(bit_rate) = &stream.bit_rate {
+        args.extend([format!("-b:a:{}", audio_position), bit_rate.clone()]);
+    }
+    args.push(output_path.to_string_lossy().to_string());
+
+    if let Err(e) = run_ffmpeg(&args, Some(streams.duration), Some(cancelled), &mut |pct| {
+        on_progress(50.0 + pct * 0.5, "Normalizing (pass 2/2)...")
+    }) {
+        let _ = fs::remove_file(&output_path);
+        return Err(e);
+    }
+
+    on_progress(99.0, "Verifying output...");
+    if let Err(e) = verify_output(
+        &output_path,
+        &ExpectedOutput::unchanged(&streams),
+        Some(cancelled),
+    ) {
+        let _ = fs::remove_file(&output_path);
+        return Err(e);
+    }
+
+    let final_path = if options.overwrite {
+        let size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        if size == 0 {
+            let _ = fs::remove_file(&output_path);
+            return Err("Temp file is empty - aborting to prevent data loss".to_string());
+        }
+        let backup =
+            journal::replace_with_backup(&validated_path, &output_path).inspect_err(|_| {
+                let _ = fs::remove_file(&output_path);
+            })?;
+        if let Some(backup) = backup {
+            journal::record(
+                JournalOperationKind::LoudnessNormalization,
+                format!(
+                    "Normalize '{}' to {} LUFS",
+                    validated_path
+                        .file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default(),
+                    options.target_lufs
+                ),
+                vec![JournalChange::Rewritten {
+                    path: validated_path.to_string_lossy().to_string(),
+                    backup: backup.to_string_lossy().to_string(),
+                }],
+            );
+        }
+        invalidate_probe_cache(path);
+        validated_path.to_path_buf()
+    } else {
+        invalidate_probe_cache(&output_path.to_string_lossy());
+        output_path
+    };
+
+    info!(
+        "Normalized stream {} of {} from {:.1} to {:.1} LUFS",
+        stream_index, path, measurement.input_i, options.target_lufs
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(NormalizationResult {
+        success: true,
+        output_path: final_path.to_string_lossy().to_string(),
+        measured: LoudnessSummary {
+            integrated: Some(measurement.input_i),
+            loudness_range: Some(measurement.input_lra),
+            true_peak: Some(measurement.input_tp),
+            threshold: Some(measurement.input_thresh),
+        },
+        target_lufs: options.target_lufs,
+        message: if options.overwrite {
+            format!(
+                "Normalized to {} LUFS. Original file updated.",
+                options.target_lufs
+            )
+        } else {
+            format!(
+                "Normalized to {} LUFS. Output saved to: {}",
+                options.target_lufs,
+                final_path.display()
+            )
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASS1: &str = r#"[Parsed_loudnorm_0 @ 0x7f8]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+"#;
+
+    #[test]
+    fn test_parse_loudnorm_json() {
+        let m = parse_loudnorm_json(PASS1).unwrap();
+        assert_eq!(m.input_i, -27.61);
+        assert_eq!(m.input_tp, -4.47);
+        assert_eq!(m.input_lra, 18.06);
+        assert_eq!(m.input_thresh, -39.20);
+        assert_eq!(m.target_offset, 0.58);
+    }
+
+    #[test]
+    fn test_parse_loudnorm_json_silent_stream() {
+        let stderr = PASS1.replace("\"-27.61\"", "\"-inf\"");
+        assert!(parse_loudnorm_json(&stderr).is_err());
+        assert!(parse_loudnorm_json("no json").is_err());
+    }
+
+    #[test]
+    fn test_second_pass_filter() {
+        let m = parse_loudnorm_json(PASS1).unwrap();
+        let filter = second_pass_filter(&NormalizationOptions::default(), &m);
+        assert!(filter.starts_with("loudnorm=I=-23:TP=-1:LRA=11:"));
+        assert!(filter.contains("measured_I=-27.61:"));
+        assert!(filter.contains("offset=0.58:linear=true"));
+    }
+
+    #[test]
+    fn test_encoder_for_codec() {
+        assert_eq!(encoder_for_codec("mp3"), Some("libmp3lame"));
+        assert_eq!(encoder_for_codec("flac"), Some("flac"));
+        assert_eq!(encoder_for_codec("pcm_s16be"), Some("pcm_s16be"));
+        assert_eq!(encoder_for_codec("dts"), None);
+        assert_eq!(encoder_for_codec("truehd"), None);
+    }
+}
@@ -0,0 +1,452 @@
+//! Lossless concatenation of multiple files
+//!
+//! This module handles:
+//! - Checking that inputs share a compatible stream layout
+//! - Explaining any mismatch per file and stream
+//! - Joining inputs with the concat demuxer (`-c copy`)
+//! - Optionally generating one chapter per source file
+
+use log::{debug, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use super::disk_space::ensure_disk_space;
+use super::ffmpeg::run_ffmpeg;
+use super::streams::get_media_streams;
+use crate::config;
+use crate::types::{
+    ConcatCheck, ConcatMismatch, ConcatResult, MediaStreams, StreamInfo, StreamType,
+};
+
+/// Streams that take part in the layout comparison
+///
+/// Attachments, data streams and cover art don't affect whether the concat demuxer
+/// can join files cleanly.
+fn comparable_streams(media: &MediaStreams) -> Vec<&StreamInfo> {
+    media
+        .streams
+        .iter()
+        .filter(|s| {
+            matches!(
+                s.stream_type,
+                StreamType::Video | StreamType::Audio | StreamType::Subtitle
+            ) && !s.is_cover_art
+        })
+        .collect()
+}
+
+fn stream_type_label(stream_type: &StreamType) -> &'static str {
+    match stream_type {
+        StreamType::Video => "video",
+        StreamType::Audio => "audio",
+        StreamType::Subtitle => "subtitle",
+        StreamType::Attachment => "attachment",
+        StreamType::Data => "data",
+        StreamType::Unknown => "unknown",
+    }
+}
+
+fn display_value(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Compare the stream layout of `other` against `reference`
+pub fn compare_layouts(reference: &MediaStreams, other: &MediaStreams) -> Vec<ConcatMismatch> {
+    let expected_streams = comparable_streams(reference);
+    let found_streams = comparable_streams(other);
+    let file_name = |p: &str| {
+        Path::new(p)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| p.to_string())
+    };
+    let reference_name = file_name(&reference.path);
+    let other_name = file_name(&other.path);
+
+    if expected_streams.len() != found_streams.len() {
+        return vec![ConcatMismatch {
+            path: other.path.clone(),
+            stream_position: None,
+            field: "stream_count".to_string(),
+            expected: expected_streams.len().to_string(),
+            found: found_streams.len().to_string(),
+            message: format!(
+                "{} has {} stream(s) but {} has {}",
+                other_name,
+                found_streams.len(),
+                reference_name,
+                expected_streams.len()
+            ),
+        }];
+    }
+
+    let mut mismatches = Vec::new();
+    for (position, (expected, found)) in expected_streams.iter().zip(&found_streams).enumerate() {
+        let mut check = |field: &str, label: &str, a: String, b: String| {
+            if a != b {
+                mismatches.push(ConcatMismatch {
+                    path: other.path.clone(),
+                    stream_position: Some(position),
+                    field: field.to_string(),
+                    message: format!(
+                        "{}: stream #{} {} is {} but {} has {}",
+                        other_name, position, label, b, reference_name, a
+                    ),
+                    expected: a,
+                    found: b,
+                });
+            }
+        };
+
+        check(
+            "stream_type",
+            "type",
+            stream_type_label(&expected.stream_type).to_string(),
+            stream_type_label(&found.stream_type).to_string(),
+        );
+        if expected.stream_type != found.stream_type {
+            continue;
+        }
+
+        check(
+            "codec",
+            "codec",
+            display_value(expected.codec_name.clone()),
+            display_value(found.codec_name.clone()),
+        );
+
+        match expected.stream_type {
+            StreamType::Video => {
+                let resolution = |s: &StreamInfo| match (s.width, s.height) {
+                    (Some(w), Some(h)) => format!("{}x{}", w, h),
+                    _ => "unknown".to_string(),
+                };
+                check(
+                    "resolution",
+                    "resolution",
+                    resolution(expected),
+                    resolution(found),
+                );
+            }
+            StreamType::Audio => {
+                check(
+                    "sample_rate",
+                    "sample rate",
+                    display_value(expected.sample_rate.clone()),
+                    display_value(found.sample_rate.clone()),
+                );
+                let layout = |s: &StreamInfo| {
+                    s.channel_layout
+                        .clone()
+                        .or_else(|| s.channels.map(|c| format!("{} channels", c)))
+                };
+                check(
+                    "channel_layout",
+                    "channel layout",
+                    display_value(layout(expected)),
+                    display_value(layout(found)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    mismatches
+}
+
+/// Check whether the given files can be joined without re-encoding
+pub fn check_concat(paths: &[String]) -> Result<ConcatCheck, String> {
+    if paths.len() < 2 {
+        return Err("At least two files are required to concatenate".to_string());
+    }
+
+    let media: Vec<MediaStreams> = paths
+        .iter()
+        .map(|p| get_media_streams(p.clone()))
+        .collect::<Result<_, _>>()?;
+
+    let mismatches: Vec<ConcatMismatch> = media[1..]
+        .iter()
+        .flat_map(|other| compare_layouts(&media[0], other))
+        .collect();
+
+    Ok(ConcatCheck {
+        compatible: mismatches.is_empty(),
+        total_duration: media.iter().map(|m| m.duration).sum(),
+        mismatches,
+    })
+}
+
+/// Quote a path for an ffconcat list entry
+fn concat_list_entry(path: &Path) -> String {
+    format!("file '{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// Escape a value for the FFMETADATA format
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Build an FFMETADATA document with one chapter per `(title, duration)` entry
+pub fn build_chapter_metadata(entries: &[(String, f64)]) -> String {
+    let mut doc = String::from(";FFMETADATA1\n");
+    let mut start_ms: u64 = 0;
+    for (title, duration) in entries {
+        let end_ms = start_ms + (duration.max(0.0) * 1000.0).round() as u64;
+        doc.push_str("[CHAPTER]\nTIMEBASE=1/1000\n");
+        doc.push_str(&format!("START={}\nEND={}\n", start_ms, end_ms));
+        doc.push_str(&format!("title={}\n", escape_ffmetadata(title)));
+        start_ms = end_ms;
+    }
+    doc
+}
+
+/// Temporary helper file next to the output, removed once the job finishes
+fn helper_path(output: &Path, label: &str) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    output.with_file_name(format!(
+        ".{}.seer_{}_{}.txt",
+        stem,
+        label,
+        std::process::id()
+    ))
+}
+
+/// Concatenate files losslessly with the concat demuxer
+pub fn concat_files(
+    paths: &[String],
+    output_path: &str,
+    add_chapters: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<ConcatResult, String> {
+    on_progress(0.0, "Checking stream layouts...");
+    let check = check_concat(paths)?;
+    if !check.compatible {
+        let reasons: Vec<String> = check.mismatches.iter().map(|m| m.message.clone()).collect();
+        return Err(format!(
+            "Files cannot be joined without re-encoding:\n{}",
+            reasons.join("\n")
+        ));
+    }
+
+    let output = Path::new(output_path);
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or("Invalid output path")?;
+    let validated_parent = config::validate_path(parent)?;
+    let file_name = output.file_name().ok_or("Invalid output path")?;
+    let output = validated_parent.join(file_name);
+    if output.exists() {
+        return Err(format!("Output already exists: {}", output.display()));
+    }
+
+    let inputs: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| config::validate_path(Path::new(p)))
+        .collect::<Result<_, _>>()?;
+    if inputs.contains(&output) {
+        return Err("Output cannot be one of the inputs".to_string());
+    }
+    let required = inputs
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    ensure_disk_space(&validated_parent, required)?;
+
+    let list_path = helper_path(&output, "concat");
+    let list: Vec<String> = inputs.iter().map(|p| concat_list_entry(p)).collect();
+    fs::write(&list_path, list.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write concat list: {}", e))?;
+
+    let mut args: Vec<String> = vec![
+        "-y".to_string(),
+        "-f".to_string(),
+        "concat".to_string(),
+        "-safe".to_string(),
+        "0".to_string(),
+        "-i".to_string(),
+        list_path.to_string_lossy().to_string(),
+    ];
+
+    let mut metadata_path = None;
+    if add_chapters {
+        let mut entries = Vec::with_capacity(inputs.len());
+        for path in paths {
+            let media = get_media_streams(path.clone())?;
+            let title = Path::new(path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            entries.push((title, media.duration));
+        }
+
+        let meta_path = helper_path(&output, "chapters");
+        if let Err(e) = fs::write(&meta_path, build_chapter_metadata(&entries)) {
+            let _ = fs::remove_file(&list_path);
+            return Err(format!("Failed to write chapter metadata: {}", e));
+        }
+        args.extend([
+            "-i".to_string(),
+            meta_path.to_string_lossy().to_string(),
+            "-map_chapters".to_string(),
+            "1".to_string(),
+        ]);
+        metadata_path = Some(meta_path);
+    }
+
+    args.extend([
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        output.to_string_lossy().to_string(),
+    ]);
+
+    debug!(
+        "concat_files: {} inputs, duration={:.2}s, chapters={}",
+        inputs.len(),
+        check.total_duration,
+        add_chapters
+    );
+
+    let stage = format!("Joining {} files...", inputs.len());
+    let result = run_ffmpeg(
+        &args,
+        Some(check.total_duration),
+        Some(cancelled),
+        &mut |pct| on_progress(pct, &stage),
+    );
+
+    let _ = fs::remove_file(&list_path);
+    if let Some(meta_path) = metadata_path {
+        let _ = fs::remove_file(meta_path);
+    }
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&output);
+        return Err(e);
+    }
+
+    info!(
+        "Concatenated {} files into {}",
+        inputs.len(),
+        output.display()
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(ConcatResult {
+        success: true,
+        output_path: output.to_string_lossy().to_string(),
+        message: format!("Joined {} files", inputs.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::parse_stream;
+    use serde_json::json;
+
+    fn media(path: &str, streams: Vec<serde_json::Value>) -> MediaStreams {
+        let streams: Vec<StreamInfo> = streams.iter().map(parse_stream).collect();
+        MediaStreams {
+            path: path.to_string(),
+            video_count: 0,
+            audio_count: 0,
+            subtitle_count: 0,
+            attachment_count: 0,
+            total_size: 0,
+            duration: 10.0,
+            streams,
+        }
+    }
+
+    fn video(codec: &str, width: i32, height: i32) -> serde_json::Value {
+        json!({"index": 0, "codec_type": "video", "codec_name": codec, "width": width, "height": height})
+    }
+
+    fn audio(codec: &str, sample_rate: &str, layout: &str) -> serde_json::Value {
+        json!({"index": 1, "codec_type": "audio", "codec_name": codec, "sample_rate": sample_rate, "channel_layout": layout})
+    }
+
+    #[test]
+    fn test_compatible_layouts() {
+        let a = media(
+            "/a.mkv",
+            vec![video("h264", 1920, 1080), audio("aac", "48000", "stereo")],
+        );
+        let b = media(
+            "/b.mkv",
+            vec![video("h264", 1920, 1080), audio("aac", "48000", "stereo")],
+        );
+        assert!(compare_layouts(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_resolution_and_sample_rate_mismatch() {
+        let a = media(
+            "/a.mkv",
+            vec![video("h264", 1920, 1080), audio("aac", "48000", "stereo")],
+        );
+        let b = media(
+            "/b.mkv",
+            vec![video("h264", 1280, 720), audio("aac", "44100", "stereo")],
+        );
+        let mismatches = compare_layouts(&a, &b);
+        let fields: Vec<&str> = mismatches.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec!["resolution", "sample_rate"]);
+        assert_eq!(mismatches[0].expected, "1920x1080");
+        assert_eq!(mismatches[0].found, "1280x720");
+        assert!(mismatches[0].message.contains("b.mkv"));
+    }
+
+    #[test]
+    fn test_stream_count_mismatch() {
+        let a = media(
+            "/a.mkv",
+            vec![video("h264", 1920, 1080), audio("aac", "48000", "stereo")],
+        );
+        let b = media("/b.mkv", vec![video("h264", 1920, 1080)]);
+        let mismatches = compare_layouts(&a, &b);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "stream_count");
+        assert_eq!(mismatches[0].stream_position, None);
+    }
+
+    #[test]
+    fn test_codec_mismatch_skips_type_specific_checks_on_type_change() {
+        let a = media("/a.mkv", vec![audio("aac", "48000", "stereo")]);
+        let b = media("/b.mkv", vec![video("h264", 1920, 1080)]);
+        let mismatches = compare_layouts(&a, &b);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "stream_type");
+    }
+
+    #[test]
+    fn test_concat_list_entry_escapes_quotes() {
+        let entry = concat_list_entry(Path::new("/media/it's.mkv"));
+        assert_eq!(entry, "file '/media/it'\\''s.mkv'");
+    }
+
+    #[test]
+    fn test_build_chapter_metadata() {
+        let doc = build_chapter_metadata(&[("Part 1".to_string(), 10.5), ("a=b".to_string(), 2.0)]);
+        assert!(doc.starts_with(";FFMETADATA1\n"));
+        assert!(doc.contains("START=0\nEND=10500\ntitle=Part 1\n"));
+        assert!(doc.contains("START=10500\nEND=12500\ntitle=a\\=b\n"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Free-space preflight and monitoring for jobs that write media files
+//!
+//! Writing jobs estimate their output size up front and refuse to start when
+//! it won't fit on the target filesystem. While ffmpeg is writing, a
+//! `SpaceMonitor` re-checks free space so the job can be aborted (and the
+//! partial output removed) before the disk fills and leaves a truncated file.
+
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::streams::get_media_streams;
+use crate::config;
+use crate::types::{DiskSpaceCheck, DiskSpaceStatus, MediaStreams};
+
+/// Space that should stay free after the output is written
+pub const SAFETY_MARGIN_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Writing is aborted once free space drops below this
+pub const MIN_FREE_DURING_WRITE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How often `SpaceMonitor` queries the filesystem
+const MONITOR_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Format a byte count for messages (e.g. `1.4 GB`)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Free space available to the current user on the filesystem holding `dir`
+pub fn available_space(dir: &Path) -> Result<u64, String> {
+    fs2::available_space(dir)
+        .map_err(|e| format!("Failed to read free space for {}: {}", dir.display(), e))
+}
+
+/// Output size of a stream-copy that drops `removed` streams
+///
+/// Streams without a size estimate are assumed to stay, so the estimate errs
+/// on the large side.
+pub fn estimate_removal_output_size(streams: &MediaStreams, removed: &[i32]) -> u64 {
+    let removed_bytes: u64 = streams
+        .streams
+        .iter()
+        .filter(|s| removed.contains(&s.index))
+        .filter_map(|s| s.estimated_size)
+        .sum();
+    streams.total_size.saturating_sub(removed_bytes)
+}
+
+/// Classify free space against the required size
+pub fn evaluate_space(target_dir: &Path, required: u64, available: u64) -> DiskSpaceCheck {
+    let (status, message) = if available < required {
+        (
+            DiskSpaceStatus::Insufficient,
+            format!(
+                "Not enough disk space: output needs ~{}, only {} free on {}",
+                format_bytes(required),
+                format_bytes(available),
+                target_dir.display()
+            ),
+        )
+    } else if available - required < SAFETY_MARGIN_BYTES {
+        (
+            DiskSpaceStatus::Low,
+            format!(
+                "Disk space is low: writing ~{} leaves {} free on {}",
+                format_bytes(required),
+                format_bytes(available - required),
+                target_dir.display()
+            ),
+        )
+    } else {
+        (
+            DiskSpaceStatus::Ok,
+            format!(
+                "~{} needed, {} free",
+                format_bytes(required),
+                format_bytes(available)
+            ),
+        )
+    };
+
+    DiskSpaceCheck {
+        target_dir: target_dir.to_string_lossy().to_string(),
+        required_bytes: required,
+        available_bytes: available,
+        status,
+        message,
+    }
+}
+
+/// Check that `required` bytes fit in `target_dir`
+pub fn check_disk_space(target_dir: &Path, required: u64) -> Result<DiskSpaceCheck, String> {
+    let available = available_space(target_dir)?;
+    Ok(evaluate_space(target_dir, required, available))
+}
+
+/// Preflight for writing jobs: errors when the output won't fit, warns when tight
+pub fn ensure_disk_space(target_dir: &Path, required: u64) -> Result<DiskSpaceCheck, String> {
+    let check = check_disk_space(target_dir, required)?;
+    match check.status {
+        DiskSpaceStatus::Insufficient => Err(check.message),
+        DiskSpaceStatus::Low => {
+            warn!("{}", check.message);
+            Ok(check)
+        }
+        DiskSpaceStatus::Ok => {
+            debug!(
+                "Disk space preflight for {}: {}",
+                check.target_dir, check.message
+            );
+            Ok(check)
+        }
+    }
+}
+
+/// Free-space check for removing streams from `path`, for the UI to show before starting
+pub fn preflight_stream_removal(
+    path: &str,
+    stream_indices: &[i32],
+) -> Result<DiskSpaceCheck, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+    let dir = validated_path.parent().unwrap_or(Path::new("."));
+    let streams = get_media_streams(path.to_string())?;
+    check_disk_space(dir, estimate_removal_output_size(&streams, stream_indices))
+}
+
+/// Periodic free-space check while a job is writing
+pub struct SpaceMonitor {
+    dir: PathBuf,
+    min_free: u64,
+    last_check: Option<Instant>,
+    exhausted: bool,
+}
+
+impl SpaceMonitor {
+    pub fn new(dir: &Path, min_free: u64) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            min_free,
+            last_check: None,
+            exhausted: false,
+        }
+    }
+
+    /// Whether free space has dropped below the minimum
+    ///
+    /// Queries the filesystem at most once per `MONITOR_INTERVAL`; once space
+    /// has run out it stays exhausted. Read errors don't abort the job.
+    pub fn is_exhausted(&mut self) -> bool {
+        if self.exhausted {
+            return true;
+        }
+        if self
+            .last_check
+            .is_some_and(|last| last.elapsed() < MONITOR_INTERVAL)
+        {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        match available_space(&self.dir) {
+            Ok(available) if available < self.min_free => {
+                warn!(
+                    "Free space on {} dropped to {}, aborting write",
+                    self.dir.display(),
+                    format_bytes(available)
+                );
+                self.exhausted = true;
+            }
+            Ok(_) => {}
+            Err(e) => debug!("{}", e),
+        }
+        self.exhausted
+    }
+
+    /// Error for a job aborted by the monitor
+    pub fn error_message(&self) -> String {
+        format!(
+            "Aborted: free space on {} dropped below {}. The partial output was removed.",
+            self.dir.display(),
+            format_bytes(self.min_free)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::parse_stream;
+    use serde_json::json;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn test_estimate_removal_output_size() {
+        let sized = |index: i32, bytes: u64| {
+            parse_stream(&json!({
+                "index": index,
+                "codec_type": "audio",
+                "tags": { "NUMBER_OF_BYTES": bytes.to_string() }
+            }))
+        };
+        let streams = MediaStreams {
+            path: "/a.mkv".to_string(),
+            streams: vec![
+                sized(0, 8 * GB),
+                sized(1, GB),
+                parse_stream(&json!({ "index": 2, "codec_type": "audio" })),
+            ],
+            video_count: 0,
+            audio_count: 0,
+            subtitle_count: 0,
+            attachment_count: 0,
+            total_size: 10 * GB,
+            duration: 0.0,
+        };
+        assert_eq!(estimate_removal_output_size(&streams, &[1]), 9 * GB);
+        // Unknown sizes are assumed to stay
+        assert_eq!(estimate_removal_output_size(&streams, &[2]), 10 * GB);
+    }
+
+    #[test]
+    fn test_evaluate_space() {
+        let dir = Path::new("/media");
+        assert_eq!(
+            evaluate_space(dir, 2 * GB, GB).status,
+            DiskSpaceStatus::Insufficient
+        );
+        assert_eq!(
+            evaluate_space(dir, 2 * GB, 2 * GB + 100).status,
+            DiskSpaceStatus::Low
+        );
+        assert_eq!(
+            evaluate_space(dir, 2 * GB, 4 * GB).status,
+            DiskSpaceStatus::Ok
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * GB / 2), "1.5 GB");
+    }
+
+    #[test]
+    fn test_space_monitor_on_real_dir() {
+        let mut plenty = SpaceMonitor::new(&std::env::temp_dir(), 0);
+        assert!(!plenty.is_exhausted());
+        let mut impossible = SpaceMonitor::new(&std::env::temp_dir(), u64::MAX);
+        assert!(impossible.is_exhausted());
+        assert!(impossible.is_exhausted());
+    }
+}
@@ -0,0 +1,149 @@
+//! Shared ffmpeg process runner
+//!
+//! This module handles:
+//! - Spawning ffmpeg with machine-readable progress output (`-progress pipe:1`)
+//! - Translating `out_time` into a percentage of the expected duration
+//! - Killing the process when the owning job is cancelled
+//! - Collecting stderr (filters such as ebur128/blackdetect log their results there)
+
+use log::{debug, error};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use super::find_command;
+
+/// Error message returned when a job is cancelled while ffmpeg is running
+pub const CANCELLED_MESSAGE: &str = "Operation cancelled";
+
+/// Parse a single `-progress` line and return the output position in seconds
+///
+/// ffmpeg reports `out_time_us` and `out_time_ms` (both in microseconds, despite
+/// the name of the latter) as well as a formatted `out_time=HH:MM:SS.micro`.
+pub fn parse_progress_line(line: &str) -> Option<f64> {
+    let (key, value) = line.trim().split_once('=')?;
+    match key {
+        "out_time_us" | "out_time_ms" => value
+            .parse::<i64>()
+            .ok()
+            .filter(|us| *us >= 0)
+            .map(|us| us as f64 / 1_000_000.0),
+        _ => None,
+    }
+}
+
+/// Run ffmpeg with the given arguments, reporting progress and honouring cancellation
+///
+/// `duration` is the expected output duration in seconds and is used to turn
+/// `out_time` into a percentage (0-100) passed to `on_progress`. When it is
+/// `None` or zero, no progress is reported.
+///
+/// Returns the captured stderr on success.
+pub fn run_ffmpeg(
+    args: &[String],
+    duration: Option<f64>,
+    cancelled: Option<&AtomicBool>,
+    on_progress: &mut dyn FnMut(f64),
+) -> Result<String, String> {
+    let ffmpeg_cmd = find_command("ffmpeg").unwrap_or_else(|| "ffmpeg".to_string());
+    debug!("Running ffmpeg: {} {}", ffmpeg_cmd, args.join(" "));
+
+    let mut child = Command::new(&ffmpeg_cmd)
+        .arg("-hide_banner")
+        .arg("-nostdin")
+        .arg("-progress")
+        .arg("pipe:1")
+        .arg("-nostats")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    let stdout_handle = child.stdout.take();
+    let stderr_handle = child.stderr.take();
+
+    // Drain stderr in a separate thread so a chatty filter cannot fill the pipe
+    let stderr_thread = thread::spawn(move || {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut stderr = Vec::new();
+            if let Some(mut err) = stderr_handle {
+                err.read_to_end(&mut stderr).ok();
+            }
+            stderr
+        }))
+        .unwrap_or_else(|_| Vec::new())
+    });
+
+    let mut was_cancelled = false;
+    if let Some(out) = stdout_handle {
+        let reader = BufReader::new(out);
+        for line in reader.lines().map_while(Result::ok) {
+            if cancelled.is_some_and(|c| c.load(Ordering::SeqCst)) {
+                was_cancelled = true;
+                let _ = child.kill();
+                break;
+            }
+
+            if let (Some(position), Some(total)) = (parse_progress_line(&line), duration) {
+                if total > 0.0 {
+                    on_progress((position / total * 100.0).clamp(0.0, 100.0));
+                }
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+    let stderr = stderr_thread
+        .join()
+        .map_err(|_| "Failed to join stderr thread")?;
+    let stderr = String::from_utf8_lossy(&stderr).to_string();
+
+    if was_cancelled || cancelled.is_some_and(|c| c.load(Ordering::SeqCst)) {
+        debug!("ffmpeg killed after cancellation");
+        return Err(CANCELLED_MESSAGE.to_string());
+    }
+
+    if !status.success() {
+        error!("ffmpeg failed: {}", stderr);
+        return Err(format!("ffmpeg failed: {}", stderr));
+    }
+
+    on_progress(100.0);
+    Ok(stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress_out_time_us() {
+        assert_eq!(parse_progress_line("out_time_us=2500000"), Some(2.5));
+    }
+
+    #[test]
+    fn test_parse_progress_out_time_ms_is_microseconds() {
+        assert_eq!(parse_progress_line("out_time_ms=1000000"), Some(1.0));
+    }
+
+    #[test]
+    fn test_parse_progress_ignores_other_keys() {
+        assert_eq!(parse_progress_line("frame=120"), None);
+        assert_eq!(parse_progress_line("progress=continue"), None);
+        assert_eq!(parse_progress_line("out_time=00:00:01.000000"), None);
+    }
+
+    #[test]
+    fn test_parse_progress_negative_and_invalid() {
+        assert_eq!(
+            parse_progress_line("out_time_us=-9223372036854775807"),
+            None
+        );
+        assert_eq!(parse_progress_line("out_time_us=N/A"), None);
+        assert_eq!(parse_progress_line("garbage"), None);
+    }
+}
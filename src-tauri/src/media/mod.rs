@@ -5,15 +5,41 @@
 //! - Stream removal using ffmpeg
 //! - Media file metadata extraction
 //! - FFprobe result caching for performance
+//! - Shared ffmpeg runner with progress and cancellation
+//! - Lossless trimming and splitting at keyframes
+//! - Lossless concatenation with the concat demuxer
+//! - Thumbnail extraction (cached on disk) and contact sheets
+//! - File size optimization suggestions
+//! - Free-space preflight and monitoring for writing jobs
+//! - Verification of rewritten files before originals are replaced
 
+mod concat;
+mod disk_space;
+mod ffmpeg;
+mod optimize;
 mod probe_cache;
+mod split;
 mod streams;
+mod thumbnails;
+mod verify;
+
+pub use concat::{build_chapter_metadata, check_concat, compare_layouts, concat_files};
+pub use disk_space::{
+    available_space, check_disk_space, ensure_disk_space, estimate_removal_output_size,
+    evaluate_space, format_bytes, preflight_stream_removal, SpaceMonitor,
+    MIN_FREE_DURING_WRITE_BYTES, SAFETY_MARGIN_BYTES,
+};
+pub use ffmpeg::{parse_progress_line, run_ffmpeg, CANCELLED_MESSAGE};
+pub use optimize::{analyze_optimizations, is_lossless_audio, suggest_optimizations};
 
 pub use probe_cache::{
     clear_cache as clear_probe_cache, get_cache_stats as get_probe_cache_stats, get_probe_data,
     get_probe_json, get_probe_string, invalidate_cache as invalidate_probe_cache,
 };
+pub use split::{get_keyframe_times, preview_split, snap_to_keyframe, split_media};
 pub use streams::{
-    find_command, get_media_streams, get_search_paths, parse_disposition, parse_stream,
-    remove_streams,
+    find_command, get_chapters, get_media_streams, get_search_paths, parse_chapters,
+    parse_disposition, parse_stream, remove_streams,
 };
+pub use thumbnails::{create_contact_sheet, get_thumbnail, DEFAULT_THUMBNAIL_WIDTH};
+pub use verify::{check_output, decode_check, verify_output, ExpectedOutput};
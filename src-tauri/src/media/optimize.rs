@@ -0,0 +1,517 @@
+//! File size optimization advisor
+//!
+//! Turns stream info (and the cached bitrate analysis, when there is one) into
+//! quantified suggestions: dropping unneeded or duplicate audio tracks, and
+//! re-encoding video stored in an older codec. Savings are estimates from
+//! per-stream sizes, so they are only as good as the container's metadata.
+
+use log::{debug, info};
+use std::path::Path;
+
+use super::streams::get_media_streams;
+use crate::bitrate::{get_latest_analysis, parse_frame_rate, reference_bits_per_pixel};
+use crate::config;
+use crate::jobs::JobType;
+use crate::types::{
+    MediaStreams, OptimizationKind, OptimizationOptions, OptimizationReport,
+    OptimizationSuggestion, StreamInfo, StreamType,
+};
+
+/// Codec re-encodes are suggested to
+const TARGET_VIDEO_CODEC: &str = "hevc";
+
+/// Video codecs that HEVC stores meaningfully smaller at the same quality
+const LEGACY_VIDEO_CODECS: [&str; 7] = [
+    "h264",
+    "mpeg2video",
+    "mpeg1video",
+    "mpeg4",
+    "msmpeg4v3",
+    "vc1",
+    "wmv3",
+];
+
+/// Whether an audio codec is lossless
+///
+/// DTS-HD Master Audio is reported as codec `dts`; only its profile tells it
+/// apart from lossy DTS.
+pub fn is_lossless_audio(codec: &str, profile: Option<&str>) -> bool {
+    matches!(
+        codec,
+        "flac" | "alac" | "truehd" | "mlp" | "wavpack" | "tta" | "ape"
+    ) || codec.starts_with("pcm_")
+        || (codec == "dts" && profile == Some("DTS-HD MA"))
+}
+
+fn is_lossless_stream(stream: &StreamInfo) -> bool {
+    stream
+        .codec_name
+        .as_deref()
+        .is_some_and(|codec| is_lossless_audio(codec, stream.profile.as_deref()))
+}
+
+fn format_size(bytes: u64) -> String {
+    let gb = bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+    if gb >= 1.0 {
+        format!("{:.1} GB", gb)
+    } else {
+        format!("{:.0} MB", bytes as f64 / 1024.0 / 1024.0)
+    }
+}
+
+/// Size of a stream from its tags/bitrate, falling back to the file duration
+fn stream_size(stream: &StreamInfo, duration: f64) -> Option<u64> {
+    stream.estimated_size.or_else(|| {
+        let bit_rate = stream.bit_rate.as_deref()?.parse::<u64>().ok()?;
+        Some((bit_rate as f64 * duration / 8.0) as u64)
+    })
+}
+
+/// e.g. `truehd 7.1 (eng)`
+fn describe_audio(stream: &StreamInfo) -> String {
+    let mut text = stream
+        .codec_name
+        .clone()
+        .unwrap_or_else(|| "audio".to_string());
+    if let Some(layout) = stream.channel_layout.as_deref() {
+        text.push(' ');
+        text.push_str(layout.trim_end_matches("(side)"));
+    } else if let Some(channels) = stream.channels {
+        text.push_str(&format!(" {}ch", channels));
+    }
+    if let Some(language) = &stream.language {
+        text.push_str(&format!(" ({})", language));
+    }
+    text
+}
+
+fn video_codec_label(codec: &str) -> String {
+    match codec {
+        "h264" => "H.264".to_string(),
+        "hevc" => "HEVC".to_string(),
+        "mpeg2video" => "MPEG-2".to_string(),
+        "mpeg1video" => "MPEG-1".to_string(),
+        "mpeg4" | "msmpeg4v3" => "MPEG-4 ASP".to_string(),
+        "vc1" | "wmv3" => "VC-1".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn removal_suggestion(
+    kind: OptimizationKind,
+    title: String,
+    detail: String,
+    streams: &[(&StreamInfo, u64)],
+) -> OptimizationSuggestion {
+    let bytes: u64 = streams.iter().map(|(_, size)| size).sum();
+    let stream_indices: Vec<i32> = streams.iter().map(|(s, _)| s.index).collect();
+    OptimizationSuggestion {
+        kind,
+        title,
+        detail,
+        stream_indices,
+        estimated_savings_bytes: bytes,
+        estimated_savings_percent: 0.0,
+        job_type: Some(JobType::STREAM_REMOVAL_NAME.to_string()),
+    }
+}
+
+/// Lossy tracks in the same language as a lossless track, grouped per lossless track
+fn duplicate_audio_suggestions(
+    audio: &[&StreamInfo],
+    duration: f64,
+) -> Vec<OptimizationSuggestion> {
+    let mut suggestions = Vec::new();
+    let mut claimed: Vec<i32> = Vec::new();
+
+    for lossless in audio
+        .iter()
+        .filter(|s| !s.is_commentary && is_lossless_stream(s))
+    {
+        let duplicates: Vec<(&StreamInfo, u64)> = audio
+            .iter()
+            .filter(|s| {
+                s.index != lossless.index
+                    && !s.is_commentary
+                    && !claimed.contains(&s.index)
+                    && s.language == lossless.language
+                    && !is_lossless_stream(s)
+                    && s.channels.unwrap_or(0) <= lossless.channels.unwrap_or(0)
+            })
+            .filter_map(|s| Some((*s, stream_size(s, duration)?)))
+            .collect();
+        if duplicates.is_empty() {
+            continue;
+        }
+        claimed.extend(duplicates.iter().map(|(s, _)| s.index));
+
+        let names: Vec<String> = duplicates.iter().map(|(s, _)| describe_audio(s)).collect();
+        suggestions.push(removal_suggestion(
+            OptimizationKind::RemoveDuplicateAudio,
+            format!(
+                "Keep lossless {}, remove lossy duplicate{}",
+                describe_audio(lossless),
+                if duplicates.len() == 1 { "" } else { "s" }
+            ),
+            format!(
+                "{} carr{} the same audio at lower quality",
+                names.join(", "),
+                if duplicates.len() == 1 { "ies" } else { "y" }
+            ),
+            &duplicates,
+        ));
+    }
+    suggestions
+}
+
+/// Non-default audio outside the kept languages (never every audio track)
+fn extra_audio_suggestion(
+    audio: &[&StreamInfo],
+    already_removed: &[i32],
+    duration: f64,
+    options: &OptimizationOptions,
+) -> Option<OptimizationSuggestion> {
+    // Without a default track the first one is what players pick
+    let primary = audio
+        .iter()
+        .find(|s| s.is_default)
+        .or_else(|| audio.first())?
+        .index;
+
+    let removable: Vec<(&StreamInfo, u64)> = audio
+        .iter()
+        .filter(|s| {
+            s.index != primary
+                && !s.is_default
+                && !already_removed.contains(&s.index)
+                && !s.language.as_ref().is_some_and(|lang| {
+                    options
+                        .keep_languages
+                        .iter()
+                        .any(|keep| keep.eq_ignore_ascii_case(lang))
+                })
+        })
+        .filter_map(|s| Some((*s, stream_size(s, duration)?)))
+        .collect();
+    if removable.is_empty() {
+        return None;
+    }
+
+    let names: Vec<String> = removable.iter().map(|(s, _)| describe_audio(s)).collect();
+    Some(removal_suggestion(
+        OptimizationKind::RemoveAudioTracks,
+        format!(
+            "Remove {} non-default audio track{}",
+            removable.len(),
+            if removable.len() == 1 { "" } else { "s" }
+        ),
+        names.join(", "),
+        &removable,
+    ))
+}
+
+/// Re-encode suggestion for the main video stream
+///
+/// An efficient source keeps its quality at the codecs' reference ratio; a
+/// bloated one (above its codec's reference bits per pixel) is brought down
+/// to the target's reference rate.
+fn reencode_suggestion(
+    video: &StreamInfo,
+    video_bitrate: Option<u64>,
+    duration: f64,
+    options: &OptimizationOptions,
+) -> Option<OptimizationSuggestion> {
+    let codec = video.codec_name.as_deref()?;
+    if !LEGACY_VIDEO_CODECS.contains(&codec) {
+        return None;
+    }
+    let source_reference = reference_bits_per_pixel(codec).unwrap_or(0.20);
+    let target_reference = reference_bits_per_pixel(TARGET_VIDEO_CODEC)?;
+
+    let size = stream_size(video, duration);
+    let bitrate = video_bitrate.or_else(|| {
+        let size = size?;
+        (duration > 0.0).then(|| (size as f64 * 8.0 / duration) as u64)
+    })?;
+    let (width, height) = (video.width?, video.height?);
+    let fps = video.frame_rate.as_deref().and_then(parse_frame_rate)?;
+    if width <= 0 || height <= 0 || bitrate == 0 {
+        return None;
+    }
+
+    let bpp = bitrate as f64 / (width as f64 * height as f64 * fps);
+    let target_bpp = target_reference * (bpp / source_reference).min(1.0);
+    let savings = 1.0 - target_bpp / bpp;
+    if savings < options.min_reencode_savings {
+        return None;
+    }
+
+    let video_bytes = size.unwrap_or((bitrate as f64 * duration / 8.0) as u64);
+    Some(OptimizationSuggestion {
+        kind: OptimizationKind::ReencodeVideo,
+        title: format!(
+            "{} at {:.1} Mbps {}p: re-encode to {} (~{:.0}% smaller)",
+            video_codec_label(codec),
+            bitrate as f64 / 1_000_000.0,
+            height,
+            video_codec_label(TARGET_VIDEO_CODEC),
+            savings * 100.0
+        ),
+        detail: format!(
+            "{:.3} bits per pixel against a {:.3} reference for {}",
+            bpp,
+            source_reference,
+            video_codec_label(codec)
+        ),
+        stream_indices: vec![video.index],
+        estimated_savings_bytes: (video_bytes as f64 * savings) as u64,
+        estimated_savings_percent: 0.0,
+        // No re-encode job yet; the suggestion is informational
+        job_type: None,
+    })
+}
+
+/// Build suggestions from stream info, largest savings first
+///
+/// `video_bitrate` is the measured average of the main video stream, if known.
+pub fn suggest_optimizations(
+    streams: &MediaStreams,
+    video_bitrate: Option<u64>,
+    options: &OptimizationOptions,
+) -> Vec<OptimizationSuggestion> {
+    let audio: Vec<&StreamInfo> = streams
+        .streams
+        .iter()
+        .filter(|s| s.stream_type == StreamType::Audio)
+        .collect();
+
+    let mut suggestions = duplicate_audio_suggestions(&audio, streams.duration);
+    let duplicates: Vec<i32> = suggestions
+        .iter()
+        .flat_map(|s| s.stream_indices.iter().copied())
+        .collect();
+    suggestions.extend(extra_audio_suggestion(
+        &audio,
+        &duplicates,
+        streams.duration,
+        options,
+    ));
+
+    if let Some(video) = streams
+        .streams
+        .iter()
+        .find(|s| s.stream_type == StreamType::Video && !s.is_cover_art)
+    {
+        suggestions.extend(reencode_suggestion(
+            video,
+            video_bitrate,
+            streams.duration,
+            options,
+        ));
+    }
+
+    suggestions.retain(|s| s.estimated_savings_bytes >= options.min_savings_bytes);
+    for suggestion in &mut suggestions {
+        if streams.total_size > 0 {
+            suggestion.estimated_savings_percent =
+                suggestion.estimated_savings_bytes as f64 / streams.total_size as f64 * 100.0;
+        }
+        suggestion.title = format!(
+            "{}: -{}",
+            suggestion.title,
+            format_size(suggestion.estimated_savings_bytes)
+        );
+    }
+    suggestions.sort_by_key(|s| std::cmp::Reverse(s.estimated_savings_bytes));
+    suggestions
+}
+
+/// Suggest ways to shrink a media file
+///
+/// Uses the cached overall bitrate analysis for the video bitrate when the
+/// file has been analyzed, and container metadata otherwise.
+pub fn analyze_optimizations(
+    path: &str,
+    options: &OptimizationOptions,
+) -> Result<OptimizationReport, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    let streams = get_media_streams(path.to_string())?;
+    let video_bitrate = streams
+        .streams
+        .iter()
+        .find(|s| s.stream_type == StreamType::Video && !s.is_cover_art)
+        .and_then(|video| {
+            let analysis = get_latest_analysis(path)?;
+            let contribution = analysis
+                .stream_contributions
+                .iter()
+                .find(|c| c.stream_index == video.index)?;
+            let count = contribution.data_points.len() as u64;
+            (count > 0).then(|| {
+                contribution
+                    .data_points
+                    .iter()
+                    .map(|p| p.bitrate)
+                    .sum::<u64>()
+                    / count
+            })
+        });
+    debug!(
+        "analyze_optimizations: path={}, measured video bitrate={:?}",
+        path, video_bitrate
+    );
+
+    let suggestions = suggest_optimizations(&streams, video_bitrate, options);
+    let total_savings_bytes = suggestions.iter().map(|s| s.estimated_savings_bytes).sum();
+    info!(
+        "Optimization advisor for {}: {} suggestions, ~{} saved",
+        path,
+        suggestions.len(),
+        format_size(total_savings_bytes)
+    );
+
+    Ok(OptimizationReport {
+        path: path.to_string(),
+        file_size: streams.total_size,
+        suggestions,
+        total_savings_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::parse_stream;
+    use serde_json::json;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn audio(
+        index: i32,
+        codec: &str,
+        channels: i32,
+        lang: &str,
+        default: bool,
+        size: u64,
+    ) -> StreamInfo {
+        parse_stream(&json!({
+            "index": index,
+            "codec_type": "audio",
+            "codec_name": codec,
+            "channels": channels,
+            "disposition": { "default": if default { 1 } else { 0 } },
+            "tags": { "language": lang, "NUMBER_OF_BYTES": size.to_string() }
+        }))
+    }
+
+    fn video(codec: &str, bit_rate: u64) -> StreamInfo {
+        parse_stream(&json!({
+            "index": 0,
+            "codec_type": "video",
+            "codec_name": codec,
+            "width": 1920,
+            "height": 1080,
+            "r_frame_rate": "24/1",
+            "bit_rate": bit_rate.to_string(),
+            "disposition": { "default": 1 }
+        }))
+    }
+
+    fn media(streams: Vec<StreamInfo>) -> MediaStreams {
+        MediaStreams {
+            path: "/movie.mkv".to_string(),
+            video_count: 0,
+            audio_count: 0,
+            subtitle_count: 0,
+            attachment_count: 0,
+            total_size: 20 * GB,
+            duration: 7200.0,
+            streams,
+        }
+    }
+
+    #[test]
+    fn test_extra_audio_tracks_respect_kept_languages() {
+        let streams = media(vec![
+            audio(1, "ac3", 6, "eng", true, GB),
+            audio(2, "ac3", 6, "fra", false, GB),
+            audio(3, "ac3", 6, "deu", false, GB),
+            audio(4, "ac3", 6, "jpn", false, GB),
+        ]);
+        let options = OptimizationOptions {
+            keep_languages: vec!["JPN".to_string()],
+            ..Default::default()
+        };
+        let suggestions = suggest_optimizations(&streams, None, &options);
+        assert_eq!(suggestions.len(), 1);
+        let s = &suggestions[0];
+        assert_eq!(s.kind, OptimizationKind::RemoveAudioTracks);
+        assert_eq!(s.stream_indices, vec![2, 3]);
+        assert_eq!(s.estimated_savings_bytes, 2 * GB);
+        assert_eq!(s.title, "Remove 2 non-default audio tracks: -2.0 GB");
+        assert_eq!(s.job_type.as_deref(), Some("stream_removal"));
+        assert_eq!(s.estimated_savings_percent, 10.0);
+    }
+
+    #[test]
+    fn test_lossy_duplicate_of_lossless_track() {
+        let streams = media(vec![
+            audio(1, "flac", 8, "eng", true, 4 * GB),
+            audio(2, "dts", 6, "eng", false, GB + GB / 2),
+            audio(3, "ac3", 2, "fra", false, GB / 2),
+        ]);
+        let suggestions = suggest_optimizations(&streams, None, &OptimizationOptions::default());
+        assert_eq!(suggestions.len(), 2);
+        // Largest first: the duplicate, then the remaining foreign track
+        assert_eq!(suggestions[0].kind, OptimizationKind::RemoveDuplicateAudio);
+        assert_eq!(suggestions[0].stream_indices, vec![2]);
+        assert!(suggestions[0].title.starts_with("Keep lossless flac"));
+        assert_eq!(suggestions[1].stream_indices, vec![3]);
+    }
+
+    #[test]
+    fn test_dts_hd_ma_is_lossless() {
+        assert!(is_lossless_audio("dts", Some("DTS-HD MA")));
+        assert!(!is_lossless_audio("dts", Some("DTS-HD HRA")));
+        assert!(!is_lossless_audio("dts", None));
+
+        let mut master = audio(1, "dts", 8, "eng", true, 4 * GB);
+        master.profile = Some("DTS-HD MA".to_string());
+        let mut core = audio(2, "dts", 6, "eng", false, GB + GB / 2);
+        core.profile = Some("DTS".to_string());
+        let streams = media(vec![master, core]);
+        let suggestions = suggest_optimizations(&streams, None, &OptimizationOptions::default());
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].kind, OptimizationKind::RemoveDuplicateAudio);
+        assert_eq!(suggestions[0].stream_indices, vec![2]);
+        assert!(suggestions[0].title.starts_with("Keep lossless dts"));
+    }
+
+    #[test]
+    fn test_reencode_bloated_h264() {
+        // 18 Mbps 1080p24 is ~0.36 bits per pixel
+        let streams = media(vec![video("h264", 18_000_000)]);
+        let suggestions = suggest_optimizations(&streams, None, &OptimizationOptions::default());
+        assert_eq!(suggestions.len(), 1);
+        let s = &suggestions[0];
+        assert_eq!(s.kind, OptimizationKind::ReencodeVideo);
+        assert!(s
+            .title
+            .starts_with("H.264 at 18.0 Mbps 1080p: re-encode to HEVC (~83% smaller)"));
+        assert!(s.job_type.is_none());
+
+        // Already HEVC: nothing to suggest
+        let streams = media(vec![video("hevc", 18_000_000)]);
+        assert!(suggest_optimizations(&streams, None, &OptimizationOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_single_audio_track_is_kept() {
+        let streams = media(vec![audio(1, "ac3", 6, "fra", false, GB)]);
+        assert!(suggest_optimizations(&streams, None, &OptimizationOptions::default()).is_empty());
+    }
+}
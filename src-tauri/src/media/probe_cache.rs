@@ -76,6 +76,7 @@ fn run_ffprobe(path: &str) -> Result<String, String> {
             "json",
             "-show_format",
             "-show_streams",
+            "-show_chapters",
             path,
         ])
         .output()
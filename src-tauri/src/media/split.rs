@@ -0,0 +1,564 @@
+//! Lossless trimming and splitting at keyframes
+//!
+//! This module handles:
+//! - Turning a split request (trim range, explicit points, chapters, duration, size)
+//!   into requested cut points
+//! - Snapping cut points to the nearest video keyframe so stream copy cuts are clean
+//! - Previewing the resulting segments with their actual times
+//! - Writing the segments with `ffmpeg -ss ... -t ... -c copy`
+
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::disk_space::ensure_disk_space;
+use super::ffmpeg::{run_ffmpeg, CANCELLED_MESSAGE};
+use super::streams::{get_chapters, get_media_streams};
+use crate::bitrate::{parse_ffprobe_packets_limited, PACKET_SCAN_TIMEOUT};
+use crate::config;
+use crate::types::{
+    ChapterInfo, CutPoint, SegmentPreview, SplitMode, SplitPreview, SplitResult, StreamType,
+};
+
+/// Segments shorter than this (in seconds) are dropped after snapping
+const MIN_SEGMENT_SECS: f64 = 0.05;
+
+/// Slowest read rate (bytes/s) assumed when timing out a keyframe scan that
+/// can't be cancelled (slow disks and network shares)
+const MIN_SCAN_BYTES_PER_SEC: u64 = 10 * 1024 * 1024;
+
+/// A segment range: (start seconds, end seconds, optional title)
+type SegmentRange = (f64, f64, Option<String>);
+
+/// Timeout for scanning every packet of a `size`-byte file
+fn keyframe_scan_timeout(size: u64) -> Duration {
+    PACKET_SCAN_TIMEOUT.max(Duration::from_secs(size / MIN_SCAN_BYTES_PER_SEC))
+}
+
+/// Get sorted keyframe timestamps for a video stream
+///
+/// Uses packet flags from ffprobe (packets flagged `K` are reported as "I").
+/// With a cancel flag the scan runs until it finishes or is cancelled;
+/// without one it times out based on the file size (`size`).
+pub fn get_keyframe_times(
+    path: &str,
+    stream_index: i32,
+    size: u64,
+    cancelled: Option<&AtomicBool>,
+) -> Result<Vec<f64>, String> {
+    let timeout = match cancelled {
+        Some(_) => None,
+        None => Some(keyframe_scan_timeout(size)),
+    };
+    let packets = parse_ffprobe_packets_limited(path, stream_index, timeout, cancelled)?;
+    let mut keyframes: Vec<f64> = packets
+        .into_iter()
+        .filter(|(_, _, frame_type)| frame_type.as_deref() == Some("I"))
+        .map(|(timestamp, _, _)| timestamp)
+        .collect();
+    keyframes.sort_by(|a, b| a.total_cmp(b));
+    keyframes.dedup();
+    Ok(keyframes)
+}
+
+/// Snap a timestamp to the nearest keyframe (ties go to the earlier keyframe)
+///
+/// Returns the timestamp unchanged when there are no keyframes (e.g. audio-only files,
+/// where every packet can start a segment).
+pub fn snap_to_keyframe(keyframes: &[f64], timestamp: f64) -> f64 {
+    if keyframes.is_empty() {
+        return timestamp;
+    }
+
+    let pos = keyframes.partition_point(|&k| k < timestamp);
+    let after = keyframes.get(pos).copied();
+    let before = if pos > 0 {
+        keyframes.get(pos - 1).copied()
+    } else {
+        None
+    };
+
+    match (before, after) {
+        (Some(b), Some(a)) => {
+            if timestamp - b <= a - timestamp {
+                b
+            } else {
+                a
+            }
+        }
+        (Some(b), None) => b,
+        (None, Some(a)) => a,
+        (None, None) => timestamp,
+    }
+}
+
+/// Build the requested (unsnapped) segment ranges for a split mode
+///
+/// Returns `(start, end, title)` tuples covering the requested parts of the file.
+pub fn requested_ranges(
+    mode: &SplitMode,
+    duration: f64,
+    file_size: u64,
+    chapters: &[ChapterInfo],
+) -> Result<Vec<SegmentRange>, String> {
+    if duration <= 0.0 {
+        return Err("Could not determine duration".to_string());
+    }
+
+    let boundaries_to_ranges = |mut points: Vec<f64>| {
+        points.retain(|p| *p > 0.0 && *p < duration);
+        points.sort_by(|a, b| a.total_cmp(b));
+        points.dedup();
+
+        let mut ranges = Vec::with_capacity(points.len() + 1);
+        let mut start = 0.0;
+        for point in points {
+            ranges.push((start, point, None));
+            start = point;
+        }
+        ranges.push((start, duration, None));
+        ranges
+    };
+
+    match mode {
+        SplitMode::Trim { start, end } => {
+            let start = start.unwrap_or(0.0).max(0.0);
+            let end = end.unwrap_or(duration).min(duration);
+            if end <= start {
+                return Err(format!(
+                    "Trim end ({:.3}s) must be after start ({:.3}s)",
+                    end, start
+                ));
+            }
+            Ok(vec![(start, end, None)])
+        }
+        SplitMode::Points { points } => {
+            if points.is_empty() {
+                return Err("No split points provided".to_string());
+            }
+            Ok(boundaries_to_ranges(points.clone()))
+        }
+        SplitMode::Chapters => {
+            if chapters.is_empty() {
+                return Err("File has no chapters".to_string());
+            }
+            Ok(chapters
+                .iter()
+                .map(|c| {
+                    (
+                        c.start_time.max(0.0),
+                        c.end_time.min(duration),
+                        c.title.clone(),
+                    )
+                })
+                .filter(|(start, end, _)| end > start)
+                .collect())
+        }
+        SplitMode::Duration { segment_seconds } => {
+            if *segment_seconds <= 0.0 {
+                return Err("Segment duration must be greater than zero".to_string());
+            }
+            let count = (duration / segment_seconds).ceil() as usize;
+            let points = (1..count).map(|i| i as f64 * segment_seconds).collect();
+            Ok(boundaries_to_ranges(points))
+        }
+        SplitMode::Size { segment_bytes } => {
+            if *segment_bytes == 0 || file_size == 0 {
+                return Err("Segment size must be greater than zero".to_string());
+            }
+            // Estimate segment length from the average byte rate of the whole file
+            let bytes_per_second = file_size as f64 / duration;
+            let segment_seconds = *segment_bytes as f64 / bytes_per_second;
+            let count = (file_size as f64 / *segment_bytes as f64).ceil() as usize;
+            let points = (1..count).map(|i| i as f64 * segment_seconds).collect();
+            Ok(boundaries_to_ranges(points))
+        }
+    }
+}
+
+/// Snap requested ranges to keyframes
+///
+/// Range starts are snapped to the nearest keyframe; range ends are snapped too unless
+/// they are the end of the file. Ranges that collapse after snapping are dropped.
+pub fn snap_ranges(
+    ranges: &[SegmentRange],
+    keyframes: &[f64],
+    duration: f64,
+) -> (Vec<SegmentRange>, Vec<CutPoint>) {
+    let mut cut_points: Vec<CutPoint> = Vec::new();
+    let mut snap = |requested: f64| -> f64 {
+        if requested <= 0.0 || requested >= duration {
+            return requested.clamp(0.0, duration);
+        }
+        let actual = snap_to_keyframe(keyframes, requested);
+        if !cut_points.iter().any(|c| c.requested == requested) {
+            cut_points.push(CutPoint { requested, actual });
+        }
+        actual
+    };
+
+    let snapped = ranges
+        .iter()
+        .map(|(start, end, title)| (snap(*start), snap(*end), title.clone()))
+        .filter(|(start, end, _)| end - start >= MIN_SEGMENT_SECS)
+        .collect();
+
+    (snapped, cut_points)
+}
+
+/// Build the output path for a segment
+fn segment_output_path(input: &Path, mode: &SplitMode, index: usize) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    let ext = input.extension().and_then(|e| e.to_str()).unwrap_or("mkv");
+    let parent = input.parent().unwrap_or(Path::new("."));
+
+    match mode {
+        SplitMode::Trim { .. } => parent.join(format!("{}_trimmed.{}", stem, ext)),
+        _ => parent.join(format!("{}_part{:03}.{}", stem, index + 1, ext)),
+    }
+}
+
+/// Preview a split: requested vs actual cut points and resulting segments
+///
+/// `cancelled` lets a split job stop the keyframe scan (see `get_keyframe_times`).
+pub fn preview_split(
+    path: &str,
+    mode: &SplitMode,
+    cancelled: Option<&AtomicBool>,
+) -> Result<SplitPreview, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    let streams = get_media_streams(path.to_string())?;
+    let duration = streams.duration;
+    let chapters = match mode {
+        SplitMode::Chapters => get_chapters(path)?,
+        _ => Vec::new(),
+    };
+
+    let ranges = requested_ranges(mode, duration, streams.total_size, &chapters)?;
+
+    // Use the first real video stream for keyframes; audio-only files need no snapping
+    let keyframes = match streams
+        .streams
+        .iter()
+        .find(|s| s.stream_type == StreamType::Video && !s.is_cover_art)
+    {
+        Some(video) => get_keyframe_times(path, video.index, streams.total_size, cancelled)?,
+        None => Vec::new(),
+    };
+    debug!(
+        "preview_split: {} requested ranges, {} keyframes",
+        ranges.len(),
+        keyframes.len()
+    );
+
+    let (snapped, cut_points) = snap_ranges(&ranges, &keyframes, duration);
+    if snapped.is_empty() {
+        return Err("No segments left after snapping to keyframes".to_string());
+    }
+
+    let bytes_per_second = if duration > 0.0 {
+        streams.total_size as f64 / duration
+    } else {
+        0.0
+    };
+
+    let segments = snapped
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, end, title))| {
+            let output = segment_output_path(&validated_path, mode, index);
+            SegmentPreview {
+                index,
+                start_time: start,
+                end_time: end,
+                duration: end - start,
+                estimated_size: ((end - start) * bytes_per_second) as u64,
+                conflict: output.exists(),
+                output_path: output.to_string_lossy().to_string(),
+                title,
+            }
+        })
+        .collect();
+
+    Ok(SplitPreview {
+        path: path.to_string(),
+        duration,
+        keyframe_count: keyframes.len(),
+        cut_points,
+        segments,
+    })
+}
+
+/// Build ffmpeg arguments for writing a single segment with stream copy
+fn segment_args(input: &str, segment: &SegmentPreview, file_duration: f64) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    if segment.start_time > 0.0 {
+        args.extend(["-ss".to_string(), format!("{:.6}", segment.start_time)]);
+    }
+    args.extend(["-i".to_string(), input.to_string()]);
+    if segment.end_time < file_duration {
+        args.extend(["-t".to_string(), format!("{:.6}", segment.duration)]);
+    }
+    args.extend([
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-avoid_negative_ts".to_string(),
+        "make_zero".to_string(),
+    ]);
+    if let Some(title) = &segment.title {
+        args.extend(["-metadata".to_string(), format!("title={}", title)]);
+    }
+    args.push(segment.output_path.clone());
+    args
+}
+
+/// Split or trim a media file losslessly
+///
+/// `on_progress` receives the overall percentage and a stage description.
+pub fn split_media(
+    path: &str,
+    mode: &SplitMode,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<SplitResult, String> {
+    on_progress(0.0, "Locating keyframes...");
+    let preview = preview_split(path, mode, Some(cancelled))?;
+
+    if let Some(conflict) = preview.segments.iter().find(|s| s.conflict) {
+        return Err(format!("Output already exists: {}", conflict.output_path));
+    }
+    if let Some(dir) = preview
+        .segments
+        .first()
+        .and_then(|s| Path::new(&s.output_path).parent())
+    {
+        let required = preview.segments.iter().map(|s| s.estimated_size).sum();
+        ensure_disk_space(dir, required)?;
+    }
+
+    let total = preview.segments.len();
+    let mut written: Vec<String> = Vec::new();
+
+    for segment in &preview.segments {
+        if cancelled.load(Ordering::SeqCst) {
+            cleanup_outputs(&written);
+            return Err(CANCELLED_MESSAGE.to_string());
+        }
+
+        let stage = format!(
+            "Writing segment {}/{} ({:.2}s - {:.2}s)",
+            segment.index + 1,
+            total,
+            segment.start_time,
+            segment.end_time
+        );
+        let base = segment.index as f64 / total as f64 * 100.0;
+        let args = segment_args(path, segment, preview.duration);
+
+        let result = run_ffmpeg(&args, Some(segment.duration), Some(cancelled), &mut |pct| {
+            on_progress(base + pct / total as f64, &stage)
+        });
+
+        // Track the output before checking the result so failures clean it up
+        written.push(segment.output_path.clone());
+        if let Err(e) = result {
+            warn!("Segment {} failed for {}: {}", segment.index + 1, path, e);
+            cleanup_outputs(&written);
+            return Err(e);
+        }
+    }
+
+    info!("Split {} into {} segment(s)", path, written.len());
+    on_progress(100.0, "Complete");
+
+    let message = match mode {
+        SplitMode::Trim { .. } => format!(
+            "Trimmed to {:.2}s - {:.2}s. Output saved to: {}",
+            preview.segments[0].start_time, preview.segments[0].end_time, written[0]
+        ),
+        _ => format!("Split into {} segment(s)", written.len()),
+    };
+
+    Ok(SplitResult {
+        success: true,
+        output_paths: written,
+        message,
+    })
+}
+
+/// Remove outputs written by an aborted split
+fn cleanup_outputs(paths: &[String]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframe_scan_timeout_scales_with_size() {
+        assert_eq!(keyframe_scan_timeout(0), PACKET_SCAN_TIMEOUT);
+        assert_eq!(
+            keyframe_scan_timeout(50 * 1024 * 1024 * 1024),
+            Duration::from_secs(5120)
+        );
+    }
+
+    // ========== snap_to_keyframe tests ==========
+
+    #[test]
+    fn test_snap_no_keyframes() {
+        assert_eq!(snap_to_keyframe(&[], 12.5), 12.5);
+    }
+
+    #[test]
+    fn test_snap_nearest() {
+        let keyframes = vec![0.0, 2.0, 4.0, 6.0];
+        assert_eq!(snap_to_keyframe(&keyframes, 2.9), 2.0);
+        assert_eq!(snap_to_keyframe(&keyframes, 3.1), 4.0);
+        assert_eq!(snap_to_keyframe(&keyframes, 4.0), 4.0);
+    }
+
+    #[test]
+    fn test_snap_tie_prefers_earlier() {
+        let keyframes = vec![0.0, 2.0, 4.0];
+        assert_eq!(snap_to_keyframe(&keyframes, 3.0), 2.0);
+    }
+
+    #[test]
+    fn test_snap_outside_range() {
+        let keyframes = vec![1.0, 5.0];
+        assert_eq!(snap_to_keyframe(&keyframes, 0.2), 1.0);
+        assert_eq!(snap_to_keyframe(&keyframes, 9.0), 5.0);
+    }
+
+    // ========== requested_ranges tests ==========
+
+    #[test]
+    fn test_ranges_trim_defaults() {
+        let mode = SplitMode::Trim {
+            start: Some(30.0),
+            end: None,
+        };
+        let ranges = requested_ranges(&mode, 100.0, 1000, &[]).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].0, 30.0);
+        assert_eq!(ranges[0].1, 100.0);
+    }
+
+    #[test]
+    fn test_ranges_trim_invalid() {
+        let mode = SplitMode::Trim {
+            start: Some(50.0),
+            end: Some(10.0),
+        };
+        assert!(requested_ranges(&mode, 100.0, 1000, &[]).is_err());
+    }
+
+    #[test]
+    fn test_ranges_points_sorted_and_filtered() {
+        let mode = SplitMode::Points {
+            points: vec![60.0, 20.0, 20.0, 150.0, 0.0],
+        };
+        let ranges = requested_ranges(&mode, 100.0, 1000, &[]).unwrap();
+        let bounds: Vec<(f64, f64)> = ranges.iter().map(|r| (r.0, r.1)).collect();
+        assert_eq!(bounds, vec![(0.0, 20.0), (20.0, 60.0), (60.0, 100.0)]);
+    }
+
+    #[test]
+    fn test_ranges_duration() {
+        let mode = SplitMode::Duration {
+            segment_seconds: 40.0,
+        };
+        let ranges = requested_ranges(&mode, 100.0, 1000, &[]).unwrap();
+        let bounds: Vec<(f64, f64)> = ranges.iter().map(|r| (r.0, r.1)).collect();
+        assert_eq!(bounds, vec![(0.0, 40.0), (40.0, 80.0), (80.0, 100.0)]);
+    }
+
+    #[test]
+    fn test_ranges_size_uses_average_rate() {
+        // 1000 bytes over 100s = 10 B/s, so 250 bytes ~= 25s
+        let mode = SplitMode::Size { segment_bytes: 250 };
+        let ranges = requested_ranges(&mode, 100.0, 1000, &[]).unwrap();
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[1].0, 25.0);
+    }
+
+    #[test]
+    fn test_ranges_chapters() {
+        let chapters = vec![
+            ChapterInfo {
+                index: 0,
+                start_time: 0.0,
+                end_time: 45.0,
+                title: Some("Intro".to_string()),
+            },
+            ChapterInfo {
+                index: 1,
+                start_time: 45.0,
+                end_time: 100.0,
+                title: None,
+            },
+        ];
+        let ranges = requested_ranges(&SplitMode::Chapters, 100.0, 1000, &chapters).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].2, Some("Intro".to_string()));
+        assert!(requested_ranges(&SplitMode::Chapters, 100.0, 1000, &[]).is_err());
+    }
+
+    // ========== snap_ranges tests ==========
+
+    #[test]
+    fn test_snap_ranges_reports_actual_times() {
+        let ranges = vec![(0.0, 21.0, None), (21.0, 100.0, None)];
+        let keyframes = vec![0.0, 10.0, 20.0, 30.0];
+        let (snapped, cuts) = snap_ranges(&ranges, &keyframes, 100.0);
+        assert_eq!(snapped[0].1, 20.0);
+        assert_eq!(snapped[1].0, 20.0);
+        assert_eq!(snapped[1].1, 100.0); // end of file is not snapped
+        assert_eq!(cuts.len(), 1);
+        assert_eq!(cuts[0].requested, 21.0);
+        assert_eq!(cuts[0].actual, 20.0);
+    }
+
+    #[test]
+    fn test_snap_ranges_drops_collapsed_segments() {
+        let ranges = vec![(0.0, 11.0, None), (11.0, 12.0, None), (12.0, 50.0, None)];
+        let keyframes = vec![0.0, 10.0, 30.0];
+        let (snapped, _) = snap_ranges(&ranges, &keyframes, 50.0);
+        assert_eq!(snapped.len(), 2);
+        assert_eq!((snapped[0].0, snapped[0].1), (0.0, 10.0));
+        assert_eq!((snapped[1].0, snapped[1].1), (10.0, 50.0));
+    }
+
+    #[test]
+    fn test_segment_args_last_segment_has_no_duration() {
+        let segment = SegmentPreview {
+            index: 1,
+            start_time: 20.0,
+            end_time: 100.0,
+            duration: 80.0,
+            estimated_size: 0,
+            output_path: "/tmp/out_part002.mkv".to_string(),
+            title: None,
+            conflict: false,
+        };
+        let args = segment_args("/tmp/in.mkv", &segment, 100.0);
+        assert!(args.contains(&"-ss".to_string()));
+        assert!(!args.contains(&"-t".to_string()));
+        assert!(args.contains(&"copy".to_string()));
+    }
+}
@@ -3,17 +3,27 @@
 //! This module handles:
 //! - Finding ffmpeg/ffprobe commands
 //! - Parsing stream information from media files
-//! - Removing streams from media files
+//! - Removing streams from media files (with a free-space preflight and output verification;
+//!   overwriting keeps an undo backup of the original)
 
 use log::debug;
 use serde_json;
 use std::fs;
 use std::path::Path;
-use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use super::disk_space::{
+    ensure_disk_space, estimate_removal_output_size, SpaceMonitor, MIN_FREE_DURING_WRITE_BYTES,
+};
+use super::ffmpeg::run_ffmpeg;
 use super::probe_cache;
+use super::verify::{verify_output, ExpectedOutput};
 use crate::config;
-use crate::types::{MediaStreams, StreamInfo, StreamRemovalResult, StreamType};
+use crate::journal;
+use crate::types::{
+    ChapterInfo, JournalChange, JournalOperationKind, MediaStreams, StreamInfo,
+    StreamRemovalResult, StreamType,
+};
 
 /// Get common search paths for finding executables
 pub fn get_search_paths() -> Vec<String> {
@@ -258,6 +268,10 @@ pub fn parse_stream(stream: &serde_json::Value) -> StreamInfo {
             .get("codec_long_name")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
+        profile: stream
+            .get("profile")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
         language,
         title,
         is_default,
@@ -305,6 +319,45 @@ pub fn parse_stream(stream: &serde_json::Value) -> StreamInfo {
     }
 }
 
+/// Parse chapters from ffprobe JSON output (requires `-show_chapters`)
+pub fn parse_chapters(data: &serde_json::Value) -> Vec<ChapterInfo> {
+    data.get("chapters")
+        .and_then(|c| c.as_array())
+        .map(|arr| {
+            arr.iter()
+                .enumerate()
+                .filter_map(|(index, chapter)| {
+                    let start_time = chapter
+                        .get("start_time")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| s.parse::<f64>().ok())?;
+                    let end_time = chapter
+                        .get("end_time")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| s.parse::<f64>().ok())?;
+                    let title = chapter
+                        .get("tags")
+                        .and_then(|t| t.get("title"))
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string());
+                    Some(ChapterInfo {
+                        index,
+                        start_time,
+                        end_time,
+                        title,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get chapters of a media file (uses the cached ffprobe result)
+pub fn get_chapters(path: &str) -> Result<Vec<ChapterInfo>, String> {
+    let data = probe_cache::get_probe_json(path)?;
+    Ok(parse_chapters(&data))
+}
+
 /// Get all media streams from a file using ffprobe
 ///
 /// This function now uses the probe_cache module to avoid redundant ffprobe calls
@@ -369,10 +422,15 @@ pub fn get_media_streams(path: String) -> Result<MediaStreams, String> {
 }
 
 /// Remove specified streams from a media file using ffmpeg
+///
+/// Refuses to start when the estimated output won't fit on the target
+/// filesystem, and aborts (removing the partial output) if free space runs
+/// out while writing.
 pub fn remove_streams(
     path: String,
     stream_indices: Vec<i32>,
     overwrite: bool,
+    cancelled: Option<&AtomicBool>,
 ) -> Result<StreamRemovalResult, String> {
     let file_path = Path::new(&path);
 
@@ -387,8 +445,6 @@ pub fn remove_streams(
         return Err("No streams selected for removal".to_string());
     }
 
-    let ffmpeg_cmd = find_command("ffmpeg").unwrap_or_else(|| "ffmpeg".to_string());
-
     // Create output path - either temp file for overwrite or _modified suffix
     let stem = validated_path
         .file_stem()
@@ -413,6 +469,10 @@ pub fn remove_streams(
     let streams_result = get_media_streams(path.clone())?;
     let total_streams = streams_result.streams.len();
 
+    // The output is written in full next to the original either way
+    let required = estimate_removal_output_size(&streams_result, &stream_indices);
+    ensure_disk_space(parent, required)?;
+
     // Build ffmpeg arguments
     let mut args: Vec<String> = vec![
         "-i".to_string(),
@@ -437,18 +497,34 @@ pub fn remove_streams(
         temp_path.to_string_lossy().to_string(),
     ]);
 
-    let output = Command::new(&ffmpeg_cmd)
-        .args(&args)
-        .output()
-        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
-
-    if !output.status.success() {
-        // Clean up temp file on failure
-        if overwrite {
-            let _ = fs::remove_file(&temp_path);
+    // Abort on cancellation or when the disk is about to fill up
+    let abort = AtomicBool::new(false);
+    let mut monitor = SpaceMonitor::new(parent, MIN_FREE_DURING_WRITE_BYTES);
+    let result = run_ffmpeg(
+        &args,
+        Some(streams_result.duration),
+        Some(&abort),
+        &mut |_| {
+            if cancelled.is_some_and(|c| c.load(Ordering::SeqCst)) || monitor.is_exhausted() {
+                abort.store(true, Ordering::SeqCst);
+            }
+        },
+    );
+
+    if let Err(e) = result {
+        // Never leave a partial output behind
+        let _ = fs::remove_file(&temp_path);
+        if monitor.is_exhausted() {
+            return Err(monitor.error_message());
         }
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffmpeg failed: {}", stderr));
+        return Err(e);
+    }
+
+    // Re-probe (and optionally decode) the output before trusting it
+    let expected = ExpectedOutput::after_removal(&streams_result, &stream_indices);
+    if let Err(e) = verify_output(&temp_path, &expected, cancelled) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
     }
 
     // If overwriting, replace original with temp file
@@ -478,12 +554,28 @@ pub fn remove_streams(
             ));
         }
 
-        // All checks passed, safe to replace
-        fs::remove_file(&path).map_err(|e| {
-            let _ = fs::remove_file(&temp_path);
-            format!("Failed to remove original file: {}", e)
-        })?;
-        fs::rename(&temp_path, &path).map_err(|e| format!("Failed to rename temp file: {}", e))?;
+        // All checks passed, safe to replace (the original is kept for undo)
+        let backup =
+            journal::replace_with_backup(&validated_path, &temp_path).inspect_err(|_| {
+                let _ = fs::remove_file(&temp_path);
+            })?;
+        if let Some(backup) = backup {
+            journal::record(
+                JournalOperationKind::StreamRemoval,
+                format!(
+                    "Remove {} stream(s) from '{}'",
+                    stream_indices.len(),
+                    validated_path
+                        .file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default()
+                ),
+                vec![JournalChange::Rewritten {
+                    path: validated_path.to_string_lossy().to_string(),
+                    backup: backup.to_string_lossy().to_string(),
+                }],
+            );
+        }
 
         // Invalidate probe cache since the file has been modified
         probe_cache::invalidate_cache(&path);
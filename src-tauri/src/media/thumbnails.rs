@@ -0,0 +1,475 @@
+//! Thumbnails and contact sheets
+//!
+//! This module handles:
+//! - Extracting a representative (non-black) frame as a thumbnail
+//! - Caching thumbnails on disk keyed by `compute_file_hash`
+//! - Building contact sheets: a grid of frames with timestamps and a
+//!   codec/resolution/bitrate header from the probe data
+
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::ffmpeg::{run_ffmpeg, CANCELLED_MESSAGE};
+use super::probe_cache;
+use crate::bitrate::compute_file_hash;
+use crate::config;
+use crate::types::{ContactSheetOptions, ContactSheetResult, ThumbnailResult};
+
+/// Default thumbnail width in pixels
+pub const DEFAULT_THUMBNAIL_WIDTH: u32 = 320;
+
+/// Frames with more than this percentage of black pixels are skipped
+const BLACK_PIXEL_PERCENT: u32 = 90;
+
+/// Number of frames the `thumbnail` filter compares to pick a representative one
+const THUMBNAIL_BATCH_FRAMES: u32 = 50;
+
+/// Height of the contact sheet header in pixels
+const HEADER_HEIGHT: u32 = 48;
+
+/// Escape a value for use as a filter option inside a filtergraph
+///
+/// Escapes `\`, `:` and `'` for the option parser, then quotes the result for
+/// the filtergraph parser.
+pub fn escape_filter_value(value: &str) -> String {
+    let mut option_escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ':' | '\'') {
+            option_escaped.push('\\');
+        }
+        option_escaped.push(c);
+    }
+    format!("'{}'", option_escaped.replace('\'', "'\\''"))
+}
+
+/// Format seconds as `HH:MM:SS`
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Timestamps for `count` tiles, centred in equal slices of the duration
+pub fn tile_timestamps(duration: f64, count: usize) -> Vec<f64> {
+    if duration <= 0.0 || count == 0 {
+        return Vec::new();
+    }
+    (0..count)
+        .map(|i| duration * (i as f64 + 0.5) / count as f64)
+        .collect()
+}
+
+fn probe_duration(data: &serde_json::Value) -> f64 {
+    data.get("format")
+        .and_then(|f| f.get("duration"))
+        .and_then(|d| d.as_str())
+        .and_then(|s| s.parse::<f64>().ok())
+        .unwrap_or(0.0)
+}
+
+/// Build the contact sheet header line from probe data
+pub fn contact_sheet_header(file_name: &str, data: &serde_json::Value) -> String {
+    let mut parts = vec![file_name.to_string()];
+
+    let video = data
+        .get("streams")
+        .and_then(|s| s.as_array())
+        .and_then(|streams| {
+            streams.iter().find(|s| {
+                s.get("codec_type").and_then(|t| t.as_str()) == Some("video")
+                    && s.get("disposition")
+                        .and_then(|d| d.get("attached_pic"))
+                        .and_then(|v| v.as_i64())
+                        != Some(1)
+            })
+        });
+
+    if let Some(video) = video {
+        let codec = video
+            .get("codec_name")
+            .and_then(|c| c.as_str())
+            .unwrap_or("unknown");
+        match (
+            video.get("width").and_then(|w| w.as_i64()),
+            video.get("height").and_then(|h| h.as_i64()),
+        ) {
+            (Some(w), Some(h)) => parts.push(format!("{} {}x{}", codec, w, h)),
+            _ => parts.push(codec.to_string()),
+        }
+    }
+
+    if let Some(bit_rate) = data
+        .get("format")
+        .and_then(|f| f.get("bit_rate"))
+        .and_then(|b| b.as_str())
+        .and_then(|b| b.parse::<f64>().ok())
+    {
+        parts.push(format!("{:.2} Mb/s", bit_rate / 1_000_000.0));
+    }
+
+    parts.push(format_timestamp(probe_duration(data)));
+    parts.join("  |  ")
+}
+
+/// Path of a cached thumbnail
+fn thumbnail_cache_path(cache_dir: &Path, file_hash: &str, width: u32) -> PathBuf {
+    cache_dir
+        .join("thumbnails")
+        .join(format!("{}_{}.jpg", file_hash, width))
+}
+
+/// ffmpeg arguments to write a single filtered frame as JPEG
+fn single_frame_args(seek: &str, input: &str, filter: &str, output: &str) -> Vec<String> {
+    [
+        "-y",
+        "-ss",
+        seek,
+        "-i",
+        input,
+        "-vf",
+        filter,
+        "-frames:v",
+        "1",
+        "-an",
+        "-q:v",
+        "3",
+        output,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+/// Unique file next to `final_path` that ffmpeg renders into before the
+/// result is renamed into place, so the cache never holds a partial JPEG
+fn partial_thumbnail_path(final_path: &Path) -> PathBuf {
+    let stem = final_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    final_path.with_file_name(format!(
+        "{}.partial-{}-{}.jpg",
+        stem,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ))
+}
+
+fn is_non_empty_file(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
+
+/// Get a thumbnail for a media file, extracting and caching it if needed
+///
+/// The frame is taken from around 10% into the file; black frames are skipped
+/// and the `thumbnail` filter picks the most representative of the next frames.
+pub fn get_thumbnail(path: &str, cache_dir: &Path, width: u32) -> Result<ThumbnailResult, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    let width = if width == 0 {
+        DEFAULT_THUMBNAIL_WIDTH
+    } else {
+        width
+    };
+    let file_hash = compute_file_hash(path)?;
+    let thumbnail_path = thumbnail_cache_path(cache_dir, &file_hash, width);
+    let duration = probe_duration(&probe_cache::get_probe_json(path)?);
+    let seek_time = duration * 0.1;
+
+    if is_non_empty_file(&thumbnail_path) {
+        debug!("Thumbnail cache hit: {}", path);
+        return Ok(ThumbnailResult {
+            path: path.to_string(),
+            thumbnail_path: thumbnail_path.to_string_lossy().to_string(),
+            seek_time,
+            cached: true,
+        });
+    }
+
+    if let Some(parent) = thumbnail_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create thumbnail cache: {}", e))?;
+    }
+
+    let input = validated_path.to_string_lossy().to_string();
+    let partial_path = partial_thumbnail_path(&thumbnail_path);
+    let output = partial_path.to_string_lossy().to_string();
+    let seek = format!("{:.3}", seek_time);
+
+    let filter = format!(
+        "blackframe=amount=0:threshold=32,\
+         metadata=mode=select:key=lavfi.blackframe.pblack:value={}:function=less,\
+         thumbnail={},scale={}:-2",
+        BLACK_PIXEL_PERCENT, THUMBNAIL_BATCH_FRAMES, width
+    );
+    let args = single_frame_args(&seek, &input, &filter, &output);
+
+    let result = run_ffmpeg(&args, None, None, &mut |_| {});
+    if result.is_err() || !is_non_empty_file(&partial_path) {
+        // Every candidate frame was black (or the filter chain failed): take the frame as is
+        warn!("No non-black frame found for {}, using plain frame", path);
+        let scale = format!("scale={}:-2", width);
+        let args = single_frame_args(&seek, &input, &scale, &output);
+        if let Err(e) = run_ffmpeg(&args, None, None, &mut |_| {}) {
+            let _ = fs::remove_file(&partial_path);
+            return Err(e);
+        }
+    }
+
+    if !is_non_empty_file(&partial_path) {
+        let _ = fs::remove_file(&partial_path);
+        return Err("Failed to extract a frame".to_string());
+    }
+    // Concurrent requests each rename a complete file; the last one wins
+    if let Err(e) = fs::rename(&partial_path, &thumbnail_path) {
+        let _ = fs::remove_file(&partial_path);
+        return Err(format!("Failed to store thumbnail: {}", e));
+    }
+
+    Ok(ThumbnailResult {
+        path: path.to_string(),
+        thumbnail_path: thumbnail_path.to_string_lossy().to_string(),
+        seek_time,
+        cached: false,
+    })
+}
+
+/// Build a contact sheet for a media file
+///
+/// `on_progress` receives the overall percentage and a stage description.
+pub fn create_contact_sheet(
+    path: &str,
+    options: &ContactSheetOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<ContactSheetResult, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+    if options.columns == 0 || options.rows == 0 || options.tile_width == 0 {
+        return Err("Columns, rows and tile width must be greater than zero".to_string());
+    }
+
+    let output = match &options.output_path {
+        Some(out) => {
+            let out = Path::new(out);
+            let parent = out
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .ok_or("Invalid output path")?;
+            config::validate_path(parent)?.join(out.file_name().ok_or("Invalid output path")?)
+        }
+        None => {
+            let stem = validated_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output");
+            validated_path.with_file_name(format!("{}_contact_sheet.jpg", stem))
+        }
+    };
+    if output.exists() {
+        return Err(format!("Output already exists: {}", output.display()));
+    }
+
+    let data = probe_cache::get_probe_json(path)?;
+    let duration = probe_duration(&data);
+    let count = (options.columns * options.rows) as usize;
+    let timestamps = tile_timestamps(duration, count);
+    if timestamps.is_empty() {
+        return Err("Could not determine duration".to_string());
+    }
+
+    let file_name = validated_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let header = contact_sheet_header(&file_name, &data);
+
+    let frames_dir = std::env::temp_dir().join(format!(
+        "seer_contact_{}_{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis()
+    ));
+    fs::create_dir_all(&frames_dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    let result = (|| {
+        let input = validated_path.to_string_lossy().to_string();
+        let font_size = (options.tile_width / 16).max(10);
+        let mut frame_count = 0;
+
+        for (i, timestamp) in timestamps.iter().enumerate() {
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(CANCELLED_MESSAGE.to_string());
+            }
+            on_progress(
+                i as f64 / count as f64 * 90.0,
+                &format!("Extracting frame {}/{}", i + 1, count),
+            );
+
+            // Frames are numbered contiguously for the image2 demuxer
+            let frame_path = frames_dir.join(format!("frame_{:03}.jpg", frame_count + 1));
+            let filter = format!(
+                "scale={}:-2,drawtext=expansion=none:text={}:x=w-tw-6:y=h-th-6:\
+                 fontsize={}:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=4",
+                options.tile_width,
+                escape_filter_value(&format_timestamp(*timestamp)),
+                font_size
+            );
+            let args = single_frame_args(
+                &format!("{:.3}", timestamp),
+                &input,
+                &filter,
+                &frame_path.to_string_lossy(),
+            );
+
+            match run_ffmpeg(&args, None, Some(cancelled), &mut |_| {}) {
+                Ok(_) if is_non_empty_file(&frame_path) => frame_count += 1,
+                Ok(_) => warn!("No frame at {:.2}s in {}", timestamp, path),
+                Err(e) if e == CANCELLED_MESSAGE => return Err(e),
+                Err(e) => warn!("Frame at {:.2}s failed for {}: {}", timestamp, path, e),
+            }
+        }
+
+        if frame_count == 0 {
+            return Err("Failed to extract any frames".to_string());
+        }
+
+        on_progress(90.0, "Assembling contact sheet...");
+        let filter = format!(
+            "tile={}x{}:padding=4:margin=4:color=black,\
+             pad=iw:ih+{}:0:{}:color=black,\
+             drawtext=expansion=none:text={}:x=8:y=({}-th)/2:fontsize={}:fontcolor=white",
+            options.columns,
+            options.rows,
+            HEADER_HEIGHT,
+            HEADER_HEIGHT,
+            escape_filter_value(&header),
+            HEADER_HEIGHT,
+            HEADER_HEIGHT / 3
+        );
+        let args: Vec<String> = vec![
+            "-y".to_string(),
+            "-framerate".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            frames_dir
+                .join("frame_%03d.jpg")
+                .to_string_lossy()
+                .to_string(),
+            "-vf".to_string(),
+            filter,
+            "-frames:v".to_string(),
+            "1".to_string(),
+            "-q:v".to_string(),
+            "3".to_string(),
+            output.to_string_lossy().to_string(),
+        ];
+        run_ffmpeg(&args, None, Some(cancelled), &mut |_| {})?;
+        Ok(frame_count)
+    })();
+
+    let _ = fs::remove_dir_all(&frames_dir);
+
+    let frame_count = match result {
+        Ok(count) => count,
+        Err(e) => {
+            let _ = fs::remove_file(&output);
+            return Err(e);
+        }
+    };
+
+    info!(
+        "Created contact sheet for {} with {} frames",
+        path, frame_count
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(ContactSheetResult {
+        success: true,
+        output_path: output.to_string_lossy().to_string(),
+        frame_count,
+        message: format!("Contact sheet created with {} frames", frame_count),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_escape_filter_value() {
+        assert_eq!(escape_filter_value("00:01:02"), "'00\\:01\\:02'");
+        assert_eq!(escape_filter_value("a\\b"), "'a\\\\b'");
+        assert_eq!(escape_filter_value("it's"), "'it\\'\\''s'");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0.0), "00:00:00");
+        assert_eq!(format_timestamp(3725.9), "01:02:05");
+        assert_eq!(format_timestamp(-5.0), "00:00:00");
+    }
+
+    #[test]
+    fn test_tile_timestamps() {
+        assert_eq!(tile_timestamps(100.0, 4), vec![12.5, 37.5, 62.5, 87.5]);
+        assert!(tile_timestamps(0.0, 4).is_empty());
+        assert!(tile_timestamps(100.0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_contact_sheet_header() {
+        let data = json!({
+            "streams": [
+                {"codec_type": "video", "codec_name": "mjpeg", "width": 600, "height": 600,
+                 "disposition": {"attached_pic": 1}},
+                {"codec_type": "video", "codec_name": "hevc", "width": 3840, "height": 2160},
+                {"codec_type": "audio", "codec_name": "aac"}
+            ],
+            "format": {"duration": "5400.0", "bit_rate": "12500000"}
+        });
+        assert_eq!(
+            contact_sheet_header("movie.mkv", &data),
+            "movie.mkv  |  hevc 3840x2160  |  12.50 Mb/s  |  01:30:00"
+        );
+    }
+
+    #[test]
+    fn test_contact_sheet_header_audio_only() {
+        let data = json!({
+            "streams": [{"codec_type": "audio", "codec_name": "flac"}],
+            "format": {"duration": "61.0"}
+        });
+        assert_eq!(
+            contact_sheet_header("song.flac", &data),
+            "song.flac  |  00:01:01"
+        );
+    }
+
+    #[test]
+    fn test_thumbnail_cache_path_includes_width() {
+        let path = thumbnail_cache_path(Path::new("/cache"), "abc", 320);
+        assert_eq!(path, PathBuf::from("/cache/thumbnails/abc_320.jpg"));
+    }
+
+    #[test]
+    fn test_partial_thumbnail_path_stays_in_cache_dir() {
+        let final_path = Path::new("/cache/thumbnails/abc_320.jpg");
+        let partial = partial_thumbnail_path(final_path);
+        assert_eq!(partial.parent(), final_path.parent());
+        assert_ne!(partial, final_path);
+        let name = partial.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("abc_320.partial-") && name.ends_with(".jpg"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Verification of rewritten media files before they replace the original
+//!
+//! After ffmpeg writes a new file, it is re-probed and compared with what the
+//! operation should have produced: the duration must match within tolerance
+//! and the expected video/audio/subtitle streams must be present. When
+//! enabled in the config, the output is also decoded in full (`-f null`) to
+//! catch corruption that probing can't see.
+
+use log::{debug, info};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use super::ffmpeg::run_ffmpeg;
+use super::probe_cache;
+use super::streams::get_media_streams;
+use crate::config;
+use crate::types::{MediaStreams, StreamType};
+
+/// Allowed duration difference, in seconds or as a fraction of the duration
+const DURATION_TOLERANCE_SECS: f64 = 0.5;
+const DURATION_TOLERANCE_RATIO: f64 = 0.005;
+
+/// Maximum decoder error lines quoted in a failure message
+const MAX_REPORTED_ERRORS: usize = 3;
+
+/// What a rewritten file should contain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedOutput {
+    pub duration: f64,
+    pub video: usize,
+    pub audio: usize,
+    pub subtitle: usize,
+}
+
+fn count_types<'a>(types: impl Iterator<Item = &'a StreamType>) -> (usize, usize, usize) {
+    types.fold((0, 0, 0), |(v, a, s), t| match t {
+        StreamType::Video => (v + 1, a, s),
+        StreamType::Audio => (v, a + 1, s),
+        StreamType::Subtitle => (v, a, s + 1),
+        _ => (v, a, s),
+    })
+}
+
+impl ExpectedOutput {
+    /// Same streams and duration as the source (metadata edits, re-encodes)
+    pub fn unchanged(source: &MediaStreams) -> Self {
+        Self::after_removal(source, &[])
+    }
+
+    /// Source streams minus `removed`
+    pub fn after_removal(source: &MediaStreams, removed: &[i32]) -> Self {
+        let (video, audio, subtitle) = count_types(
+            source
+                .streams
+                .iter()
+                .filter(|s| !removed.contains(&s.index))
+                .map(|s| &s.stream_type),
+        );
+        Self {
+            duration: source.duration,
+            video,
+            audio,
+            subtitle,
+        }
+    }
+}
+
+/// Compare a probed output with the expectation
+///
+/// Attachments and data streams are not compared: muxers may legitimately drop them.
+pub fn check_output(expected: &ExpectedOutput, actual: &MediaStreams) -> Result<(), String> {
+    if expected.duration > 0.0 {
+        let tolerance = DURATION_TOLERANCE_SECS.max(expected.duration * DURATION_TOLERANCE_RATIO);
+        if (actual.duration - expected.duration).abs() > tolerance {
+            return Err(format!(
+                "duration is {:.2}s, expected {:.2}s",
+                actual.duration, expected.duration
+            ));
+        }
+    }
+
+    let (video, audio, subtitle) = count_types(actual.streams.iter().map(|s| &s.stream_type));
+    for (kind, found, wanted) in [
+        ("video", video, expected.video),
+        ("audio", audio, expected.audio),
+        ("subtitle", subtitle, expected.subtitle),
+    ] {
+        if found != wanted {
+            return Err(format!(
+                "{} {} stream(s) present, expected {}",
+                found, kind, wanted
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decode every video/audio stream and fail on any decoder error
+pub fn decode_check(
+    path: &Path,
+    duration: f64,
+    cancelled: Option<&AtomicBool>,
+) -> Result<(), String> {
+    let args: Vec<String> = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        path.to_string_lossy().to_string(),
+        "-map".to_string(),
+        "0:v?".to_string(),
+        "-map".to_string(),
+        "0:a?".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let stderr = run_ffmpeg(&args, Some(duration), cancelled, &mut |_| {})?;
+
+    let errors: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "decoding reported {} error(s): {}",
+            errors.len(),
+            errors
+                .iter()
+                .take(MAX_REPORTED_ERRORS)
+                .copied()
+                .collect::<Vec<_>>()
+                .join("; ")
+        ))
+    }
+}
+
+/// Verify a freshly written file before it replaces (or is kept next to) the original
+///
+/// Probes `output` and checks it against `expected`, then decodes it fully when
+/// `full_decode_verification` is enabled. The caller removes the output on error.
+pub fn verify_output(
+    output: &Path,
+    expected: &ExpectedOutput,
+    cancelled: Option<&AtomicBool>,
+) -> Result<(), String> {
+    let output_str = output.to_string_lossy().to_string();
+    // The path may have been probed before (e.g. an earlier `_modified` file)
+    probe_cache::invalidate_cache(&output_str);
+
+    let actual = get_media_streams(output_str.clone())
+        .map_err(|e| format!("Verification failed: output could not be probed: {}", e))?;
+    probe_cache::invalidate_cache(&output_str);
+    check_output(expected, &actual).map_err(|e| format!("Verification failed: {}", e))?;
+
+    if config::full_decode_verification() {
+        debug!("Decoding {} for verification", output.display());
+        decode_check(output, expected.duration, cancelled)
+            .map_err(|e| format!("Verification failed: {}", e))?;
+    }
+
+    info!("Verified output {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::parse_stream;
+    use serde_json::json;
+
+    fn media(types: &[&str], duration: f64) -> MediaStreams {
+        MediaStreams {
+            path: "/a.mkv".to_string(),
+            streams: types
+                .iter()
+                .enumerate()
+                .map(|(i, t)| parse_stream(&json!({ "index": i, "codec_type": t })))
+                .collect(),
+            video_count: 0,
+            audio_count: 0,
+            subtitle_count: 0,
+            attachment_count: 0,
+            total_size: 0,
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_expected_after_removal() {
+        let source = media(
+            &["video", "audio", "audio", "subtitle", "attachment"],
+            600.0,
+        );
+        let expected = ExpectedOutput::after_removal(&source, &[2, 3]);
+        assert_eq!(
+            expected,
+            ExpectedOutput {
+                duration: 600.0,
+                video: 1,
+                audio: 1,
+                subtitle: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_output_accepts_matching_file() {
+        let source = media(&["video", "audio", "attachment"], 600.0);
+        let expected = ExpectedOutput::unchanged(&source);
+        // Dropped attachment and a small duration drift are fine
+        assert!(check_output(&expected, &media(&["video", "audio"], 600.8)).is_ok());
+    }
+
+    #[test]
+    fn test_check_output_rejects_truncated_or_missing_streams() {
+        let expected = ExpectedOutput::unchanged(&media(&["video", "audio"], 600.0));
+        let err = check_output(&expected, &media(&["video", "audio"], 312.0)).unwrap_err();
+        assert!(err.contains("duration"));
+        let err = check_output(&expected, &media(&["video"], 600.0)).unwrap_err();
+        assert_eq!(err, "0 audio stream(s) present, expected 1");
+    }
+}
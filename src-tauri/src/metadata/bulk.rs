@@ -0,0 +1,527 @@
+//! Batch metadata editing across many files
+//!
+//! Every file is planned against its current tags first: operations its
+//! container can't store (or that need a missing tool or stream) are skipped
+//! with a reason, no-op operations are dropped, and the before/after value of
+//! each affected tag is reported. Unless it's a dry run, the remaining
+//! operations are applied through `update_metadata`, so each file is verified
+//! and journaled exactly like a single edit.
+
+use log::{info, warn};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{list_metadata, native, update_metadata};
+use crate::media::CANCELLED_MESSAGE;
+use crate::types::{
+    is_image_extension, BulkMetadataFileResult, BulkMetadataResult, BulkMetadataTarget,
+    MetadataAction, MetadataChangePreview, MetadataEntry, MetadataOperation, MetadataScope,
+    MetadataSnapshot, SkippedMetadataOperation,
+};
+
+/// Which tag keys a container can store at one level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagSupport {
+    /// Arbitrary keys (Matroska, Vorbis comments, ID3 TXXX frames)
+    Any,
+    /// Only these keys are written by the muxer
+    Keys(&'static [&'static str]),
+    /// Tags at this level are dropped
+    None,
+}
+
+/// Global tags the MP4/MOV muxer writes (others are dropped silently)
+const MP4_FORMAT_KEYS: &[&str] = &[
+    "title",
+    "artist",
+    "album_artist",
+    "album",
+    "composer",
+    "date",
+    "genre",
+    "comment",
+    "copyright",
+    "description",
+    "synopsis",
+    "show",
+    "episode_id",
+    "episode_sort",
+    "season_number",
+    "network",
+    "lyrics",
+    "grouping",
+    "compilation",
+    "track",
+    "disc",
+    "encoder",
+    "media_type",
+    "hd_video",
+    "gapless_playback",
+];
+const MP4_STREAM_KEYS: &[&str] = &["language", "title", "handler_name"];
+
+/// RIFF INFO chunk keys (AVI, WAV)
+const RIFF_FORMAT_KEYS: &[&str] = &[
+    "title",
+    "artist",
+    "album",
+    "comment",
+    "copyright",
+    "date",
+    "genre",
+    "language",
+    "track",
+    "encoder",
+    "encoded_by",
+    "timecode",
+];
+
+const MPEGTS_FORMAT_KEYS: &[&str] = &["service_name", "service_provider"];
+const MPEGTS_STREAM_KEYS: &[&str] = &["language"];
+
+/// Tag support of a container (by extension) for format- and stream-level tags
+pub fn tag_support(extension: &str) -> (TagSupport, TagSupport) {
+    match extension.to_lowercase().as_str() {
+        "mkv" | "mka" | "mks" | "webm" | "ogg" | "oga" | "ogv" | "opus" => {
+            (TagSupport::Any, TagSupport::Any)
+        }
+        "mp4" | "m4v" | "m4a" | "m4b" | "mov" | "3gp" => (
+            TagSupport::Keys(MP4_FORMAT_KEYS),
+            TagSupport::Keys(MP4_STREAM_KEYS),
+        ),
+        "avi" | "wav" => (TagSupport::Keys(RIFF_FORMAT_KEYS), TagSupport::None),
+        "ts" | "m2ts" | "mts" => (
+            TagSupport::Keys(MPEGTS_FORMAT_KEYS),
+            TagSupport::Keys(MPEGTS_STREAM_KEYS),
+        ),
+        "mp3" | "flac" | "aac" | "wma" | "wmv" | "asf" => (TagSupport::Any, TagSupport::None),
+        // Unknown containers are left to ffmpeg
+        _ => (TagSupport::Any, TagSupport::Any),
+    }
+}
+
+/// Why `op` can't be stored in a file with this extension, if it can't
+///
+/// Deletes are always possible; only setting a tag depends on the container.
+pub fn unsupported_reason(extension: &str, op: &MetadataOperation) -> Option<String> {
+    if op.action == MetadataAction::Delete || op.scope == MetadataScope::File {
+        return None;
+    }
+    if op.key.trim().eq_ignore_ascii_case(native::COVER_ART_KEY) {
+        return (op.scope != MetadataScope::Format || !native::supports_extension(extension))
+            .then(|| format!("{} files can't store cover art", extension.to_uppercase()));
+    }
+    let (format, stream) = tag_support(extension);
+    let (support, level) = match op.scope {
+        MetadataScope::Format => (format, "container"),
+        MetadataScope::Stream => (stream, "stream"),
+        MetadataScope::File => return None,
+    };
+    let key = op.key.trim().to_lowercase();
+    match support {
+        TagSupport::Any => None,
+        TagSupport::Keys(keys) if keys.contains(&key.as_str()) => None,
+        TagSupport::Keys(_) => Some(format!(
+            "{} files can't store the {} tag '{}'",
+            extension.to_uppercase(),
+            level,
+            op.key
+        )),
+        TagSupport::None => Some(format!(
+            "{} files can't store {} tags",
+            extension.to_uppercase(),
+            level
+        )),
+    }
+}
+
+fn tags_for<'a>(snapshot: &'a MetadataSnapshot, op: &MetadataOperation) -> Vec<&'a MetadataEntry> {
+    let entries = match op.scope {
+        MetadataScope::Format => &snapshot.format_tags,
+        MetadataScope::Stream => &snapshot.stream_tags,
+        MetadataScope::File => &snapshot.file_tags,
+    };
+    entries
+        .iter()
+        .filter(|e| op.scope != MetadataScope::Stream || e.stream_index == op.stream_index)
+        .collect()
+}
+
+/// Result of planning operations for one file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilePlan {
+    /// Operations that change something and can be applied
+    pub operations: Vec<MetadataOperation>,
+    pub changes: Vec<MetadataChangePreview>,
+    pub skipped: Vec<SkippedMetadataOperation>,
+}
+
+/// Work out what `operations` would change in the file described by `snapshot`
+pub fn plan_file(snapshot: &MetadataSnapshot, operations: &[MetadataOperation]) -> FilePlan {
+    let extension = snapshot.extension.as_deref().unwrap_or("");
+    let is_image = is_image_extension(extension);
+    let tools = &snapshot.tool_availability;
+    let mut plan = FilePlan::default();
+
+    for op in operations {
+        let skip_reason = if op.scope == MetadataScope::File && !tools.exiftool {
+            Some("exiftool is required for file-level tags".to_string())
+        } else if op.scope != MetadataScope::File
+            && !tools.ffmpeg
+            && !native::can_write(Path::new(&snapshot.path), std::slice::from_ref(op))
+        {
+            Some("ffmpeg is required for container/stream tags".to_string())
+        } else if op.scope != MetadataScope::File && is_image {
+            Some("Images only support file-level tags".to_string())
+        } else if op.scope == MetadataScope::Stream
+            && !snapshot
+                .stream_summaries
+                .iter()
+                .any(|s| Some(s.index) == op.stream_index && s.index >= 0)
+        {
+            Some(format!(
+                "Stream {} does not exist",
+                op.stream_index.unwrap_or(-1)
+            ))
+        } else {
+            unsupported_reason(extension, op)
+        };
+        if let Some(reason) = skip_reason {
+            plan.skipped.push(SkippedMetadataOperation {
+                operation: op.clone(),
+                reason,
+            });
+            continue;
+        }
+
+        let existing = tags_for(snapshot, op);
+        let key = op.key.trim();
+        let preview =
+            |key: &str, before: Option<String>, after: Option<String>| MetadataChangePreview {
+                key: key.to_string(),
+                scope: op.scope.clone(),
+                stream_index: op.stream_index,
+                before,
+                after,
+            };
+
+        let changes: Vec<MetadataChangePreview> = match op.action {
+            MetadataAction::Delete if key == "*" => existing
+                .iter()
+                .map(|e| preview(&e.key, Some(e.value.clone()), None))
+                .collect(),
+            MetadataAction::Delete => existing
+                .iter()
+                .filter(|e| e.key.eq_ignore_ascii_case(key))
+                .map(|e| preview(&e.key, Some(e.value.clone()), None))
+                .collect(),
+            MetadataAction::Set => {
+                let before = existing
+                    .iter()
+                    .find(|e| e.key.eq_ignore_ascii_case(key))
+                    .map(|e| e.value.clone());
+                let after = op.value.clone().unwrap_or_default();
+                if before.as_deref() == Some(after.as_str()) {
+                    Vec::new()
+                } else {
+                    vec![preview(key, before, Some(after))]
+                }
+            }
+        };
+
+        if changes.is_empty() {
+            let reason = match op.action {
+                MetadataAction::Set => "Already set to this value",
+                MetadataAction::Delete => "Tag not present",
+            };
+            plan.skipped.push(SkippedMetadataOperation {
+                operation: op.clone(),
+                reason: reason.to_string(),
+            });
+        } else {
+            plan.changes.extend(changes);
+            plan.operations.push(op.clone());
+        }
+    }
+
+    plan
+}
+
+/// Operations to apply to one file, plus any already skipped while building them
+pub(crate) type FileOperations = (Vec<MetadataOperation>, Vec<SkippedMetadataOperation>);
+
+/// Plan (and unless `dry_run`, apply) metadata operations across many files
+///
+/// Targets without their own operations use `shared`. A failing file is
+/// reported in its result and doesn't stop the batch.
+pub fn bulk_update_metadata(
+    targets: &[BulkMetadataTarget],
+    shared: &[MetadataOperation],
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<BulkMetadataResult, String> {
+    let paths: Vec<&str> = targets.iter().map(|t| t.path.as_str()).collect();
+    run_batch(&paths, dry_run, cancelled, on_progress, &mut |i, _| {
+        let operations = targets[i].operations.as_deref().unwrap_or(shared);
+        Ok((operations.to_vec(), Vec::new()))
+    })
+}
+
+/// Run a batch update, asking `operations_for(index, snapshot)` what to apply to each file
+pub(crate) fn run_batch(
+    paths: &[&str],
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+    operations_for: &mut dyn FnMut(usize, &MetadataSnapshot) -> Result<FileOperations, String>,
+) -> Result<BulkMetadataResult, String> {
+    run_file_batch(
+        "Bulk metadata update",
+        paths,
+        dry_run,
+        cancelled,
+        on_progress,
+        &mut |i, result| {
+            let snapshot = list_metadata(result.path.clone())?;
+            let (operations, skipped) = operations_for(i, &snapshot)?;
+            let mut plan = plan_file(&snapshot, &operations);
+            plan.skipped.splice(0..0, skipped);
+            result.changes = plan.changes;
+            result.skipped = plan.skipped;
+            if !dry_run && !plan.operations.is_empty() {
+                update_metadata(result.path.clone(), plan.operations).map_err(|e| {
+                    warn!("Bulk metadata update failed for {}: {}", result.path, e);
+                    e
+                })?;
+                result.applied = true;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Run `process(index, result)` on every file with progress and cancellation
+///
+/// `process` fills in the file's changes and skips (and applies them unless
+/// it's a dry run); an error is recorded on that file and the batch goes on.
+pub(crate) fn run_file_batch(
+    label: &str,
+    paths: &[&str],
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+    process: &mut dyn FnMut(usize, &mut BulkMetadataFileResult) -> Result<(), String>,
+) -> Result<BulkMetadataResult, String> {
+    if paths.is_empty() {
+        return Err("No files provided".to_string());
+    }
+
+    let total = paths.len();
+    let mut files = Vec::with_capacity(total);
+
+    for (i, path) in paths.iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err(CANCELLED_MESSAGE.to_string());
+        }
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+        on_progress(
+            i as f64 / total as f64 * 100.0,
+            &format!("{} ({}/{})", name, i + 1, total),
+        );
+
+        let mut result = BulkMetadataFileResult {
+            path: path.to_string(),
+            changes: Vec::new(),
+            skipped: Vec::new(),
+            applied: false,
+            error: None,
+        };
+        if let Err(e) = process(i, &mut result) {
+            result.error = Some(e);
+        }
+        files.push(result);
+    }
+
+    let failed = files.iter().filter(|f| f.error.is_some()).count();
+    let changed = files
+        .iter()
+        .filter(|f| f.error.is_none() && !f.changes.is_empty())
+        .count();
+    info!(
+        "{}{}: {} changed, {} failed of {} file(s)",
+        label,
+        if dry_run { " (dry run)" } else { "" },
+        changed,
+        failed,
+        total
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(BulkMetadataResult {
+        dry_run,
+        changed,
+        unchanged: total - changed - failed,
+        failed,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::test_support::{self, entry, op, summary};
+
+    fn snapshot(extension: &str) -> MetadataSnapshot {
+        test_support::snapshot(
+            &format!("a.{}", extension),
+            vec![
+                entry(MetadataScope::Format, "title", "Old Title", None),
+                entry(MetadataScope::Format, "encoder", "libmkv", None),
+            ],
+            vec![entry(MetadataScope::Stream, "language", "eng", Some(1))],
+            vec![summary(1, "audio")],
+        )
+    }
+
+    #[test]
+    fn test_unsupported_reason_by_container() {
+        let custom = op(
+            MetadataAction::Set,
+            MetadataScope::Format,
+            "MY_TAG",
+            Some("x"),
+            None,
+        );
+        assert!(unsupported_reason("mkv", &custom).is_none());
+        assert_eq!(
+            unsupported_reason("mp4", &custom).unwrap(),
+            "MP4 files can't store the container tag 'MY_TAG'"
+        );
+        let title = op(
+            MetadataAction::Set,
+            MetadataScope::Format,
+            "Title",
+            Some("x"),
+            None,
+        );
+        assert!(unsupported_reason("mp4", &title).is_none());
+        let stream = op(
+            MetadataAction::Set,
+            MetadataScope::Stream,
+            "title",
+            Some("x"),
+            Some(1),
+        );
+        assert!(unsupported_reason("avi", &stream).is_some());
+        let delete = op(
+            MetadataAction::Delete,
+            MetadataScope::Stream,
+            "title",
+            None,
+            Some(1),
+        );
+        assert!(unsupported_reason("avi", &delete).is_none());
+    }
+
+    #[test]
+    fn test_plan_file_previews_and_skips() {
+        let operations = vec![
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "title",
+                Some("New Title"),
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Stream,
+                "language",
+                Some("eng"),
+                Some(1),
+            ),
+            op(
+                MetadataAction::Delete,
+                MetadataScope::Format,
+                "comment",
+                None,
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "MY_TAG",
+                Some("x"),
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::File,
+                "Artist",
+                Some("x"),
+                None,
+            ),
+        ];
+        let plan = plan_file(&snapshot("mp4"), &operations);
+
+        assert_eq!(plan.operations, vec![operations[0].clone()]);
+        assert_eq!(
+            plan.changes,
+            vec![MetadataChangePreview {
+                key: "title".to_string(),
+                scope: MetadataScope::Format,
+                stream_index: None,
+                before: Some("Old Title".to_string()),
+                after: Some("New Title".to_string()),
+            }]
+        );
+        let reasons: Vec<&str> = plan.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "Already set to this value",
+                "Tag not present",
+                "MP4 files can't store the container tag 'MY_TAG'",
+                "exiftool is required for file-level tags",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_file_wildcard_delete_and_missing_stream() {
+        let mut wipe = op(
+            MetadataAction::Delete,
+            MetadataScope::Format,
+            "*",
+            None,
+            None,
+        );
+        let mut missing = op(
+            MetadataAction::Set,
+            MetadataScope::Stream,
+            "title",
+            Some("x"),
+            Some(1),
+        );
+        missing.stream_index = Some(5);
+        let plan = plan_file(&snapshot("mkv"), &[wipe.clone(), missing]);
+
+        assert_eq!(plan.operations, vec![wipe.clone()]);
+        assert_eq!(plan.changes.len(), 2);
+        assert!(plan.changes.iter().all(|c| c.after.is_none()));
+        assert_eq!(plan.skipped[0].reason, "Stream 5 does not exist");
+
+        // Nothing to wipe on a file without tags
+        let mut empty = snapshot("mkv");
+        empty.format_tags.clear();
+        wipe.key = " * ".to_string();
+        assert_eq!(
+            plan_file(&empty, &[wipe]).skipped[0].reason,
+            "Tag not present"
+        );
+    }
+}
@@ -0,0 +1,498 @@
+//! Embedded cover art: extract, set (add or replace) and remove
+//!
+//! ffprobe exposes covers as attached-picture video streams for every
+//! supported container (Matroska `cover.*` attachments included), so they
+//! are extracted with a plain stream copy. New images are checked by their
+//! magic bytes, scaled down to `config::cover_art_max_dimension` and
+//! re-encoded to JPEG/PNG when needed. Tag-based formats are written
+//! natively through `update_metadata`; Matroska is remuxed with ffmpeg,
+//! verified and journaled like any other rewrite.
+
+use log::info;
+use serde_json::Value;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::{
+    create_temp_path, is_matroska, native, scratch_path, update_metadata, update_metadata_with,
+    UpdateOptions,
+};
+use crate::config;
+use crate::journal;
+use crate::media::{
+    ensure_disk_space, estimate_removal_output_size, get_media_streams, get_probe_json,
+    invalidate_probe_cache, run_ffmpeg, verify_output, ExpectedOutput,
+};
+use crate::types::{
+    CoverArt, JournalChange, JournalOperationKind, MetadataAction, MetadataOperation, MetadataScope,
+};
+
+/// Image formats accepted as cover art input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageType {
+    Jpeg,
+    Png,
+    Webp,
+    Gif,
+    Bmp,
+}
+
+impl ImageType {
+    /// Detect the image type from the file's first bytes
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        match header {
+            [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(Self::Png),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::Webp),
+            [b'G', b'I', b'F', b'8', ..] => Some(Self::Gif),
+            [b'B', b'M', ..] => Some(Self::Bmp),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+            Self::Gif => "image/gif",
+            Self::Bmp => "image/bmp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+        }
+    }
+
+    /// Whether every container/player handles it (others are re-encoded)
+    fn is_embeddable(self) -> bool {
+        matches!(self, Self::Jpeg | Self::Png)
+    }
+}
+
+fn read_image_type(path: &Path) -> Result<ImageType, String> {
+    let mut header = Vec::with_capacity(16);
+    fs::File::open(path)
+        .and_then(|f| f.take(16).read_to_end(&mut header))
+        .map_err(|e| format!("Failed to read image '{}': {}", path.display(), e))?;
+    ImageType::detect(&header).ok_or_else(|| {
+        format!(
+            "'{}' is not a supported image (JPEG, PNG, WebP, GIF or BMP)",
+            path.display()
+        )
+    })
+}
+
+/// Width and height of an image, probed with ffprobe
+fn image_size(path: &Path) -> Option<(u32, u32)> {
+    let path = path.to_string_lossy().to_string();
+    let probe = get_probe_json(&path).ok();
+    // Temporary images shouldn't linger in the probe cache
+    invalidate_probe_cache(&path);
+    let stream = probe?.get("streams")?.as_array()?.first()?.clone();
+    let dimension = |key: &str| stream.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+    Some((dimension("width")?, dimension("height")?))
+}
+
+/// ffmpeg scale filter fitting an image within `max_dimension` (no upscaling)
+fn scale_filter(max_dimension: u32) -> String {
+    format!(
+        "scale=w='min({m},iw)':h='min({m},ih)':force_original_aspect_ratio=decrease",
+        m = max_dimension
+    )
+}
+
+/// An image ready to embed; a re-encoded temporary copy is removed on drop
+struct PreparedImage {
+    path: PathBuf,
+    image_type: ImageType,
+    temporary: bool,
+}
+
+impl Drop for PreparedImage {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Validate an image and scale/re-encode it if it's too large or not JPEG/PNG
+fn prepare_image(image_path: &Path, max_dimension: u32) -> Result<PreparedImage, String> {
+    let image_type = read_image_type(image_path)?;
+    let too_large =
+        max_dimension > 0 && image_size(image_path).is_some_and(|(w, h)| w.max(h) > max_dimension);
+    if image_type.is_embeddable() && !too_large {
+        return Ok(PreparedImage {
+            path: image_path.to_path_buf(),
+            image_type,
+            temporary: false,
+        });
+    }
+
+    // PNG stays lossless; everything else becomes JPEG
+    let output_type = if image_type == ImageType::Png {
+        ImageType::Png
+    } else {
+        ImageType::Jpeg
+    };
+    let prepared = PreparedImage {
+        path: scratch_path("cover", output_type.extension()),
+        image_type: output_type,
+        temporary: true,
+    };
+    let mut args = vec![
+        "-y".to_string(),
+        "-i".to_string(),
+        image_path.to_string_lossy().to_string(),
+    ];
+    if too_large {
+        args.extend(["-vf".to_string(), scale_filter(max_dimension)]);
+    }
+    args.extend(["-frames:v".to_string(), "1".to_string()]);
+    if output_type == ImageType::Jpeg {
+        args.extend(["-q:v".to_string(), "2".to_string()]);
+    }
+    args.push(prepared.path.to_string_lossy().to_string());
+
+    info!(
+        "Re-encoding cover image {} ({:?}{})",
+        image_path.display(),
+        image_type,
+        if too_large { ", scaled down" } else { "" }
+    );
+    run_ffmpeg(&args, None, None, &mut |_| {})
+        .map_err(|e| format!("Failed to convert cover image: {}", e))?;
+    Ok(prepared)
+}
+
+fn is_attached_pic(stream: &Value) -> bool {
+    stream
+        .get("disposition")
+        .and_then(|d| d.get("attached_pic"))
+        .and_then(|v| v.as_i64())
+        == Some(1)
+}
+
+/// The cover stream (index, codec): an attached picture, preferring one
+/// named `cover.*` when a Matroska file has several
+fn find_cover(probe: &Value) -> Option<(i32, String)> {
+    let streams = probe.get("streams")?.as_array()?;
+    let pictures: Vec<&Value> = streams.iter().filter(|s| is_attached_pic(s)).collect();
+    let is_named_cover = |s: &&Value| {
+        s.get("tags")
+            .and_then(|t| t.get("filename"))
+            .and_then(|f| f.as_str())
+            .is_some_and(|f| f.to_lowercase().starts_with("cover."))
+    };
+    let cover = pictures
+        .iter()
+        .copied()
+        .find(is_named_cover)
+        .or_else(|| pictures.first().copied())?;
+    let index = cover.get("index")?.as_i64()? as i32;
+    let codec = cover
+        .get("codec_name")
+        .and_then(|c| c.as_str())
+        .unwrap_or("mjpeg")
+        .to_string();
+    Some((index, codec))
+}
+
+fn count_video_streams(probe: &Value) -> usize {
+    probe
+        .get("streams")
+        .and_then(|s| s.as_array())
+        .map(|streams| {
+            streams
+                .iter()
+                .filter(|s| s.get("codec_type").and_then(|t| t.as_str()) == Some("video"))
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+/// Extract the embedded cover, if any
+pub fn get_cover_art(path: &str) -> Result<Option<CoverArt>, String> {
+    let validated = config::validate_path(Path::new(path))?;
+    let probe = get_probe_json(path)?;
+    let Some((index, codec)) = find_cover(&probe) else {
+        return Ok(None);
+    };
+
+    let extension = match codec.as_str() {
+        "png" => "png",
+        "bmp" => "bmp",
+        "gif" => "gif",
+        "webp" => "webp",
+        _ => "jpg",
+    };
+    let output = scratch_path("extract", extension);
+    let args = vec![
+        "-y".to_string(),
+        "-i".to_string(),
+        validated.to_string_lossy().to_string(),
+        "-map".to_string(),
+        format!("0:{}", index),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-update".to_string(),
+        "1".to_string(),
+        output.to_string_lossy().to_string(),
+    ];
+    let extracted = run_ffmpeg(&args, None, None, &mut |_| {})
+        .and_then(|_| fs::read(&output).map_err(|e| e.to_string()));
+    let size = image_size(&output);
+    let _ = fs::remove_file(&output);
+    let data = extracted.map_err(|e| format!("Failed to extract cover art: {}", e))?;
+
+    let mime_type = ImageType::detect(&data)
+        .map(|t| t.mime_type().to_string())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    Ok(Some(CoverArt {
+        data,
+        mime_type,
+        width: size.map(|(w, _)| w),
+        height: size.map(|(_, h)| h),
+        stream_index: index,
+    }))
+}
+
+/// ffmpeg arguments replacing the cover of a Matroska file: drop `existing`
+/// and add `image` as an attached picture (the `video_kept`-th video stream)
+fn matroska_cover_args(
+    input: &Path,
+    existing: Option<i32>,
+    image: Option<(&Path, ImageType)>,
+    video_kept: usize,
+    output: &Path,
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string(), "-i".to_string()];
+    args.push(input.to_string_lossy().to_string());
+    if let Some((image_path, _)) = image {
+        args.extend(["-i".to_string(), image_path.to_string_lossy().to_string()]);
+    }
+    args.extend(["-map".to_string(), "0".to_string()]);
+    if let Some(index) = existing {
+        args.extend(["-map".to_string(), format!("-0:{}", index)]);
+    }
+    if let Some((_, image_type)) = image {
+        let spec = format!("v:{}", video_kept);
+        args.extend([
+            "-map".to_string(),
+            "1".to_string(),
+            format!("-disposition:{}", spec),
+            "attached_pic".to_string(),
+            format!("-metadata:s:{}", spec),
+            format!("filename=cover.{}", image_type.extension()),
+            format!("-metadata:s:{}", spec),
+            format!("mimetype={}", image_type.mime_type()),
+        ]);
+    }
+    args.extend(["-c".to_string(), "copy".to_string()]);
+    args.push(output.to_string_lossy().to_string());
+    args
+}
+
+/// Remux a Matroska file with its cover replaced (or removed when `image` is None)
+fn rewrite_matroska_cover(path: &Path, image: Option<&PreparedImage>) -> Result<(), String> {
+    let path_str = path.to_string_lossy().to_string();
+    let probe = get_probe_json(&path_str)?;
+    let source = get_media_streams(path_str.clone())?;
+    let existing = find_cover(&probe).map(|(index, _)| index);
+    let video_kept = count_video_streams(&probe) - usize::from(existing.is_some());
+
+    let removed: Vec<i32> = existing.into_iter().collect();
+    let mut expected = ExpectedOutput::after_removal(&source, &removed);
+    if image.is_some() {
+        expected.video += 1;
+    }
+
+    // The remux is written in full next to the original
+    let image_size = image
+        .and_then(|i| fs::metadata(&i.path).ok())
+        .map_or(0, |m| m.len());
+    let required = estimate_removal_output_size(&source, &removed) + image_size;
+    ensure_disk_space(path.parent().unwrap_or(Path::new(".")), required)?;
+
+    let output = create_temp_path(path, "cover");
+    let args = matroska_cover_args(
+        path,
+        existing,
+        image.map(|i| (i.path.as_path(), i.image_type)),
+        video_kept,
+        &output,
+    );
+
+    let written = run_ffmpeg(&args, Some(source.duration), None, &mut |_| {})
+        .and_then(|_| verify_output(&output, &expected, None));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&output);
+        return Err(e);
+    }
+
+    if let Some(backup) = journal::replace_with_backup(path, &output)? {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        journal::record(
+            JournalOperationKind::MetadataUpdate,
+            if image.is_some() {
+                format!("Set cover art of '{}'", file_name)
+            } else {
+                format!("Remove cover art from '{}'", file_name)
+            },
+            vec![JournalChange::Rewritten {
+                path: path_str.clone(),
+                backup: backup.to_string_lossy().to_string(),
+            }],
+        );
+    }
+    invalidate_probe_cache(&path_str);
+    Ok(())
+}
+
+/// Native cover operation; the image to set is passed to
+/// `update_metadata_with` as bytes
+fn cover_operation(action: MetadataAction) -> MetadataOperation {
+    MetadataOperation {
+        action,
+        key: native::COVER_ART_KEY.to_string(),
+        value: None,
+        scope: MetadataScope::Format,
+        stream_index: None,
+    }
+}
+
+fn unsupported_format() -> String {
+    "Cover art is supported for MP3, FLAC, Ogg/Opus, M4A/MP4 and Matroska files".to_string()
+}
+
+/// Add or replace the cover with an image file
+pub fn set_cover_art(path: &str, image_path: &str) -> Result<(), String> {
+    let validated = config::validate_path(Path::new(path))?;
+    let image = config::validate_path(Path::new(image_path))?;
+    let extension = validated.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !is_matroska(&validated) && !native::supports_extension(extension) {
+        return Err(unsupported_format());
+    }
+
+    let prepared = prepare_image(&image, config::cover_art_max_dimension())?;
+    if is_matroska(&validated) {
+        rewrite_matroska_cover(&validated, Some(&prepared))
+    } else {
+        let bytes =
+            fs::read(&prepared.path).map_err(|e| format!("Failed to read cover image: {}", e))?;
+        update_metadata_with(
+            path.to_string(),
+            vec![cover_operation(MetadataAction::Set)],
+            UpdateOptions {
+                cover: Some(&bytes),
+                ..UpdateOptions::default()
+            },
+        )
+        .map(|_| ())
+    }
+}
+
+/// Remove the embedded cover; returns false if there was none
+pub fn remove_cover_art(path: &str) -> Result<bool, String> {
+    let validated = config::validate_path(Path::new(path))?;
+    let extension = validated.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !is_matroska(&validated) && !native::supports_extension(extension) {
+        return Err(unsupported_format());
+    }
+    if find_cover(&get_probe_json(path)?).is_none() {
+        return Ok(false);
+    }
+
+    if is_matroska(&validated) {
+        rewrite_matroska_cover(&validated, None)?;
+    } else {
+        update_metadata(
+            path.to_string(),
+            vec![cover_operation(MetadataAction::Delete)],
+        )?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_detect_image_type() {
+        assert_eq!(
+            ImageType::detect(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some(ImageType::Jpeg)
+        );
+        assert_eq!(
+            ImageType::detect(b"\x89PNG\r\n\x1a\n\0\0"),
+            Some(ImageType::Png)
+        );
+        assert_eq!(
+            ImageType::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageType::Webp)
+        );
+        assert_eq!(ImageType::detect(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(ImageType::detect(b"%PDF-1.7"), None);
+        assert_eq!(ImageType::detect(&[]), None);
+    }
+
+    #[test]
+    fn test_find_cover_prefers_named_cover() {
+        let probe = json!({"streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "h264", "disposition": {"attached_pic": 0}},
+            {"index": 1, "codec_type": "audio", "codec_name": "aac"},
+            {"index": 2, "codec_type": "video", "codec_name": "mjpeg", "disposition": {"attached_pic": 1},
+             "tags": {"filename": "small_cover.jpg"}},
+            {"index": 3, "codec_type": "video", "codec_name": "png", "disposition": {"attached_pic": 1},
+             "tags": {"filename": "Cover.png"}},
+        ]});
+        assert_eq!(find_cover(&probe), Some((3, "png".to_string())));
+        assert_eq!(count_video_streams(&probe), 3);
+
+        let no_cover = json!({"streams": [{"index": 0, "codec_type": "audio"}]});
+        assert_eq!(find_cover(&no_cover), None);
+    }
+
+    #[test]
+    fn test_matroska_cover_args() {
+        let args = matroska_cover_args(
+            Path::new("/m/a.mkv"),
+            Some(3),
+            Some((Path::new("/tmp/c.jpg"), ImageType::Jpeg)),
+            1,
+            Path::new("/m/.a.seer_cover.mkv"),
+        );
+        assert_eq!(
+            args.join(" "),
+            "-y -i /m/a.mkv -i /tmp/c.jpg -map 0 -map -0:3 -map 1 -disposition:v:1 attached_pic \
+             -metadata:s:v:1 filename=cover.jpg -metadata:s:v:1 mimetype=image/jpeg \
+             -c copy /m/.a.seer_cover.mkv"
+        );
+
+        let args =
+            matroska_cover_args(Path::new("/m/a.mkv"), Some(3), None, 1, Path::new("/o.mkv"));
+        assert_eq!(
+            args.join(" "),
+            "-y -i /m/a.mkv -map 0 -map -0:3 -c copy /o.mkv"
+        );
+        assert_eq!(
+            scale_filter(1200),
+            "scale=w='min(1200,iw)':h='min(1200,ih)':force_original_aspect_ratio=decrease"
+        );
+    }
+}
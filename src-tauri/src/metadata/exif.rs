@@ -0,0 +1,1045 @@
+//! Structured EXIF/XMP/IPTC editing for images
+//!
+//! exiftool is read with `-G0:1`, so every tag arrives as
+//! `Family:Group:Name` and is grouped by family (EXIF, XMP, IPTC,
+//! MakerNotes, ...). Values are typed from the tag name and exiftool's
+//! JSON (dates, GPS coordinates as signed degrees, rationals, lists) and
+//! edits are validated against the same type before exiftool runs.
+//! Only EXIF, XMP and IPTC tags are editable; maker notes are read-only.
+//!
+//! Every rewrite goes through `-out` into a temporary file that replaces
+//! the original with an undo backup, like other metadata edits.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+
+use super::bulk::run_file_batch;
+use super::{create_temp_path, finalize_replacement};
+use crate::config;
+use crate::journal;
+use crate::media::{find_command, invalidate_probe_cache};
+use crate::types::{
+    BulkMetadataResult, ExifEdit, ExifEditAction, ExifGroup, ExifMetadata, ExifShift, ExifTag,
+    ExifValue, JournalChange, JournalOperationKind, MetadataAction, MetadataChangePreview,
+    MetadataOperation, MetadataScope, SkippedMetadataOperation,
+};
+
+/// Decimal degrees for GPS coordinates (`-c` option); only Composite and XMP
+/// values carry a sign, EXIF GPS tags are signed from their Ref tag
+const COORDINATE_FORMAT: &str = "%+.8f";
+
+/// Families shown first, in this order; others follow alphabetically
+const FAMILY_ORDER: &[&str] = &["EXIF", "XMP", "IPTC", "MakerNotes", "Composite", "File"];
+
+/// Families whose tags can be edited
+const EDITABLE_FAMILIES: &[&str] = &["EXIF", "XMP", "IPTC"];
+
+/// Tags holding a list of values
+const LIST_TAGS: &[&str] = &[
+    "Keywords",
+    "Subject",
+    "HierarchicalSubject",
+    "SupplementalCategories",
+    "Creator",
+    "Contributor",
+    "By-line",
+    "PersonInImage",
+    "TagsList",
+    "CatalogSets",
+];
+
+/// Tags stored as rationals (written as "1/250" or a decimal)
+const RATIONAL_TAGS: &[&str] = &[
+    "ExposureTime",
+    "FNumber",
+    "FocalLength",
+    "ApertureValue",
+    "MaxApertureValue",
+    "ShutterSpeedValue",
+    "ExposureCompensation",
+    "BrightnessValue",
+    "DigitalZoomRatio",
+    "XResolution",
+    "YResolution",
+];
+
+const INTEGER_TAGS: &[&str] = &[
+    "ISO",
+    "ISOSpeed",
+    "RecommendedExposureIndex",
+    "Rating",
+    "RatingPercent",
+];
+
+/// EXIF dates covered by exiftool's `AllDates` shortcut
+const ALL_DATES: &[&str] = &["DateTimeOriginal", "CreateDate", "ModifyDate"];
+
+/// Largest accepted date shift (about 200 years)
+const MAX_DATE_SHIFT_SECONDS: i64 = 200 * 366 * 24 * 3600;
+
+/// How a tag's value is interpreted and validated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagKind {
+    Text,
+    DateTime,
+    Date,
+    Latitude,
+    Longitude,
+    Altitude,
+    Rational,
+    Integer,
+    List,
+}
+
+pub fn tag_kind(group: &str, name: &str) -> TagKind {
+    if LIST_TAGS.contains(&name) {
+        return TagKind::List;
+    }
+    if RATIONAL_TAGS.contains(&name) {
+        return TagKind::Rational;
+    }
+    if INTEGER_TAGS.contains(&name) {
+        return TagKind::Integer;
+    }
+    match name {
+        "GPSLatitude" => TagKind::Latitude,
+        "GPSLongitude" => TagKind::Longitude,
+        "GPSAltitude" => TagKind::Altitude,
+        "GPSDateStamp" => TagKind::Date,
+        // IPTC stores dates and times in separate tags
+        _ if group == "IPTC" && (name.ends_with("Date") || name == "DateCreated") => TagKind::Date,
+        _ if name.contains("Date") => TagKind::DateTime,
+        _ => TagKind::Text,
+    }
+}
+
+/// Family of an editable group name (IFD0 → EXIF, XMP-dc → XMP)
+fn family_of_group(group: &str) -> Option<&'static str> {
+    match group {
+        "IFD0" | "IFD1" | "ExifIFD" | "GPS" | "InteropIFD" | "SubIFD" => Some("EXIF"),
+        "IPTC" => Some("IPTC"),
+        _ if group.starts_with("XMP") => Some("XMP"),
+        _ => None,
+    }
+}
+
+/// A date with optional time, fraction and UTC offset
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedDate {
+    date: NaiveDate,
+    time: Option<NaiveTime>,
+    /// Fractional seconds including the dot (".123")
+    fraction: Option<String>,
+    /// "+02:00" (Z becomes "+00:00")
+    offset: Option<String>,
+}
+
+static DATE_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(\d{4})[:-](\d{2})[:-](\d{2})(?:[ T](\d{2}):(\d{2})(?::(\d{2}))?(\.\d+)?(Z|[+-]\d{2}:?\d{2})?)?$",
+    )
+    .expect("valid date pattern")
+});
+
+static RATIONAL_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(-?\d+)/(\d+)$").expect("valid rational pattern"));
+
+/// Parse "2024:05:01 12:00:00", "2024-05-01T12:00" or "2024:05:01"
+fn parse_date(value: &str) -> Option<ParsedDate> {
+    let caps = DATE_PATTERN.captures(value.trim())?;
+    let number = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+    let date = NaiveDate::from_ymd_opt(number(1)? as i32, number(2)?, number(3)?)?;
+    let time = match number(4) {
+        Some(hour) => Some(NaiveTime::from_hms_opt(
+            hour,
+            number(5)?,
+            number(6).unwrap_or(0),
+        )?),
+        None => None,
+    };
+    let offset = caps.get(8).map(|m| match m.as_str() {
+        "Z" => "+00:00".to_string(),
+        o if o.contains(':') => o.to_string(),
+        o => format!("{}:{}", &o[..3], &o[3..]),
+    });
+    Some(ParsedDate {
+        date,
+        time,
+        fraction: caps.get(7).map(|m| m.as_str().to_string()),
+        offset,
+    })
+}
+
+impl ParsedDate {
+    fn suffix(&self) -> String {
+        format!(
+            "{}{}",
+            self.fraction.as_deref().unwrap_or(""),
+            self.offset.as_deref().unwrap_or("")
+        )
+    }
+
+    /// exiftool's notation ("2024:05:01 12:00:00")
+    fn to_exif(&self) -> String {
+        match self.time {
+            Some(time) => format!(
+                "{} {}{}",
+                self.date.format("%Y:%m:%d"),
+                time.format("%H:%M:%S"),
+                self.suffix()
+            ),
+            None => self.date.format("%Y:%m:%d").to_string(),
+        }
+    }
+
+    fn to_iso(&self) -> String {
+        match self.time {
+            Some(time) => format!(
+                "{}T{}{}",
+                self.date.format("%Y-%m-%d"),
+                time.format("%H:%M:%S"),
+                self.suffix()
+            ),
+            None => self.date.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Signed degrees from "+37.775", "-122.4" or "37.775 N"
+fn parse_coordinate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (number, negative) = match value.chars().last()?.to_ascii_uppercase() {
+        'N' | 'E' => (&value[..value.len() - 1], false),
+        'S' | 'W' => (&value[..value.len() - 1], true),
+        _ => (value, false),
+    };
+    let degrees: f64 = number.trim().parse().ok()?;
+    degrees
+        .is_finite()
+        .then_some(if negative { -degrees.abs() } else { degrees })
+}
+
+/// Leading number of "123.4 m" or "50.0 mm"
+fn leading_number(value: &str) -> Option<f64> {
+    value
+        .split_whitespace()
+        .next()
+        .and_then(|n| n.parse::<f64>().ok())
+        .filter(|n| n.is_finite())
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(value_to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Type an exiftool JSON value
+fn typed_value(kind: TagKind, value: &Value) -> ExifValue {
+    let text = value_to_string(value);
+    if let Value::Array(items) = value {
+        return ExifValue::List {
+            values: items.iter().map(value_to_string).collect(),
+        };
+    }
+    if let Some(rest) = text.strip_prefix("(Binary data ") {
+        return ExifValue::Binary {
+            size: rest.split_whitespace().next().and_then(|n| n.parse().ok()),
+        };
+    }
+
+    match kind {
+        TagKind::List => {
+            return ExifValue::List { values: vec![text] };
+        }
+        TagKind::Latitude | TagKind::Longitude => {
+            if let Some(degrees) = parse_coordinate(&text) {
+                return ExifValue::Coordinate { value: degrees };
+            }
+        }
+        TagKind::Date | TagKind::DateTime => {
+            if let Some(parsed) = parse_date(&text) {
+                return ExifValue::DateTime {
+                    value: text,
+                    iso: Some(parsed.to_iso()),
+                };
+            }
+        }
+        TagKind::Altitude => {
+            if let Some(metres) = leading_number(&text) {
+                let below = text.to_lowercase().contains("below");
+                return ExifValue::Number {
+                    value: if below { -metres } else { metres },
+                };
+            }
+        }
+        TagKind::Rational => {
+            if let Some(caps) = RATIONAL_PATTERN.captures(text.trim()) {
+                let numerator: i64 = caps[1].parse().unwrap_or(0);
+                let denominator: i64 = caps[2].parse().unwrap_or(0);
+                if denominator != 0 {
+                    return ExifValue::Rational {
+                        numerator,
+                        denominator,
+                        value: numerator as f64 / denominator as f64,
+                    };
+                }
+            }
+            if let Some(number) = leading_number(&text) {
+                return ExifValue::Number { value: number };
+            }
+        }
+        TagKind::Text | TagKind::Integer => {}
+    }
+
+    match value {
+        Value::Number(n) => ExifValue::Number {
+            value: n.as_f64().unwrap_or_default(),
+        },
+        _ => ExifValue::Text { value: text },
+    }
+}
+
+/// Sign an EXIF GPS coordinate from its reference tag
+///
+/// `GPS:GPSLatitude`/`GPSLongitude` are stored unsigned, with the hemisphere
+/// in `GPSLatitudeRef`/`GPSLongitudeRef` ("South", "W", ...).
+fn apply_gps_reference(
+    fields: &serde_json::Map<String, Value>,
+    family: &str,
+    name: &str,
+    value: ExifValue,
+) -> ExifValue {
+    let ExifValue::Coordinate { value: degrees } = value else {
+        return value;
+    };
+    let negative = fields
+        .get(&format!("{}:GPS:{}Ref", family, name))
+        .map(value_to_string)
+        .and_then(|r| r.trim().chars().next())
+        .is_some_and(|c| matches!(c.to_ascii_uppercase(), 'S' | 'W'));
+    ExifValue::Coordinate {
+        value: if negative { -degrees.abs() } else { degrees },
+    }
+}
+
+/// Group exiftool `-json -G0:1` output by family
+pub(super) fn parse_exif_json(object: &Value) -> Vec<ExifGroup> {
+    let mut families: BTreeMap<String, Vec<ExifTag>> = BTreeMap::new();
+    let Some(fields) = object.as_object() else {
+        return Vec::new();
+    };
+
+    for (key, value) in fields {
+        let mut parts = key.splitn(3, ':');
+        let (Some(family), Some(group), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if value.is_null() {
+            continue;
+        }
+        let mut typed = typed_value(tag_kind(group, name), value);
+        if group == "GPS" {
+            typed = apply_gps_reference(fields, family, name, typed);
+        }
+        let editable = EDITABLE_FAMILIES.contains(&family)
+            && family_of_group(group) == Some(family)
+            && !matches!(typed, ExifValue::Binary { .. });
+        families
+            .entry(family.to_string())
+            .or_default()
+            .push(ExifTag {
+                key: format!("{}:{}", group, name),
+                group: group.to_string(),
+                name: name.to_string(),
+                display: value_to_string(value),
+                list: tag_kind(group, name) == TagKind::List,
+                value: typed,
+                editable,
+            });
+    }
+
+    let mut groups: Vec<ExifGroup> = families
+        .into_iter()
+        .map(|(family, tags)| ExifGroup { family, tags })
+        .collect();
+    groups.sort_by_key(|g| {
+        FAMILY_ORDER
+            .iter()
+            .position(|f| *f == g.family)
+            .unwrap_or(FAMILY_ORDER.len())
+    });
+    groups
+}
+
+fn exiftool_command() -> Result<String, String> {
+    find_command("exiftool")
+        .ok_or_else(|| "exiftool is required for EXIF/XMP/IPTC metadata".to_string())
+}
+
+/// Read all tags of a file, grouped by family
+pub fn read_exif(path: &str) -> Result<ExifMetadata, String> {
+    let validated = config::validate_path(Path::new(path))?;
+    let output = Command::new(exiftool_command()?)
+        .args(["-json", "-a", "-G0:1", "-sort", "-c", COORDINATE_FORMAT])
+        .arg(&validated)
+        .output()
+        .map_err(|e| format!("Failed to run exiftool: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "exiftool failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let parsed: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse exiftool output: {}", e))?;
+    let first = parsed
+        .as_array()
+        .and_then(|files| files.first())
+        .ok_or_else(|| "exiftool returned no data".to_string())?;
+    Ok(ExifMetadata {
+        path: validated.to_string_lossy().to_string(),
+        groups: parse_exif_json(first),
+    })
+}
+
+/// Validate a date for a tag of `kind` and convert it to exiftool's notation
+fn normalize_date(family: &str, kind: TagKind, value: &str) -> Result<String, String> {
+    let parsed = parse_date(value).ok_or_else(|| {
+        format!(
+            "'{}' is not a valid date (expected YYYY:MM:DD HH:MM:SS)",
+            value
+        )
+    })?;
+    match kind {
+        TagKind::Date => Ok(parsed.date.format("%Y:%m:%d").to_string()),
+        _ if parsed.time.is_none() && family != "XMP" => {
+            Err(format!("'{}' needs a time as well as a date", value))
+        }
+        // Sub-seconds and offsets live in separate EXIF tags
+        _ if family == "EXIF" && (parsed.fraction.is_some() || parsed.offset.is_some()) => Err(
+            "EXIF dates can't hold fractional seconds or a time zone (use the SubSecTime/OffsetTime tags)"
+                .to_string(),
+        ),
+        _ => Ok(parsed.to_exif()),
+    }
+}
+
+/// exiftool arguments for one edit, validated against the tag's type
+pub fn edit_args(edit: &ExifEdit) -> Result<Vec<String>, String> {
+    let key = edit.key.trim();
+    let (group, name) = key
+        .split_once(':')
+        .ok_or_else(|| format!("Tag '{}' needs a group (e.g. ExifIFD:{})", key, key))?;
+    let family = family_of_group(group).ok_or_else(|| {
+        format!(
+            "Tags in group '{}' can't be edited (EXIF, XMP and IPTC only)",
+            group
+        )
+    })?;
+    if name.is_empty()
+        || name.eq_ignore_ascii_case("all")
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid tag name '{}'", name));
+    }
+
+    let kind = tag_kind(group, name);
+    let values: Vec<&str> = edit
+        .values
+        .iter()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect();
+    let assign = |op: &str, value: &str| format!("-{}{}{}", key, op, value);
+    // EXIF GPS coordinates are unsigned with a separate reference tag
+    let with_ref = group == "GPS" || (family == "XMP" && kind == TagKind::Altitude);
+    let reference = |value: &str| format!("-{}:{}Ref{}", group, name, value);
+
+    match edit.action {
+        ExifEditAction::Delete => {
+            let mut args = vec![assign("=", "")];
+            if with_ref
+                && matches!(
+                    kind,
+                    TagKind::Latitude | TagKind::Longitude | TagKind::Altitude
+                )
+            {
+                args.push(reference("="));
+            }
+            Ok(args)
+        }
+        ExifEditAction::Add | ExifEditAction::Remove => {
+            if kind != TagKind::List {
+                return Err(format!("'{}' is not a list tag", name));
+            }
+            if values.is_empty() {
+                return Err(format!("No values given for '{}'", name));
+            }
+            let op = if edit.action == ExifEditAction::Add {
+                "+="
+            } else {
+                "-="
+            };
+            Ok(values.iter().map(|v| assign(op, v)).collect())
+        }
+        ExifEditAction::Set => {
+            if values.is_empty() {
+                return Err(format!(
+                    "No value given for '{}' (use Delete to remove it)",
+                    name
+                ));
+            }
+            if kind == TagKind::List {
+                return Ok(values.iter().map(|v| assign("=", v)).collect());
+            }
+            let [value] = values[..] else {
+                return Err(format!("'{}' takes a single value", name));
+            };
+
+            let coordinate = |limit: f64, refs: (&str, &str)| -> Result<Vec<String>, String> {
+                let degrees = parse_coordinate(value)
+                    .filter(|d| d.abs() <= limit)
+                    .ok_or_else(|| format!("'{}' is not a valid {} coordinate", value, name))?;
+                if !with_ref {
+                    return Ok(vec![assign("=", &format!("{:.8}", degrees))]);
+                }
+                let direction = if degrees < 0.0 { refs.1 } else { refs.0 };
+                Ok(vec![
+                    assign("=", &format!("{:.8}", degrees.abs())),
+                    reference(&format!("={}", direction)),
+                ])
+            };
+
+            match kind {
+                TagKind::Latitude => coordinate(90.0, ("N", "S")),
+                TagKind::Longitude => coordinate(180.0, ("E", "W")),
+                TagKind::Altitude => {
+                    let metres = leading_number(value)
+                        .ok_or_else(|| format!("'{}' is not a valid altitude", value))?;
+                    let mut args = vec![assign("=", &metres.abs().to_string())];
+                    if with_ref {
+                        // 0 = above sea level, 1 = below
+                        args.push(reference(&format!("#={}", u8::from(metres < 0.0))));
+                    }
+                    Ok(args)
+                }
+                TagKind::Date | TagKind::DateTime => {
+                    Ok(vec![assign("=", &normalize_date(family, kind, value)?)])
+                }
+                TagKind::Rational => {
+                    let valid = RATIONAL_PATTERN
+                        .captures(value)
+                        .map(|caps| &caps[2] != "0" && caps[2].parse::<i64>().is_ok())
+                        .unwrap_or_else(|| value.parse::<f64>().is_ok_and(|n| n.is_finite()));
+                    if !valid {
+                        return Err(format!(
+                            "'{}' is not a valid value for {} (e.g. 1/250 or 5.6)",
+                            value, name
+                        ));
+                    }
+                    Ok(vec![assign("=", value)])
+                }
+                TagKind::Integer => {
+                    let number: i64 = value
+                        .parse()
+                        .map_err(|_| format!("{} must be a whole number", name))?;
+                    Ok(vec![assign("=", &number.to_string())])
+                }
+                TagKind::Text | TagKind::List => Ok(vec![assign("=", value)]),
+            }
+        }
+    }
+}
+
+/// Run exiftool into a temporary copy and replace `path` with it (journaled)
+pub(super) fn rewrite_with_exiftool(
+    path: &Path,
+    args: &[String],
+    description: String,
+) -> Result<(), String> {
+    let temp_output = create_temp_path(path, "exif");
+    let output = Command::new(exiftool_command()?)
+        .arg("-quiet")
+        .arg("-out")
+        .arg(&temp_output)
+        .args(args)
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to run exiftool: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&temp_output);
+        return Err(format!(
+            "exiftool failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    // Nothing is written when no tag changed
+    if !temp_output.exists() {
+        return Ok(());
+    }
+
+    if let Some(backup) = finalize_replacement(path, &temp_output)? {
+        journal::record(
+            JournalOperationKind::MetadataUpdate,
+            description,
+            vec![JournalChange::Rewritten {
+                path: path.to_string_lossy().to_string(),
+                backup: backup.to_string_lossy().to_string(),
+            }],
+        );
+    }
+    invalidate_probe_cache(&path.to_string_lossy());
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Apply structured edits and return the updated tags
+pub fn edit_exif(path: &str, edits: &[ExifEdit]) -> Result<ExifMetadata, String> {
+    let validated = config::validate_path(Path::new(path))?;
+    if edits.is_empty() {
+        return Err("No edits provided".to_string());
+    }
+    let mut args = Vec::new();
+    for edit in edits {
+        args.extend(edit_args(edit)?);
+    }
+
+    rewrite_with_exiftool(
+        &validated,
+        &args,
+        format!(
+            "Edit {} EXIF/XMP/IPTC tag(s) on '{}'",
+            edits.len(),
+            file_name(&validated)
+        ),
+    )?;
+    read_exif(path)
+}
+
+/// exiftool shift notation ("0:0:1 02:00:00") and direction for `seconds`
+fn format_shift(seconds: i64) -> (&'static str, String) {
+    let total = seconds.unsigned_abs();
+    let (days, rest) = (total / 86_400, total % 86_400);
+    let shift = format!(
+        "0:0:{} {:02}:{:02}:{:02}",
+        days,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    );
+    (if seconds < 0 { "-=" } else { "+=" }, shift)
+}
+
+fn find_tag<'a>(metadata: &'a ExifMetadata, group: &str, name: &str) -> Option<&'a ExifTag> {
+    metadata
+        .groups
+        .iter()
+        .flat_map(|g| &g.tags)
+        .find(|t| t.group == group && t.name == name)
+}
+
+fn coordinate_of(metadata: &ExifMetadata, name: &str) -> Option<f64> {
+    match find_tag(metadata, "Composite", name)?.value {
+        ExifValue::Coordinate { value } => Some(value),
+        _ => None,
+    }
+}
+
+/// What a shift does to one file
+#[derive(Debug, Default)]
+struct ShiftPlan {
+    args: Vec<String>,
+    changes: Vec<MetadataChangePreview>,
+    skipped: Vec<SkippedMetadataOperation>,
+}
+
+fn change(key: &str, before: String, after: String) -> MetadataChangePreview {
+    MetadataChangePreview {
+        key: key.to_string(),
+        scope: MetadataScope::File,
+        stream_index: None,
+        before: Some(before),
+        after: Some(after),
+    }
+}
+
+fn skip(key: &str, value: String, reason: &str) -> SkippedMetadataOperation {
+    SkippedMetadataOperation {
+        operation: MetadataOperation {
+            action: MetadataAction::Set,
+            key: key.to_string(),
+            value: Some(value),
+            scope: MetadataScope::File,
+            stream_index: None,
+        },
+        reason: reason.to_string(),
+    }
+}
+
+fn plan_shift(metadata: &ExifMetadata, shift: &ExifShift) -> Result<ShiftPlan, String> {
+    let mut plan = ShiftPlan::default();
+
+    if let Some(seconds) = shift.date_shift_seconds.filter(|s| *s != 0) {
+        let (op, amount) = format_shift(seconds);
+        let delta = chrono::Duration::seconds(seconds);
+        for name in ALL_DATES {
+            let tag =
+                find_tag(metadata, "ExifIFD", name).or_else(|| find_tag(metadata, "IFD0", name));
+            let Some(tag) = tag else { continue };
+            let shifted = parse_date(&tag.display)
+                .and_then(|p| Some(NaiveDateTime::new(p.date, p.time?)))
+                .and_then(|dt| dt.checked_add_signed(delta));
+            if let Some(shifted) = shifted {
+                plan.changes.push(change(
+                    &tag.key,
+                    tag.display.clone(),
+                    shifted.format("%Y:%m:%d %H:%M:%S").to_string(),
+                ));
+            }
+        }
+        if plan.changes.is_empty() {
+            plan.skipped.push(skip(
+                "AllDates",
+                format!("{}{}", op, amount),
+                "No EXIF dates to shift",
+            ));
+        } else {
+            plan.args.push(format!("-AllDates{}{}", op, amount));
+        }
+    }
+
+    let (lat_offset, lon_offset) = (
+        shift.latitude_offset.unwrap_or(0.0),
+        shift.longitude_offset.unwrap_or(0.0),
+    );
+    if lat_offset != 0.0 || lon_offset != 0.0 {
+        let requested = format!("{:+} / {:+}", lat_offset, lon_offset);
+        let position =
+            coordinate_of(metadata, "GPSLatitude").zip(coordinate_of(metadata, "GPSLongitude"));
+        match position {
+            None => plan
+                .skipped
+                .push(skip("GPSPosition", requested, "No GPS position to shift")),
+            Some((lat, _)) if (lat + lat_offset).abs() > 90.0 => plan.skipped.push(skip(
+                "GPSPosition",
+                requested,
+                "Shifted latitude is out of range",
+            )),
+            Some((lat, lon)) => {
+                // Wrap across the antimeridian
+                let mut new_lon = lon + lon_offset;
+                if new_lon > 180.0 {
+                    new_lon -= 360.0;
+                } else if new_lon < -180.0 {
+                    new_lon += 360.0;
+                }
+                let mut groups: Vec<&str> = ["GPS", "XMP-exif"]
+                    .into_iter()
+                    .filter(|g| find_tag(metadata, g, "GPSLatitude").is_some())
+                    .collect();
+                if groups.is_empty() {
+                    groups.push("GPS");
+                }
+                for group in groups {
+                    for (name, before, after) in [
+                        ("GPSLatitude", lat, lat + lat_offset),
+                        ("GPSLongitude", lon, new_lon),
+                    ] {
+                        let key = format!("{}:{}", group, name);
+                        plan.args.extend(edit_args(&ExifEdit {
+                            key: key.clone(),
+                            action: ExifEditAction::Set,
+                            values: vec![format!("{:.8}", after)],
+                        })?);
+                        plan.changes.push(change(
+                            &key,
+                            format!("{:.8}", before),
+                            format!("{:.8}", after),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Shift dates and/or GPS positions across many photos
+pub fn shift_exif(
+    paths: &[String],
+    shift: &ExifShift,
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<BulkMetadataResult, String> {
+    let date_shift = shift.date_shift_seconds.unwrap_or(0);
+    let offsets = [shift.latitude_offset, shift.longitude_offset].map(|o| o.unwrap_or(0.0));
+    if date_shift == 0 && offsets.iter().all(|o| *o == 0.0) {
+        return Err("Nothing to shift".to_string());
+    }
+    if date_shift.abs() > MAX_DATE_SHIFT_SECONDS {
+        return Err("Date shift is too large".to_string());
+    }
+    if offsets.iter().any(|o| !o.is_finite() || o.abs() > 360.0) {
+        return Err("GPS offsets must be within ±360 degrees".to_string());
+    }
+    exiftool_command()?;
+
+    let paths: Vec<&str> = paths.iter().map(|p| p.as_str()).collect();
+    run_file_batch(
+        "EXIF shift",
+        &paths,
+        dry_run,
+        cancelled,
+        on_progress,
+        &mut |_, result| {
+            let plan = plan_shift(&read_exif(&result.path)?, shift)?;
+            result.changes = plan.changes;
+            result.skipped = plan.skipped;
+            if !dry_run && !plan.args.is_empty() {
+                let path = Path::new(&result.path);
+                let description = format!("Shift dates/GPS of '{}'", file_name(path));
+                rewrite_with_exiftool(path, &plan.args, description).map_err(|e| {
+                    warn!("EXIF shift failed for {}: {}", result.path, e);
+                    e
+                })?;
+                result.applied = true;
+            }
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(key: &str, action: ExifEditAction, values: &[&str]) -> ExifEdit {
+        ExifEdit {
+            key: key.to_string(),
+            action,
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_exif_json_groups_and_types() {
+        let groups = parse_exif_json(&json!({
+            "SourceFile": "/photos/a.jpg",
+            "File:System:FileName": "a.jpg",
+            "EXIF:IFD0:Make": "Canon",
+            "EXIF:ExifIFD:DateTimeOriginal": "2024:05:01 12:30:00",
+            "EXIF:ExifIFD:ExposureTime": "1/250",
+            "EXIF:ExifIFD:ISO": 400,
+            "EXIF:IFD1:ThumbnailImage": "(Binary data 5120 bytes, use -b option to extract)",
+            "IPTC:IPTC:Keywords": ["beach", "sunset"],
+            "XMP:XMP-dc:Subject": "beach",
+            "MakerNotes:Canon:LensModel": "EF50mm f/1.8",
+            "EXIF:GPS:GPSLatitude": "+33.85680000",
+            "EXIF:GPS:GPSLatitudeRef": "South",
+            "EXIF:GPS:GPSLongitude": "+151.21530000",
+            "EXIF:GPS:GPSLongitudeRef": "East",
+            "Composite:Composite:GPSLatitude": "-33.85680000",
+        }));
+
+        let families: Vec<&str> = groups.iter().map(|g| g.family.as_str()).collect();
+        assert_eq!(
+            families,
+            ["EXIF", "XMP", "IPTC", "MakerNotes", "Composite", "File"]
+        );
+
+        let exif = &groups[0].tags;
+        let tag = |name: &str| exif.iter().find(|t| t.name == name).unwrap();
+        assert_eq!(tag("DateTimeOriginal").key, "ExifIFD:DateTimeOriginal");
+        assert_eq!(
+            tag("DateTimeOriginal").value,
+            ExifValue::DateTime {
+                value: "2024:05:01 12:30:00".to_string(),
+                iso: Some("2024-05-01T12:30:00".to_string()),
+            }
+        );
+        assert_eq!(
+            tag("ExposureTime").value,
+            ExifValue::Rational {
+                numerator: 1,
+                denominator: 250,
+                value: 0.004,
+            }
+        );
+        assert_eq!(tag("ISO").value, ExifValue::Number { value: 400.0 });
+        assert_eq!(
+            tag("ThumbnailImage").value,
+            ExifValue::Binary { size: Some(5120) }
+        );
+        assert!(!tag("ThumbnailImage").editable);
+        assert!(tag("Make").editable);
+        assert_eq!(
+            tag("GPSLatitude").value,
+            ExifValue::Coordinate { value: -33.8568 }
+        );
+        assert_eq!(
+            tag("GPSLongitude").value,
+            ExifValue::Coordinate { value: 151.2153 }
+        );
+
+        assert!(groups[1].tags[0].list);
+        assert_eq!(
+            groups[2].tags[0].value,
+            ExifValue::List {
+                values: vec!["beach".to_string(), "sunset".to_string()]
+            }
+        );
+        assert!(!groups[3].tags[0].editable);
+        assert_eq!(
+            groups[4].tags[0].value,
+            ExifValue::Coordinate { value: -33.8568 }
+        );
+    }
+
+    #[test]
+    fn test_edit_args_validation() {
+        assert_eq!(
+            edit_args(&edit(
+                "ExifIFD:DateTimeOriginal",
+                ExifEditAction::Set,
+                &["2024-05-01T12:30"]
+            )),
+            Ok(vec![
+                "-ExifIFD:DateTimeOriginal=2024:05:01 12:30:00".to_string()
+            ])
+        );
+        assert!(edit_args(&edit(
+            "ExifIFD:DateTimeOriginal",
+            ExifEditAction::Set,
+            &["2024-02-30 10:00:00"]
+        ))
+        .is_err());
+        assert!(edit_args(&edit(
+            "ExifIFD:DateTimeOriginal",
+            ExifEditAction::Set,
+            &["2024:05:01 12:30:00+02:00"]
+        ))
+        .is_err());
+        assert_eq!(
+            edit_args(&edit(
+                "XMP-xmp:CreateDate",
+                ExifEditAction::Set,
+                &["2024:05:01 12:30:00Z"]
+            )),
+            Ok(vec![
+                "-XMP-xmp:CreateDate=2024:05:01 12:30:00+00:00".to_string()
+            ])
+        );
+        assert_eq!(
+            edit_args(&edit("GPS:GPSLatitude", ExifEditAction::Set, &["-33.8568"])),
+            Ok(vec![
+                "-GPS:GPSLatitude=33.85680000".to_string(),
+                "-GPS:GPSLatitudeRef=S".to_string()
+            ])
+        );
+        assert!(edit_args(&edit("GPS:GPSLatitude", ExifEditAction::Set, &["95"])).is_err());
+        assert!(edit_args(&edit("ExifIFD:ExposureTime", ExifEditAction::Set, &["1/0"])).is_err());
+        assert!(edit_args(&edit("ExifIFD:ISO", ExifEditAction::Set, &["fast"])).is_err());
+        assert!(edit_args(&edit("Canon:LensModel", ExifEditAction::Set, &["x"])).is_err());
+        assert!(edit_args(&edit("DateTimeOriginal", ExifEditAction::Set, &["x"])).is_err());
+        assert!(edit_args(&edit("IFD0:all", ExifEditAction::Delete, &[])).is_err());
+    }
+
+    #[test]
+    fn test_list_edits() {
+        assert_eq!(
+            edit_args(&edit(
+                "IPTC:Keywords",
+                ExifEditAction::Set,
+                &["beach", " ", "sunset"]
+            )),
+            Ok(vec![
+                "-IPTC:Keywords=beach".to_string(),
+                "-IPTC:Keywords=sunset".to_string()
+            ])
+        );
+        assert_eq!(
+            edit_args(&edit("XMP-dc:Subject", ExifEditAction::Remove, &["beach"])),
+            Ok(vec!["-XMP-dc:Subject-=beach".to_string()])
+        );
+        assert!(edit_args(&edit("IFD0:Make", ExifEditAction::Add, &["x"])).is_err());
+        assert!(edit_args(&edit("IFD0:Make", ExifEditAction::Set, &["a", "b"])).is_err());
+    }
+
+    #[test]
+    fn test_plan_shift() {
+        let metadata = ExifMetadata {
+            path: "/photos/a.jpg".to_string(),
+            groups: parse_exif_json(&json!({
+                "EXIF:ExifIFD:DateTimeOriginal": "2024:12:31 23:30:00",
+                "EXIF:IFD0:ModifyDate": "2025:01:01 08:00:00",
+                "EXIF:GPS:GPSLatitude": "+33.85680000",
+                "Composite:Composite:GPSLatitude": "-33.85680000",
+                "Composite:Composite:GPSLongitude": "+179.50000000",
+            })),
+        };
+
+        assert_eq!(format_shift(-90_061), ("-=", "0:0:1 01:01:01".to_string()));
+        let plan = plan_shift(
+            &metadata,
+            &ExifShift {
+                date_shift_seconds: Some(3600),
+                latitude_offset: None,
+                longitude_offset: Some(1.0),
+            },
+        )
+        .unwrap();
+        assert_eq!(plan.args[0], "-AllDates+=0:0:0 01:00:00");
+        assert_eq!(
+            plan.changes[0].after.as_deref(),
+            Some("2025:01:01 00:30:00")
+        );
+        assert_eq!(plan.changes[1].key, "IFD0:ModifyDate");
+        assert!(plan
+            .args
+            .contains(&"-GPS:GPSLongitude=179.50000000".to_string()));
+        assert!(plan.args.contains(&"-GPS:GPSLongitudeRef=W".to_string()));
+        assert!(plan.skipped.is_empty());
+
+        let plan = plan_shift(
+            &ExifMetadata {
+                path: "/photos/b.jpg".to_string(),
+                groups: Vec::new(),
+            },
+            &ExifShift {
+                date_shift_seconds: Some(-60),
+                latitude_offset: Some(0.5),
+                longitude_offset: None,
+            },
+        )
+        .unwrap();
+        assert!(plan.args.is_empty());
+        assert_eq!(plan.skipped.len(), 2);
+    }
+}
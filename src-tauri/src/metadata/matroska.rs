@@ -0,0 +1,704 @@
+//! In-place Matroska tag editing via mkvpropedit
+//!
+//! Changing a title or a track language shouldn't remux a multi-GB file.
+//! When mkvpropedit is installed, `update_metadata` edits Matroska/WebM files
+//! in place: the segment title, track names and languages, and global tags
+//! (written back as a whole from the current ones, with the target type and
+//! language ffprobe encodes in their keys). Anything else falls back to
+//! ffmpeg. Nothing is copied, so instead of a backup the undo journal records
+//! the operations that restore the previous values.
+//!
+//! Track flags (`flag-default`, `flag-forced`) are header properties too, but
+//! metadata operations only carry tags; dispositions aren't edited through
+//! `update_metadata` on any path, so there is nothing to map them from.
+
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use super::{list_metadata, scratch_path};
+use crate::media::{
+    find_command, get_media_streams, invalidate_probe_cache, verify_output, ExpectedOutput,
+};
+use crate::types::{
+    JournalChange, JournalOperationKind, MetadataAction, MetadataEntry, MetadataOperation,
+    MetadataScope, MetadataSnapshot, StreamSummary,
+};
+use crate::{config, files, journal};
+
+const MATROSKA_EXTENSIONS: &[&str] = &["mkv", "mka", "mks", "webm"];
+
+/// Format "tags" ffprobe derives from segment info rather than the Tags element
+const SEGMENT_INFO_KEYS: &[&str] = &["encoder", "creation_time"];
+
+/// Stream tags that are track header properties: (ffprobe key, mkvpropedit property)
+///
+/// Only tags map to properties; see the module docs for track flags.
+const TRACK_PROPERTIES: &[(&str, &str)] = &[("title", "name"), ("language", "language")];
+
+/// Matroska TargetType and its TargetTypeValue
+type Target = (&'static str, u32);
+
+/// Matroska target types and their TargetTypeValue; ffprobe prefixes the
+/// names of tags with a TargetType as `TYPE/NAME`
+const TARGET_TYPES: &[Target] = &[
+    ("COLLECTION", 70),
+    ("EDITION", 60),
+    ("ISSUE", 60),
+    ("VOLUME", 60),
+    ("OPUS", 60),
+    ("SEASON", 60),
+    ("SEQUEL", 60),
+    ("ALBUM", 50),
+    ("OPERA", 50),
+    ("CONCERT", 50),
+    ("MOVIE", 50),
+    ("EPISODE", 50),
+    ("PART", 40),
+    ("SESSION", 40),
+    ("TRACK", 30),
+    ("SONG", 30),
+    ("CHAPTER", 30),
+    ("SUBTRACK", 20),
+    ("MOVEMENT", 20),
+    ("SCENE", 20),
+    ("SHOT", 10),
+];
+
+pub fn is_matroska(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MATROSKA_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// mkvpropedit selector for a stream; ffprobe lists tracks first, then
+/// attachments (which aren't tracks)
+fn track_selector(stream_index: i32, streams: &[StreamSummary]) -> Option<String> {
+    let mut tracks: Vec<i32> = streams
+        .iter()
+        .filter(|s| s.codec_type.as_deref() != Some("attachment"))
+        .map(|s| s.index)
+        .collect();
+    tracks.sort_unstable();
+    tracks
+        .iter()
+        .position(|&i| i == stream_index)
+        .map(|position| format!("track:{}", position + 1))
+}
+
+fn track_property(key: &str) -> Option<&'static str> {
+    TRACK_PROPERTIES
+        .iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(key.trim()))
+        .map(|(_, property)| *property)
+}
+
+fn is_segment_info_key(key: &str) -> bool {
+    SEGMENT_INFO_KEYS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(key.trim()))
+}
+
+/// Whether mkvpropedit can apply all of `operations`
+pub fn matroska_supports(operations: &[MetadataOperation], streams: &[StreamSummary]) -> bool {
+    !operations.is_empty()
+        && operations.iter().all(|op| match op.scope {
+            MetadataScope::Format => !is_segment_info_key(&op.key),
+            MetadataScope::Stream => {
+                track_property(&op.key).is_some()
+                    && op
+                        .stream_index
+                        .and_then(|i| track_selector(i, streams))
+                        .is_some()
+            }
+            MetadataScope::File => false,
+        })
+}
+
+fn existing_tag<'a>(
+    snapshot: &'a MetadataSnapshot,
+    op: &MetadataOperation,
+) -> Option<&'a MetadataEntry> {
+    let entries = match op.scope {
+        MetadataScope::Format => &snapshot.format_tags,
+        _ => &snapshot.stream_tags,
+    };
+    entries.iter().find(|e| {
+        e.key.eq_ignore_ascii_case(op.key.trim())
+            && (op.scope != MetadataScope::Stream || e.stream_index == op.stream_index)
+    })
+}
+
+/// Operations that restore what `operations` change, given the tags before
+pub fn revert_operations(
+    snapshot: &MetadataSnapshot,
+    operations: &[MetadataOperation],
+) -> Vec<MetadataOperation> {
+    let restore = |entry: &MetadataEntry, op: &MetadataOperation| MetadataOperation {
+        action: MetadataAction::Set,
+        key: entry.key.clone(),
+        value: Some(entry.value.clone()),
+        scope: op.scope.clone(),
+        stream_index: op.stream_index,
+    };
+
+    let mut revert = Vec::new();
+    for op in operations.iter().rev() {
+        if op.scope == MetadataScope::Format && op.key.trim() == "*" {
+            revert.extend(
+                snapshot
+                    .format_tags
+                    .iter()
+                    .filter(|e| !is_segment_info_key(&e.key))
+                    .map(|e| restore(e, op)),
+            );
+            continue;
+        }
+        match (existing_tag(snapshot, op), &op.action) {
+            (Some(entry), _) => revert.push(restore(entry, op)),
+            (None, MetadataAction::Set) => revert.push(MetadataOperation {
+                action: MetadataAction::Delete,
+                value: None,
+                ..op.clone()
+            }),
+            (None, MetadataAction::Delete) => {}
+        }
+    }
+    revert
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Split an ffprobe global tag key (`[TYPE/]NAME[-lng]`) into its target
+/// type, tag name and language
+fn parse_global_key(key: &str) -> (Option<Target>, &str, Option<String>) {
+    let (target, rest) = match key.split_once('/') {
+        Some((prefix, rest)) => match TARGET_TYPES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(prefix))
+        {
+            Some(&target) => (Some(target), rest),
+            None => (None, key),
+        },
+        None => (None, key),
+    };
+    match rest.rsplit_once('-') {
+        Some((name, language))
+            if !name.is_empty()
+                && language.len() == 3
+                && language.chars().all(|c| c.is_ascii_alphabetic()) =>
+        {
+            (target, name, Some(language.to_lowercase()))
+        }
+        _ => (target, rest, None),
+    }
+}
+
+/// Global tags as a Matroska tags XML file, one Tag per target type
+/// (no Targets = whole segment)
+fn global_tags_xml(tags: &[(String, String)]) -> String {
+    let mut groups: Vec<(Option<Target>, Vec<String>)> = Vec::new();
+    for (key, value) in tags {
+        let (target, name, language) = parse_global_key(key);
+        let language = language
+            .map(|l| format!("<TagLanguage>{}</TagLanguage>", l))
+            .unwrap_or_default();
+        let simple = format!(
+            "    <Simple><Name>{}</Name><String>{}</String>{}</Simple>\n",
+            xml_escape(name),
+            xml_escape(value),
+            language
+        );
+        match groups.iter_mut().find(|(t, _)| *t == target) {
+            Some((_, simples)) => simples.push(simple),
+            None => groups.push((target, vec![simple])),
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Tags>\n");
+    for (target, simples) in groups {
+        xml.push_str("  <Tag>\n");
+        if let Some((name, value)) = target {
+            xml.push_str(&format!(
+                "    <Targets><TargetTypeValue>{}</TargetTypeValue><TargetType>{}</TargetType></Targets>\n",
+                value, name
+            ));
+        }
+        for simple in simples {
+            xml.push_str(&simple);
+        }
+        xml.push_str("  </Tag>\n");
+    }
+    xml.push_str("</Tags>\n");
+    xml
+}
+
+/// What an edit changes: the title, track properties and global tags
+/// (None = untouched, Some(None) = removed)
+#[derive(Debug, Default, PartialEq)]
+struct MatroskaEdit {
+    title: Option<Option<String>>,
+    tracks: BTreeMap<String, Vec<(&'static str, Option<String>)>>,
+    global_tags: Option<Vec<(String, String)>>,
+}
+
+fn plan_edit(snapshot: &MetadataSnapshot, operations: &[MetadataOperation]) -> MatroskaEdit {
+    let mut edit = MatroskaEdit::default();
+    let mut global: Vec<(String, String)> = snapshot
+        .format_tags
+        .iter()
+        .filter(|e| !is_segment_info_key(&e.key) && !e.key.eq_ignore_ascii_case("title"))
+        .map(|e| (e.key.clone(), e.value.clone()))
+        .collect();
+    let original = global.clone();
+
+    for op in operations {
+        let key = op.key.trim();
+        let value = match op.action {
+            MetadataAction::Set => Some(op.value.clone().unwrap_or_default()),
+            MetadataAction::Delete => None,
+        };
+        match op.scope {
+            MetadataScope::Format if key == "*" => {
+                edit.title = Some(None);
+                global.clear();
+            }
+            MetadataScope::Format if key.eq_ignore_ascii_case("title") => edit.title = Some(value),
+            MetadataScope::Format => {
+                match (
+                    value,
+                    global.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)),
+                ) {
+                    (Some(value), Some(existing)) => existing.1 = value,
+                    // Matroska tag names are conventionally upper case
+                    (Some(value), None) => global.push((key.to_uppercase(), value)),
+                    (None, _) => global.retain(|(k, _)| !k.eq_ignore_ascii_case(key)),
+                }
+            }
+            MetadataScope::Stream => {
+                let selector = op
+                    .stream_index
+                    .and_then(|i| track_selector(i, &snapshot.stream_summaries));
+                if let (Some(selector), Some(property)) = (selector, track_property(key)) {
+                    let changes = edit.tracks.entry(selector).or_default();
+                    changes.retain(|(p, _)| *p != property);
+                    changes.push((property, value));
+                }
+            }
+            MetadataScope::File => {}
+        }
+    }
+
+    // Untouched global tags are left exactly as they are in the file
+    if global != original {
+        edit.global_tags = Some(global);
+    }
+    edit
+}
+
+fn push_change(args: &mut Vec<String>, property: &str, value: &Option<String>) {
+    match value {
+        Some(value) => args.extend(["--set".to_string(), format!("{}={}", property, value)]),
+        None => args.extend(["--delete".to_string(), property.to_string()]),
+    }
+}
+
+/// mkvpropedit arguments (after the file name) for an edit
+fn mkvpropedit_args(edit: &MatroskaEdit, tags_file: &Path) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(title) = &edit.title {
+        args.extend(["--edit".to_string(), "info".to_string()]);
+        push_change(&mut args, "title", title);
+    }
+    for (selector, changes) in &edit.tracks {
+        args.extend(["--edit".to_string(), selector.clone()]);
+        for (property, value) in changes {
+            push_change(&mut args, property, value);
+        }
+    }
+    match &edit.global_tags {
+        // An empty file name removes all global tags
+        Some(tags) if tags.is_empty() => args.extend(["--tags".to_string(), "global:".to_string()]),
+        Some(_) => args.extend([
+            "--tags".to_string(),
+            format!("global:{}", tags_file.to_string_lossy()),
+        ]),
+        None => {}
+    }
+    args
+}
+
+/// Apply `operations` to the file in place with mkvpropedit
+fn run_mkvpropedit(
+    path: &Path,
+    snapshot: &MetadataSnapshot,
+    operations: &[MetadataOperation],
+) -> Result<(), String> {
+    let mkvpropedit =
+        find_command("mkvpropedit").ok_or_else(|| "mkvpropedit is not installed".to_string())?;
+    let edit = plan_edit(snapshot, operations);
+    let tags_file = scratch_path("tags", "xml");
+    let args = mkvpropedit_args(&edit, &tags_file);
+    if args.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(tags) = edit.global_tags.as_ref().filter(|t| !t.is_empty()) {
+        fs::write(&tags_file, global_tags_xml(tags))
+            .map_err(|e| format!("Failed to write tags file: {}", e))?;
+    }
+    debug!("mkvpropedit {} {:?}", path.display(), args);
+    // The file isn't replaced, so copying attributes doesn't apply; put its
+    // times back instead
+    let times = config::preserve_file_attributes()
+        .then(|| files::file_times(path))
+        .flatten();
+    let output = Command::new(&mkvpropedit).arg(path).args(&args).output();
+    let _ = fs::remove_file(&tags_file);
+    if let Some(times) = times {
+        files::restore_file_times(path, times);
+    }
+    let output = output.map_err(|e| format!("Failed to run mkvpropedit: {}", e))?;
+
+    // Exit code 1 means success with warnings
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(()),
+        _ => Err(format!(
+            "mkvpropedit failed: {}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )),
+    }
+}
+
+/// Edit a Matroska file's tags in place, verifying its streams and
+/// journaling the previous values for undo
+pub fn edit_matroska_in_place(
+    path: &Path,
+    snapshot: &MetadataSnapshot,
+    operations: &[MetadataOperation],
+) -> Result<(), String> {
+    let path_str = path.to_string_lossy().to_string();
+    let source = get_media_streams(path_str.clone())?;
+    let revert = revert_operations(snapshot, operations);
+
+    run_mkvpropedit(path, snapshot, operations)?;
+    invalidate_probe_cache(&path_str);
+
+    if let Err(e) = verify_output(path, &ExpectedOutput::unchanged(&source), None) {
+        if let Err(revert_err) = revert_matroska_tags(&path_str, &revert) {
+            warn!(
+                "Failed to restore tags of {} after a failed check: {}",
+                path_str, revert_err
+            );
+        }
+        return Err(e);
+    }
+
+    journal::record(
+        JournalOperationKind::MetadataUpdate,
+        format!(
+            "Edit {} metadata field(s) on '{}'",
+            operations.len(),
+            path.file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default()
+        ),
+        vec![JournalChange::TagsEdited {
+            path: path_str,
+            revert,
+        }],
+    );
+    Ok(())
+}
+
+/// Reapply the previous tag values recorded by an in-place edit
+pub fn revert_matroska_tags(path: &str, revert: &[MetadataOperation]) -> Result<(), String> {
+    if revert.is_empty() {
+        return Ok(());
+    }
+    let snapshot = list_metadata(path.to_string())?;
+    run_mkvpropedit(Path::new(path), &snapshot, revert)?;
+    invalidate_probe_cache(path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::test_support::{self, entry, op, summary};
+
+    fn snapshot() -> MetadataSnapshot {
+        test_support::snapshot(
+            "a.mkv",
+            vec![
+                entry(MetadataScope::Format, "title", "Old", None),
+                entry(MetadataScope::Format, "encoder", "libebml", None),
+                entry(MetadataScope::Format, "ARTIST", "Someone", None),
+            ],
+            vec![entry(MetadataScope::Stream, "language", "eng", Some(1))],
+            vec![
+                summary(0, "video"),
+                summary(1, "audio"),
+                summary(2, "attachment"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_matroska_supports() {
+        let streams = snapshot().stream_summaries;
+        let title = op(
+            MetadataAction::Set,
+            MetadataScope::Format,
+            "title",
+            Some("x"),
+            None,
+        );
+        let lang = op(
+            MetadataAction::Set,
+            MetadataScope::Stream,
+            "language",
+            Some("ger"),
+            Some(1),
+        );
+        let bps = op(
+            MetadataAction::Set,
+            MetadataScope::Stream,
+            "BPS",
+            Some("1"),
+            Some(1),
+        );
+        let attachment = op(
+            MetadataAction::Set,
+            MetadataScope::Stream,
+            "title",
+            Some("x"),
+            Some(2),
+        );
+        let encoder = op(
+            MetadataAction::Set,
+            MetadataScope::Format,
+            "encoder",
+            Some("x"),
+            None,
+        );
+
+        assert!(matroska_supports(&[title, lang], &streams));
+        assert!(!matroska_supports(&[bps], &streams));
+        assert!(!matroska_supports(&[attachment], &streams));
+        assert!(!matroska_supports(&[encoder], &streams));
+        assert!(!matroska_supports(&[], &streams));
+        assert_eq!(track_selector(1, &streams), Some("track:2".to_string()));
+    }
+
+    #[test]
+    fn test_mkvpropedit_args() {
+        let operations = vec![
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "title",
+                Some("New"),
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Stream,
+                "language",
+                Some("ger"),
+                Some(1),
+            ),
+            op(
+                MetadataAction::Delete,
+                MetadataScope::Stream,
+                "title",
+                None,
+                Some(0),
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "artist",
+                Some("A & B"),
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "genre",
+                Some("Rock"),
+                None,
+            ),
+        ];
+        let edit = plan_edit(&snapshot(), &operations);
+        assert_eq!(
+            edit.global_tags,
+            Some(vec![
+                ("ARTIST".to_string(), "A & B".to_string()),
+                ("GENRE".to_string(), "Rock".to_string()),
+            ])
+        );
+        let args = mkvpropedit_args(&edit, Path::new("/tmp/tags.xml"));
+        assert_eq!(
+            args,
+            vec![
+                "--edit",
+                "info",
+                "--set",
+                "title=New",
+                "--edit",
+                "track:1",
+                "--delete",
+                "name",
+                "--edit",
+                "track:2",
+                "--set",
+                "language=ger",
+                "--tags",
+                "global:/tmp/tags.xml",
+            ]
+        );
+        assert!(global_tags_xml(edit.global_tags.as_ref().unwrap())
+            .contains("<Simple><Name>ARTIST</Name><String>A &amp; B</String></Simple>"));
+
+        let wipe = op(
+            MetadataAction::Delete,
+            MetadataScope::Format,
+            "*",
+            None,
+            None,
+        );
+        let args = mkvpropedit_args(&plan_edit(&snapshot(), &[wipe]), Path::new("/t.xml"));
+        assert_eq!(
+            args,
+            vec!["--edit", "info", "--delete", "title", "--tags", "global:"]
+        );
+    }
+
+    #[test]
+    fn test_global_tags_keep_targets_and_languages() {
+        assert_eq!(parse_global_key("ARTIST"), (None, "ARTIST", None));
+        assert_eq!(
+            parse_global_key("ALBUM/TITLE-ger"),
+            (Some(("ALBUM", 50)), "TITLE", Some("ger".to_string()))
+        );
+        assert_eq!(parse_global_key("A-HA"), (None, "A-HA", None));
+
+        let xml = global_tags_xml(&[
+            ("ARTIST".to_string(), "Someone".to_string()),
+            ("ALBUM/TITLE-ger".to_string(), "Titel".to_string()),
+            ("ALBUM/TITLE".to_string(), "Title".to_string()),
+        ]);
+        assert!(xml.contains(
+            "<Tag>\n    <Simple><Name>ARTIST</Name><String>Someone</String></Simple>\n  </Tag>"
+        ));
+        assert!(xml.contains(
+            "<Targets><TargetTypeValue>50</TargetTypeValue><TargetType>ALBUM</TargetType></Targets>\n    \
+             <Simple><Name>TITLE</Name><String>Titel</String><TagLanguage>ger</TagLanguage></Simple>\n    \
+             <Simple><Name>TITLE</Name><String>Title</String></Simple>"
+        ));
+    }
+
+    #[test]
+    fn test_unchanged_global_tags_are_not_rewritten() {
+        let same = op(
+            MetadataAction::Set,
+            MetadataScope::Format,
+            "artist",
+            Some("Someone"),
+            None,
+        );
+        let missing = op(
+            MetadataAction::Delete,
+            MetadataScope::Format,
+            "genre",
+            None,
+            None,
+        );
+        assert_eq!(plan_edit(&snapshot(), &[same, missing]).global_tags, None);
+    }
+
+    #[test]
+    fn test_revert_operations() {
+        let operations = vec![
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "title",
+                Some("New"),
+                None,
+            ),
+            op(
+                MetadataAction::Set,
+                MetadataScope::Format,
+                "genre",
+                Some("Rock"),
+                None,
+            ),
+            op(
+                MetadataAction::Delete,
+                MetadataScope::Stream,
+                "language",
+                None,
+                Some(1),
+            ),
+            op(
+                MetadataAction::Delete,
+                MetadataScope::Format,
+                "comment",
+                None,
+                None,
+            ),
+        ];
+        let revert = revert_operations(&snapshot(), &operations);
+        assert_eq!(
+            revert,
+            vec![
+                op(
+                    MetadataAction::Set,
+                    MetadataScope::Stream,
+                    "language",
+                    Some("eng"),
+                    Some(1)
+                ),
+                op(
+                    MetadataAction::Delete,
+                    MetadataScope::Format,
+                    "genre",
+                    None,
+                    None
+                ),
+                op(
+                    MetadataAction::Set,
+                    MetadataScope::Format,
+                    "title",
+                    Some("Old"),
+                    None
+                ),
+            ]
+        );
+
+        // Reverting a wipe restores every tag except segment info
+        let wipe = op(
+            MetadataAction::Delete,
+            MetadataScope::Format,
+            "*",
+            None,
+            None,
+        );
+        let keys: Vec<String> = revert_operations(&snapshot(), &[wipe])
+            .into_iter()
+            .map(|op| op.key)
+            .collect();
+        assert_eq!(keys, vec!["title", "ARTIST"]);
+    }
+}
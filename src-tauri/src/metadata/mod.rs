@@ -1,3 +1,40 @@
+//! Metadata listing and editing
+//!
+//! - Container/stream tags via ffprobe/ffmpeg, file-level tags via exiftool
+//! - `native`: audio tag writing without a remux (ID3v2, Vorbis comments, MP4 ilst)
+//! - `matroska`: in-place Matroska tag editing via mkvpropedit
+//! - Edits are verified and journaled before replacing the original
+//! - `bulk`: the same edits across many files, with a dry-run preview
+//! - `templates`: saved presets whose values derive from filename and probe data
+//! - `cover_art`: extracting, replacing and removing embedded cover images
+//! - `exif`: grouped, typed EXIF/XMP/IPTC editing and bulk date/GPS shifts
+//! - `scrub`: bulk removal of identifying tags before sharing files
+
+mod bulk;
+mod cover_art;
+mod exif;
+mod matroska;
+mod native;
+mod scrub;
+mod templates;
+#[cfg(test)]
+mod test_support;
+
+pub use bulk::{
+    bulk_update_metadata, plan_file, tag_support, unsupported_reason, FilePlan, TagSupport,
+};
+pub use cover_art::{get_cover_art, remove_cover_art, set_cover_art, ImageType};
+pub use exif::{edit_args, edit_exif, read_exif, shift_exif, tag_kind, TagKind};
+pub use matroska::{
+    edit_matroska_in_place, is_matroska, matroska_supports, revert_matroska_tags, revert_operations,
+};
+pub use scrub::{is_identifying_exif, is_identifying_stream_key, scrub_metadata};
+pub use templates::{
+    apply_metadata_template, delete_template, get_template, get_templates, list_templates,
+    load_template, remove_template, resolve_stream_selector, resolve_template, save_template,
+    store_template, validate_template,
+};
+
 use log::{debug, warn};
 use serde_json::Value;
 use std::fs;
@@ -7,11 +44,14 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::config;
 use crate::files;
-use crate::media::{find_command, invalidate_probe_cache};
+use crate::journal;
+use crate::media::{
+    find_command, get_media_streams, invalidate_probe_cache, verify_output, ExpectedOutput,
+};
 use crate::types::{
-    is_image_extension, is_video_audio_extension, FileMetadata, MetadataAction, MetadataEntry,
-    MetadataOperation, MetadataOrigin, MetadataScope, MetadataSnapshot, MetadataToolAvailability,
-    MetadataUpdateResult, StreamSummary,
+    is_image_extension, is_video_audio_extension, FileMetadata, JournalChange,
+    JournalOperationKind, MetadataAction, MetadataEntry, MetadataOperation, MetadataOrigin,
+    MetadataScope, MetadataSnapshot, MetadataToolAvailability, MetadataUpdateResult, StreamSummary,
 };
 
 fn detect_tools() -> MetadataToolAvailability {
@@ -19,6 +59,7 @@ fn detect_tools() -> MetadataToolAvailability {
         ffmpeg: find_command("ffmpeg").is_some(),
         ffprobe: find_command("ffprobe").is_some(),
         exiftool: find_command("exiftool").is_some(),
+        mkvpropedit: find_command("mkvpropedit").is_some(),
     }
 }
 
@@ -223,14 +264,26 @@ fn create_temp_path(original: &Path, label: &str) -> PathBuf {
     parent.join(format!("{stem}.seer_{label}_{ts}{ext}"))
 }
 
-fn create_backup_path(original: &Path) -> PathBuf {
-    create_temp_path(original, "backup")
+/// Scratch file in the system temp folder, unique per process and call
+fn scratch_path(label: &str, extension: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!(
+        "seer_{}_{}_{}.{}",
+        label,
+        std::process::id(),
+        ts,
+        extension
+    ))
 }
 
 fn apply_ffmpeg_operations(
     input: &Path,
     output: &Path,
     operations: &[MetadataOperation],
+    bitexact: bool,
 ) -> Result<(), String> {
     let ffmpeg_cmd = find_command("ffmpeg").unwrap_or_else(|| "ffmpeg".to_string());
     let mut cmd = Command::new(&ffmpeg_cmd);
@@ -289,6 +342,10 @@ fn apply_ffmpeg_operations(
     if format_wipe {
         cmd.arg("-map_metadata").arg("-1");
     }
+    // Otherwise the muxer writes its own encoder tag back
+    if bitexact {
+        cmd.arg("-fflags").arg("+bitexact");
+    }
 
     for idx in stream_wipes {
         cmd.arg(format!("-map_metadata:s:{}", idx)).arg("-1");
@@ -354,22 +411,12 @@ fn apply_exiftool_operations(
     Ok(())
 }
 
-fn finalize_replacement(original: &Path, new_file: &Path) -> Result<(), String> {
+/// Replace the original with the edited file, returning the undo backup if one was kept
+fn finalize_replacement(original: &Path, new_file: &Path) -> Result<Option<PathBuf>, String> {
     if original == new_file {
-        return Ok(());
-    }
-
-    let backup_path = create_backup_path(original);
-
-    fs::rename(original, &backup_path).map_err(|e| format!("Failed to create backup: {}", e))?;
-
-    if let Err(err) = fs::rename(new_file, original) {
-        let _ = fs::rename(&backup_path, original);
-        return Err(format!("Failed to replace original file: {}", err));
+        return Ok(None);
     }
-
-    let _ = fs::remove_file(&backup_path);
-    Ok(())
+    journal::replace_with_backup(original, new_file)
 }
 
 pub fn list_metadata(path: String) -> Result<MetadataSnapshot, String> {
@@ -422,6 +469,35 @@ pub fn list_metadata(path: String) -> Result<MetadataSnapshot, String> {
 pub fn update_metadata(
     path: String,
     operations: Vec<MetadataOperation>,
+) -> Result<MetadataUpdateResult, String> {
+    update_metadata_with(path, operations, UpdateOptions::default())
+}
+
+/// Extra inputs for `update_metadata_with`
+#[derive(Debug, Default)]
+struct UpdateOptions<'a> {
+    /// Image for a `cover_art` operation that has no value (e.g. a re-encoded
+    /// image that only exists in memory or outside the allowed directories)
+    cover: Option<&'a [u8]>,
+    /// Remux with `-fflags +bitexact` so ffmpeg doesn't write its own
+    /// `encoder` tag; only the privacy scrub asks for this
+    bitexact: bool,
+    /// Always remux with ffmpeg, even where tags could be edited natively or
+    /// with mkvpropedit: those only touch the tags they map (leaving e.g.
+    /// ID3v1/APE tags or the Matroska muxing app behind), while the privacy
+    /// scrub needs everything but the listed streams gone
+    remux: bool,
+}
+
+/// Whether `update_metadata_with` writes `operations` with lofty instead of ffmpeg
+fn writes_natively(path: &Path, operations: &[MetadataOperation], options: &UpdateOptions) -> bool {
+    !options.remux && !operations.is_empty() && native::can_write(path, operations)
+}
+
+fn update_metadata_with(
+    path: String,
+    operations: Vec<MetadataOperation>,
+    options: UpdateOptions,
 ) -> Result<MetadataUpdateResult, String> {
     if operations.is_empty() {
         return Err("No operations provided".to_string());
@@ -445,7 +521,34 @@ pub fn update_metadata(
         .filter(|op| matches!(op.scope, MetadataScope::File))
         .collect();
 
-    if !ffmpeg_ops.is_empty() && !tools.ffmpeg {
+    // Audio tags are written natively when possible; ffmpeg remuxes otherwise
+    let native = writes_natively(&validated, &ffmpeg_ops, &options);
+    if !native && native::touches_cover_art(&ffmpeg_ops) {
+        return Err(
+            "Cover art can only be edited in MP3, FLAC, Ogg/Opus and M4A/MP4 files".to_string(),
+        );
+    }
+
+    // Matroska tags are edited in place when mkvpropedit is available
+    if !native
+        && !options.remux
+        && exif_ops.is_empty()
+        && tools.mkvpropedit
+        && tools.ffprobe
+        && is_matroska(&validated)
+    {
+        let snapshot = list_metadata(path.clone())?;
+        if matroska_supports(&ffmpeg_ops, &snapshot.stream_summaries) {
+            edit_matroska_in_place(&validated, &snapshot, &ffmpeg_ops)?;
+            return Ok(MetadataUpdateResult {
+                success: true,
+                applied: ffmpeg_ops,
+                errors: Vec::new(),
+            });
+        }
+    }
+
+    if !ffmpeg_ops.is_empty() && !native && !tools.ffmpeg {
         return Err("ffmpeg is required to edit container/stream metadata".to_string());
     }
 
@@ -455,10 +558,37 @@ pub fn update_metadata(
 
     let mut temp_files: Vec<PathBuf> = Vec::new();
     let mut current_input = validated.clone();
+    let mut source_streams = None;
+
+    if native {
+        let temp_output = create_temp_path(&validated, "tags");
+        match native::write_tags(&current_input, &temp_output, &ffmpeg_ops, options.cover) {
+            Ok(()) => {
+                temp_files.push(temp_output.clone());
+                current_input = temp_output;
+            }
+            Err(e) if tools.ffmpeg && !native::touches_cover_art(&ffmpeg_ops) => {
+                warn!(
+                    "Native tag write failed for {}, falling back to ffmpeg: {}",
+                    path, e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if !ffmpeg_ops.is_empty() && current_input == validated {
+        // Rewritten audio/video is checked against the source before replacing it
+        let is_video_audio = validated
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(is_video_audio_extension);
+        if is_video_audio {
+            source_streams = Some(get_media_streams(path.clone())?);
+        }
 
-    if !ffmpeg_ops.is_empty() {
         let temp_output = create_temp_path(&validated, "ffmpeg");
-        apply_ffmpeg_operations(&current_input, &temp_output, &ffmpeg_ops)?;
+        apply_ffmpeg_operations(&current_input, &temp_output, &ffmpeg_ops, options.bitexact)?;
         temp_files.push(temp_output.clone());
         current_input = temp_output;
     }
@@ -471,7 +601,32 @@ pub fn update_metadata(
     }
 
     if current_input != validated {
-        finalize_replacement(&validated, &current_input)?;
+        if let Some(source) = &source_streams {
+            let expected = ExpectedOutput::unchanged(source);
+            if let Err(e) = verify_output(&current_input, &expected, None) {
+                for temp in &temp_files {
+                    let _ = fs::remove_file(temp);
+                }
+                return Err(e);
+            }
+        }
+        if let Some(backup) = finalize_replacement(&validated, &current_input)? {
+            journal::record(
+                JournalOperationKind::MetadataUpdate,
+                format!(
+                    "Edit {} metadata field(s) on '{}'",
+                    operations.len(),
+                    validated
+                        .file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default()
+                ),
+                vec![JournalChange::Rewritten {
+                    path: validated.to_string_lossy().to_string(),
+                    backup: backup.to_string_lossy().to_string(),
+                }],
+            );
+        }
     }
 
     for temp in temp_files {
@@ -540,4 +695,79 @@ mod tests {
         assert!(args.contains(&"-map_metadata".to_string()));
         assert!(args.contains(&"-1".to_string()));
     }
+
+    fn delete(key: &str, scope: MetadataScope, stream_index: Option<i32>) -> MetadataOperation {
+        test_support::op(MetadataAction::Delete, scope, key, None, stream_index)
+    }
+
+    #[test]
+    fn test_remux_option_skips_native_writes() {
+        let path = Path::new("/music/a.mp3");
+        let ops = vec![delete("*", MetadataScope::Format, None)];
+
+        assert!(writes_natively(path, &ops, &UpdateOptions::default()));
+        let remux = UpdateOptions {
+            remux: true,
+            ..UpdateOptions::default()
+        };
+        assert!(!writes_natively(path, &ops, &remux));
+    }
+
+    #[test]
+    fn test_scrub_remux_output_has_no_identifying_tags() {
+        let (Some(ffmpeg), Some(ffprobe)) = (find_command("ffmpeg"), find_command("ffprobe"))
+        else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("seer_scrub_remux_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.mkv");
+        let output = dir.join("out.mkv");
+        let status = Command::new(&ffmpeg)
+            .args(["-v", "error", "-y", "-f", "lavfi", "-i", "sine=duration=1"])
+            .args(["-c:a", "pcm_s16le", "-metadata", "title=Private"])
+            .args([
+                "-metadata",
+                "comment=Secret",
+                "-metadata:s:a:0",
+                "handler_name=Phone",
+            ])
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let ops = vec![
+            delete("*", MetadataScope::Format, None),
+            delete("handler_name", MetadataScope::Stream, Some(0)),
+        ];
+        apply_ffmpeg_operations(&input, &output, &ops, true).unwrap();
+
+        let probe = Command::new(&ffprobe)
+            .args([
+                "-v",
+                "error",
+                "-show_entries",
+                "format_tags:stream_tags",
+                "-of",
+                "json",
+            ])
+            .arg(&output)
+            .output()
+            .unwrap();
+        let json: Value = serde_json::from_slice(&probe.stdout).unwrap();
+        let keys = |tags: &Value| -> Vec<String> {
+            tags.as_object()
+                .map(|t| t.keys().map(|k| k.to_lowercase()).collect())
+                .unwrap_or_default()
+        };
+        let format_tags = keys(&json["format"]["tags"]);
+        let stream_tags = keys(&json["streams"][0]["tags"]);
+        let _ = fs::remove_dir_all(&dir);
+
+        for key in ["title", "comment", "encoder"] {
+            assert!(!format_tags.contains(&key.to_string()), "{} kept", key);
+        }
+        assert!(!stream_tags.contains(&"handler_name".to_string()));
+    }
 }
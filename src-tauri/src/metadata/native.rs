@@ -0,0 +1,386 @@
+//! Native tag writing for audio containers (no ffmpeg remux)
+//!
+//! MP3 (ID3v2.3/2.4), FLAC/Ogg/Opus (Vorbis comments) and M4A/MP4 (ilst
+//! atoms) tags are edited with lofty on a copy of the file, so only the tag
+//! block changes and the audio is never re-muxed. `update_metadata` uses this
+//! when every container-level operation maps to a native tag and falls back
+//! to ffmpeg otherwise. The `cover_art` key sets (from an image path, which
+//! must be in an allowed directory, or from image bytes passed alongside) or
+//! removes the embedded cover, replacing any picture in the tag, and is only
+//! available natively.
+
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, FileType, TaggedFile, TaggedFileExt};
+use lofty::picture::{Picture, PictureType};
+use lofty::tag::{ItemKey, Tag, TagExt, TagType};
+use log::debug;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::config;
+use crate::types::{MetadataAction, MetadataOperation, MetadataScope};
+
+/// Pseudo tag key for the embedded front cover (value: path of an image file)
+pub const COVER_ART_KEY: &str = "cover_art";
+
+/// Duration drift allowed between original and retagged file (MP3 durations
+/// are estimated and can shift slightly with the tag size)
+const DURATION_TOLERANCE_MS: u128 = 50;
+
+/// Whether files with this extension can be tagged natively
+pub fn supports_extension(extension: &str) -> bool {
+    matches!(
+        extension.to_lowercase().as_str(),
+        "mp3" | "flac" | "ogg" | "oga" | "opus" | "m4a" | "m4b" | "mp4"
+    )
+}
+
+/// Tag format written for files with this extension
+fn tag_type_for(extension: &str) -> Option<TagType> {
+    match extension.to_lowercase().as_str() {
+        "mp3" => Some(TagType::Id3v2),
+        "flac" | "ogg" | "oga" | "opus" => Some(TagType::VorbisComments),
+        "m4a" | "m4b" | "mp4" => Some(TagType::Mp4Ilst),
+        _ => None,
+    }
+}
+
+/// Map an ffmpeg-style tag key to a lofty item key for `tag_type`
+///
+/// Vorbis comments take arbitrary keys; ID3v2 and MP4 only mapped ones.
+pub fn item_key(tag_type: TagType, key: &str) -> Option<ItemKey> {
+    let mapped = match key.trim().to_lowercase().as_str() {
+        "title" => ItemKey::TrackTitle,
+        "artist" => ItemKey::TrackArtist,
+        "album" => ItemKey::AlbumTitle,
+        "album_artist" | "albumartist" => ItemKey::AlbumArtist,
+        "date" => ItemKey::RecordingDate,
+        "year" => ItemKey::Year,
+        "genre" => ItemKey::Genre,
+        "comment" => ItemKey::Comment,
+        "track" | "tracknumber" => ItemKey::TrackNumber,
+        "disc" | "discnumber" => ItemKey::DiscNumber,
+        "composer" => ItemKey::Composer,
+        "lyricist" => ItemKey::Lyricist,
+        "copyright" => ItemKey::CopyrightMessage,
+        "lyrics" => ItemKey::Lyrics,
+        "encoder" => ItemKey::EncoderSoftware,
+        "publisher" => ItemKey::Publisher,
+        "label" => ItemKey::Label,
+        "grouping" => ItemKey::ContentGroup,
+        "description" => ItemKey::Description,
+        "language" => ItemKey::Language,
+        "bpm" => ItemKey::Bpm,
+        "isrc" => ItemKey::Isrc,
+        "compilation" => ItemKey::FlagCompilation,
+        _ => match ItemKey::from_key(tag_type, key.trim()) {
+            ItemKey::Unknown(_) if tag_type == TagType::VorbisComments => {
+                ItemKey::Unknown(key.trim().to_uppercase())
+            }
+            ItemKey::Unknown(_) => return None,
+            known => known,
+        },
+    };
+    Some(mapped)
+}
+
+/// Whether `operations` on the file at `path` can all be written natively
+pub fn can_write(path: &Path, operations: &[MetadataOperation]) -> bool {
+    let Some(tag_type) = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(tag_type_for)
+    else {
+        return false;
+    };
+    !operations.is_empty()
+        && operations.iter().all(|op| {
+            let key = op.key.trim();
+            op.scope == MetadataScope::Format
+                && (key == "*"
+                    || key.eq_ignore_ascii_case(COVER_ART_KEY)
+                    || item_key(tag_type, key).is_some())
+        })
+}
+
+/// Whether any operation edits the cover art (which ffmpeg can't do here)
+pub fn touches_cover_art(operations: &[MetadataOperation]) -> bool {
+    operations
+        .iter()
+        .any(|op| op.key.trim().eq_ignore_ascii_case(COVER_ART_KEY))
+}
+
+/// Split "3/12" into number and total
+fn split_total(value: &str) -> (String, Option<String>) {
+    match value.split_once('/') {
+        Some((number, total)) => (
+            number.trim().to_string(),
+            Some(total.trim().to_string()).filter(|t| !t.is_empty()),
+        ),
+        None => (value.trim().to_string(), None),
+    }
+}
+
+fn load_picture(image_path: &str) -> Result<Picture, String> {
+    let validated = config::validate_path(Path::new(image_path))?;
+    let mut file = fs::File::open(&validated)
+        .map_err(|e| format!("Failed to open cover image '{}': {}", image_path, e))?;
+    let mut picture = Picture::from_reader(&mut file)
+        .map_err(|e| format!("Unsupported cover image '{}': {}", image_path, e))?;
+    picture.set_pic_type(PictureType::CoverFront);
+    Ok(picture)
+}
+
+fn picture_from_bytes(bytes: &[u8]) -> Result<Picture, String> {
+    let mut picture = Picture::from_reader(&mut &bytes[..])
+        .map_err(|e| format!("Unsupported cover image: {}", e))?;
+    picture.set_pic_type(PictureType::CoverFront);
+    Ok(picture)
+}
+
+/// Apply one operation; `cover` is the image set by a `cover_art` operation
+/// without a value
+fn apply_operation(
+    tag: &mut Tag,
+    op: &MetadataOperation,
+    cover: Option<&[u8]>,
+) -> Result<(), String> {
+    let key = op.key.trim();
+    let value = op.value.clone().unwrap_or_default();
+
+    if key.eq_ignore_ascii_case(COVER_ART_KEY) {
+        // Whatever picture is embedded is shown as the cover, so setting or
+        // removing it replaces all of them, not only a front cover
+        while !tag.pictures().is_empty() {
+            tag.remove_picture(0);
+        }
+        if op.action == MetadataAction::Set {
+            let picture = match (op.value.as_deref(), cover) {
+                (None, Some(bytes)) => picture_from_bytes(bytes)?,
+                _ => load_picture(&value)?,
+            };
+            tag.push_picture(picture);
+        }
+        return Ok(());
+    }
+
+    if key == "*" {
+        if op.action == MetadataAction::Delete {
+            // Like ffmpeg's -map_metadata -1, the cover (a stream there) stays
+            let pictures = tag.pictures().to_vec();
+            tag.clear();
+            for picture in pictures {
+                tag.push_picture(picture);
+            }
+        }
+        return Ok(());
+    }
+
+    let item = item_key(tag.tag_type(), key)
+        .ok_or_else(|| format!("Tag '{}' can't be written natively", key))?;
+    let total = match item {
+        ItemKey::TrackNumber => Some(ItemKey::TrackTotal),
+        ItemKey::DiscNumber => Some(ItemKey::DiscTotal),
+        _ => None,
+    };
+
+    match op.action {
+        MetadataAction::Delete => {
+            tag.remove_key(&item);
+            if let Some(total) = total {
+                tag.remove_key(&total);
+            }
+        }
+        MetadataAction::Set => match total {
+            Some(total_key) => {
+                let (number, total) = split_total(&value);
+                tag.insert_text(item, number);
+                match total {
+                    Some(total) => {
+                        tag.insert_text(total_key, total);
+                    }
+                    None => {
+                        tag.remove_key(&total_key);
+                    }
+                }
+            }
+            None => {
+                tag.insert_text(item, value);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Whether an MP3 starts with an ID3v2.3 tag (kept at 2.3 when rewriting)
+fn has_id3v23(path: &Path) -> bool {
+    let mut header = [0u8; 4];
+    fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok()
+        && &header[..3] == b"ID3"
+        && header[3] == 3
+}
+
+fn read_tagged(path: &Path) -> Result<TaggedFile, String> {
+    lofty::read_from_path(path).map_err(|e| format!("Failed to read tags: {}", e))
+}
+
+/// Check the retagged file still parses with the same audio properties
+fn verify_retagged(original: &TaggedFile, output: &Path) -> Result<(), String> {
+    let rewritten = read_tagged(output)?;
+    let (before, after) = (original.properties(), rewritten.properties());
+    if before.sample_rate() != after.sample_rate() || before.channels() != after.channels() {
+        return Err("Retagged file has different audio properties".to_string());
+    }
+    let drift = before
+        .duration()
+        .as_millis()
+        .abs_diff(after.duration().as_millis());
+    if drift > DURATION_TOLERANCE_MS {
+        return Err(format!("Retagged file duration changed by {} ms", drift));
+    }
+    Ok(())
+}
+
+/// Write `operations` into a copy of `input` at `output`, leaving `input` untouched
+///
+/// `cover` is the image for a `cover_art` operation that has no value.
+pub fn write_tags(
+    input: &Path,
+    output: &Path,
+    operations: &[MetadataOperation],
+    cover: Option<&[u8]>,
+) -> Result<(), String> {
+    let tag_type = input
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(tag_type_for)
+        .ok_or_else(|| "File type can't be tagged natively".to_string())?;
+    let original = read_tagged(input)?;
+    // Ogg FLAC and similar oddities are left to ffmpeg
+    if !original.supports_tag_type(tag_type) {
+        return Err(format!(
+            "{:?} files don't support {:?} tags",
+            original.file_type(),
+            tag_type
+        ));
+    }
+
+    fs::copy(input, output).map_err(|e| format!("Failed to copy file for tagging: {}", e))?;
+    let id3v23 = original.file_type() == FileType::Mpeg && has_id3v23(input);
+    let result = retag_copy(output, tag_type, operations, cover, id3v23)
+        .and_then(|()| verify_retagged(&original, output));
+
+    match &result {
+        Ok(()) => debug!(
+            "Natively wrote {} tag operation(s) to {}",
+            operations.len(),
+            output.display()
+        ),
+        Err(_) => {
+            let _ = fs::remove_file(output);
+        }
+    }
+    result
+}
+
+fn retag_copy(
+    output: &Path,
+    tag_type: TagType,
+    operations: &[MetadataOperation],
+    cover: Option<&[u8]>,
+    id3v23: bool,
+) -> Result<(), String> {
+    let mut tagged = read_tagged(output)?;
+    if tagged.tag(tag_type).is_none() {
+        tagged.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged
+        .tag_mut(tag_type)
+        .ok_or_else(|| "Failed to create tag".to_string())?;
+    for op in operations {
+        apply_operation(tag, op, cover)?;
+    }
+
+    tagged
+        .save_to_path(output, WriteOptions::default().use_id3v23(id3v23))
+        .map_err(|e| format!("Failed to write tags: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(action: MetadataAction, scope: MetadataScope, key: &str) -> MetadataOperation {
+        MetadataOperation {
+            action,
+            key: key.to_string(),
+            value: Some("x".to_string()),
+            scope,
+            stream_index: None,
+        }
+    }
+
+    #[test]
+    fn test_item_key_mapping() {
+        assert_eq!(item_key(TagType::Id3v2, "Title"), Some(ItemKey::TrackTitle));
+        assert_eq!(
+            item_key(TagType::Mp4Ilst, "album_artist"),
+            Some(ItemKey::AlbumArtist)
+        );
+        assert_eq!(
+            item_key(TagType::VorbisComments, "my_tag"),
+            Some(ItemKey::Unknown("MY_TAG".to_string()))
+        );
+        assert_eq!(item_key(TagType::Mp4Ilst, "my_tag"), None);
+    }
+
+    #[test]
+    fn test_can_write() {
+        let title = op(MetadataAction::Set, MetadataScope::Format, "title");
+        let cover = op(MetadataAction::Delete, MetadataScope::Format, "cover_art");
+        let stream = op(MetadataAction::Set, MetadataScope::Stream, "language");
+        let wipe = op(MetadataAction::Delete, MetadataScope::Format, "*");
+
+        assert!(can_write(
+            Path::new("/music/a.mp3"),
+            &[title.clone(), cover.clone(), wipe]
+        ));
+        assert!(can_write(
+            Path::new("/music/a.FLAC"),
+            std::slice::from_ref(&title)
+        ));
+        assert!(!can_write(
+            Path::new("/music/a.mp3"),
+            &[title.clone(), stream]
+        ));
+        assert!(!can_write(Path::new("/video/a.mkv"), &[title]));
+        assert!(!can_write(Path::new("/music/a.mp3"), &[]));
+        assert!(touches_cover_art(&[cover]));
+    }
+
+    #[test]
+    fn test_split_total() {
+        assert_eq!(
+            split_total("3/12"),
+            ("3".to_string(), Some("12".to_string()))
+        );
+        assert_eq!(split_total(" 3 "), ("3".to_string(), None));
+        assert_eq!(split_total("3/"), ("3".to_string(), None));
+    }
+
+    #[test]
+    fn test_cover_art_delete_removes_every_picture() {
+        let mut tag = Tag::new(TagType::Id3v2);
+        for pic_type in [PictureType::Other, PictureType::CoverFront] {
+            tag.push_picture(Picture::new_unchecked(pic_type, None, None, vec![0]));
+        }
+        let delete = op(MetadataAction::Delete, MetadataScope::Format, "cover_art");
+
+        apply_operation(&mut tag, &delete, None).unwrap();
+
+        assert!(tag.pictures().is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+//! Privacy scrub: strip identifying metadata before files are shared
+//!
+//! Images are cleaned with exiftool: GPS, serial numbers, camera/lens
+//! make and model, owner/author names, software tags, XMP edit history
+//! (xmpMM) and maker notes.
+//! Audio/video loses every container tag (`-map_metadata -1`) and the
+//! identifying stream tags (encoder, handler, creation time, location, ...)
+//! while stream languages and titles stay. It is always a full ffmpeg remux,
+//! never a native or mkvpropedit tag edit, which would leave other tag
+//! formats and header fields behind; the remux runs with
+//! `-fflags +bitexact` so ffmpeg doesn't add an encoder tag of its own. Tags on the keep list
+//! (`config::scrub_keep_tags`, e.g. copyright) are never removed, and the
+//! preview lists exactly which tags each file loses.
+
+use log::warn;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use super::bulk::run_file_batch;
+use super::exif::{read_exif, rewrite_with_exiftool};
+use super::{list_metadata, update_metadata_with, UpdateOptions};
+use crate::config;
+use crate::types::{
+    is_image_extension, BulkMetadataResult, ExifMetadata, MetadataAction, MetadataChangePreview,
+    MetadataEntry, MetadataOperation, MetadataScope, MetadataSnapshot, SkippedMetadataOperation,
+};
+
+/// Container "tags" that describe the format and are rewritten by the muxer
+const STRUCTURAL_FORMAT_KEYS: &[&str] = &["major_brand", "minor_version", "compatible_brands"];
+
+const OWNER_TAGS: &[&str] = &[
+    "OwnerName",
+    "CameraOwnerName",
+    "Artist",
+    "Author",
+    "XPAuthor",
+    "Creator",
+    "By-line",
+    "CaptionWriter",
+    "Writer-Editor",
+    "LastModifiedBy",
+];
+
+const SOFTWARE_TAGS: &[&str] = &[
+    "Software",
+    "ProcessingSoftware",
+    "CreatorTool",
+    "HostComputer",
+    "OriginatingProgram",
+    "ProgramVersion",
+    "Encoder",
+    "EncodedBy",
+];
+
+/// Camera and lens identification, matching the "make"/"model" stream keys
+const DEVICE_TAGS: &[&str] = &[
+    "Make",
+    "Model",
+    "LensMake",
+    "LensModel",
+    "UniqueCameraModel",
+];
+
+/// Groups removed with `-GROUP:all=` when nothing in them is kept
+const WHOLE_GROUPS: &[&str] = &["GPS", "XMP-xmpMM"];
+
+/// Stream tags that identify the device or software
+const IDENTIFYING_STREAM_KEYS: &[&str] = &["encoder", "handler_name", "vendor_id", "creation_time"];
+
+/// Substrings of other identifying stream tag keys
+const IDENTIFYING_KEY_PARTS: &[&str] = &[
+    "gps",
+    "location",
+    "serial",
+    "owner",
+    "author",
+    "make",
+    "model",
+    "software",
+    "writing_app",
+    "com.apple",
+    "com.android",
+];
+
+/// Whether an exiftool tag identifies the photographer, device or workflow
+pub fn is_identifying_exif(family: &str, group: &str, name: &str) -> bool {
+    if family == "MakerNotes" {
+        return true;
+    }
+    if !matches!(family, "EXIF" | "XMP" | "IPTC") {
+        return false;
+    }
+    group == "GPS"
+        || group == "XMP-xmpMM"
+        || name.starts_with("GPS")
+        || name.contains("Serial")
+        || name == "ImageUniqueID"
+        || name == "DocumentAncestors"
+        || name.starts_with("CreatorContact")
+        || DEVICE_TAGS.contains(&name)
+        || OWNER_TAGS.contains(&name)
+        || SOFTWARE_TAGS.contains(&name)
+}
+
+/// Whether a stream tag identifies the device or software
+pub fn is_identifying_stream_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    IDENTIFYING_STREAM_KEYS.contains(&key.as_str())
+        || IDENTIFYING_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+fn is_kept(keep: &[String], key: &str, name: &str) -> bool {
+    keep.iter()
+        .any(|k| k.eq_ignore_ascii_case(name) || k.eq_ignore_ascii_case(key))
+}
+
+fn removal(
+    key: &str,
+    scope: MetadataScope,
+    stream_index: Option<i32>,
+    before: &str,
+) -> MetadataChangePreview {
+    MetadataChangePreview {
+        key: key.to_string(),
+        scope,
+        stream_index,
+        before: Some(before.to_string()),
+        after: None,
+    }
+}
+
+fn delete_op(key: &str, scope: MetadataScope, stream_index: Option<i32>) -> MetadataOperation {
+    MetadataOperation {
+        action: MetadataAction::Delete,
+        key: key.to_string(),
+        value: None,
+        scope,
+        stream_index,
+    }
+}
+
+fn kept(operation: MetadataOperation) -> SkippedMetadataOperation {
+    SkippedMetadataOperation {
+        operation,
+        reason: "On the keep list".to_string(),
+    }
+}
+
+/// What a scrub removes from one image
+#[derive(Debug, Default)]
+struct ImageScrub {
+    args: Vec<String>,
+    changes: Vec<MetadataChangePreview>,
+    skipped: Vec<SkippedMetadataOperation>,
+}
+
+fn plan_image_scrub(metadata: &ExifMetadata, keep: &[String]) -> ImageScrub {
+    let mut plan = ImageScrub::default();
+    let identifying = metadata.groups.iter().flat_map(|g| {
+        g.tags
+            .iter()
+            .filter(|t| is_identifying_exif(&g.family, &t.group, &t.name))
+            .map(move |t| (g.family.as_str(), t))
+    });
+    let kept_tags: Vec<_> = identifying
+        .clone()
+        .filter(|(_, t)| is_kept(keep, &t.key, &t.name))
+        .collect();
+    // Maker notes are one block: keeping any of its tags keeps all of them
+    let keep_maker_notes = kept_tags.iter().any(|(f, _)| *f == "MakerNotes");
+    let group_kept = |group: &str| kept_tags.iter().any(|(_, t)| t.group == group);
+
+    for (_, tag) in &kept_tags {
+        plan.skipped
+            .push(kept(delete_op(&tag.key, MetadataScope::File, None)));
+    }
+    for (family, tag) in identifying {
+        if is_kept(keep, &tag.key, &tag.name) || (family == "MakerNotes" && keep_maker_notes) {
+            continue;
+        }
+        plan.changes
+            .push(removal(&tag.key, MetadataScope::File, None, &tag.display));
+
+        let arg = if family == "MakerNotes" {
+            "-MakerNotes:all=".to_string()
+        } else if WHOLE_GROUPS.contains(&tag.group.as_str()) && !group_kept(&tag.group) {
+            format!("-{}:all=", tag.group)
+        } else {
+            format!("-{}=", tag.key)
+        };
+        if !plan.args.contains(&arg) {
+            plan.args.push(arg);
+        }
+    }
+    plan
+}
+
+/// Operations removing container tags and identifying stream tags, with previews
+fn plan_media_scrub(
+    snapshot: &MetadataSnapshot,
+    keep: &[String],
+) -> (
+    Vec<MetadataOperation>,
+    Vec<MetadataChangePreview>,
+    Vec<SkippedMetadataOperation>,
+) {
+    let mut operations = Vec::new();
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+
+    let format_tags: Vec<&MetadataEntry> = snapshot
+        .format_tags
+        .iter()
+        .filter(|e| !STRUCTURAL_FORMAT_KEYS.contains(&e.key.to_lowercase().as_str()))
+        .collect();
+    let (kept_format, removed_format): (Vec<&MetadataEntry>, Vec<&MetadataEntry>) = format_tags
+        .into_iter()
+        .partition(|e| is_kept(keep, &e.key, &e.key));
+    for entry in &kept_format {
+        skipped.push(kept(delete_op(&entry.key, MetadataScope::Format, None)));
+    }
+    if !removed_format.is_empty() {
+        if kept_format.is_empty() {
+            operations.push(delete_op("*", MetadataScope::Format, None));
+        } else {
+            operations.extend(
+                removed_format
+                    .iter()
+                    .map(|e| delete_op(&e.key, MetadataScope::Format, None)),
+            );
+        }
+        changes.extend(
+            removed_format
+                .iter()
+                .map(|e| removal(&e.key, MetadataScope::Format, None, &e.value)),
+        );
+    }
+
+    for entry in &snapshot.stream_tags {
+        if !is_identifying_stream_key(&entry.key) {
+            continue;
+        }
+        let op = delete_op(&entry.key, MetadataScope::Stream, entry.stream_index);
+        if is_kept(keep, &entry.key, &entry.key) {
+            skipped.push(kept(op));
+            continue;
+        }
+        operations.push(op);
+        changes.push(removal(
+            &entry.key,
+            MetadataScope::Stream,
+            entry.stream_index,
+            &entry.value,
+        ));
+    }
+
+    (operations, changes, skipped)
+}
+
+/// Strip identifying metadata from many files, keeping `keep` (or the saved keep list)
+pub fn scrub_metadata(
+    paths: &[String],
+    keep: Option<Vec<String>>,
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<BulkMetadataResult, String> {
+    let keep = keep.unwrap_or_else(config::scrub_keep_tags);
+    let paths: Vec<&str> = paths.iter().map(|p| p.as_str()).collect();
+
+    run_file_batch(
+        "Privacy scrub",
+        &paths,
+        dry_run,
+        cancelled,
+        on_progress,
+        &mut |_, result| {
+            let path = Path::new(&result.path);
+            let extension = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            let applied = if is_image_extension(&extension) {
+                let plan = plan_image_scrub(&read_exif(&result.path)?, &keep);
+                result.changes = plan.changes;
+                result.skipped = plan.skipped;
+                if dry_run || plan.args.is_empty() {
+                    return Ok(());
+                }
+                let description = format!(
+                    "Scrub identifying metadata from '{}'",
+                    path.file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default()
+                );
+                rewrite_with_exiftool(path, &plan.args, description)
+            } else {
+                let snapshot = list_metadata(result.path.clone())?;
+                let (operations, changes, skipped) = plan_media_scrub(&snapshot, &keep);
+                result.changes = changes;
+                result.skipped = skipped;
+                if dry_run || operations.is_empty() {
+                    return Ok(());
+                }
+                update_metadata_with(
+                    result.path.clone(),
+                    operations,
+                    UpdateOptions {
+                        bitexact: true,
+                        remux: true,
+                        ..UpdateOptions::default()
+                    },
+                )
+                .map(|_| ())
+            };
+
+            applied.map_err(|e| {
+                warn!("Privacy scrub failed for {}: {}", result.path, e);
+                e
+            })?;
+            result.applied = true;
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::exif::parse_exif_json;
+    use crate::metadata::test_support::{entry, snapshot};
+    use serde_json::json;
+
+    #[test]
+    fn test_identifying_tags() {
+        assert!(is_identifying_exif("EXIF", "GPS", "GPSLatitude"));
+        assert!(is_identifying_exif("EXIF", "ExifIFD", "BodySerialNumber"));
+        assert!(is_identifying_exif("XMP", "XMP-xmpMM", "HistoryAction"));
+        assert!(is_identifying_exif("MakerNotes", "Canon", "LensModel"));
+        assert!(is_identifying_exif("IPTC", "IPTC", "By-line"));
+        assert!(is_identifying_exif("EXIF", "IFD0", "Model"));
+        assert!(is_identifying_exif("EXIF", "ExifIFD", "LensModel"));
+        assert!(!is_identifying_exif("EXIF", "IFD0", "Copyright"));
+        assert!(!is_identifying_exif("EXIF", "IFD0", "Orientation"));
+        assert!(!is_identifying_exif(
+            "Composite",
+            "Composite",
+            "GPSPosition"
+        ));
+
+        assert!(is_identifying_stream_key("handler_name"));
+        assert!(is_identifying_stream_key("com.apple.quicktime.make"));
+        assert!(is_identifying_stream_key("_STATISTICS_WRITING_APP"));
+        assert!(!is_identifying_stream_key("language"));
+        assert!(!is_identifying_stream_key("title"));
+    }
+
+    #[test]
+    fn test_plan_image_scrub() {
+        let metadata = ExifMetadata {
+            path: "/photos/a.jpg".to_string(),
+            groups: parse_exif_json(&json!({
+                "EXIF:IFD0:Artist": "Jane Doe",
+                "EXIF:IFD0:Make": "Canon",
+                "EXIF:IFD0:Copyright": "(c) Jane Doe",
+                "EXIF:IFD0:Software": "Photo Editor 2.1",
+                "EXIF:IFD0:Orientation": "Horizontal (normal)",
+                "EXIF:GPS:GPSLatitude": "+33.85680000",
+                "EXIF:GPS:GPSLatitudeRef": "South",
+                "XMP:XMP-xmpMM:DocumentID": "xmp.did:1234",
+                "MakerNotes:Canon:InternalSerialNumber": "XA1234",
+                "MakerNotes:Canon:LensModel": "EF50mm f/1.8",
+            })),
+        };
+
+        let plan = plan_image_scrub(&metadata, &["Software".to_string()]);
+        let mut args = plan.args.clone();
+        args.sort();
+        assert_eq!(
+            args,
+            [
+                "-GPS:all=",
+                "-IFD0:Artist=",
+                "-IFD0:Make=",
+                "-MakerNotes:all=",
+                "-XMP-xmpMM:all="
+            ]
+        );
+        let removed: Vec<&str> = plan.changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(removed.len(), 7);
+        assert!(removed.contains(&"Canon:LensModel"));
+        assert!(!removed.contains(&"IFD0:Copyright"));
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].operation.key, "IFD0:Software");
+
+        // Keeping a maker note keeps the whole block
+        let plan = plan_image_scrub(&metadata, &["LensModel".to_string()]);
+        assert!(!plan.args.contains(&"-MakerNotes:all=".to_string()));
+        assert!(plan.changes.iter().all(|c| !c.key.starts_with("Canon:")));
+    }
+
+    #[test]
+    fn test_plan_media_scrub() {
+        let streams = vec![
+            entry(MetadataScope::Stream, "language", "eng", Some(0)),
+            entry(
+                MetadataScope::Stream,
+                "handler_name",
+                "Core Media Video",
+                Some(0),
+            ),
+            entry(MetadataScope::Stream, "encoder", "Lavc60 libx264", Some(0)),
+        ];
+
+        let (operations, changes, skipped) = plan_media_scrub(
+            &snapshot(
+                "a.mp4",
+                vec![
+                    entry(MetadataScope::Format, "major_brand", "isom", None),
+                    entry(
+                        MetadataScope::Format,
+                        "location",
+                        "+33.8568+151.2153/",
+                        None,
+                    ),
+                    entry(MetadataScope::Format, "encoder", "Lavf60", None),
+                ],
+                streams.clone(),
+                Vec::new(),
+            ),
+            &["copyright".to_string()],
+        );
+        assert_eq!(operations[0], delete_op("*", MetadataScope::Format, None));
+        assert_eq!(operations.len(), 3);
+        assert_eq!(changes.len(), 4);
+        assert!(changes
+            .iter()
+            .all(|c| c.key != "major_brand" && c.key != "language"));
+        assert!(skipped.is_empty());
+
+        let (operations, changes, skipped) = plan_media_scrub(
+            &snapshot(
+                "a.mp4",
+                vec![
+                    entry(MetadataScope::Format, "title", "Holiday", None),
+                    entry(MetadataScope::Format, "copyright", "(c) Jane Doe", None),
+                ],
+                Vec::new(),
+                Vec::new(),
+            ),
+            &["Copyright".to_string()],
+        );
+        assert_eq!(
+            operations,
+            [delete_op("title", MetadataScope::Format, None)]
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(skipped[0].operation.key, "copyright");
+    }
+}
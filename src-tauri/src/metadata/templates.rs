@@ -0,0 +1,475 @@
+//! Saved metadata templates
+//!
+//! A template is a list of tag edits whose values are rename templates
+//! (`files::rename_patterns::expand_template`), so one preset can derive a
+//! title or year from each file's name and probe data. Stream fields pick
+//! their stream with a selector like `a:0`, resolved per file. Templates are
+//! stored in the `metadata_templates` table and applied through the bulk
+//! update path, which previews, verifies and journals every file.
+
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::atomic::AtomicBool;
+
+use super::bulk::{run_batch, FileOperations};
+use crate::database;
+use crate::files::rename_patterns::expand_template;
+use crate::types::{
+    BulkMetadataResult, MetadataAction, MetadataOperation, MetadataScope, MetadataTemplate,
+    MetadataTemplateField, SkippedMetadataOperation, StreamSummary,
+};
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+// ============================================================================
+// Resolving
+// ============================================================================
+
+/// Which stream a stream-scoped template field targets
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StreamSelector {
+    /// Absolute stream index
+    Index(i32),
+    /// The n-th stream of a codec type
+    Typed(&'static str, usize),
+}
+
+/// Parse "a:0", "v:1", "s:0", "d:0", "t:0" or an absolute index
+fn parse_stream_selector(selector: &str) -> Result<StreamSelector, String> {
+    let selector = selector.trim();
+    let invalid = || format!("Invalid stream selector '{}'", selector);
+    if let Ok(index) = selector.parse::<i32>() {
+        return Ok(StreamSelector::Index(index));
+    }
+
+    let (kind, position) = selector.split_once(':').ok_or_else(invalid)?;
+    let codec_type = match kind.trim() {
+        "a" => "audio",
+        "v" => "video",
+        "s" => "subtitle",
+        "d" => "data",
+        "t" => "attachment",
+        _ => return Err(invalid()),
+    };
+    let position = position.trim().parse().map_err(|_| invalid())?;
+    Ok(StreamSelector::Typed(codec_type, position))
+}
+
+/// Resolve a stream selector to a stream index of this file
+pub fn resolve_stream_selector(selector: &str, streams: &[StreamSummary]) -> Result<i32, String> {
+    match parse_stream_selector(selector)? {
+        StreamSelector::Index(index) => streams
+            .iter()
+            .find(|s| s.index == index)
+            .map(|s| s.index)
+            .ok_or_else(|| format!("Stream {} does not exist", index)),
+        StreamSelector::Typed(codec_type, position) => {
+            let mut matching: Vec<&StreamSummary> = streams
+                .iter()
+                .filter(|s| s.codec_type.as_deref() == Some(codec_type))
+                .collect();
+            matching.sort_by_key(|s| s.index);
+            matching.get(position).map(|s| s.index).ok_or_else(|| {
+                format!(
+                    "No {} stream {} (selector '{}')",
+                    codec_type,
+                    position,
+                    selector.trim()
+                )
+            })
+        }
+    }
+}
+
+/// Check a template's structure before saving (values are expanded per file)
+pub fn validate_template(template: &MetadataTemplate) -> Result<(), String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+    if template.fields.is_empty() {
+        return Err("Template has no fields".to_string());
+    }
+    for field in &template.fields {
+        if field.key.trim().is_empty() {
+            return Err("Template field key cannot be empty".to_string());
+        }
+        if field.action == MetadataAction::Set && field.value.is_none() {
+            return Err(format!("Template field '{}' has no value", field.key));
+        }
+        match (&field.scope, &field.stream) {
+            (MetadataScope::Stream, None) => {
+                return Err(format!(
+                    "Stream field '{}' needs a stream selector",
+                    field.key
+                ))
+            }
+            // Syntax only; whether the stream exists depends on the file
+            (MetadataScope::Stream, Some(selector)) => {
+                parse_stream_selector(selector)?;
+            }
+            (_, Some(_)) => {
+                return Err(format!(
+                    "Only stream fields can have a stream selector ('{}')",
+                    field.key
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Turn a template into operations for one file
+///
+/// `index` feeds the `{index}`/`{counter}` variables. Set fields whose value
+/// expands to nothing (e.g. a regex that didn't match) are skipped rather
+/// than clearing the tag.
+pub fn resolve_template(
+    fields: &[MetadataTemplateField],
+    path: &str,
+    streams: &[StreamSummary],
+    index: usize,
+) -> Result<FileOperations, String> {
+    let mut operations = Vec::with_capacity(fields.len());
+    let mut skipped = Vec::new();
+
+    for field in fields {
+        let stream_index = match (&field.scope, &field.stream) {
+            (MetadataScope::Stream, Some(selector)) => {
+                Some(resolve_stream_selector(selector, streams)?)
+            }
+            (MetadataScope::Stream, None) => {
+                return Err(format!(
+                    "Stream field '{}' needs a stream selector",
+                    field.key
+                ))
+            }
+            _ => None,
+        };
+        let value = match (&field.action, &field.value) {
+            (MetadataAction::Set, Some(template)) => Some(
+                expand_template(path, template, index, index + 1)?
+                    .trim()
+                    .to_string(),
+            ),
+            _ => None,
+        };
+
+        let operation = MetadataOperation {
+            action: field.action.clone(),
+            key: field.key.trim().to_string(),
+            value,
+            scope: field.scope.clone(),
+            stream_index,
+        };
+        if operation.action == MetadataAction::Set
+            && operation.value.as_deref().unwrap_or("").is_empty()
+        {
+            skipped.push(SkippedMetadataOperation {
+                operation,
+                reason: "Template produced an empty value".to_string(),
+            });
+        } else {
+            operations.push(operation);
+        }
+    }
+
+    Ok((operations, skipped))
+}
+
+/// Apply a template to files (previewing only when `dry_run`)
+pub fn apply_metadata_template(
+    template: &MetadataTemplate,
+    paths: &[String],
+    dry_run: bool,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<BulkMetadataResult, String> {
+    info!(
+        "Applying metadata template '{}' to {} file(s){}",
+        template.name,
+        paths.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    let path_refs: Vec<&str> = paths.iter().map(String::as_str).collect();
+    run_batch(
+        &path_refs,
+        dry_run,
+        cancelled,
+        on_progress,
+        &mut |i, snapshot| {
+            resolve_template(&template.fields, &paths[i], &snapshot.stream_summaries, i)
+        },
+    )
+}
+
+// ============================================================================
+// Storage
+// ============================================================================
+
+const TEMPLATE_COLUMNS: &str =
+    "SELECT id, name, fields, created_at, updated_at FROM metadata_templates";
+
+fn load_templates(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<MetadataTemplate>, String> {
+    let mut stmt = conn.prepare(sql).map_err(sql_err)?;
+    let rows = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(sql_err)?;
+
+    let mut templates = Vec::new();
+    for row in rows {
+        let (id, name, fields, created_at, updated_at) = row.map_err(sql_err)?;
+        let fields = serde_json::from_str(&fields)
+            .map_err(|e| format!("Invalid fields for template '{}': {}", name, e))?;
+        templates.push(MetadataTemplate {
+            id: Some(id),
+            name,
+            fields,
+            created_at: Some(created_at),
+            updated_at: Some(updated_at),
+        });
+    }
+    Ok(templates)
+}
+
+/// All templates, by name
+pub fn list_templates(conn: &Connection) -> Result<Vec<MetadataTemplate>, String> {
+    let sql = format!("{} ORDER BY name COLLATE NOCASE", TEMPLATE_COLUMNS);
+    load_templates(conn, &sql, [])
+}
+
+pub fn load_template(conn: &Connection, id: i64) -> Result<Option<MetadataTemplate>, String> {
+    let sql = format!("{} WHERE id = ?1", TEMPLATE_COLUMNS);
+    Ok(load_templates(conn, &sql, params![id])?.into_iter().next())
+}
+
+/// Insert a new template, or update the one with the same id
+pub fn save_template(
+    conn: &Connection,
+    template: &MetadataTemplate,
+) -> Result<MetadataTemplate, String> {
+    validate_template(template)?;
+    let name = template.name.trim();
+    let fields = serde_json::to_string(&template.fields)
+        .map_err(|e| format!("Failed to serialize template: {}", e))?;
+
+    let name_taken = conn
+        .query_row(
+            "SELECT id FROM metadata_templates WHERE name = ?1",
+            params![name],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(sql_err)?
+        .is_some_and(|id| Some(id) != template.id);
+    if name_taken {
+        return Err(format!("A template named '{}' already exists", name));
+    }
+
+    let id = match template.id {
+        Some(id) => {
+            let updated = conn
+                .execute(
+                    "UPDATE metadata_templates SET name = ?1, fields = ?2, updated_at = datetime('now') WHERE id = ?3",
+                    params![name, fields, id],
+                )
+                .map_err(sql_err)?;
+            if updated == 0 {
+                return Err(format!("Template {} not found", id));
+            }
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO metadata_templates (name, fields) VALUES (?1, ?2)",
+                params![name, fields],
+            )
+            .map_err(sql_err)?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    load_template(conn, id)?.ok_or_else(|| format!("Template {} not found", id))
+}
+
+/// Delete a template; returns whether it existed
+pub fn delete_template(conn: &Connection, id: i64) -> Result<bool, String> {
+    let deleted = conn
+        .execute("DELETE FROM metadata_templates WHERE id = ?1", params![id])
+        .map_err(sql_err)?;
+    Ok(deleted > 0)
+}
+
+pub fn get_templates() -> Result<Vec<MetadataTemplate>, String> {
+    let conn = database::open_connection()?;
+    list_templates(&conn)
+}
+
+pub fn store_template(template: &MetadataTemplate) -> Result<MetadataTemplate, String> {
+    let conn = database::open_connection()?;
+    save_template(&conn, template)
+}
+
+pub fn remove_template(id: i64) -> Result<bool, String> {
+    let conn = database::open_connection()?;
+    delete_template(&conn, id)
+}
+
+pub fn get_template(id: i64) -> Result<MetadataTemplate, String> {
+    let conn = database::open_connection()?;
+    load_template(&conn, id)?.ok_or_else(|| format!("Template {} not found", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::test_support::summary;
+
+    fn test_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        database::configure_connection(&conn).unwrap();
+        database::apply_migrations(&conn);
+        conn
+    }
+
+    fn field(
+        key: &str,
+        value: &str,
+        scope: MetadataScope,
+        stream: Option<&str>,
+    ) -> MetadataTemplateField {
+        MetadataTemplateField {
+            action: MetadataAction::Set,
+            key: key.to_string(),
+            value: Some(value.to_string()),
+            scope,
+            stream: stream.map(str::to_string),
+        }
+    }
+
+    fn template(name: &str) -> MetadataTemplate {
+        MetadataTemplate {
+            id: None,
+            name: name.to_string(),
+            fields: vec![
+                field(
+                    "title",
+                    "{name|replace:'.',' '}",
+                    MetadataScope::Format,
+                    None,
+                ),
+                field("date", r"{regex:name:(\d{4})}", MetadataScope::Format, None),
+                field("language", "eng", MetadataScope::Stream, Some("a:0")),
+            ],
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_stream_selector() {
+        let streams = vec![
+            summary(0, "video"),
+            summary(2, "audio"),
+            summary(1, "audio"),
+        ];
+        assert_eq!(resolve_stream_selector("a:0", &streams), Ok(1));
+        assert_eq!(resolve_stream_selector(" a:1 ", &streams), Ok(2));
+        assert_eq!(resolve_stream_selector("v:0", &streams), Ok(0));
+        assert_eq!(resolve_stream_selector("2", &streams), Ok(2));
+        assert!(resolve_stream_selector("s:0", &streams).is_err());
+        assert!(resolve_stream_selector("5", &streams).is_err());
+        assert_eq!(
+            resolve_stream_selector("x:0", &streams),
+            Err("Invalid stream selector 'x:0'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_template() {
+        let streams = vec![summary(0, "video"), summary(1, "audio")];
+        let fields = template("Movies").fields;
+
+        let (operations, skipped) =
+            resolve_template(&fields, "/movies/The.Movie.2019.mkv", &streams, 0).unwrap();
+        assert!(skipped.is_empty());
+        let values: Vec<(&str, Option<&str>, Option<i32>)> = operations
+            .iter()
+            .map(|op| (op.key.as_str(), op.value.as_deref(), op.stream_index))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("title", Some("The Movie 2019"), None),
+                ("date", Some("2019"), None),
+                ("language", Some("eng"), Some(1)),
+            ]
+        );
+
+        // No year in the name: the date field is skipped instead of cleared
+        let (operations, skipped) =
+            resolve_template(&fields, "/movies/Home.Video.mkv", &streams, 0).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(skipped[0].operation.key, "date");
+
+        // Missing audio stream
+        assert!(resolve_template(&fields, "/movies/a.mkv", &streams[..1], 0).is_err());
+    }
+
+    #[test]
+    fn test_template_storage() {
+        let conn = test_connection();
+        let saved = save_template(&conn, &template("Movies")).unwrap();
+        let id = saved.id.unwrap();
+        assert_eq!(saved.fields, template("Movies").fields);
+        assert!(save_template(&conn, &template("Movies")).is_err());
+
+        let mut renamed = saved.clone();
+        renamed.name = "Films".to_string();
+        renamed.fields.truncate(1);
+        let updated = save_template(&conn, &renamed).unwrap();
+        assert_eq!(updated.id, Some(id));
+        assert_eq!(updated.fields.len(), 1);
+
+        save_template(&conn, &template("Anime")).unwrap();
+        let names: Vec<String> = list_templates(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["Anime", "Films"]);
+
+        assert!(delete_template(&conn, id).unwrap());
+        assert!(!delete_template(&conn, id).unwrap());
+        assert!(load_template(&conn, id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert!(validate_template(&template("Movies")).is_ok());
+        let mut invalid = template("Movies");
+        invalid.fields[2].stream = None;
+        assert!(validate_template(&invalid).is_err());
+        let mut invalid = template("Movies");
+        invalid.fields[0].stream = Some("a:0".to_string());
+        assert!(validate_template(&invalid).is_err());
+        let mut invalid = template("Movies");
+        invalid.fields[2].stream = Some("audio".to_string());
+        assert!(validate_template(&invalid).is_err());
+        assert!(validate_template(&template(" ")).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Fixtures shared by the metadata tests
+
+use crate::types::{
+    MetadataAction, MetadataEntry, MetadataOperation, MetadataOrigin, MetadataScope,
+    MetadataSnapshot, MetadataToolAvailability, StreamSummary,
+};
+
+pub fn entry(
+    scope: MetadataScope,
+    key: &str,
+    value: &str,
+    stream_index: Option<i32>,
+) -> MetadataEntry {
+    MetadataEntry {
+        key: key.to_string(),
+        value: value.to_string(),
+        scope,
+        stream_index,
+        origin: MetadataOrigin::Ffprobe,
+        editable: true,
+    }
+}
+
+pub fn op(
+    action: MetadataAction,
+    scope: MetadataScope,
+    key: &str,
+    value: Option<&str>,
+    stream_index: Option<i32>,
+) -> MetadataOperation {
+    MetadataOperation {
+        action,
+        key: key.to_string(),
+        value: value.map(str::to_string),
+        scope,
+        stream_index,
+    }
+}
+
+pub fn summary(index: i32, codec_type: &str) -> StreamSummary {
+    StreamSummary {
+        index,
+        codec_type: Some(codec_type.to_string()),
+        codec_name: None,
+        codec_long_name: None,
+        duration: None,
+        bit_rate: None,
+    }
+}
+
+/// Snapshot of `/media/<file_name>` with ffmpeg and ffprobe available
+pub fn snapshot(
+    file_name: &str,
+    format_tags: Vec<MetadataEntry>,
+    stream_tags: Vec<MetadataEntry>,
+    stream_summaries: Vec<StreamSummary>,
+) -> MetadataSnapshot {
+    MetadataSnapshot {
+        path: format!("/media/{}", file_name),
+        file_name: file_name.to_string(),
+        size: 0,
+        modified: None,
+        created: None,
+        extension: file_name.rsplit_once('.').map(|(_, e)| e.to_string()),
+        format_tags,
+        stream_tags,
+        file_tags: Vec::new(),
+        stream_summaries,
+        tool_availability: MetadataToolAvailability {
+            ffmpeg: true,
+            ffprobe: true,
+            exiftool: false,
+            mkvpropedit: false,
+        },
+    }
+}
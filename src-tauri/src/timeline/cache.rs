@@ -0,0 +1,297 @@
+//! Detection timeline cache
+//!
+//! Timeline analyses are cached in the app's SQLite database
+//! (`timeline_analysis`, `timeline_events`, `timeline_suggestions`), keyed by
+//! file path, `compute_file_hash` and the detection options they were run with.
+
+use log::{debug, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::bitrate::compute_file_hash;
+use crate::database;
+use crate::types::{
+    DetectionOptions, TimelineAnalysis, TimelineEvent, TimelineEventType, TimelineSuggestion,
+    TimelineSuggestionKind,
+};
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+fn enum_name<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn parse_enum<T: serde::de::DeserializeOwned>(name: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+}
+
+fn options_json(options: &DetectionOptions) -> Result<String, String> {
+    serde_json::to_string(options).map_err(|e| format!("Failed to serialize options: {}", e))
+}
+
+/// Load a cached timeline for `path` if it was stored for `file_hash` and `options`
+pub fn load_analysis(
+    conn: &Connection,
+    path: &str,
+    file_hash: &str,
+    options: &DetectionOptions,
+) -> Result<Option<TimelineAnalysis>, String> {
+    let analysis: Option<(i64, f64)> = conn
+        .query_row(
+            "SELECT id, duration FROM timeline_analysis
+             WHERE file_path = ?1 AND file_hash = ?2 AND options = ?3",
+            params![path, file_hash, options_json(options)?],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(sql_err)?;
+    let Some((analysis_id, duration)) = analysis else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT event_type, start_time, end_time, score FROM timeline_events
+             WHERE analysis_id = ?1 ORDER BY start_time, id",
+        )
+        .map_err(sql_err)?;
+    let events = stmt
+        .query_map(params![analysis_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?
+        .into_iter()
+        .filter_map(|(event_type, start_time, end_time, score)| {
+            Some(TimelineEvent {
+                event_type: parse_enum::<TimelineEventType>(&event_type)?,
+                start_time,
+                end_time,
+                score,
+            })
+        })
+        .collect();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT kind, timestamp, reason FROM timeline_suggestions
+             WHERE analysis_id = ?1 ORDER BY timestamp, id",
+        )
+        .map_err(sql_err)?;
+    let suggestions = stmt
+        .query_map(params![analysis_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(sql_err)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_err)?
+        .into_iter()
+        .filter_map(|(kind, timestamp, reason)| {
+            Some(TimelineSuggestion {
+                kind: parse_enum::<TimelineSuggestionKind>(&kind)?,
+                timestamp,
+                reason,
+            })
+        })
+        .collect();
+
+    Ok(Some(TimelineAnalysis {
+        path: path.to_string(),
+        duration,
+        options: options.clone(),
+        events,
+        suggestions,
+    }))
+}
+
+/// Store a timeline, replacing anything previously cached for `path`
+pub fn store_analysis(
+    conn: &mut Connection,
+    path: &str,
+    file_hash: &str,
+    result: &TimelineAnalysis,
+) -> Result<(), String> {
+    let tx = conn.transaction().map_err(sql_err)?;
+
+    tx.execute(
+        "DELETE FROM timeline_analysis WHERE file_path = ?1",
+        params![path],
+    )
+    .map_err(sql_err)?;
+    tx.execute(
+        "INSERT INTO timeline_analysis (file_path, file_hash, duration, options)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            path,
+            file_hash,
+            result.duration,
+            options_json(&result.options)?
+        ],
+    )
+    .map_err(sql_err)?;
+    let analysis_id = tx.last_insert_rowid();
+
+    {
+        let mut insert_event = tx
+            .prepare(
+                "INSERT INTO timeline_events (analysis_id, event_type, start_time, end_time, score)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(sql_err)?;
+        for event in &result.events {
+            insert_event
+                .execute(params![
+                    analysis_id,
+                    enum_name(&event.event_type),
+                    event.start_time,
+                    event.end_time,
+                    event.score
+                ])
+                .map_err(sql_err)?;
+        }
+
+        let mut insert_suggestion = tx
+            .prepare(
+                "INSERT INTO timeline_suggestions (analysis_id, kind, timestamp, reason)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(sql_err)?;
+        for suggestion in &result.suggestions {
+            insert_suggestion
+                .execute(params![
+                    analysis_id,
+                    enum_name(&suggestion.kind),
+                    suggestion.timestamp,
+                    suggestion.reason
+                ])
+                .map_err(sql_err)?;
+        }
+    }
+
+    tx.commit().map_err(sql_err)
+}
+
+/// Get a cached timeline for a file, if one exists for its current contents
+/// and the same detection options
+pub fn get_cached_timeline(path: &str, options: &DetectionOptions) -> Option<TimelineAnalysis> {
+    let file_hash = compute_file_hash(path).ok()?;
+    let conn = database::open_connection()
+        .map_err(|e| debug!("Timeline cache unavailable: {}", e))
+        .ok()?;
+    match load_analysis(&conn, path, &file_hash, options) {
+        Ok(cached) => {
+            if cached.is_some() {
+                debug!("Timeline cache hit for {}", path);
+            }
+            cached
+        }
+        Err(e) => {
+            warn!("Failed to read timeline cache for {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Save a timeline to the cache, keyed by path and the file's current hash
+pub fn save_timeline_to_cache(path: &str, result: &TimelineAnalysis) -> Result<(), String> {
+    let file_hash = compute_file_hash(path)?;
+    let mut conn = database::open_connection()?;
+    store_analysis(&mut conn, path, &file_hash, result)?;
+    debug!("Saved timeline analysis for {} to cache", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        database::configure_connection(&conn).unwrap();
+        database::apply_migrations(&conn);
+        conn
+    }
+
+    fn sample_analysis() -> TimelineAnalysis {
+        TimelineAnalysis {
+            path: "/media/clip.mkv".to_string(),
+            duration: 120.0,
+            options: DetectionOptions::default(),
+            events: vec![
+                TimelineEvent {
+                    event_type: TimelineEventType::Black,
+                    start_time: 0.0,
+                    end_time: 2.0,
+                    score: None,
+                },
+                TimelineEvent {
+                    event_type: TimelineEventType::SceneCut,
+                    start_time: 12.5,
+                    end_time: 12.5,
+                    score: Some(45.1),
+                },
+            ],
+            suggestions: vec![TimelineSuggestion {
+                kind: TimelineSuggestionKind::TrimStart,
+                timestamp: 2.0,
+                reason: "Leading black frames".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_store_and_load_round_trip() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(&mut conn, &analysis.path, "hash", &analysis).unwrap();
+
+        let loaded = load_analysis(&conn, &analysis.path, "hash", &analysis.options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.duration, 120.0);
+        assert_eq!(loaded.events.len(), 2);
+        assert_eq!(loaded.events[1].event_type, TimelineEventType::SceneCut);
+        assert_eq!(loaded.events[1].score, Some(45.1));
+        assert_eq!(loaded.suggestions.len(), 1);
+        assert_eq!(
+            loaded.suggestions[0].kind,
+            TimelineSuggestionKind::TrimStart
+        );
+        assert_eq!(loaded.suggestions[0].reason, "Leading black frames");
+    }
+
+    #[test]
+    fn test_load_misses_on_hash_or_options_change() {
+        let mut conn = test_connection();
+        let analysis = sample_analysis();
+        store_analysis(&mut conn, &analysis.path, "hash", &analysis).unwrap();
+
+        assert!(
+            load_analysis(&conn, &analysis.path, "other", &analysis.options)
+                .unwrap()
+                .is_none()
+        );
+
+        let options = DetectionOptions {
+            scene_threshold: 25.0,
+            ..DetectionOptions::default()
+        };
+        assert!(load_analysis(&conn, &analysis.path, "hash", &options)
+            .unwrap()
+            .is_none());
+    }
+}
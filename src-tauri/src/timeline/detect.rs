@@ -0,0 +1,260 @@
+//! Scene-change, black-frame and silence detection
+//!
+//! Runs `scdet`, `blackdetect` and `silencedetect` in a single ffmpeg pass and
+//! parses their log output into timeline events.
+
+use log::{debug, info};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use super::suggest::suggest_points;
+use crate::config;
+use crate::media::{get_media_streams, run_ffmpeg};
+use crate::types::{
+    DetectionOptions, StreamType, TimelineAnalysis, TimelineEvent, TimelineEventType,
+};
+
+/// Width video is scaled to before detection (detection doesn't need full resolution)
+const DETECTION_WIDTH: u32 = 320;
+
+/// Numeric value following `key` in a filter log line (e.g. `black_start:12.5`)
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let rest = &line[line.find(key)? + key.len()..];
+    rest.trim_start()
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .next()?
+        .parse::<f64>()
+        .ok()
+}
+
+/// Parse detector log output into events sorted by start time
+///
+/// A `silence_start` without a matching `silence_end` (silence running to the
+/// end of the file) is closed at `duration`.
+pub fn parse_detection_log(stderr: &str, duration: f64) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+    let mut open_silence: Option<f64> = None;
+
+    for line in stderr.lines() {
+        if line.contains("lavfi.scd.score") {
+            if let (Some(score), Some(time)) = (
+                value_after(line, "lavfi.scd.score:"),
+                value_after(line, "lavfi.scd.time:"),
+            ) {
+                events.push(TimelineEvent {
+                    event_type: TimelineEventType::SceneCut,
+                    start_time: time,
+                    end_time: time,
+                    score: Some(score),
+                });
+            }
+        } else if line.contains("black_start:") {
+            if let (Some(start), Some(end)) = (
+                value_after(line, "black_start:"),
+                value_after(line, "black_end:"),
+            ) {
+                events.push(TimelineEvent {
+                    event_type: TimelineEventType::Black,
+                    start_time: start,
+                    end_time: end,
+                    score: None,
+                });
+            }
+        } else if line.contains("silence_start:") {
+            open_silence = value_after(line, "silence_start:").map(|s| s.max(0.0));
+        } else if line.contains("silence_end:") {
+            if let (Some(start), Some(end)) =
+                (open_silence.take(), value_after(line, "silence_end:"))
+            {
+                events.push(TimelineEvent {
+                    event_type: TimelineEventType::Silence,
+                    start_time: start,
+                    end_time: end,
+                    score: None,
+                });
+            }
+        }
+    }
+
+    if let Some(start) = open_silence {
+        if duration > start {
+            events.push(TimelineEvent {
+                event_type: TimelineEventType::Silence,
+                start_time: start,
+                end_time: duration,
+                score: None,
+            });
+        }
+    }
+
+    events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    events
+}
+
+/// Build the filtergraph for the available streams
+pub fn build_detection_filter(
+    video_index: Option<i32>,
+    audio_index: Option<i32>,
+    options: &DetectionOptions,
+) -> Option<(String, Vec<String>)> {
+    let mut chains = Vec::new();
+    let mut outputs = Vec::new();
+
+    if let Some(index) = video_index {
+        chains.push(format!(
+            "[0:{}]scale={}:-2,scdet=threshold={},blackdetect=d={}:pix_th={}[v]",
+            index,
+            DETECTION_WIDTH,
+            options.scene_threshold,
+            options.black_min_duration,
+            options.black_pixel_threshold
+        ));
+        outputs.push("[v]".to_string());
+    }
+    if let Some(index) = audio_index {
+        chains.push(format!(
+            "[0:{}]silencedetect=n={}dB:d={}[a]",
+            index, options.silence_noise_db, options.silence_min_duration
+        ));
+        outputs.push("[a]".to_string());
+    }
+
+    if chains.is_empty() {
+        None
+    } else {
+        Some((chains.join(";"), outputs))
+    }
+}
+
+/// Run scene/black/silence detection over a file and build the timeline
+pub fn analyze_timeline(
+    path: &str,
+    options: &DetectionOptions,
+    cancelled: &AtomicBool,
+    on_progress: &mut dyn FnMut(f64, &str),
+) -> Result<TimelineAnalysis, String> {
+    let validated_path = config::validate_path(Path::new(path))?;
+    if !validated_path.exists() {
+        return Err("File does not exist".to_string());
+    }
+
+    on_progress(0.0, "Getting stream info...");
+    let streams = get_media_streams(path.to_string())?;
+    let video_index = streams
+        .streams
+        .iter()
+        .find(|s| s.stream_type == StreamType::Video && !s.is_cover_art)
+        .map(|s| s.index);
+    let audio_index = streams
+        .streams
+        .iter()
+        .find(|s| s.stream_type == StreamType::Audio)
+        .map(|s| s.index);
+
+    let (filter, outputs) = build_detection_filter(video_index, audio_index, options)
+        .ok_or("File has no video or audio streams to analyze")?;
+
+    let mut args: Vec<String> = vec![
+        "-i".to_string(),
+        validated_path.to_string_lossy().to_string(),
+        "-filter_complex".to_string(),
+        filter,
+    ];
+    for output in outputs {
+        args.extend(["-map".to_string(), output]);
+    }
+    args.extend(["-f".to_string(), "null".to_string(), "-".to_string()]);
+
+    debug!(
+        "analyze_timeline: path={}, video={:?}, audio={:?}",
+        path, video_index, audio_index
+    );
+    let stderr = run_ffmpeg(&args, Some(streams.duration), Some(cancelled), &mut |pct| {
+        on_progress(pct * 0.95, "Detecting scenes, black frames and silence...")
+    })?;
+
+    on_progress(95.0, "Building timeline...");
+    let events = parse_detection_log(&stderr, streams.duration);
+    let suggestions = suggest_points(&events, streams.duration);
+
+    info!(
+        "Timeline analysis complete for {}: {} events, {} suggestions",
+        path,
+        events.len(),
+        suggestions.len()
+    );
+    on_progress(100.0, "Complete");
+
+    Ok(TimelineAnalysis {
+        path: path.to_string(),
+        duration: streams.duration,
+        options: options.clone(),
+        events,
+        suggestions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[scdet @ 0x1] lavfi.scd.score: 45.120, lavfi.scd.time: 12.5
+[blackdetect @ 0x2] black_start:0 black_end:2.04 black_duration:2.04
+[silencedetect @ 0x3] silence_start: 0
+[silencedetect @ 0x3] silence_end: 1.8 | silence_duration: 1.8
+[scdet @ 0x1] lavfi.scd.score: 12.000, lavfi.scd.time: 30
+[silencedetect @ 0x3] silence_start: 95.25
+";
+
+    #[test]
+    fn test_parse_detection_log() {
+        let events = parse_detection_log(LOG, 100.0);
+        assert_eq!(events.len(), 5);
+
+        let scene: Vec<&TimelineEvent> = events
+            .iter()
+            .filter(|e| e.event_type == TimelineEventType::SceneCut)
+            .collect();
+        assert_eq!(scene.len(), 2);
+        assert_eq!(scene[0].start_time, 12.5);
+        assert_eq!(scene[0].score, Some(45.12));
+
+        let black = events
+            .iter()
+            .find(|e| e.event_type == TimelineEventType::Black)
+            .unwrap();
+        assert_eq!((black.start_time, black.end_time), (0.0, 2.04));
+    }
+
+    #[test]
+    fn test_parse_detection_log_closes_trailing_silence() {
+        let events = parse_detection_log(LOG, 100.0);
+        let last = events.last().unwrap();
+        assert_eq!(last.event_type, TimelineEventType::Silence);
+        assert_eq!((last.start_time, last.end_time), (95.25, 100.0));
+    }
+
+    #[test]
+    fn test_parse_detection_log_sorted() {
+        let events = parse_detection_log(LOG, 100.0);
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].start_time <= w[1].start_time));
+    }
+
+    #[test]
+    fn test_build_detection_filter() {
+        let options = DetectionOptions::default();
+        let (filter, outputs) = build_detection_filter(Some(0), Some(1), &options).unwrap();
+        assert!(filter.contains("[0:0]scale=320:-2,scdet=threshold=10,blackdetect=d=0.5"));
+        assert!(filter.contains("[0:1]silencedetect=n=-50dB:d=1[a]"));
+        assert_eq!(outputs, vec!["[v]", "[a]"]);
+
+        let (filter, outputs) = build_detection_filter(None, Some(2), &options).unwrap();
+        assert!(!filter.contains("scdet"));
+        assert_eq!(outputs, vec!["[a]"]);
+
+        assert!(build_detection_filter(None, None, &options).is_none());
+    }
+}
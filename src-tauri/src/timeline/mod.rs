@@ -0,0 +1,16 @@
+//! Detection timeline module
+//!
+//! This module builds a unified event timeline for a media file, including:
+//! - Scene cuts (`scdet`), black segments (`blackdetect`) and silent segments (`silencedetect`)
+//! - Suggested chapter points, intro end / credits start, and trim points
+//!
+//! Results are cached in SQLite (`timeline_analysis`, `timeline_events`,
+//! `timeline_suggestions`), keyed by file path, file hash and detection options.
+
+mod cache;
+mod detect;
+mod suggest;
+
+pub use cache::{get_cached_timeline, save_timeline_to_cache};
+pub use detect::{analyze_timeline, build_detection_filter, parse_detection_log};
+pub use suggest::suggest_points;
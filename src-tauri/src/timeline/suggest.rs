@@ -0,0 +1,230 @@
+//! Chapter, intro/credits and trim suggestions from a detection timeline
+//!
+//! Heuristics:
+//! - Black frames overlapping silence are strong break points (chapter candidates);
+//!   scene cuts inside silence are weaker ones
+//! - Black/silence at the very start or end of the file suggests trim points
+//! - The first break early in the file marks the end of an intro, the last
+//!   break near the end marks the start of credits
+
+use crate::types::{TimelineEvent, TimelineEventType, TimelineSuggestion, TimelineSuggestionKind};
+
+/// Tolerance (seconds) for "at the start/end of the file"
+const EDGE_TOLERANCE_SECS: f64 = 0.5;
+
+/// Minimum spacing between suggested chapter points
+const MIN_CHAPTER_SPACING_SECS: f64 = 30.0;
+
+/// Intro breaks must fall within this fraction of the file (and the absolute cap)
+const INTRO_MAX_FRACTION: f64 = 0.2;
+const INTRO_MAX_SECS: f64 = 300.0;
+
+/// Credits breaks must fall within this trailing fraction of the file
+const CREDITS_MIN_FRACTION: f64 = 0.85;
+
+fn segments(events: &[TimelineEvent], event_type: TimelineEventType) -> Vec<(f64, f64)> {
+    events
+        .iter()
+        .filter(|e| e.event_type == event_type)
+        .map(|e| (e.start_time, e.end_time))
+        .collect()
+}
+
+/// Break candidates as `(timestamp, strong)` sorted by time
+fn break_candidates(events: &[TimelineEvent], duration: f64) -> Vec<(f64, bool)> {
+    let black = segments(events, TimelineEventType::Black);
+    let silence = segments(events, TimelineEventType::Silence);
+    let is_interior = |t: f64| t > EDGE_TOLERANCE_SECS && t < duration - EDGE_TOLERANCE_SECS;
+
+    // Black overlapping silence: break at the middle of the overlap
+    let mut candidates: Vec<(f64, bool)> = black
+        .iter()
+        .flat_map(|(bs, be)| {
+            silence.iter().filter_map(move |(ss, se)| {
+                let start = bs.max(*ss);
+                let end = be.min(*se);
+                (end > start).then_some(((start + end) / 2.0, true))
+            })
+        })
+        .filter(|(t, _)| is_interior(*t))
+        .collect();
+
+    // Scene cuts inside silence
+    candidates.extend(
+        events
+            .iter()
+            .filter(|e| e.event_type == TimelineEventType::SceneCut)
+            .map(|e| e.start_time)
+            .filter(|t| silence.iter().any(|(ss, se)| t >= ss && t <= se))
+            .filter(|t| is_interior(*t))
+            .map(|t| (t, false)),
+    );
+
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates
+}
+
+/// Keep candidates at least `MIN_CHAPTER_SPACING_SECS` apart, preferring strong ones
+fn space_out(candidates: &[(f64, bool)]) -> Vec<(f64, bool)> {
+    let mut kept: Vec<(f64, bool)> = Vec::new();
+    for &(t, strong) in candidates {
+        match kept.last_mut() {
+            Some(last) if t - last.0 < MIN_CHAPTER_SPACING_SECS => {
+                if strong && !last.1 {
+                    *last = (t, strong);
+                }
+            }
+            _ => kept.push((t, strong)),
+        }
+    }
+    kept
+}
+
+/// Suggest chapter, intro/credits and trim points for a timeline
+pub fn suggest_points(events: &[TimelineEvent], duration: f64) -> Vec<TimelineSuggestion> {
+    let mut suggestions = Vec::new();
+    if duration <= 0.0 {
+        return suggestions;
+    }
+
+    // Trim points: black at the very start/end of the file
+    let black = segments(events, TimelineEventType::Black);
+    if let Some((_, end)) = black.iter().find(|(s, _)| *s <= EDGE_TOLERANCE_SECS) {
+        suggestions.push(TimelineSuggestion {
+            kind: TimelineSuggestionKind::TrimStart,
+            timestamp: *end,
+            reason: format!("Black frames from the start until {:.2}s", end),
+        });
+    }
+    if let Some((start, _)) = black
+        .iter()
+        .rev()
+        .find(|(_, e)| *e >= duration - EDGE_TOLERANCE_SECS)
+    {
+        suggestions.push(TimelineSuggestion {
+            kind: TimelineSuggestionKind::TrimEnd,
+            timestamp: *start,
+            reason: format!("Black frames from {:.2}s to the end", start),
+        });
+    }
+
+    let breaks = space_out(&break_candidates(events, duration));
+
+    let intro_limit = (duration * INTRO_MAX_FRACTION).min(INTRO_MAX_SECS);
+    if let Some((t, _)) = breaks.iter().find(|(t, _)| *t <= intro_limit) {
+        suggestions.push(TimelineSuggestion {
+            kind: TimelineSuggestionKind::IntroEnd,
+            timestamp: *t,
+            reason: format!("Break at {:.2}s near the start", t),
+        });
+    }
+    if let Some((t, _)) = breaks
+        .iter()
+        .rev()
+        .find(|(t, _)| *t >= duration * CREDITS_MIN_FRACTION)
+    {
+        suggestions.push(TimelineSuggestion {
+            kind: TimelineSuggestionKind::CreditsStart,
+            timestamp: *t,
+            reason: format!("Break at {:.2}s near the end", t),
+        });
+    }
+
+    suggestions.extend(breaks.iter().map(|(t, strong)| TimelineSuggestion {
+        kind: TimelineSuggestionKind::Chapter,
+        timestamp: *t,
+        reason: if *strong {
+            "Black frames during silence".to_string()
+        } else {
+            "Scene cut during silence".to_string()
+        },
+    }));
+
+    suggestions.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: TimelineEventType, start: f64, end: f64) -> TimelineEvent {
+        TimelineEvent {
+            event_type,
+            start_time: start,
+            end_time: end,
+            score: None,
+        }
+    }
+
+    fn kinds_at(suggestions: &[TimelineSuggestion], kind: TimelineSuggestionKind) -> Vec<f64> {
+        suggestions
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| s.timestamp)
+            .collect()
+    }
+
+    #[test]
+    fn test_trim_points_from_edge_black() {
+        let events = vec![
+            event(TimelineEventType::Black, 0.0, 3.0),
+            event(TimelineEventType::Black, 1190.0, 1200.0),
+        ];
+        let suggestions = suggest_points(&events, 1200.0);
+        assert_eq!(
+            kinds_at(&suggestions, TimelineSuggestionKind::TrimStart),
+            vec![3.0]
+        );
+        assert_eq!(
+            kinds_at(&suggestions, TimelineSuggestionKind::TrimEnd),
+            vec![1190.0]
+        );
+    }
+
+    #[test]
+    fn test_black_during_silence_is_chapter() {
+        let events = vec![
+            event(TimelineEventType::Black, 600.0, 602.0),
+            event(TimelineEventType::Silence, 599.0, 601.0),
+            // Black without silence is not a break
+            event(TimelineEventType::Black, 800.0, 801.0),
+        ];
+        let suggestions = suggest_points(&events, 1200.0);
+        assert_eq!(
+            kinds_at(&suggestions, TimelineSuggestionKind::Chapter),
+            vec![600.5]
+        );
+    }
+
+    #[test]
+    fn test_intro_and_credits() {
+        let events = vec![
+            event(TimelineEventType::Black, 90.0, 91.0),
+            event(TimelineEventType::Silence, 90.0, 91.0),
+            event(TimelineEventType::SceneCut, 1100.5, 1100.5),
+            event(TimelineEventType::Silence, 1100.0, 1102.0),
+        ];
+        let suggestions = suggest_points(&events, 1200.0);
+        assert_eq!(
+            kinds_at(&suggestions, TimelineSuggestionKind::IntroEnd),
+            vec![90.5]
+        );
+        assert_eq!(
+            kinds_at(&suggestions, TimelineSuggestionKind::CreditsStart),
+            vec![1100.5]
+        );
+    }
+
+    #[test]
+    fn test_spacing_prefers_strong_breaks() {
+        let candidates = vec![(100.0, false), (110.0, true), (200.0, false)];
+        assert_eq!(space_out(&candidates), vec![(110.0, true), (200.0, false)]);
+    }
+
+    #[test]
+    fn test_no_suggestions_for_empty_timeline() {
+        assert!(suggest_points(&[], 100.0).is_empty());
+        assert!(suggest_points(&[], 0.0).is_empty());
+    }
+}
@@ -116,6 +116,9 @@ pub struct MetadataToolAvailability {
     pub ffmpeg: bool,
     pub ffprobe: bool,
     pub exiftool: bool,
+    /// Optional; Matroska tags are edited in place when available
+    #[serde(default)]
+    pub mkvpropedit: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -158,6 +161,200 @@ pub struct MetadataUpdateResult {
     pub errors: Vec<String>,
 }
 
+/// A file in a bulk metadata update, with its own operations or the shared list
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMetadataTarget {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operations: Option<Vec<MetadataOperation>>,
+}
+
+/// Before/after value of one tag (None = absent)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataChangePreview {
+    pub key: String,
+    pub scope: MetadataScope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_index: Option<i32>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedMetadataOperation {
+    pub operation: MetadataOperation,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMetadataFileResult {
+    pub path: String,
+    pub changes: Vec<MetadataChangePreview>,
+    pub skipped: Vec<SkippedMetadataOperation>,
+    /// Whether the file was rewritten (always false for dry runs)
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkMetadataResult {
+    pub dry_run: bool,
+    pub files: Vec<BulkMetadataFileResult>,
+    /// Files with changes (applied, or that would be applied in a dry run)
+    pub changed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+/// One field of a metadata template; `value` may use rename template variables
+/// (e.g. `{name|replace:'.',' '}`, `{regex:name:(\d{4})}`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataTemplateField {
+    pub action: MetadataAction,
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub scope: MetadataScope,
+    /// Stream selector for stream-scoped fields: "a:0", "v:0", "s:1" or an absolute index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+}
+
+/// A saved metadata preset (`id` is None until it's saved)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataTemplate {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    pub fields: Vec<MetadataTemplateField>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Embedded cover art read from a file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverArt {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// The attached picture stream it was read from
+    pub stream_index: i32,
+}
+
+/// A typed exiftool value
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExifValue {
+    Text {
+        value: String,
+    },
+    Number {
+        value: f64,
+    },
+    /// e.g. an exposure time of 1/250
+    Rational {
+        numerator: i64,
+        denominator: i64,
+        value: f64,
+    },
+    /// `value` as stored ("2024:05:01 12:00:00+02:00"), `iso` when it parses
+    DateTime {
+        value: String,
+        iso: Option<String>,
+    },
+    /// Signed decimal degrees (south/west negative)
+    Coordinate {
+        value: f64,
+    },
+    List {
+        values: Vec<String>,
+    },
+    Binary {
+        size: Option<u64>,
+    },
+}
+
+/// One exiftool tag; `key` ("ExifIFD:DateTimeOriginal") is what edits refer to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifTag {
+    pub key: String,
+    /// Specific location within the family (IFD0, ExifIFD, GPS, XMP-dc, ...)
+    pub group: String,
+    pub name: String,
+    /// exiftool's human-readable value
+    pub display: String,
+    pub value: ExifValue,
+    pub editable: bool,
+    /// Whether the tag holds a list (keywords, subjects, ...)
+    pub list: bool,
+}
+
+/// Tags of one metadata family (EXIF, XMP, IPTC, MakerNotes, ...)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifGroup {
+    pub family: String,
+    pub tags: Vec<ExifTag>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifMetadata {
+    pub path: String,
+    pub groups: Vec<ExifGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExifEditAction {
+    /// Replace the value (every item for list tags)
+    Set,
+    Delete,
+    /// Append items to a list tag
+    Add,
+    /// Remove items from a list tag
+    Remove,
+}
+
+/// A structured exiftool edit, validated against the tag's type
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifEdit {
+    pub key: String,
+    pub action: ExifEditAction,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// Shift applied to many photos at once (camera clock or position corrections)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifShift {
+    /// Added to DateTimeOriginal, CreateDate and ModifyDate
+    #[serde(default)]
+    pub date_shift_seconds: Option<i64>,
+    /// Degrees added to the GPS position of photos that have one
+    #[serde(default)]
+    pub latitude_offset: Option<f64>,
+    #[serde(default)]
+    pub longitude_offset: Option<f64>,
+}
+
 // ============================================================================
 // Bulk Rename Types
 // ============================================================================
@@ -259,6 +456,8 @@ pub struct StreamInfo {
     pub stream_type: StreamType,
     pub codec_name: Option<String>,
     pub codec_long_name: Option<String>,
+    /// Codec profile, e.g. `DTS-HD MA` for a lossless DTS track
+    pub profile: Option<String>,
     pub language: Option<String>,
     pub title: Option<String>,
     pub is_default: bool,
@@ -317,6 +516,162 @@ pub struct BulkStreamRemovalResult {
     pub errors: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskSpaceStatus {
+    Ok,
+    /// Enough space, but less than the safety margin would be left
+    Low,
+    /// The output will not fit; writing jobs refuse to start
+    Insufficient,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskSpaceCheck {
+    pub target_dir: String,
+    /// Estimated size of the output
+    pub required_bytes: u64,
+    pub available_bytes: u64,
+    pub status: DiskSpaceStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterInfo {
+    pub index: usize,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub title: Option<String>,
+}
+
+// ============================================================================
+// Trim / Split Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SplitMode {
+    /// Keep a single range (cut intro and/or outro)
+    Trim {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
+    /// Split at explicit timestamps
+    Points { points: Vec<f64> },
+    /// One segment per chapter
+    Chapters,
+    /// Fixed-length segments
+    Duration { segment_seconds: f64 },
+    /// Segments of roughly the given size (estimated from the average bitrate)
+    Size { segment_bytes: u64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CutPoint {
+    pub requested: f64,
+    pub actual: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentPreview {
+    pub index: usize,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub duration: f64,
+    pub estimated_size: u64,
+    pub output_path: String,
+    pub title: Option<String>,
+    pub conflict: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitPreview {
+    pub path: String,
+    pub duration: f64,
+    pub keyframe_count: usize,
+    pub cut_points: Vec<CutPoint>,
+    pub segments: Vec<SegmentPreview>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SplitResult {
+    pub success: bool,
+    pub output_paths: Vec<String>,
+    pub message: String,
+}
+
+// ============================================================================
+// Concatenation Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcatMismatch {
+    pub path: String,
+    /// Position of the stream among the compared streams (None for stream count mismatches)
+    pub stream_position: Option<usize>,
+    pub field: String,
+    pub expected: String,
+    pub found: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcatCheck {
+    pub compatible: bool,
+    pub total_duration: f64,
+    pub mismatches: Vec<ConcatMismatch>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConcatResult {
+    pub success: bool,
+    pub output_path: String,
+    pub message: String,
+}
+
+// ============================================================================
+// Thumbnail Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThumbnailResult {
+    pub path: String,
+    pub thumbnail_path: String,
+    /// Where the frame search started (10% into the file); the `thumbnail`
+    /// filter picks a frame at or shortly after this point
+    pub seek_time: f64,
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ContactSheetOptions {
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    /// Defaults to `{stem}_contact_sheet.jpg` next to the source file
+    pub output_path: Option<String>,
+}
+
+impl Default for ContactSheetOptions {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            rows: 4,
+            tile_width: 320,
+            output_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactSheetResult {
+    pub success: bool,
+    pub output_path: String,
+    pub frame_count: usize,
+    pub message: String,
+}
+
 // ============================================================================
 // Bitrate Analysis Types
 // ============================================================================
@@ -370,6 +725,39 @@ pub struct BitrateStatistics {
     pub total_frames: usize,
 }
 
+/// Quantizer and picture types for one interval (same timeline as `BitrateDataPoint`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityDataPoint {
+    pub timestamp: f64,
+    /// Frame-level QP averaged over the interval (None if the decoder doesn't export it)
+    pub avg_qp: Option<f64>,
+    pub min_qp: Option<f64>,
+    pub max_qp: Option<f64>,
+    pub i_frames: usize,
+    pub p_frames: usize,
+    pub b_frames: usize,
+}
+
+/// A span where the encoder quantized noticeably harder than usual
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StarvedInterval {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub duration: f64,
+    pub avg_qp: f64,
+    pub avg_bitrate: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityAnalysis {
+    /// Whether the decoder exported QP for this codec
+    pub qp_available: bool,
+    pub avg_qp: Option<f64>,
+    pub max_qp: Option<f64>,
+    pub data_points: Vec<QualityDataPoint>,
+    pub starved_intervals: Vec<StarvedInterval>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct BitrateAnalysis {
     pub path: String,
@@ -378,6 +766,9 @@ pub struct BitrateAnalysis {
     pub duration: f64,
     pub data_points: Vec<BitrateDataPoint>,
     pub statistics: BitrateStatistics,
+    /// Per-interval QP and picture types (only when requested, video streams)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityAnalysis>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -389,6 +780,58 @@ pub struct StreamContribution {
     pub data_points: Vec<BitrateDataPoint>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplingPlacement {
+    /// Samples start at evenly spaced positions
+    Uniform,
+    /// One sample at a random position within each evenly spaced slot
+    Random,
+}
+
+/// How large files are sampled instead of fully analyzed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /// Files at least this large are sampled
+    pub threshold_bytes: u64,
+    pub sample_count: usize,
+    pub sample_duration_secs: f64,
+    pub placement: SamplingPlacement,
+    /// Seed for random placement (random if not set)
+    pub seed: Option<u64>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: crate::bitrate::SAMPLING_THRESHOLD_BYTES,
+            sample_count: crate::bitrate::SAMPLE_COUNT,
+            sample_duration_secs: crate::bitrate::SAMPLE_DURATION_SECS,
+            placement: SamplingPlacement::Uniform,
+            seed: None,
+        }
+    }
+}
+
+/// How a sampled analysis was taken and how far its average can be trusted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingInfo {
+    pub placement: SamplingPlacement,
+    /// Samples that were read successfully
+    pub sample_count: usize,
+    pub sampled_seconds: f64,
+    /// Fraction of the duration that was actually read (0-1)
+    pub coverage: f64,
+    /// Average bitrate estimated from the samples
+    pub estimated_avg_bitrate: u64,
+    /// Half-width of the confidence interval around the estimated average
+    pub margin_bitrate: u64,
+    /// `margin_bitrate` relative to the estimate (0-1)
+    pub relative_margin: f64,
+    pub confidence_level: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverallBitrateAnalysis {
     pub path: String,
@@ -397,6 +840,526 @@ pub struct OverallBitrateAnalysis {
     pub statistics: BitrateStatistics,
     pub stream_contributions: Vec<StreamContribution>,
     pub from_cache: bool,
+    /// Set when the file was sampled; data between samples is interpolated
+    #[serde(default)]
+    pub sampling: Option<SamplingInfo>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BitrateExportFormat {
+    /// One row per interval: total bitrate plus a column per stream
+    Csv,
+    /// The full analysis as pretty-printed JSON
+    Json,
+    /// Standalone chart with stacked stream contributions and peak intervals
+    Svg,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BitrateExportResult {
+    pub output_path: String,
+    pub format: BitrateExportFormat,
+    pub bytes_written: u64,
+}
+
+// ============================================================================
+// GOP Analysis Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GopInfo {
+    pub index: usize,
+    pub start_time: f64,
+    pub duration: f64,
+    pub frame_count: usize,
+    /// Open GOP: leading B-frames reference the previous GOP
+    pub open: bool,
+    pub i_frames: usize,
+    pub p_frames: usize,
+    pub b_frames: usize,
+    pub total_size: u64,
+    /// Average P-frame size relative to the average I-frame size
+    pub p_to_i_ratio: Option<f64>,
+    /// Average B-frame size relative to the average I-frame size
+    pub b_to_i_ratio: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GopLengthBucket {
+    pub frame_count: usize,
+    pub occurrences: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyframeIntervalIssue {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub interval: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GopAnalysis {
+    pub path: String,
+    pub stream_index: i32,
+    pub keyframe_times: Vec<f64>,
+    pub gops: Vec<GopInfo>,
+    pub length_distribution: Vec<GopLengthBucket>,
+    pub average_gop_seconds: f64,
+    pub median_gop_seconds: f64,
+    pub max_gop_seconds: f64,
+    pub open_gop_count: usize,
+    pub closed_gop_count: usize,
+    pub irregular_intervals: Vec<KeyframeIntervalIssue>,
+}
+
+// ============================================================================
+// Bitrate Compliance Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompliancePreset {
+    BluRay,
+    Dvd,
+    Hls1080p,
+    Hls720p,
+    Hls540p,
+    Hls360p,
+    BroadcastAtsc,
+}
+
+/// VBV/HRD buffer model parameters
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VbvSettings {
+    pub maxrate_kbps: u64,
+    pub bufsize_kbits: u64,
+    /// CBR targets also treat buffer overflow (bitrate below maxrate) as a violation
+    pub constant_bitrate: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompliancePresetInfo {
+    pub preset: CompliancePreset,
+    pub name: String,
+    pub settings: VbvSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ComplianceOptions {
+    /// Delivery target to start from; explicit values below override it
+    pub preset: Option<CompliancePreset>,
+    pub maxrate_kbps: Option<u64>,
+    pub bufsize_kbits: Option<u64>,
+    pub constant_bitrate: Option<bool>,
+    /// Buffer fullness when playback starts (0-1)
+    pub initial_fullness: f64,
+    /// Check a single stream's series instead of the combined one
+    pub stream_index: Option<i32>,
+}
+
+impl Default for ComplianceOptions {
+    fn default() -> Self {
+        Self {
+            preset: None,
+            maxrate_kbps: None,
+            bufsize_kbits: None,
+            constant_bitrate: None,
+            initial_fullness: 0.9,
+            stream_index: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ComplianceViolationKind {
+    Underflow,
+    Overflow,
+}
+
+/// A span where the buffer model was violated (same timeline as `PeakInterval`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceViolation {
+    pub kind: ComplianceViolationKind,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub peak_bitrate: u64,
+    pub duration: f64,
+    /// Largest amount the buffer went below empty / above full, in kbits
+    pub max_excess_kbits: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferLevelPoint {
+    pub timestamp: f64,
+    /// Buffer fullness (0-1)
+    pub fullness: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceReport {
+    pub path: String,
+    pub preset: Option<CompliancePreset>,
+    pub settings: VbvSettings,
+    pub stream_index: Option<i32>,
+    pub compliant: bool,
+    pub violations: Vec<ComplianceViolation>,
+    pub buffer_levels: Vec<BufferLevelPoint>,
+    /// Total seconds where the interval bitrate exceeded maxrate
+    pub time_over_maxrate: f64,
+}
+
+// ============================================================================
+// Batch Bitrate Report Types
+// ============================================================================
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BatchBitrateOptions {
+    pub interval_seconds: f64,
+    /// Include media files in subdirectories
+    pub recursive: bool,
+    pub sampling: SamplingConfig,
+    /// Files this many times above or below the folder median are outliers
+    pub outlier_factor: f64,
+    /// Length of the "most wasteful encodes" list
+    pub max_wasteful: usize,
+}
+
+impl Default for BatchBitrateOptions {
+    fn default() -> Self {
+        Self {
+            interval_seconds: 1.0,
+            recursive: false,
+            sampling: SamplingConfig::default(),
+            outlier_factor: 2.0,
+            max_wasteful: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchFileReport {
+    pub path: String,
+    pub file_size: u64,
+    pub duration: f64,
+    pub avg_bitrate: u64,
+    pub peak_bitrate: u64,
+    pub std_deviation: f64,
+    pub video_codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub frame_rate: Option<f64>,
+    /// Average bitrate of the main video stream
+    pub video_bitrate: Option<u64>,
+    /// Video bits per pixel per frame
+    pub bits_per_pixel: Option<f64>,
+    /// Average bitrate relative to the folder median
+    pub ratio_to_median: f64,
+    pub outlier: bool,
+    pub sampled: bool,
+    pub from_cache: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchFileError {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WastefulEncode {
+    pub path: String,
+    pub codec: String,
+    pub bits_per_pixel: f64,
+    /// Bits per pixel that typically looks transparent for the codec
+    pub reference_bits_per_pixel: f64,
+    /// `bits_per_pixel / reference_bits_per_pixel`
+    pub excess_ratio: f64,
+    /// Rough bytes saved by re-encoding the video at the reference rate
+    pub potential_savings_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchBitrateReport {
+    pub directory: String,
+    pub files: Vec<BatchFileReport>,
+    pub failed: Vec<BatchFileError>,
+    pub median_avg_bitrate: u64,
+    pub median_bits_per_pixel: Option<f64>,
+    /// Video encodes furthest above their codec's reference bits per pixel
+    pub wasteful: Vec<WastefulEncode>,
+}
+
+// ============================================================================
+// Size Optimization Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationKind {
+    /// Non-default audio tracks in languages the user doesn't keep
+    RemoveAudioTracks,
+    /// Lossy audio tracks duplicating a lossless track in the same language
+    RemoveDuplicateAudio,
+    /// Video in an older codec that a modern codec stores much smaller
+    ReencodeVideo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OptimizationOptions {
+    /// Audio in these languages is never suggested for removal (e.g. `["eng", "jpn"]`)
+    pub keep_languages: Vec<String>,
+    /// Suggestions saving less than this are dropped
+    pub min_savings_bytes: u64,
+    /// Minimum fraction of the video size a re-encode must save to be suggested
+    pub min_reencode_savings: f64,
+}
+
+impl Default for OptimizationOptions {
+    fn default() -> Self {
+        Self {
+            keep_languages: Vec::new(),
+            min_savings_bytes: 10 * 1024 * 1024,
+            min_reencode_savings: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizationSuggestion {
+    pub kind: OptimizationKind,
+    /// One-line summary, e.g. "Remove 3 non-default audio tracks"
+    pub title: String,
+    pub detail: String,
+    /// Streams the suggestion applies to
+    pub stream_indices: Vec<i32>,
+    pub estimated_savings_bytes: u64,
+    /// Savings as a percentage of the file size
+    pub estimated_savings_percent: f64,
+    /// Job type that applies the suggestion (`JobType::name`), if the app has one
+    pub job_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizationReport {
+    pub path: String,
+    pub file_size: u64,
+    /// Sorted by estimated savings, largest first
+    pub suggestions: Vec<OptimizationSuggestion>,
+    pub total_savings_bytes: u64,
+}
+
+// ============================================================================
+// Loudness Analysis Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoudnessDataPoint {
+    pub timestamp: f64,
+    /// Momentary loudness (400ms window) in LUFS
+    pub momentary: f64,
+    /// Short-term loudness (3s window) in LUFS
+    pub short_term: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoudnessSummary {
+    /// Integrated loudness in LUFS
+    pub integrated: Option<f64>,
+    /// Loudness range in LU
+    pub loudness_range: Option<f64>,
+    /// True peak in dBTP
+    pub true_peak: Option<f64>,
+    /// Gating threshold for integrated loudness in LUFS
+    pub threshold: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoudnessAnalysis {
+    pub path: String,
+    pub stream_index: i32,
+    pub duration: f64,
+    pub summary: LoudnessSummary,
+    pub data_points: Vec<LoudnessDataPoint>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NormalizationOptions {
+    /// Target integrated loudness in LUFS
+    pub target_lufs: f64,
+    /// Maximum true peak in dBTP
+    pub true_peak: f64,
+    /// Target loudness range in LU
+    pub loudness_range: f64,
+    pub overwrite: bool,
+}
+
+impl Default for NormalizationOptions {
+    fn default() -> Self {
+        Self {
+            target_lufs: -23.0,
+            true_peak: -1.0,
+            loudness_range: 11.0,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NormalizationResult {
+    pub success: bool,
+    pub output_path: String,
+    /// Loudness measured in the first pass
+    pub measured: LoudnessSummary,
+    pub target_lufs: f64,
+    pub message: String,
+}
+
+// ============================================================================
+// Detection Timeline Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventType {
+    SceneCut,
+    Black,
+    Silence,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub event_type: TimelineEventType,
+    pub start_time: f64,
+    /// Same as `start_time` for scene cuts
+    pub end_time: f64,
+    /// Scene change score (0-100) for scene cuts
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineSuggestionKind {
+    Chapter,
+    IntroEnd,
+    CreditsStart,
+    TrimStart,
+    TrimEnd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineSuggestion {
+    pub kind: TimelineSuggestionKind,
+    pub timestamp: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionOptions {
+    /// scdet threshold (0-100)
+    pub scene_threshold: f64,
+    /// Minimum black segment length in seconds
+    pub black_min_duration: f64,
+    /// Luminance below which a pixel counts as black (0-1)
+    pub black_pixel_threshold: f64,
+    /// Noise floor for silence in dB
+    pub silence_noise_db: f64,
+    /// Minimum silent segment length in seconds
+    pub silence_min_duration: f64,
+}
+
+impl Default for DetectionOptions {
+    fn default() -> Self {
+        Self {
+            scene_threshold: 10.0,
+            black_min_duration: 0.5,
+            black_pixel_threshold: 0.10,
+            silence_noise_db: -50.0,
+            silence_min_duration: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineAnalysis {
+    pub path: String,
+    pub duration: f64,
+    pub options: DetectionOptions,
+    pub events: Vec<TimelineEvent>,
+    pub suggestions: Vec<TimelineSuggestion>,
+}
+
+// ============================================================================
+// Undo Journal Types
+// ============================================================================
+
+/// Operation recorded in the undo journal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOperationKind {
+    Rename,
+    Move,
+    BulkRename,
+    CreateFolders,
+    StreamRemoval,
+    MetadataUpdate,
+    LoudnessNormalization,
+}
+
+/// A single filesystem change made by a journaled operation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalChange {
+    /// A file or folder moved (or renamed) from `from` to `to`
+    Moved { from: String, to: String },
+    /// A folder that did not exist before the operation
+    CreatedDir { path: String },
+    /// `path` was rewritten in place; its previous contents are kept at `backup`
+    Rewritten { path: String, backup: String },
+    /// Tags of `path` were edited in place; applying `revert` restores them
+    TagsEdited {
+        path: String,
+        revert: Vec<MetadataOperation>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Applied and can be undone
+    Applied,
+    Undone,
+    /// Backups were removed by the retention policy, so it can't be undone
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalOperation {
+    pub id: i64,
+    pub kind: JournalOperationKind,
+    pub description: String,
+    pub status: JournalStatus,
+    pub created_at: String,
+    pub undone_at: Option<String>,
+    /// Size of the backups kept for rewritten files
+    pub backup_bytes: u64,
+    pub changes: Vec<JournalChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoResult {
+    pub operation_id: i64,
+    pub description: String,
+    /// Changes reverted (or found already reverted)
+    pub restored: usize,
+    pub errors: Vec<String>,
 }
 
 // ============================================================================
@@ -423,6 +1386,15 @@ pub struct JobInfo {
     pub progress_stage: Option<String>,
 }
 
+/// Progress event emitted for queued jobs that don't have a dedicated event
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgressEvent {
+    pub path: String,
+    pub job_type: String,
+    pub percentage: f64,
+    pub stage: String,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct QueueStatus {
     pub queued: Vec<JobInfo>,
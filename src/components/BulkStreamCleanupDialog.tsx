@@ -35,6 +35,7 @@ interface StreamInfo {
 	stream_type: StreamType;
 	codec_name: string | null;
 	codec_long_name: string | null;
+	profile: string | null;
 	language: string | null;
 	title: string | null;
 	is_default: boolean;
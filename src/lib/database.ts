@@ -1,15 +1,9 @@
 /**
  * Database service for SQLite operations
- * Handles job tracking, caching, and bitrate analysis storage using tauri-plugin-sql
+ * Handles job tracking, caching, and bitrate analysis cache maintenance using tauri-plugin-sql
  */
 
 import Database from "@tauri-apps/plugin-sql";
-import type {
-	BitrateDataPoint,
-	BitrateStatistics,
-	OverallBitrateAnalysis,
-	StreamContribution,
-} from "@/types/bitrate";
 import {
 	CACHE_DEFAULT_TTL_SECONDS,
 	type CacheEntry,
@@ -641,356 +635,8 @@ export async function getCacheStatistics(): Promise<CacheStatistics> {
 // Bitrate Analysis Storage
 // ============================================================================
 
-interface BitrateAnalysisRow {
-	id: number;
-	file_path: string;
-	file_hash: string;
-	file_size: number;
-	duration: number;
-	interval_seconds: number;
-	created_at: string;
-}
-
-interface BitrateStatisticsRow {
-	id: number;
-	analysis_id: number;
-	min_bitrate: number;
-	max_bitrate: number;
-	avg_bitrate: number;
-	median_bitrate: number;
-	std_deviation: number;
-	total_frames: number;
-}
-
-interface PeakIntervalRow {
-	id: number;
-	statistics_id: number;
-	start_time: number;
-	end_time: number;
-	peak_bitrate: number;
-	duration: number;
-}
-
-interface StreamContributionRow {
-	id: number;
-	analysis_id: number;
-	stream_index: number;
-	stream_type: string;
-	codec_name: string;
-	percentage: number;
-}
-
-interface DataPointRow {
-	id: number;
-	analysis_id?: number;
-	contribution_id?: number;
-	timestamp: number;
-	bitrate: number;
-	frame_type: string | null;
-}
-
-/**
- * Save a complete bitrate analysis to the database
- */
-export async function saveBitrateAnalysis(
-	analysis: OverallBitrateAnalysis,
-	fileHash: string,
-	fileSize: number,
-	intervalSeconds: number,
-): Promise<number> {
-	const database = await getDatabase();
-
-	// Delete any existing analysis for this file/hash combo
-	await deleteBitrateAnalysisByPath(analysis.path);
-
-	// Insert main analysis record
-	const analysisResult = await database.execute(
-		`INSERT INTO bitrate_analysis (file_path, file_hash, file_size, duration, interval_seconds)
-		 VALUES ($1, $2, $3, $4, $5)`,
-		[analysis.path, fileHash, fileSize, analysis.duration, intervalSeconds],
-	);
-
-	if (analysisResult.lastInsertId === undefined) {
-		throw new Error("Failed to insert bitrate analysis");
-	}
-
-	const analysisId = analysisResult.lastInsertId;
-
-	// Insert data points in batches for better performance
-	await insertDataPointsBatch(database, analysisId, analysis.data_points);
-
-	// Insert statistics
-	const statsResult = await database.execute(
-		`INSERT INTO bitrate_statistics
-		 (analysis_id, min_bitrate, max_bitrate, avg_bitrate, median_bitrate, std_deviation, total_frames)
-		 VALUES ($1, $2, $3, $4, $5, $6, $7)`,
-		[
-			analysisId,
-			analysis.statistics.min_bitrate,
-			analysis.statistics.max_bitrate,
-			analysis.statistics.avg_bitrate,
-			analysis.statistics.median_bitrate,
-			analysis.statistics.std_deviation,
-			analysis.statistics.total_frames,
-		],
-	);
-
-	if (statsResult.lastInsertId === undefined) {
-		throw new Error("Failed to insert bitrate statistics");
-	}
-
-	const statsId = statsResult.lastInsertId;
-
-	// Insert peak intervals
-	for (const peak of analysis.statistics.peak_intervals) {
-		await database.execute(
-			`INSERT INTO peak_intervals (statistics_id, start_time, end_time, peak_bitrate, duration)
-			 VALUES ($1, $2, $3, $4, $5)`,
-			[
-				statsId,
-				peak.start_time,
-				peak.end_time,
-				peak.peak_bitrate,
-				peak.duration,
-			],
-		);
-	}
-
-	// Insert stream contributions and their data points
-	for (const contribution of analysis.stream_contributions) {
-		const contribResult = await database.execute(
-			`INSERT INTO stream_contributions (analysis_id, stream_index, stream_type, codec_name, percentage)
-			 VALUES ($1, $2, $3, $4, $5)`,
-			[
-				analysisId,
-				contribution.stream_index,
-				contribution.stream_type,
-				contribution.codec_name,
-				contribution.percentage,
-			],
-		);
-
-		if (contribResult.lastInsertId !== undefined) {
-			await insertStreamDataPointsBatch(
-				database,
-				contribResult.lastInsertId,
-				contribution.data_points,
-			);
-		}
-	}
-
-	console.log(
-		`[Database] Saved bitrate analysis for ${analysis.path} (id: ${analysisId})`,
-	);
-	return analysisId;
-}
-
-/**
- * Insert data points in batches
- */
-async function insertDataPointsBatch(
-	database: Database,
-	analysisId: number,
-	dataPoints: BitrateDataPoint[],
-): Promise<void> {
-	const BATCH_SIZE = 500;
-	const FIELDS_PER_ROW = 4;
-
-	for (let i = 0; i < dataPoints.length; i += BATCH_SIZE) {
-		const batch = dataPoints.slice(i, i + BATCH_SIZE);
-		const placeholders: string[] = [];
-		const values: unknown[] = [];
-
-		batch.forEach((point, idx) => {
-			const offset = idx * FIELDS_PER_ROW;
-			placeholders.push(
-				`($${offset + 1}, $${offset + 2}, $${offset + 3}, $${offset + 4})`,
-			);
-			values.push(
-				analysisId,
-				point.timestamp,
-				point.bitrate,
-				point.frame_type ?? null,
-			);
-		});
-
-		// Validate placeholder count matches values count
-		const expectedParams = batch.length * FIELDS_PER_ROW;
-		if (values.length !== expectedParams) {
-			throw new Error(
-				`Batch insert validation failed: expected ${expectedParams} values, got ${values.length}`,
-			);
-		}
-
-		await database.execute(
-			`INSERT INTO bitrate_data_points (analysis_id, timestamp, bitrate, frame_type)
-			 VALUES ${placeholders.join(", ")}`,
-			values,
-		);
-	}
-}
-
-/**
- * Insert stream data points in batches
- */
-async function insertStreamDataPointsBatch(
-	database: Database,
-	contributionId: number,
-	dataPoints: BitrateDataPoint[],
-): Promise<void> {
-	const BATCH_SIZE = 500;
-	const FIELDS_PER_ROW = 4;
-
-	for (let i = 0; i < dataPoints.length; i += BATCH_SIZE) {
-		const batch = dataPoints.slice(i, i + BATCH_SIZE);
-		const placeholders: string[] = [];
-		const values: unknown[] = [];
-
-		batch.forEach((point, idx) => {
-			const offset = idx * FIELDS_PER_ROW;
-			placeholders.push(
-				`($${offset + 1}, $${offset + 2}, $${offset + 3}, $${offset + 4})`,
-			);
-			values.push(
-				contributionId,
-				point.timestamp,
-				point.bitrate,
-				point.frame_type ?? null,
-			);
-		});
-
-		// Validate placeholder count matches values count
-		const expectedParams = batch.length * FIELDS_PER_ROW;
-		if (values.length !== expectedParams) {
-			throw new Error(
-				`Batch insert validation failed: expected ${expectedParams} values, got ${values.length}`,
-			);
-		}
-
-		await database.execute(
-			`INSERT INTO stream_data_points (contribution_id, timestamp, bitrate, frame_type)
-			 VALUES ${placeholders.join(", ")}`,
-			values,
-		);
-	}
-}
-
-/**
- * Get bitrate analysis by file path and optional hash validation
- */
-export async function getBitrateAnalysis(
-	filePath: string,
-	currentFileHash?: string,
-): Promise<OverallBitrateAnalysis | null> {
-	const database = await getDatabase();
-
-	// Get the analysis record
-	const analysisRows = await database.select<BitrateAnalysisRow[]>(
-		"SELECT * FROM bitrate_analysis WHERE file_path = $1 ORDER BY created_at DESC LIMIT 1",
-		[filePath],
-	);
-
-	if (analysisRows.length === 0) {
-		return null;
-	}
-
-	const analysis = analysisRows[0];
-
-	// Validate hash if provided
-	if (currentFileHash && analysis.file_hash !== currentFileHash) {
-		console.log(
-			`[Database] Bitrate analysis invalidated for ${filePath}: file hash changed`,
-		);
-		await deleteBitrateAnalysisByPath(filePath);
-		return null;
-	}
-
-	// Get data points
-	const dataPointRows = await database.select<DataPointRow[]>(
-		"SELECT * FROM bitrate_data_points WHERE analysis_id = $1 ORDER BY timestamp",
-		[analysis.id],
-	);
-
-	const dataPoints: BitrateDataPoint[] = dataPointRows.map((row) => ({
-		timestamp: row.timestamp,
-		bitrate: row.bitrate,
-		frame_type: row.frame_type ?? undefined,
-	}));
-
-	// Get statistics
-	const statsRows = await database.select<BitrateStatisticsRow[]>(
-		"SELECT * FROM bitrate_statistics WHERE analysis_id = $1",
-		[analysis.id],
-	);
-
-	if (statsRows.length === 0) {
-		console.error(`[Database] Missing statistics for analysis ${analysis.id}`);
-		return null;
-	}
-
-	const stats = statsRows[0];
-
-	// Get peak intervals
-	const peakRows = await database.select<PeakIntervalRow[]>(
-		"SELECT * FROM peak_intervals WHERE statistics_id = $1",
-		[stats.id],
-	);
-
-	const statistics: BitrateStatistics = {
-		min_bitrate: stats.min_bitrate,
-		max_bitrate: stats.max_bitrate,
-		avg_bitrate: stats.avg_bitrate,
-		median_bitrate: stats.median_bitrate,
-		std_deviation: stats.std_deviation,
-		total_frames: stats.total_frames,
-		peak_intervals: peakRows.map((row) => ({
-			start_time: row.start_time,
-			end_time: row.end_time,
-			peak_bitrate: row.peak_bitrate,
-			duration: row.duration,
-		})),
-	};
-
-	// Get stream contributions
-	const contribRows = await database.select<StreamContributionRow[]>(
-		"SELECT * FROM stream_contributions WHERE analysis_id = $1",
-		[analysis.id],
-	);
-
-	const streamContributions: StreamContribution[] = await Promise.all(
-		contribRows.map(async (contrib) => {
-			const streamDataRows = await database.select<DataPointRow[]>(
-				"SELECT * FROM stream_data_points WHERE contribution_id = $1 ORDER BY timestamp",
-				[contrib.id],
-			);
-
-			return {
-				stream_index: contrib.stream_index,
-				stream_type: contrib.stream_type,
-				codec_name: contrib.codec_name,
-				percentage: contrib.percentage,
-				data_points: streamDataRows.map((row) => ({
-					timestamp: row.timestamp,
-					bitrate: row.bitrate,
-					frame_type: row.frame_type ?? undefined,
-				})),
-			};
-		}),
-	);
-
-	console.log(
-		`[Database] Retrieved bitrate analysis for ${filePath} from cache`,
-	);
-
-	return {
-		path: analysis.file_path,
-		duration: analysis.duration,
-		data_points: dataPoints,
-		statistics,
-		stream_contributions: streamContributions,
-		from_cache: true,
-	};
-}
+// Analyses are written and looked up by the backend (keyed by path, file hash,
+// interval and sampling); the frontend only deletes them and reads stats.
 
 /**
  * Delete bitrate analysis by file path
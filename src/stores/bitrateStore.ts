@@ -1,14 +1,11 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { stat } from "@tauri-apps/plugin-fs";
 import { toast } from "sonner";
 import { create } from "zustand";
 import {
 	clearAllBitrateAnalysis,
 	deleteBitrateAnalysisByPath,
-	getBitrateAnalysis,
 	getBitrateAnalysisStats,
-	saveBitrateAnalysis,
 } from "@/lib/database";
 import type {
 	BitrateAnalysis,
@@ -31,18 +28,6 @@ async function computeFileHash(path: string): Promise<string> {
 	}
 }
 
-/**
- * Get file size using Tauri fs plugin
- */
-async function getFileSize(path: string): Promise<number> {
-	try {
-		const fileStat = await stat(path);
-		return fileStat.size;
-	} catch {
-		return 0;
-	}
-}
-
 interface BitrateState {
 	currentAnalysis: BitrateAnalysis | OverallBitrateAnalysis | null;
 	loading: boolean;
@@ -65,7 +50,7 @@ interface BitrateState {
 
 	// Actions
 	analyzeStream: (path: string, streamIndex: number) => Promise<void>;
-	analyzeOverall: (path: string, fileHash?: string) => Promise<void>;
+	analyzeOverall: (path: string) => Promise<void>;
 	forceAnalyze: (path: string) => Promise<void>;
 	cancelAnalysis: (path?: string) => Promise<void>;
 	cancelAllJobs: () => Promise<void>;
@@ -152,7 +137,7 @@ export const useBitrateStore = create<BitrateState>((set, get) => ({
 		}
 	},
 
-	analyzeOverall: async (path: string, providedFileHash?: string) => {
+	analyzeOverall: async (path: string) => {
 		const { currentJobPath, intervalSeconds } = get();
 
 		// If already analyzing this file, don't start another
@@ -165,31 +150,12 @@ export const useBitrateStore = create<BitrateState>((set, get) => ({
 		console.log(`[BitrateStore] Current state:`, {
 			currentJobPath,
 			intervalSeconds,
-			providedFileHash,
 		});
 		set({ loading: true, error: null, currentJobPath: path });
 
 		try {
-			// Compute file hash using backend if not provided
-			console.log("[BitrateStore] Computing file hash...");
-			const fileHash = providedFileHash || (await computeFileHash(path));
-			console.log("[BitrateStore] File hash:", fileHash);
-
-			// Check database cache first
-			console.log("[BitrateStore] Checking database cache...");
-			const cached = await getBitrateAnalysis(path, fileHash);
-			if (cached) {
-				console.log("[BitrateStore] Returning cached analysis from database");
-				set({
-					currentAnalysis: cached,
-					loading: false,
-					currentJobPath: null,
-				});
-				return;
-			}
-			console.log("[BitrateStore] No cache hit, calling backend...");
-
-			// No cache hit, perform analysis via backend
+			// The backend returns its cached analysis (from_cache: true) when the
+			// file, interval and sampling are unchanged, and caches fresh ones
 			console.log("[BitrateStore] Invoking analyze_overall_bitrate command...");
 
 			// Add a timeout wrapper to detect hanging invokes
@@ -232,23 +198,12 @@ export const useBitrateStore = create<BitrateState>((set, get) => ({
 				result.statistics,
 			);
 
-			// Save to database cache
-			try {
-				const fileSize = await getFileSize(path);
-				await saveBitrateAnalysis(result, fileHash, fileSize, intervalSeconds);
-				console.log("[BitrateStore] Saved analysis to database cache");
+			if (!result.from_cache) {
 				await get().refreshCacheStats();
-			} catch (saveError) {
-				console.error(
-					"[BitrateStore] Failed to save analysis to database:",
-					saveError,
-				);
-				// Don't fail the whole operation if caching fails
 			}
 
-			// Mark as from_cache: false since it's a fresh analysis
 			set({
-				currentAnalysis: { ...result, from_cache: false },
+				currentAnalysis: result,
 				loading: false,
 				currentJobPath: null,
 			});
@@ -299,11 +254,8 @@ export const useBitrateStore = create<BitrateState>((set, get) => ({
 			// Reset state and analyze
 			set({ currentAnalysis: null, error: null });
 
-			// Compute fresh hash
-			const fileHash = await computeFileHash(path);
-
-			// Start fresh analysis (will skip cache check since we just cleared it)
-			await get().analyzeOverall(path, fileHash);
+			// Start fresh analysis (the backend misses its cache since we just cleared it)
+			await get().analyzeOverall(path);
 			await get().refreshCacheStats();
 		} catch (error) {
 			console.error("[BitrateStore] Force analyze error:", error);